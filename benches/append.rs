@@ -0,0 +1,35 @@
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use todo::{stream, TodoList};
+
+/// Adding to a 100k-line file by appending, which holds one line in memory
+/// at a time, against loading, changing and rewriting the whole list.
+fn add(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.txt");
+    let content: String = (0..100_000)
+        .map(|i| format!("- [x] archived task number {}\n", i))
+        .collect();
+
+    let mut group = c.benchmark_group("add/100000");
+    group.sample_size(20);
+    group.bench_function("append", |b| {
+        b.iter_batched(
+            || fs::write(&path, &content).unwrap(),
+            |_| stream::append(&path, "new task").unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("load", |b| {
+        b.iter_batched(
+            || fs::write(&path, &content).unwrap(),
+            |_| TodoList::load(&path).unwrap().add("new task").unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, add);
+criterion_main!(benches);
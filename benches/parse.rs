@@ -0,0 +1,46 @@
+use std::fs;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use todo::TodoList;
+
+/// A todo file of `lines` tasks mixing states, priorities and tokens.
+fn fixture(lines: usize) -> String {
+    (0..lines)
+        .map(|i| match i % 4 {
+            0 => format!("- [ ] task number {}\n", i),
+            1 => format!("- [x] (B) finished task {} #work\n", i),
+            2 => format!("- [ ] call someone about {} due:2024-07-01\n", i),
+            _ => format!("- [ ] pin:1 +project @home longer note for task {}\n", i),
+        })
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for lines in [1_000, 10_000, 100_000] {
+        let content = fixture(lines);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(lines),
+            &content,
+            |b, content| b.iter(|| TodoList::read(Path::new(""), content.as_bytes()).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn load(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut group = c.benchmark_group("load");
+    for lines in [1_000, 10_000, 100_000] {
+        let path = dir.path().join(format!("{}.txt", lines));
+        fs::write(&path, fixture(lines)).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(lines), &path, |b, path| {
+            b.iter(|| TodoList::load(path).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, load);
+criterion_main!(benches);
@@ -0,0 +1,122 @@
+//! The activity log, one line per operation on a task, such as
+//! `2024-06-02T10:11:12Z check 3 "buy milk"`.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use crate::{date, paths, Activity, TodoError};
+
+/// The default log, `activity.log` next to the named lists.
+pub fn default_path() -> Result<PathBuf, TodoError> {
+    Ok(paths::lists_dir()?.join("activity.log"))
+}
+
+/// Appends `entries`, done at `now`, to the log at `path`, creating it and
+/// its directory if needed.
+pub fn append(path: &Path, entries: &[Activity], now: DateTime<Utc>) -> io::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&format!("{} {}\n", date::format_timestamp(now), entry));
+    }
+    // One write, so that lines of concurrent invocations don't interleave.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(lines.as_bytes())
+}
+
+/// An entry read back from the log.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub verb: String,
+    pub index: usize,
+    pub note: String,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, ' ');
+        let at = date::parse_timestamp(fields.next()?)?;
+        let verb = fields.next()?.to_owned();
+        let index = fields.next()?.parse().ok()?;
+        let note = serde_json::from_str(fields.next()?).ok()?;
+        Some(Entry {
+            at,
+            verb,
+            index,
+            note,
+        })
+    }
+}
+
+/// The last `limit` entries of the log at `path`, oldest first. Lines that
+/// can't be read as entries are skipped, and a missing log has none.
+pub fn recent(path: &Path, limit: usize) -> Result<Vec<Entry>, TodoError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let entries: Vec<Entry> = content.lines().filter_map(Entry::parse).collect();
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_recent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo").join("activity.log");
+        let now = date::parse_timestamp("2024-06-02T10:11:12Z").unwrap();
+        let entries = [
+            Activity {
+                verb: "add",
+                index: 3,
+                note: "buy milk".to_owned(),
+            },
+            Activity {
+                verb: "check",
+                index: 3,
+                note: "say \"hi\"".to_owned(),
+            },
+        ];
+        append(&path, &entries, now).unwrap();
+        append(&path, &[], now).unwrap();
+        let mut log = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            log,
+            "2024-06-02T10:11:12Z add 3 \"buy milk\"\n\
+             2024-06-02T10:11:12Z check 3 \"say \\\"hi\\\"\"\n"
+        );
+
+        log.push_str("not an entry\n");
+        fs::write(&path, log).unwrap();
+        let recent = recent(&path, 1).unwrap();
+        assert_eq!(
+            recent,
+            vec![Entry {
+                at: now,
+                verb: "check".to_owned(),
+                index: 3,
+                note: "say \"hi\"".to_owned(),
+            }]
+        );
+        assert!(super::recent(&dir.path().join("missing.log"), 5)
+            .unwrap()
+            .is_empty());
+    }
+}
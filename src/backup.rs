@@ -0,0 +1,139 @@
+//! Automatic backups of a todo file, kept in a `.<name>.backups` directory
+//! next to it: the content the file had before each save, newest last.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::{date, ensure_writable, is_dry_run, journal, replace_file, Fingerprint, TodoError};
+
+/// The number of backups kept of each file.
+pub const KEEP: usize = 10;
+
+const FORMAT: &str = "%Y%m%dT%H%M%S%.9fZ";
+
+/// A backup of a todo file.
+pub struct Backup {
+    pub path: PathBuf,
+    /// When the content was replaced.
+    pub taken: DateTime<Utc>,
+}
+
+pub(crate) fn backups_dir(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.backups", name))
+}
+
+/// Copies the current content of the file at `path`, if it has any, to a
+/// new backup, and removes the backups beyond the newest `KEEP`. Returns
+/// the file name of the new backup.
+pub fn take(path: &Path) -> io::Result<Option<String>> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() > 0 => {}
+        Ok(_) => return Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let dir = backups_dir(path);
+    fs::create_dir_all(&dir)?;
+    let name = format!("{}.txt", date::now().format(FORMAT));
+    fs::copy(path, dir.join(&name))?;
+
+    let backups = list(path);
+    for backup in &backups[..backups.len().saturating_sub(KEEP)] {
+        fs::remove_file(&backup.path)?;
+    }
+    Ok(Some(name))
+}
+
+/// The backups of the file at `path`, oldest first.
+pub fn list(path: &Path) -> Vec<Backup> {
+    let mut backups: Vec<Backup> = fs::read_dir(backups_dir(path))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let stem = path.file_stem()?.to_str()?;
+            let taken = NaiveDateTime::parse_from_str(stem, FORMAT).ok()?.and_utc();
+            Some(Backup { path, taken })
+        })
+        .collect();
+    backups.sort_by_key(|b| b.taken);
+    backups
+}
+
+/// Replaces the content of the file at `path` with `backup`, after backing
+/// up the current content, so that restoring can be undone in turn.
+pub fn restore(path: &Path, backup: &Backup) -> Result<(), TodoError> {
+    let content = fs::read(&backup.path)?;
+    rewrite(path, &content, "recover")
+}
+
+/// Replaces the content of the file at `path` with `content` the way saves
+/// do: backed up first, written through a temporary file and journaled as
+/// `what`, for `todo undo-last`.
+pub fn rewrite(path: &Path, content: &[u8], what: &str) -> Result<(), TodoError> {
+    ensure_writable(path)?;
+    if is_dry_run() {
+        return Ok(());
+    }
+    let save_error = |source| TodoError::Save {
+        path: path.to_owned(),
+        source,
+    };
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let previous = Fingerprint::of(&target);
+    let before = take(&target).map_err(save_error)?;
+    replace_file(&target, |file| io::Write::write_all(file, content)).map_err(save_error)?;
+    journal::record_save(&target, before, previous, what);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TodoList;
+
+    #[test]
+    fn test_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let mut todo = TodoList::load(&path).unwrap();
+        // The new, empty file has nothing to back up.
+        todo.add("buy milk").unwrap();
+        assert!(contents(&path).is_empty());
+
+        todo.add("call mom").unwrap();
+        todo.clear().unwrap();
+        assert_eq!(
+            contents(&path),
+            vec!["- [ ] buy milk\n", "- [ ] buy milk\n- [ ] call mom\n"]
+        );
+
+        restore(&path, list(&path).last().unwrap()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [ ] call mom\n"
+        );
+        // The cleared list is empty, so restoring didn't add a backup.
+        assert_eq!(list(&path).len(), 2);
+
+        for i in 0..KEEP {
+            TodoList::load(&path).unwrap().add(&i.to_string()).unwrap();
+        }
+        assert_eq!(list(&path).len(), KEEP);
+    }
+
+    fn contents(path: &Path) -> Vec<String> {
+        list(path)
+            .iter()
+            .map(|b| fs::read_to_string(&b.path).unwrap())
+            .collect()
+    }
+}
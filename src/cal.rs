@@ -0,0 +1,250 @@
+//! The calendar of `todo cal`: a month's grid with the number of tasks due
+//! each day, and those tasks listed by day under it.
+
+use std::collections::BTreeMap;
+use std::env;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use serde::Deserialize;
+
+use crate::Render;
+
+/// The day a week starts on in the grid.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    Monday,
+    Sunday,
+}
+
+/// The regions whose calendars start weeks on Sunday, as the Unicode
+/// common locale data has them.
+const SUNDAY_REGIONS: &[&str] = &[
+    "AG", "AS", "BD", "BR", "BS", "BT", "BW", "BZ", "CA", "CN", "CO", "DM", "DO", "ET", "GT", "GU",
+    "HK", "HN", "ID", "IL", "IN", "JM", "JP", "KE", "KH", "KR", "LA", "MH", "MM", "MO", "MT", "MX",
+    "MZ", "NI", "NP", "PA", "PE", "PH", "PK", "PR", "PT", "PY", "SA", "SG", "SV", "TH", "TT", "TW",
+    "UM", "US", "VE", "VI", "WS", "YE", "ZA", "ZW",
+];
+
+impl WeekStart {
+    /// The start of the week of the locale's region, from `LC_ALL`,
+    /// `LC_TIME` or `LANG` in that order, as in `en_US.UTF-8`; Monday
+    /// without a region.
+    pub fn from_locale() -> Self {
+        let locale = ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        WeekStart::of_locale(&locale)
+    }
+
+    fn of_locale(locale: &str) -> Self {
+        let region = locale
+            .split(['.', '@'])
+            .next()
+            .and_then(|name| name.split_once('_'))
+            .map(|(_, region)| region);
+        match region {
+            Some(region) if SUNDAY_REGIONS.contains(&region) => WeekStart::Sunday,
+            _ => WeekStart::Monday,
+        }
+    }
+
+    fn weekday(self) -> Weekday {
+        match self {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
+}
+
+/// The width of a day's cell: its number and the count of tasks due.
+const CELL: usize = 6;
+
+/// The calendar of the month of `month` from `due`, the pending tasks due
+/// per day as their indices and notes: a boxed grid of weeks starting on
+/// `week_start`, with `today` highlighted and each day's count colored as
+/// its due dates are in listings, then the month's tasks under their day.
+/// Fits in 52 columns.
+pub fn render(
+    render: &Render,
+    due: &BTreeMap<NaiveDate, Vec<(usize, String)>>,
+    month: NaiveDate,
+    today: NaiveDate,
+    week_start: WeekStart,
+) -> String {
+    let first = month.with_day(1).unwrap();
+    let next = first + Months::new(1);
+    let urgency = |day: NaiveDate| match (day - today).num_days() {
+        ..=-1 => render.theme.overdue,
+        0 | 1 => render.theme.due_soon,
+        _ => render.theme.due_later,
+    };
+
+    let width = 7 * CELL + 6;
+    let rule = render.glyph("─", "-").repeat(width + 2);
+    let side = render.glyph("│", "|");
+    let mut out = String::new();
+    out.push_str(&format!(
+        "{}{}{}\n",
+        render.glyph("┌", "+"),
+        rule,
+        render.glyph("┐", "+")
+    ));
+    let title = first.format("%B %Y").to_string();
+    out.push_str(&format!("{} {:^width$} {}\n", side, title, side));
+    out.push_str(&format!(
+        "{}{}{}\n",
+        render.glyph("├", "+"),
+        rule,
+        render.glyph("┤", "+")
+    ));
+    let weekdays: Vec<String> =
+        std::iter::successors(Some(week_start.weekday()), |day| Some(day.succ()))
+            .take(7)
+            .map(|day| format!("{:<CELL$}", &day.to_string()[..2]))
+            .collect();
+    out.push_str(&format!("{} {} {}\n", side, weekdays.join(" "), side));
+
+    let lead = first.weekday().days_since(week_start.weekday()) as i64;
+    let mut day = first - Duration::days(lead);
+    while day < next {
+        let mut cells = Vec::new();
+        for _ in 0..7 {
+            if day < first || day >= next {
+                cells.push(" ".repeat(CELL));
+            } else {
+                let number = format!("{:>2}", day.day());
+                let number = match day == today {
+                    true => render
+                        .style(render.theme.highlight)
+                        .paint(number)
+                        .to_string(),
+                    false => number,
+                };
+                let count = match due.get(&day).map_or(0, Vec::len) {
+                    0 => " ".repeat(CELL - 2),
+                    n => {
+                        let count = match n {
+                            ..=99 => format!("({})", n),
+                            _ => "(**)".to_owned(),
+                        };
+                        let padding = " ".repeat(CELL - 2 - count.len());
+                        format!("{}{}", render.style(urgency(day)).paint(count), padding)
+                    }
+                };
+                cells.push(format!("{}{}", number, count));
+            }
+            day += Duration::days(1);
+        }
+        out.push_str(&format!("{} {} {}\n", side, cells.join(" "), side));
+    }
+    out.push_str(&format!(
+        "{}{}{}\n",
+        render.glyph("└", "+"),
+        rule,
+        render.glyph("┘", "+")
+    ));
+
+    let days: Vec<_> = due.range(first..next).collect();
+    if days.is_empty() {
+        out.push_str(&format!("nothing due in {}\n", title));
+    }
+    for (&day, tasks) in days {
+        let heading = day.format("%a %-d %b").to_string();
+        out.push_str(&format!("{}\n", render.style(urgency(day)).paint(heading)));
+        for (index, note) in tasks {
+            out.push_str(&format!("{:>4}. {}\n", render.shown_index(*index), note));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::parse_date;
+
+    /// `text` without its escape sequences.
+    fn plain(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_render() {
+        let date = |s| parse_date(s).unwrap();
+        let today = date("2024-07-03");
+        let due = BTreeMap::from([
+            (date("2024-06-28"), vec![(1, "call mom".to_owned())]),
+            (
+                date("2024-07-02"),
+                vec![(2, "pay rent".to_owned()), (4, "water plants".to_owned())],
+            ),
+            (date("2024-07-15"), vec![(3, "file taxes".to_owned())]),
+        ]);
+        assert_eq!(
+            plain(&render(
+                &Render::default(),
+                &due,
+                today,
+                today,
+                WeekStart::Monday
+            )),
+            "┌──────────────────────────────────────────────────┐\n\
+             │                    July 2024                     │\n\
+             ├──────────────────────────────────────────────────┤\n\
+             │ Mo     Tu     We     Th     Fr     Sa     Su     │\n\
+             │  1      2(2)   3      4      5      6      7     │\n\
+             │  8      9     10     11     12     13     14     │\n\
+             │ 15(1)  16     17     18     19     20     21     │\n\
+             │ 22     23     24     25     26     27     28     │\n\
+             │ 29     30     31                                 │\n\
+             └──────────────────────────────────────────────────┘\n\
+             Tue 2 Jul\n   2. pay rent\n   4. water plants\n\
+             Mon 15 Jul\n   3. file taxes\n"
+        );
+        let june = plain(&render(
+            &Render::default(),
+            &due,
+            date("2024-06-10"),
+            today,
+            WeekStart::Sunday,
+        ));
+        assert!(june.contains(
+            "│ Su     Mo     Tu     We     Th     Fr     Sa     │\n\
+             │                                            1     │\n"
+        ));
+        assert!(june.ends_with(
+            "│ 30                                               │\n\
+             └──────────────────────────────────────────────────┘\n\
+             Fri 28 Jun\n   1. call mom\n"
+        ));
+        let august = plain(&render(
+            &Render::default(),
+            &due,
+            date("2024-08-01"),
+            today,
+            WeekStart::Monday,
+        ));
+        assert!(august.ends_with("nothing due in August 2024\n"));
+    }
+
+    #[test]
+    fn test_week_start_of_locale() {
+        assert_eq!(WeekStart::of_locale("en_US.UTF-8"), WeekStart::Sunday);
+        assert_eq!(WeekStart::of_locale("de_DE.UTF-8"), WeekStart::Monday);
+        assert_eq!(WeekStart::of_locale("ja_JP"), WeekStart::Sunday);
+        assert_eq!(WeekStart::of_locale("C"), WeekStart::Monday);
+        assert_eq!(WeekStart::of_locale(""), WeekStart::Monday);
+    }
+}
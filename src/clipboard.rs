@@ -0,0 +1,54 @@
+//! Copying text to the system clipboard through the platform's clipboard
+//! tool, so no display libraries need to be linked in.
+
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// The commands to try, in order, each reading the text from stdin.
+fn candidates() -> Vec<&'static [&'static str]> {
+    if cfg!(target_os = "macos") {
+        return vec![&["pbcopy"]];
+    }
+    if cfg!(windows) {
+        return vec![&["clip"]];
+    }
+    let mut candidates: Vec<&'static [&'static str]> = Vec::new();
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        candidates.push(&["wl-copy"]);
+    }
+    if env::var_os("DISPLAY").is_some() {
+        candidates.push(&["xclip", "-selection", "clipboard"]);
+        candidates.push(&["xsel", "--clipboard", "--input"]);
+    }
+    candidates
+}
+
+fn run(command: &[&str], text: &str) -> io::Result<bool> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    Ok(child.wait()?.success())
+}
+
+/// Puts `text` on the clipboard: with `pbcopy` on macOS, `clip` on Windows,
+/// and `wl-copy` under Wayland or `xclip`/`xsel` under X11 elsewhere.
+pub fn copy(text: &str) -> Result<(), String> {
+    let candidates = candidates();
+    if candidates.is_empty() {
+        return Err("no clipboard is available".to_owned());
+    }
+    for command in &candidates {
+        if let Ok(true) = run(command, text) {
+            return Ok(());
+        }
+    }
+    let names: Vec<&str> = candidates.iter().map(|c| c[0]).collect();
+    Err(format!("couldn't copy with {}", names.join(" or ")))
+}
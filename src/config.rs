@@ -0,0 +1,423 @@
+//! The optional configuration file, `todo/config.toml` in the user's
+//! configuration directory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::cal::WeekStart;
+use crate::date::{self, Span};
+use crate::hooks;
+use crate::theme::{Preset, Theme};
+use crate::{SortKey, Symbols, TodoError};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The list used when `--list` isn't given.
+    pub default_list: Option<String>,
+    /// Named sets of tasks added together by `todo template <name>`.
+    pub templates: BTreeMap<String, Vec<String>>,
+    /// The status symbols used when `--symbols` isn't given.
+    pub symbols: Option<Symbols>,
+    /// Always behave as if `--ascii` was given.
+    pub ascii: bool,
+    /// The color preset used when `--theme` isn't given.
+    pub theme: Option<Preset>,
+    /// Styles replacing those of the preset, by name; see `Theme::new`.
+    pub colors: BTreeMap<String, String>,
+    /// Whether listings make URLs links, whatever `links::supported` says
+    /// of the terminal. Never without colors.
+    pub hyperlinks: Option<bool>,
+    /// Always behave as if `--zero-indexed` was given.
+    pub zero_indexed: bool,
+    /// How many days old a task may be before `ls --age` stresses its age;
+    /// 30 if not set.
+    pub stale_days: Option<u32>,
+    /// How long after their completion done tasks are moved to the list's
+    /// archive, as a `Span` such as `"14d"`; never if not set.
+    pub auto_archive_after: Option<String>,
+    /// The time zone dates are local to, such as `"Europe/Berlin"`, for a
+    /// system whose own is wrong; see `date::set_timezone`.
+    pub timezone: Option<String>,
+    /// The day weeks start on in `todo cal`, `"monday"` or `"sunday"`; that
+    /// of the locale if not set.
+    pub week_start: Option<WeekStart>,
+    /// Always behave as if `--skip-invalid` was given.
+    pub skip_invalid: bool,
+    /// Who the tasks added are assigned to, unless they say otherwise.
+    pub default_assignee: Option<String>,
+    /// Where the activity log is kept, instead of `activity.log` next to
+    /// the named lists.
+    pub activity_log: Option<PathBuf>,
+    /// The commands of `todo sync`, in a `[sync]` table.
+    pub sync: Sync,
+    /// The command run after each change, in a `[hooks]` table.
+    pub hooks: Hooks,
+    /// The credentials for lists on a WebDAV server, in a `[webdav]` table.
+    pub webdav: WebDav,
+    /// Saved `ls` options by name, in `[views.<name>]` tables.
+    pub views: BTreeMap<String, View>,
+    /// Commands of the user's own, such as `alias.w = "ls --tag work"`,
+    /// standing for the words they're set to.
+    #[serde(rename = "alias")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sync {
+    /// Fetches the list, e.g. `rsync host:todo.txt "$TODO_FILE"`.
+    pub pull_cmd: Option<String>,
+    /// Sends the list, e.g. `rsync "$TODO_FILE" host:`.
+    pub push_cmd: Option<String>,
+    /// Which command to run after each save, if any.
+    pub auto: Option<AutoSync>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoSync {
+    Push,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// Run after each change to a list; see `hooks::run`.
+    pub on_change: Option<String>,
+    /// How many seconds the command may run before it's stopped.
+    pub timeout: Option<u64>,
+}
+
+impl Hooks {
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+            .map_or(hooks::DEFAULT_TIMEOUT, Duration::from_secs)
+    }
+}
+
+/// How to log in to WebDAV servers: with `user` and `password`, or with the
+/// entry for the server in a netrc file, `~/.netrc` unless `netrc` says
+/// otherwise.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebDav {
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub netrc: Option<PathBuf>,
+}
+
+/// A saved set of `ls` options, for `todo ls --view <name>`. The fields are
+/// named after the options, in the plural for those given once per value.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct View {
+    pub tags: Vec<String>,
+    pub any_tag: bool,
+    pub not_tags: Vec<String>,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    pub not_match: Vec<String>,
+    pub all: bool,
+    pub done: bool,
+    pub overdue: bool,
+    pub ready: bool,
+    pub hide_waiting: bool,
+    pub assignee: Option<String>,
+    pub unassigned: bool,
+    pub sort: Option<SortKey>,
+    pub reverse: bool,
+    pub limit: Option<usize>,
+}
+
+impl View {
+    /// The `ls` options the view stands for.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut each = |option: &str, values: &[String]| {
+            for value in values {
+                args.push(format!("--{} {}", option, value));
+            }
+        };
+        each("tag", &self.tags);
+        each("not-tag", &self.not_tags);
+        each("project", &self.projects);
+        each("context", &self.contexts);
+        each("not-match", &self.not_match);
+        each("assignee", self.assignee.as_slice());
+        for (set, flag) in [
+            (self.any_tag, "--any-tag"),
+            (self.all, "--all"),
+            (self.done, "--done"),
+            (self.overdue, "--overdue"),
+            (self.ready, "--ready"),
+            (self.hide_waiting, "--hide-waiting"),
+            (self.unassigned, "--unassigned"),
+            (self.reverse, "--reverse"),
+        ] {
+            if set {
+                args.push(flag.to_owned());
+            }
+        }
+        if let Some(sort) = self.sort {
+            args.push(format!("--sort {}", sort.name()));
+        }
+        if let Some(limit) = self.limit {
+            args.push(format!("--limit {}", limit));
+        }
+        args
+    }
+}
+
+/// Expands the placeholders of a template line: `{date}` becomes `today`
+/// in `YYYY-MM-DD` form.
+pub fn expand_template(line: &str, today: NaiveDate) -> String {
+    line.replace("{date}", &today.format("%Y-%m-%d").to_string())
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("todo").join("config.toml"))
+    }
+
+    /// Loads the configuration at `path`; a missing file is an empty
+    /// configuration.
+    pub fn load(path: &Path) -> Result<Self, TodoError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let config: Config = toml::from_str(&content).map_err(|e| TodoError::Config {
+            path: path.to_owned(),
+            message: e.message().to_owned(),
+        })?;
+        Theme::new(Preset::default(), &config.colors).map_err(|message| TodoError::Config {
+            path: path.to_owned(),
+            message,
+        })?;
+        if config.webdav.user.is_some() != config.webdav.password.is_some() {
+            return Err(TodoError::Config {
+                path: path.to_owned(),
+                message: "webdav needs both a user and a password, or neither".to_owned(),
+            });
+        }
+        if let Some((name, _)) = config.views.iter().find(|(_, v)| v.all && v.done) {
+            return Err(TodoError::Config {
+                path: path.to_owned(),
+                message: format!("view '{}' can't be both all and done", name),
+            });
+        }
+        if let Some((name, _)) = config
+            .views
+            .iter()
+            .find(|(_, v)| v.assignee.is_some() && v.unassigned)
+        {
+            return Err(TodoError::Config {
+                path: path.to_owned(),
+                message: format!("view '{}' can't be both assigned and unassigned", name),
+            });
+        }
+        if let Some((name, _)) = config.aliases.iter().find(|(_, a)| a.trim().is_empty()) {
+            return Err(TodoError::Config {
+                path: path.to_owned(),
+                message: format!("alias '{}' doesn't stand for a command", name),
+            });
+        }
+        if config.sync.auto == Some(AutoSync::Push) && config.sync.push_cmd.is_none() {
+            return Err(TodoError::Config {
+                path: path.to_owned(),
+                message: "sync.auto = \"push\" needs a sync.push_cmd".to_owned(),
+            });
+        }
+        if let Some(name) = config
+            .timezone
+            .as_deref()
+            .filter(|name| !date::is_timezone(name))
+        {
+            return Err(TodoError::Config {
+                path: path.to_owned(),
+                message: format!(
+                    "timezone '{}' isn't in the system's time zone database",
+                    name
+                ),
+            });
+        }
+        if config.auto_archive_after.is_some() && config.auto_archive_after().is_none() {
+            return Err(TodoError::Config {
+                path: path.to_owned(),
+                message: "auto_archive_after needs a number of days, weeks, months or years, \
+                          such as \"14d\""
+                    .to_owned(),
+            });
+        }
+        Ok(config)
+    }
+
+    /// How long done tasks stay in the list, if they're archived at all.
+    pub fn auto_archive_after(&self) -> Option<Span> {
+        self.auto_archive_after.as_deref().and_then(Span::parse)
+    }
+
+    /// The theme of `preset` with the configured colors.
+    pub fn theme(&self, preset: Preset) -> Theme {
+        // `load` has checked the colors.
+        Theme::new(preset, &self.colors).unwrap_or_else(|_| Theme::preset(preset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert!(Config::load(&path).unwrap().default_list.is_none());
+
+        fs::write(&path, "default_list = \"work\"\n").unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().default_list.as_deref(),
+            Some("work")
+        );
+
+        fs::write(
+            &path,
+            "[templates]\nweekly = [\"review inbox\", \"plan week due:{date}\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().templates["weekly"],
+            vec!["review inbox", "plan week due:{date}"]
+        );
+
+        fs::write(&path, "symbols = \"ascii\"\n").unwrap();
+        assert_eq!(Config::load(&path).unwrap().symbols, Some(Symbols::Ascii));
+        fs::write(&path, "week_start = \"sunday\"\n").unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().week_start,
+            Some(WeekStart::Sunday)
+        );
+
+        fs::write(&path, "activity_log = \"/tmp/todo.log\"\n").unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().activity_log,
+            Some(PathBuf::from("/tmp/todo.log"))
+        );
+
+        fs::write(&path, "theme = \"light\"\n[colors]\nindex = \"244\"\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.theme, Some(Preset::Light));
+        assert_eq!(
+            config.theme(Preset::Light).index,
+            ansi_term::Colour::Fixed(244).normal()
+        );
+        fs::write(&path, "[colors]\nindex = \"grey\"\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+
+        fs::write(
+            &path,
+            "[sync]\npush_cmd = \"rsync \\\"$TODO_FILE\\\" host:\"\nauto = \"push\"\n",
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(
+            config.sync.push_cmd.as_deref(),
+            Some("rsync \"$TODO_FILE\" host:")
+        );
+        assert_eq!(config.sync.auto, Some(AutoSync::Push));
+        fs::write(&path, "[sync]\nauto = \"push\"\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+
+        fs::write(&path, "[webdav]\nuser = \"me\"\npassword = \"secret\"\n").unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().webdav.user.as_deref(),
+            Some("me")
+        );
+        fs::write(&path, "[webdav]\nuser = \"me\"\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+
+        for name in ["Mars/Olympus", "../../etc/passwd", ""] {
+            fs::write(&path, format!("timezone = \"{}\"\n", name)).unwrap();
+            assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+        }
+
+        fs::write(
+            &path,
+            "[views.work]\ntags = [\"work\"]\nsort = \"est\"\nhide_waiting = true\n",
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(
+            config.views["work"],
+            View {
+                tags: vec!["work".to_owned()],
+                sort: Some(SortKey::Estimate),
+                hide_waiting: true,
+                ..View::default()
+            }
+        );
+        assert_eq!(
+            config.views["work"].args(),
+            ["--tag work", "--hide-waiting", "--sort est"]
+        );
+        fs::write(
+            &path,
+            "default_assignee = \"alice\"\n[views.mine]\nassignee = \"alice\"\n",
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.default_assignee.as_deref(), Some("alice"));
+        assert_eq!(config.views["mine"].args(), ["--assignee alice"]);
+        fs::write(
+            &path,
+            "[views.odd]\nassignee = \"alice\"\nunassigned = true\n",
+        )
+        .unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+        fs::write(&path, "[views.odd]\nall = true\ndone = true\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+        fs::write(&path, "[views.odd]\ntag = [\"work\"]\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+
+        fs::write(&path, "[hooks]\non_change = \"make\"\ntimeout = 2\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.hooks.on_change.as_deref(), Some("make"));
+        assert_eq!(config.hooks.timeout(), Duration::from_secs(2));
+        assert_eq!(Config::default().hooks.timeout(), hooks::DEFAULT_TIMEOUT);
+
+        fs::write(&path, "auto_archive_after = \"2w\"\n").unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().auto_archive_after(),
+            Some(Span::Weeks(2))
+        );
+        assert_eq!(Config::default().auto_archive_after(), None);
+        fs::write(&path, "auto_archive_after = \"soon\"\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+
+        fs::write(&path, "alias.w = \"ls --tag work\"\n").unwrap();
+        assert_eq!(Config::load(&path).unwrap().aliases["w"], "ls --tag work");
+        fs::write(&path, "[alias]\nw = \" \"\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+
+        fs::write(&path, "default_lsit = \"work\"\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(TodoError::Config { .. })));
+    }
+
+    #[test]
+    fn test_expand_template() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        assert_eq!(
+            expand_template("plan week due:{date}", today),
+            "plan week due:2024-07-01"
+        );
+        assert_eq!(expand_template("{dates}", today), "{dates}");
+    }
+}
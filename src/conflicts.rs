@@ -0,0 +1,97 @@
+//! Copies of a list left next to it by sync clients that found it changed
+//! on two machines, such as Dropbox's `todo (conflicted copy
+//! 2024-06-02).txt` and Syncthing's
+//! `todo.sync-conflict-20240602-101112-ABCDEFG.txt`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether the file `name` is a conflict copy of the list file `list`.
+pub fn is_copy_of(list: &str, name: &str) -> bool {
+    let (stem, extension) = match list.rfind('.') {
+        Some(i) if i > 0 => list.split_at(i),
+        _ => (list, ""),
+    };
+    let Some(middle) = name
+        .strip_prefix(stem)
+        .and_then(|rest| rest.strip_suffix(extension))
+    else {
+        return false;
+    };
+    (middle.starts_with(" (") && middle.ends_with(')') && middle.contains("conflicted copy"))
+        || middle.starts_with(".sync-conflict-")
+}
+
+/// The conflict copies of the list at `path`, by name.
+pub fn find(path: &Path) -> Vec<PathBuf> {
+    let Some(list) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut copies: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|name| is_copy_of(list, name))
+        })
+        .collect();
+    copies.sort();
+    copies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_copy_of() {
+        for name in [
+            "todo (conflicted copy 2024-06-02).txt",
+            "todo (Jane's conflicted copy 2024-06-02).txt",
+            "todo.sync-conflict-20240602-101112-ABCDEFG.txt",
+        ] {
+            assert!(is_copy_of("todo.txt", name), "{}", name);
+        }
+        for name in [
+            "todo.txt",
+            "todo (copy).txt",
+            "work (conflicted copy 2024-06-02).txt",
+            "todo (conflicted copy 2024-06-02).md",
+        ] {
+            assert!(!is_copy_of("todo.txt", name), "{}", name);
+        }
+        assert!(is_copy_of(
+            "todo",
+            "todo.sync-conflict-20240602-101112-ABCDEFG"
+        ));
+    }
+
+    #[test]
+    fn test_find() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        for name in [
+            "todo.txt",
+            "todo.sync-conflict-20240602-101112-ABCDEFG.txt",
+            "todo (conflicted copy 2024-06-02).txt",
+            "notes.txt",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(
+            find(&path),
+            vec![
+                dir.path().join("todo (conflicted copy 2024-06-02).txt"),
+                dir.path()
+                    .join("todo.sync-conflict-20240602-101112-ABCDEFG.txt"),
+            ]
+        );
+    }
+}
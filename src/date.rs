@@ -0,0 +1,320 @@
+//! Calendar dates carried by task tokens such as `t:2024-07-01`.
+
+use std::env;
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, SecondsFormat, Utc, Weekday};
+
+/// The local calendar date, which every comparison of dates goes by:
+/// overdue tasks, snoozes, streaks and reports. It's the date of `now` in
+/// the local time zone, unless overridden with a `YYYY-MM-DD` date in the
+/// `TODO_TODAY` environment variable, which tests use to pin "today".
+pub fn today() -> NaiveDate {
+    env::var("TODO_TODAY")
+        .ok()
+        .and_then(|s| parse_date(&s))
+        .unwrap_or_else(|| local_date(now()))
+}
+
+/// The calendar date at `t` in the local time zone.
+pub fn local_date(t: DateTime<Utc>) -> NaiveDate {
+    t.with_timezone(&Local).date_naive()
+}
+
+/// Makes the local time zone the one named `name` in the system's time
+/// zone database, such as `Europe/Berlin`, rather than the system's own, by
+/// setting `TZ` for the process. It's to be called before any date is
+/// taken, as the configuration is read.
+pub fn set_timezone(name: &str) {
+    env::set_var("TZ", name);
+}
+
+/// Whether `name` is a zone of the system's time zone database, in
+/// `$TZDIR` or `/usr/share/zoneinfo`. There's none on Windows.
+pub fn is_timezone(name: &str) -> bool {
+    let dir = env::var_os("TZDIR").unwrap_or_else(|| "/usr/share/zoneinfo".into());
+    cfg!(unix)
+        && !name.is_empty()
+        && !name.starts_with('/')
+        && !name.split('/').any(|part| part == "..")
+        && Path::new(&dir).join(name).is_file()
+}
+
+/// The current time, unless overridden with an RFC 3339 timestamp in the
+/// `TODO_NOW` environment variable.
+pub fn now() -> DateTime<Utc> {
+    env::var("TODO_NOW")
+        .ok()
+        .and_then(|s| parse_timestamp(&s))
+        .unwrap_or_else(Utc::now)
+}
+
+pub fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+pub fn format_timestamp(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Formats a number of seconds as hours and minutes, e.g. `2h 05m`.
+pub fn format_duration(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, m) => format!("{}h {:02}m", h, m),
+    }
+}
+
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// A length of time: a number of days, weeks, months or years followed by
+/// `d`, `w`, `m` or `y`, as in `14d` or `1y`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Span {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Years(u32),
+}
+
+impl Span {
+    pub fn parse(s: &str) -> Option<Span> {
+        let unit = s.chars().last()?;
+        let count: u32 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+        match unit {
+            'd' => Some(Span::Days(count)),
+            'w' => Some(Span::Weeks(count)),
+            'm' => Some(Span::Months(count)),
+            'y' => Some(Span::Years(count)),
+            _ => None,
+        }
+    }
+
+    /// The date this long after `date`.
+    pub fn after(self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Span::Days(n) => date.checked_add_signed(Duration::days(n.into())),
+            Span::Weeks(n) => date.checked_add_signed(Duration::weeks(n.into())),
+            Span::Months(n) => date.checked_add_months(Months::new(n)),
+            Span::Years(n) => date.checked_add_months(Months::new(n.checked_mul(12)?)),
+        }
+    }
+
+    /// The date this long before `date`.
+    pub fn before(self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Span::Days(n) => date.checked_sub_signed(Duration::days(n.into())),
+            Span::Weeks(n) => date.checked_sub_signed(Duration::weeks(n.into())),
+            Span::Months(n) => date.checked_sub_months(Months::new(n)),
+            Span::Years(n) => date.checked_sub_months(Months::new(n.checked_mul(12)?)),
+        }
+    }
+
+    /// A span such as `parse` reads, but only of days, weeks or months, as
+    /// dates of tasks are given.
+    fn parse_short(s: &str) -> Option<Span> {
+        Span::parse(s).filter(|span| !matches!(span, Span::Years(_)))
+    }
+
+    /// A number of days, weeks or months written out, as in `3 days`, `1
+    /// week` or `a month`.
+    fn parse_words(s: &str) -> Option<Span> {
+        let (count, unit) = s.split_once(' ')?;
+        let count = match count {
+            "a" | "an" | "one" => 1,
+            count => count.parse().ok()?,
+        };
+        match unit.strip_suffix('s').unwrap_or(unit) {
+            "day" => Some(Span::Days(count)),
+            "week" => Some(Span::Weeks(count)),
+            "month" => Some(Span::Months(count)),
+            _ => None,
+        }
+    }
+}
+
+/// The forms `parse_when` reads, for error messages.
+pub const WHEN_FORMS: &str = "2024-07-01, today, tomorrow, friday, next friday, next week, \
+                              next month, in 3 days, in 2 weeks, or 3d, 2w, 1m";
+
+/// The forms `parse_since` reads, for error messages.
+pub const SINCE_FORMS: &str = "2024-07-01, today, yesterday, monday, last monday, last week, \
+                               last month, 3 days ago, or 3d, 2w, 1m";
+
+/// Parses a date from today on: an ISO `YYYY-MM-DD` date, `today`,
+/// `tomorrow`, a weekday name, alone or after `next`, for its next
+/// occurrence after today, `next week` or `next month`, or a duration counted
+/// from `today`, as `in 3 days` or a number of days, weeks or months followed
+/// by `d`, `w` or `m`. Case and extra spaces don't matter; anything else,
+/// such as `this friday`, is none rather than a guess.
+pub fn parse_when(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Some(date) = parse_date(s) {
+        return Some(date);
+    }
+    let phrase = phrase(s);
+    let weekday = |name: &str| {
+        let weekday = name.parse::<Weekday>().ok()?;
+        let ahead =
+            (weekday.num_days_from_monday() + 6 - today.weekday().num_days_from_monday()) % 7 + 1;
+        today.checked_add_signed(Duration::days(ahead.into()))
+    };
+    match phrase.split_once(' ') {
+        _ if phrase == "today" => Some(today),
+        _ if phrase == "tomorrow" => today.succ_opt(),
+        Some(("next", "week")) => Span::Weeks(1).after(today),
+        Some(("next", "month")) => Span::Months(1).after(today),
+        Some(("next", name)) => weekday(name),
+        Some(("in", span)) => Span::parse_words(span)?.after(today),
+        Some(_) => None,
+        None => weekday(&phrase).or_else(|| Span::parse_short(&phrase)?.after(today)),
+    }
+}
+
+/// Parses a date in the past: an ISO `YYYY-MM-DD` date, `today`,
+/// `yesterday`, a weekday name for its last occurrence (today included),
+/// `last` and a weekday name for its last occurrence before today, `last
+/// week` or `last month`, or a duration counted back from `today`, as `3
+/// days ago` or a number of days, weeks or months ago followed by `d`, `w`
+/// or `m`.
+pub fn parse_since(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Some(date) = parse_date(s) {
+        return Some(date);
+    }
+    let phrase = phrase(s);
+    let weekday = |name: &str, least: u32| {
+        let weekday = name.parse::<Weekday>().ok()?;
+        let back =
+            (today.weekday().num_days_from_monday() + 7 - least - weekday.num_days_from_monday())
+                % 7
+                + least;
+        today.checked_sub_signed(Duration::days(back.into()))
+    };
+    if let Some(span) = phrase.strip_suffix(" ago") {
+        return Span::parse_words(span)?.before(today);
+    }
+    match phrase.split_once(' ') {
+        _ if phrase == "today" => Some(today),
+        _ if phrase == "yesterday" => today.pred_opt(),
+        Some(("last", "week")) => Span::Weeks(1).before(today),
+        Some(("last", "month")) => Span::Months(1).before(today),
+        Some(("last", name)) => weekday(name, 1),
+        Some(_) => None,
+        None => weekday(&phrase, 0).or_else(|| Span::parse_short(&phrase)?.before(today)),
+    }
+}
+
+/// `s` in lowercase, with its words a single space apart.
+fn phrase(s: &str) -> String {
+    s.to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_when_relative() {
+        let today = date("2024-01-31");
+        assert_eq!(parse_when("3d", today), Some(date("2024-02-03")));
+        assert_eq!(parse_when("2w", today), Some(date("2024-02-14")));
+        assert_eq!(parse_when("1m", today), Some(date("2024-02-29")));
+        assert_eq!(parse_when("12m", today), Some(date("2025-01-31")));
+        assert_eq!(parse_when("0d", today), Some(today));
+    }
+
+    #[test]
+    fn test_parse_since() {
+        // A Wednesday.
+        let today = date("2024-07-03");
+        assert_eq!(parse_since("monday", today), Some(date("2024-07-01")));
+        assert_eq!(parse_since("Wed", today), Some(today));
+        assert_eq!(parse_since("thursday", today), Some(date("2024-06-27")));
+        assert_eq!(parse_since("7d", today), Some(date("2024-06-26")));
+        assert_eq!(parse_since("1m", today), Some(date("2024-06-03")));
+        assert_eq!(parse_since("yesterday", today), Some(date("2024-07-02")));
+        assert_eq!(parse_since("2024-01-01", today), Some(date("2024-01-01")));
+        assert_eq!(parse_since("someday", today), None);
+        assert_eq!(
+            parse_since("last wednesday", today),
+            Some(date("2024-06-26"))
+        );
+        assert_eq!(parse_since("Last  Monday", today), Some(date("2024-07-01")));
+        assert_eq!(parse_since("last week", today), Some(date("2024-06-26")));
+        assert_eq!(parse_since("last month", today), Some(date("2024-06-03")));
+        assert_eq!(parse_since("3 days ago", today), Some(date("2024-06-30")));
+        assert_eq!(parse_since("a week ago", today), Some(date("2024-06-26")));
+        assert_eq!(parse_since("in 3 days", today), None);
+        assert_eq!(parse_since("last", today), None);
+        assert_eq!(parse_since("3 years ago", today), None);
+    }
+
+    #[test]
+    fn test_parse_when_phrases() {
+        // A Friday.
+        let today = date("2024-07-05");
+        assert_eq!(parse_when("today", today), Some(today));
+        assert_eq!(parse_when("Tomorrow", today), Some(date("2024-07-06")));
+        assert_eq!(parse_when("monday", today), Some(date("2024-07-08")));
+        assert_eq!(parse_when("friday", today), Some(date("2024-07-12")));
+        assert_eq!(parse_when("next friday", today), Some(date("2024-07-12")));
+        assert_eq!(parse_when("next sat", today), Some(date("2024-07-06")));
+        assert_eq!(parse_when("next week", today), Some(date("2024-07-12")));
+        assert_eq!(parse_when("next month", today), Some(date("2024-08-05")));
+        assert_eq!(parse_when("in 2 weeks", today), Some(date("2024-07-19")));
+        assert_eq!(parse_when(" in  a day ", today), Some(date("2024-07-06")));
+        assert_eq!(parse_when("in 1 month", today), Some(date("2024-08-05")));
+        assert_eq!(parse_when("this friday", today), None);
+        assert_eq!(parse_when("next", today), None);
+        assert_eq!(parse_when("in 2 fortnights", today), None);
+        assert_eq!(parse_when("3 days ago", today), None);
+        assert_eq!(parse_when("yesterday", today), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(59), "0m");
+        assert_eq!(format_duration(45 * 60), "45m");
+        assert_eq!(format_duration(2 * 3600 + 5 * 60 + 30), "2h 05m");
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let t = parse_timestamp("2024-06-02T12:11:12+02:00").unwrap();
+        assert_eq!(format_timestamp(t), "2024-06-02T10:11:12Z");
+        assert_eq!(parse_timestamp("2024-06-02"), None);
+    }
+
+    #[test]
+    fn test_span() {
+        let today = date("2024-02-29");
+        assert_eq!(Span::parse("14d"), Some(Span::Days(14)));
+        assert_eq!(Span::parse("1y"), Some(Span::Years(1)));
+        assert_eq!(Span::parse("y"), None);
+        assert_eq!(Span::parse("2h"), None);
+        assert_eq!(Span::Years(1).before(today), Some(date("2023-02-28")));
+        assert_eq!(Span::Weeks(1).after(today), Some(date("2024-03-07")));
+    }
+
+    #[test]
+    fn test_parse_when_absolute() {
+        let today = date("2024-01-31");
+        assert_eq!(parse_when("2024-07-01", today), Some(date("2024-07-01")));
+        assert_eq!(parse_when("2024-13-01", today), None);
+        assert_eq!(parse_when("3y", today), None);
+        assert_eq!(parse_when("d", today), None);
+        assert_eq!(parse_when("-3d", today), None);
+        assert_eq!(parse_when("", today), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! `todo fsck`: a strict check of a list's file, line by line, for what a
+//! hand edit or a bad sync merge may have left in it, with the repairs that
+//! can't go wrong.
+
+use std::collections::HashMap;
+
+use crate::{date, tidy, Task};
+
+/// Something wrong with a line of the file.
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    /// The 1-based line number.
+    pub line: usize,
+    pub message: String,
+    /// Whether `repaired` has it repaired.
+    pub fixable: bool,
+}
+
+/// The problems of a file, and its content with the fixable ones repaired.
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    pub problems: Vec<Problem>,
+    pub repaired: Vec<u8>,
+}
+
+/// The tokens whose values are `YYYY-MM-DD` dates.
+const DATE_TOKENS: &[&str] = &["t", "due", "done"];
+
+/// Checks the content of a list file. Lines that aren't valid UTF-8 or
+/// don't read as tasks, bad dates and priorities are left to the user;
+/// carriage returns, blank lines, loosely written checkboxes, a byte order
+/// mark and ids given to several tasks are repaired, the later tasks taking
+/// new ids so that dependencies stay on the first.
+pub fn check(content: &[u8]) -> Check {
+    let mut problems = Vec::new();
+    let mut problem = |line, message: String, fixable| {
+        problems.push(Problem {
+            line,
+            message,
+            fixable,
+        })
+    };
+    let content = content
+        .strip_prefix("\u{feff}".as_bytes())
+        .map_or(content, |rest| {
+            problem(1, "starts with a byte order mark".to_owned(), true);
+            rest
+        });
+
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    // The ids of the tasks, with their lines in `lines` and in the file.
+    let mut ids: Vec<(String, usize, usize)> = Vec::new();
+    let mut after_task = false;
+    let body = content.strip_suffix(b"\n").unwrap_or(content);
+    let raw_lines = (!content.is_empty()).then(|| body.split(|&b| b == b'\n'));
+    for (n, raw) in raw_lines.into_iter().flatten().enumerate() {
+        let n = n + 1;
+        let Ok(text) = std::str::from_utf8(raw) else {
+            problem(n, "isn't valid UTF-8".to_owned(), false);
+            lines.push(raw.to_owned());
+            after_task = false;
+            continue;
+        };
+        let mut text = text.to_owned();
+        if text.contains('\r') {
+            problem(n, "has a carriage return".to_owned(), true);
+            text.retain(|c| c != '\r');
+        }
+        if text.trim().is_empty() {
+            problem(n, "is blank".to_owned(), true);
+            continue;
+        }
+        let is_item = |text: &str| after_task && text.strip_prefix("  ").is_some_and(is_task);
+        if !is_task(&text) && !is_item(&text) {
+            match tidy::task_line(text.trim_end(), &mut tidy::Changes::default()) {
+                Some(canonical) if is_task(&canonical) || is_item(&canonical) => {
+                    problem(
+                        n,
+                        format!("{:?} should be written {:?}", text, canonical),
+                        true,
+                    );
+                    text = canonical;
+                }
+                _ => {
+                    problem(n, format!("{:?} isn't a task", text), false);
+                    lines.push(text.into_bytes());
+                    after_task = false;
+                    continue;
+                }
+            }
+        }
+        after_task = true;
+        if let Ok(task) = text.parse::<Task>() {
+            let data = task.data();
+            for key in DATE_TOKENS {
+                if let Some(value) = data.token(key) {
+                    if date::parse_date(value).is_none() {
+                        problem(
+                            n,
+                            format!("{}:{} isn't a YYYY-MM-DD date", key, value),
+                            false,
+                        );
+                    }
+                }
+            }
+            let note = data.note.as_bytes();
+            if note.len() >= 4
+                && note[0] == b'('
+                && note[2] == b')'
+                && note[3] == b' '
+                && !note[1].is_ascii_uppercase()
+            {
+                problem(
+                    n,
+                    format!(
+                        "{} isn't a priority, which is a capital letter",
+                        &data.note[..3]
+                    ),
+                    false,
+                );
+            }
+            if let Some(id) = data.id() {
+                ids.push((id.to_owned(), lines.len(), n));
+            }
+        }
+        lines.push(text.into_bytes());
+    }
+
+    let mut next = ids
+        .iter()
+        .filter_map(|(id, ..)| id.parse::<u64>().ok())
+        .max()
+        .map_or(1, |id| id + 1);
+    let mut first: HashMap<&str, usize> = HashMap::new();
+    for (id, at, n) in &ids {
+        match first.get(&id[..]) {
+            None => {
+                first.insert(&id[..], *n);
+            }
+            Some(line) => {
+                problem(
+                    *n,
+                    format!("id:{} is also on line {}; giving it id:{}", id, line, next),
+                    true,
+                );
+                let mut task: Task = String::from_utf8_lossy(&lines[*at]).parse().unwrap();
+                task.data_mut().set_token("id", Some(&next.to_string()));
+                lines[*at] = format!("{:?}", task).into_bytes();
+                next += 1;
+            }
+        }
+    }
+    problems.sort_by_key(|p| p.line);
+
+    let mut repaired = Vec::new();
+    for line in lines {
+        repaired.extend(line);
+        repaired.push(b'\n');
+    }
+    Check { problems, repaired }
+}
+
+fn is_task(text: &str) -> bool {
+    text.parse::<Task>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let content = b"\xef\xbb\xbf- [ ] buy milk id:1\r\n\n* [X] call mom\n\
+                        \x20 - [ ] item\nnotes\n- [ ] pay rent due:2024-13-01\n\
+                        - [ ] (a) water plants id:1\n- [ ] caf\xe9\n";
+        let check = check(content);
+        let problems: Vec<(usize, &str, bool)> = check
+            .problems
+            .iter()
+            .map(|p| (p.line, &p.message[..], p.fixable))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                (1, "starts with a byte order mark", true),
+                (1, "has a carriage return", true),
+                (2, "is blank", true),
+                (
+                    3,
+                    "\"* [X] call mom\" should be written \"- [x] call mom\"",
+                    true
+                ),
+                (5, "\"notes\" isn't a task", false),
+                (6, "due:2024-13-01 isn't a YYYY-MM-DD date", false),
+                (7, "(a) isn't a priority, which is a capital letter", false),
+                (7, "id:1 is also on line 1; giving it id:2", true),
+                (8, "isn't valid UTF-8", false),
+            ]
+        );
+        assert_eq!(
+            check.repaired,
+            b"- [ ] buy milk id:1\n- [x] call mom\n  - [ ] item\nnotes\n\
+              - [ ] pay rent due:2024-13-01\n- [ ] (a) water plants id:2\n- [ ] caf\xe9\n"
+        );
+
+        let clean = b"- [ ] buy milk\n  - [x] oat milk\n- [x] call mom done:2024-07-01\n";
+        let check = super::check(clean);
+        assert!(check.problems.is_empty());
+        assert_eq!(check.repaired, clean);
+    }
+}
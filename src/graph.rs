@@ -0,0 +1,88 @@
+//! The chart of `todo stats --graph`: a cell per day for the tasks checked
+//! that day, with the weekdays under them.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::Render;
+
+/// The cells of days with completions, from fewest to most.
+const BARS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+const ASCII_BARS: [&str; 8] = ["_", "-", "~", "=", "+", "*", "#", "@"];
+
+/// The chart of the `days` days up to `today` from `counts`, the tasks
+/// checked per day: a line of cells, as high as each day's count is of the
+/// most in a day, then a line of weekday initials and a summary line.
+pub fn render(
+    render: &Render,
+    counts: &BTreeMap<NaiveDate, usize>,
+    today: NaiveDate,
+    days: u32,
+) -> String {
+    let first = today - Duration::days(i64::from(days.max(1)) - 1);
+    let shown: Vec<(NaiveDate, usize)> = first
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(|day| (day, counts.get(&day).copied().unwrap_or(0)))
+        .collect();
+    let most = shown.iter().map(|&(_, n)| n).max().unwrap_or(0);
+
+    let mut cells = String::new();
+    let mut weekdays = String::new();
+    for &(day, n) in &shown {
+        if n == 0 {
+            let dot = render.glyph("·", ".");
+            cells.push_str(&render.style(render.theme.muted).paint(dot).to_string());
+        } else {
+            let level = (n * BARS.len()).div_ceil(most) - 1;
+            cells.push_str(render.glyph(BARS[level], ASCII_BARS[level]));
+        }
+        weekdays.push_str(&day.weekday().to_string()[..1]);
+    }
+    let total: usize = shown.iter().map(|&(_, n)| n).sum();
+    let summary = match shown.iter().find(|&&(_, n)| n == most) {
+        Some(&(day, n)) if n > 0 => format!(
+            "{} checked from {} to {}, at most {} on {}",
+            total, first, today, n, day
+        ),
+        _ => format!("nothing checked from {} to {}", first, today),
+    };
+    format!("{}\n{}\n{}\n", cells, weekdays, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::parse_date;
+
+    #[test]
+    fn test_render() {
+        let today = parse_date("2024-07-01").unwrap();
+        let counts = BTreeMap::from([
+            (parse_date("2024-06-20").unwrap(), 5),
+            (parse_date("2024-06-25").unwrap(), 8),
+            (parse_date("2024-06-30").unwrap(), 1),
+            (parse_date("2024-07-02").unwrap(), 3),
+        ]);
+        let render = |counts: &BTreeMap<NaiveDate, usize>, days| {
+            render(&Render::default(), counts, today, days)
+                .replace("\x1b[2m", "")
+                .replace("\x1b[0m", "")
+        };
+        assert_eq!(
+            render(&counts, 14),
+            "··▅····█····▁·\nTWTFSSMTWTFSSM\n\
+             14 checked from 2024-06-18 to 2024-07-01, at most 8 on 2024-06-25\n"
+        );
+        assert_eq!(
+            render(&counts, 7),
+            "█····▁·\nTWTFSSM\n\
+             9 checked from 2024-06-25 to 2024-07-01, at most 8 on 2024-06-25\n"
+        );
+        assert_eq!(
+            render(&BTreeMap::new(), 1),
+            "·\nM\nnothing checked from 2024-07-01 to 2024-07-01\n"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! The user's command run after each change to a list, such as one that
+//! regenerates a dashboard. Like the sync commands, it finds the list's file
+//! in `TODO_FILE`, and what changed in `TODO_VERB`, `TODO_COUNT`,
+//! `TODO_SUMMARY`, `TODO_INDEX` and `TODO_NOTE`.
+
+use std::io;
+use std::path::Path;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::sync::shell;
+use crate::{Activity, Render, TodoError};
+
+/// How long a hook may run when the configuration doesn't say.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The tasks `changes` changed, counted by verb in the order they come, as
+/// in "check 1 task, remove 12 tasks". Empty if there are none.
+pub fn summary(changes: &[Activity]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for change in changes {
+        match counts.iter_mut().find(|(verb, _)| *verb == change.verb) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((change.verb, 1)),
+        }
+    }
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(verb, n)| format!("{} {} task{}", verb, n, if *n == 1 { "" } else { "s" }))
+        .collect();
+    counts.join(", ")
+}
+
+/// Runs `command` once for the command `verb` that made `changes` to the
+/// list at `path`, however many there are: their number and `summary` go
+/// in `TODO_COUNT` and `TODO_SUMMARY`, and the index and note of a single
+/// change in `TODO_INDEX`, numbered as `render` shows tasks, and
+/// `TODO_NOTE`. Its output goes to stderr, so as not to mix with listings.
+/// A run that takes more than `timeout` is killed.
+pub fn run(
+    render: &Render,
+    command: &str,
+    path: &Path,
+    verb: &str,
+    changes: &[Activity],
+    timeout: Duration,
+) -> Result<(), TodoError> {
+    let error = |message: String| TodoError::Hook {
+        command: command.to_owned(),
+        message,
+    };
+    let single = match changes {
+        [change] => Some(change),
+        _ => None,
+    };
+    let mut child = shell(command)
+        .env("TODO_FILE", path)
+        .env("TODO_VERB", verb)
+        .env("TODO_COUNT", changes.len().to_string())
+        .env("TODO_SUMMARY", summary(changes))
+        .env(
+            "TODO_INDEX",
+            single.map_or(String::new(), |c| render.shown_index(c.index).to_string()),
+        )
+        .env("TODO_NOTE", single.map_or("", |c| &c.note[..]))
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(io::stderr()))
+        .spawn()
+        .map_err(|e| error(e.to_string()))?;
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().map_err(|e| error(e.to_string()))? {
+            if status.success() {
+                return Ok(());
+            }
+            return Err(error(status.to_string()));
+        }
+        if started.elapsed() >= timeout {
+            // It may have just exited; either way it's done.
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(error(format!("stopped after {}s", timeout.as_secs_f64())));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let log = dir.path().join("log");
+        let command = format!(
+            "echo \"$TODO_VERB $TODO_COUNT $TODO_INDEX $TODO_NOTE: $TODO_SUMMARY\" >> '{}'",
+            log.display()
+        );
+        let render = Render::default();
+        let changes = [
+            Activity::new("check", 1, "buy milk"),
+            Activity::new("add", 3, "call mom"),
+            Activity::new("check", 4, "pay rent"),
+        ];
+        run(&render, &command, &path, "do", &changes, DEFAULT_TIMEOUT).unwrap();
+        run(
+            &render,
+            &command,
+            &path,
+            "check",
+            &changes[..1],
+            DEFAULT_TIMEOUT,
+        )
+        .unwrap();
+        run(&render, &command, &path, "undo", &[], DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "do 3  : check 2 tasks, add 1 task\n\
+             check 1 1 buy milk: check 1 task\n\
+             undo 0  : \n"
+        );
+
+        let err = run(&render, "exit 3", &path, "undo", &[], DEFAULT_TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("exit status: 3"), "{}", err);
+        let started = Instant::now();
+        let err = run(
+            &render,
+            "sleep 5",
+            &path,
+            "undo",
+            &[],
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("stopped after 0.1s"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
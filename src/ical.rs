@@ -0,0 +1,140 @@
+//! iCalendar (RFC 5545) output of tasks as VTODO components.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// A task as exported to a VTODO.
+pub struct Todo<'a> {
+    pub uid: String,
+    pub summary: &'a str,
+    pub done: bool,
+    pub due: Option<NaiveDate>,
+}
+
+/// Escapes a TEXT value: backslashes, semicolons and commas are prefixed
+/// with a backslash and newlines become `\n`.
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Folds a content line so that no line exceeds 75 octets, continuing on
+/// lines that start with a space, and terminates it with CRLF.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// A 64-bit FNV-1a hash, which unlike `std`'s hasher is stable across
+/// releases and so suitable for UIDs.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub fn calendar(todos: &[Todo], now: DateTime<Utc>) -> String {
+    let stamp = now.format("%Y%m%dT%H%M%SZ");
+    let mut out = String::new();
+    out.push_str(&fold("BEGIN:VCALENDAR"));
+    out.push_str(&fold("VERSION:2.0"));
+    out.push_str(&fold("PRODID:-//todo.rs//todo//EN"));
+    for todo in todos {
+        out.push_str(&fold("BEGIN:VTODO"));
+        out.push_str(&fold(&format!("UID:{}", todo.uid)));
+        out.push_str(&fold(&format!("DTSTAMP:{}", stamp)));
+        out.push_str(&fold(&format!("SUMMARY:{}", escape_text(todo.summary))));
+        let status = if todo.done {
+            "COMPLETED"
+        } else {
+            "NEEDS-ACTION"
+        };
+        out.push_str(&fold(&format!("STATUS:{}", status)));
+        if let Some(due) = todo.due {
+            out.push_str(&fold(&format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d"))));
+        }
+        out.push_str(&fold("END:VTODO"));
+    }
+    out.push_str(&fold("END:VCALENDAR"));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(escape_text("buy milk"), "buy milk");
+        assert_eq!(
+            escape_text(r"eggs, milk; bread\butter"),
+            r"eggs\, milk\; bread\\butter"
+        );
+        assert_eq!(escape_text("line one\r\nline two"), r"line one\nline two");
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("SUMMARY:short"), "SUMMARY:short\r\n");
+
+        let long = format!("SUMMARY:{}", "x".repeat(100));
+        let folded = fold(&long);
+        let lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines.concat().replace("\r\n ", "").len(), long.len() + 1);
+
+        let wide = format!("SUMMARY:{}", "買".repeat(30));
+        for line in fold(&wide).split("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!(fold(&wide).replace("\r\n ", ""), format!("{}\r\n", wide));
+    }
+
+    #[test]
+    fn test_calendar() {
+        let now = DateTime::parse_from_rfc3339("2024-06-02T10:11:12Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let todos = [Todo {
+            uid: "1@todo".to_owned(),
+            summary: "pay rent, today",
+            done: false,
+            due: NaiveDate::from_ymd_opt(2024, 7, 1),
+        }];
+        assert_eq!(
+            calendar(&todos, now),
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//todo.rs//todo//EN\r\n\
+             BEGIN:VTODO\r\nUID:1@todo\r\nDTSTAMP:20240602T101112Z\r\n\
+             SUMMARY:pay rent\\, today\r\nSTATUS:NEEDS-ACTION\r\n\
+             DUE;VALUE=DATE:20240701\r\nEND:VTODO\r\nEND:VCALENDAR\r\n"
+        );
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
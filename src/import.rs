@@ -0,0 +1,194 @@
+//! Conversion of other tools' task lists into todo notes.
+
+use chrono::{Local, NaiveDateTime};
+use serde::Deserialize;
+
+use crate::TodoError;
+
+#[derive(Deserialize)]
+struct TaskwarriorTask {
+    status: String,
+    description: String,
+    priority: Option<String>,
+    due: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// The tasks of a Taskwarrior export, as `(done, note)` pairs, and the
+/// statuses that were skipped with their counts.
+#[derive(Debug, Default, PartialEq)]
+pub struct TaskwarriorImport {
+    pub tasks: Vec<(bool, String)>,
+    pub skipped: Vec<(String, usize)>,
+}
+
+/// Converts the JSON array written by `task export`. Pending and completed
+/// tasks are kept, with H/M/L priorities mapped to (A)/(B)/(C), due dates
+/// to `due:` tokens in local time and tags to `#tag` words; tasks with any
+/// other status are skipped.
+pub fn taskwarrior(json: &str) -> Result<TaskwarriorImport, TodoError> {
+    let exported: Vec<TaskwarriorTask> =
+        serde_json::from_str(json).map_err(|e| TodoError::Import {
+            message: e.to_string(),
+        })?;
+
+    let mut import = TaskwarriorImport::default();
+    for task in exported {
+        let done = match &task.status[..] {
+            "pending" => false,
+            "completed" => true,
+            status => {
+                match import.skipped.iter_mut().find(|(s, _)| s == status) {
+                    Some((_, count)) => *count += 1,
+                    None => import.skipped.push((status.to_owned(), 1)),
+                }
+                continue;
+            }
+        };
+
+        let mut words = Vec::new();
+        match task.priority.as_deref() {
+            Some("H") => words.push("(A)".to_owned()),
+            Some("M") => words.push("(B)".to_owned()),
+            Some("L") => words.push("(C)".to_owned()),
+            _ => (),
+        }
+        words.push(
+            task.description
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        if let Some(due) = &task.due {
+            let due = NaiveDateTime::parse_from_str(due, "%Y%m%dT%H%M%SZ").map_err(|_| {
+                TodoError::Import {
+                    message: format!("invalid due date '{}'", due),
+                }
+            })?;
+            let due = due.and_utc().with_timezone(&Local).date_naive();
+            words.push(format!("due:{}", due.format("%Y-%m-%d")));
+        }
+        words.extend(task.tags.iter().map(|t| format!("#{}", t)));
+        import.tasks.push((done, words.join(" ")));
+    }
+    Ok(import)
+}
+
+/// Extracts the checkboxes of a markdown document as `(done, note)` pairs:
+/// list items starting with `[ ]`, `[x]` or `[X]`, at any indentation. The
+/// rest of the document, including fenced code blocks and checkboxes
+/// without text, is ignored.
+pub fn markdown(text: &str) -> Vec<(bool, String)> {
+    let mut tasks = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let line = line.trim_start();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|bullet| line.strip_prefix(bullet))
+        else {
+            continue;
+        };
+        let item = item.trim_start();
+        let (done, note) = match item.get(..3) {
+            Some("[ ]") => (false, &item[3..]),
+            Some("[x]" | "[X]") => (true, &item[3..]),
+            _ => continue,
+        };
+        if !note.is_empty() && !note.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let note = note.trim();
+        if !note.is_empty() {
+            tasks.push((done, note.to_owned()));
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taskwarrior() {
+        let json = r#"[
+            {"id": 1, "status": "pending", "description": "buy milk", "priority": "H",
+             "tags": ["home", "errand"], "uuid": "a"},
+            {"id": 0, "status": "completed", "description": "file  taxes", "priority": "L"},
+            {"id": 0, "status": "deleted", "description": "old"},
+            {"id": 2, "status": "waiting", "description": "later"},
+            {"id": 3, "status": "deleted", "description": "older"},
+            {"id": 4, "status": "pending", "description": "call mom", "priority": "X"}
+        ]"#;
+        assert_eq!(
+            taskwarrior(json).unwrap(),
+            TaskwarriorImport {
+                tasks: vec![
+                    (false, "(A) buy milk #home #errand".to_owned()),
+                    (true, "(C) file taxes".to_owned()),
+                    (false, "call mom".to_owned()),
+                ],
+                skipped: vec![("deleted".to_owned(), 2), ("waiting".to_owned(), 1)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        let text = "\
+# Meeting 2024-07-01
+
+- [ ] send the minutes
+- [x] book the room
+* notes:
+  * [X] nested item
+    + [ ]   deeply nested
+- [ ]
+- [link](https://example.com)
+- [x]not a checkbox
+1. [ ] numbered items aren't picked up
+
+```
+- [ ] inside a code block
+```
+";
+        assert_eq!(
+            markdown(text),
+            vec![
+                (false, "send the minutes".to_owned()),
+                (true, "book the room".to_owned()),
+                (true, "nested item".to_owned()),
+                (false, "deeply nested".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_taskwarrior_due() {
+        let json =
+            r#"[{"status": "pending", "description": "pay rent", "due": "20240701T120000Z"}]"#;
+        let import = taskwarrior(json).unwrap();
+        assert!(import.tasks[0].1.starts_with("pay rent due:2024-07-0"));
+    }
+
+    #[test]
+    fn test_taskwarrior_malformed() {
+        assert!(matches!(
+            taskwarrior("[{\"status\": \"pending\"}"),
+            Err(TodoError::Import { .. })
+        ));
+        assert!(matches!(
+            taskwarrior(r#"[{"status": "pending", "description": "x", "due": "tomorrow"}]"#),
+            Err(TodoError::Import { .. })
+        ));
+    }
+}
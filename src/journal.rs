@@ -0,0 +1,483 @@
+//! The undo journal of a todo file, kept with its backups: how to get back
+//! the content the file had before each of the last saves made by todo,
+//! and after each save undone since.
+//!
+//! Each undo entry names either the backup taken before a save or, for an
+//! append, the length the file had, along with the length and modification
+//! time the save left the file with and what the save did. Undoing moves
+//! the entry to the redo entries, with the backup of the content it
+//! replaced, and any other save clears them. An entry is only applied while
+//! the file still has the length and modification time it expects, so a
+//! file that has been changed by something else, such as an editor, is
+//! never rolled back over those changes, and the file is only read to undo.
+//! Like the check a list makes before saving, this can miss an edit that
+//! keeps the length within the same tick of the file system's clock.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::backup::{self, KEEP};
+use crate::{ensure_writable, is_dry_run, replace_file, Activity, Fingerprint, TodoError};
+
+/// Where content before or after a save can be found.
+#[derive(Clone, Debug, PartialEq)]
+enum Content {
+    /// The backup with this file name.
+    Backup(String),
+    /// The first this many bytes of the current file, for a save that only
+    /// appended to it.
+    Prefix(u64),
+}
+
+impl Content {
+    fn parse(kind: &str, value: &str) -> Option<Self> {
+        match kind {
+            "backup" => Some(Content::Backup(value.to_owned())),
+            "prefix" => value.parse().ok().map(Content::Prefix),
+            _ => None,
+        }
+    }
+
+    /// The content, if it can still be told, given the current content of
+    /// the file at `path`.
+    fn read(&self, path: &Path, current: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Content::Backup(name) => fs::read(backup::backups_dir(path).join(name)).ok(),
+            Content::Prefix(len) => current.get(..*len as usize).map(<[u8]>::to_vec),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        match self {
+            Content::Backup(name) => backup::backups_dir(path).join(name).exists(),
+            Content::Prefix(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Content {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Content::Backup(name) => write!(f, "backup {}", name),
+            Content::Prefix(len) => write!(f, "prefix {}", len),
+        }
+    }
+}
+
+/// A save that can be undone.
+#[derive(Clone, Debug, PartialEq)]
+struct Undo {
+    before: Content,
+    /// The file as the save left it.
+    after: Fingerprint,
+    /// What the save did, as in the activity log.
+    what: String,
+}
+
+/// An undone save that can be made again.
+#[derive(Clone, Debug, PartialEq)]
+struct Redo {
+    /// The content the save left, backed up by the undo.
+    after: Content,
+    /// The file as the undo left it.
+    before: Fingerprint,
+    undo: Undo,
+}
+
+#[derive(Debug, Default)]
+struct Journal {
+    undo: Vec<Undo>,
+    redo: Vec<Redo>,
+}
+
+impl Undo {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, ' ');
+        let before = Content::parse(fields.next()?, fields.next()?)?;
+        let after = parse_stamp(fields.next()?)?;
+        let what = fields.next().unwrap_or_default().to_owned();
+        Some(Undo {
+            before,
+            after,
+            what,
+        })
+    }
+}
+
+impl fmt::Display for Undo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.before, Stamp(&self.after), self.what)
+    }
+}
+
+impl Redo {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, ' ');
+        let after = Content::parse(fields.next()?, fields.next()?)?;
+        let before = parse_stamp(fields.next()?)?;
+        let undo = Undo::parse(fields.next()?)?;
+        Some(Redo {
+            after,
+            before,
+            undo,
+        })
+    }
+}
+
+impl fmt::Display for Redo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.after, Stamp(&self.before), self.undo)
+    }
+}
+
+/// A fingerprint as the journal keeps it: the length, then the
+/// modification time in nanoseconds since the epoch or `-` if it's
+/// unknown, as in `42:1719792000000000000`.
+struct Stamp<'a>(&'a Fingerprint);
+
+impl fmt::Display for Stamp<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let since_epoch = self
+            .0
+            .modified
+            .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok());
+        match since_epoch {
+            Some(since) => write!(f, "{}:{}", self.0.len, since.as_nanos()),
+            None => write!(f, "{}:-", self.0.len),
+        }
+    }
+}
+
+fn parse_stamp(stamp: &str) -> Option<Fingerprint> {
+    let (len, modified) = stamp.split_once(':')?;
+    let modified = match modified {
+        "-" => None,
+        nanos => {
+            let nanos: u128 = nanos.parse().ok()?;
+            let since = Duration::new(
+                u64::try_from(nanos / 1_000_000_000).ok()?,
+                (nanos % 1_000_000_000) as u32,
+            );
+            Some(SystemTime::UNIX_EPOCH.checked_add(since)?)
+        }
+    };
+    Some(Fingerprint {
+        len: len.parse().ok()?,
+        modified,
+    })
+}
+
+fn journal_path(path: &Path) -> PathBuf {
+    backup::backups_dir(path).join("journal")
+}
+
+/// The journal of the file at `path`, leaving out the entries whose backup
+/// has been removed.
+fn read(path: &Path) -> Journal {
+    let mut journal = Journal::default();
+    for line in fs::read_to_string(journal_path(path))
+        .unwrap_or_default()
+        .lines()
+    {
+        match line.split_once(' ') {
+            Some(("undo", rest)) => journal.undo.extend(Undo::parse(rest)),
+            Some(("redo", rest)) => journal.redo.extend(Redo::parse(rest)),
+            _ => {}
+        }
+    }
+    journal.undo.retain(|u| u.before.exists(path));
+    journal
+        .redo
+        .retain(|r| r.after.exists(path) && r.undo.before.exists(path));
+    journal
+}
+
+fn write(path: &Path, journal: &Journal) -> io::Result<()> {
+    fs::create_dir_all(backup::backups_dir(path))?;
+    replace_file(&journal_path(path), |file| {
+        for undo in &journal.undo {
+            writeln!(file, "undo {}", undo)?;
+        }
+        for redo in &journal.redo {
+            writeln!(file, "redo {}", redo)?;
+        }
+        Ok(())
+    })
+}
+
+/// Describes the operations of a save, as the first one and how many more.
+pub fn describe(activity: &[Activity]) -> String {
+    match activity {
+        [] => String::new(),
+        [only] => only.to_string(),
+        [first, rest @ ..] => format!("{} and {} more", first, rest.len()),
+    }
+}
+
+/// Records a save that replaced the file at `path`, given the name of the
+/// backup `backup::take` made of it, if it had any content, its
+/// fingerprint before the save, and what the save did.
+pub fn record_save(path: &Path, backup: Option<String>, previous: Option<Fingerprint>, what: &str) {
+    // A journal that couldn't be updated no longer matches the file, so
+    // it's turned down by `undo_last` rather than applied wrongly.
+    let _ = record(
+        path,
+        backup.map_or(Content::Prefix(0), Content::Backup),
+        previous,
+        what,
+    );
+}
+
+/// Records a save that appended to the file at `path`, which was `len`
+/// bytes long and had the fingerprint `previous` before.
+pub fn record_append(path: &Path, len: u64, previous: Option<Fingerprint>, what: &str) {
+    let _ = record(path, Content::Prefix(len), previous, what);
+}
+
+/// Records a save of the file at `path`, which was `before` it, and
+/// clears the redo entries. Undo entries are dropped when the file they
+/// left was changed by something else before this save, going by its
+/// fingerprint `previous`, and beyond the last `KEEP`.
+fn record(
+    path: &Path,
+    before: Content,
+    previous: Option<Fingerprint>,
+    what: &str,
+) -> io::Result<()> {
+    let after = Fingerprint::of(path).ok_or(io::ErrorKind::NotFound)?;
+    let mut journal = read(path);
+    if journal.undo.last().map(|u| u.after) != previous {
+        journal.undo.clear();
+    }
+    journal.undo.push(Undo {
+        before,
+        after,
+        what: what.to_owned(),
+    });
+    let skip = journal.undo.len().saturating_sub(KEEP);
+    journal.undo.drain(..skip);
+    journal.redo.clear();
+    write(path, &journal)
+}
+
+/// The number of saves of the file at `path` that can be undone, and of
+/// those that can be redone.
+pub fn len(path: &Path) -> (usize, usize) {
+    let journal = read(path);
+    (journal.undo.len(), journal.redo.len())
+}
+
+fn current(path: &Path) -> Result<Vec<u8>, TodoError> {
+    match fs::read(path) {
+        Ok(current) => Ok(current),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Backs up the file at `path` and replaces its content with `content`,
+/// returning the name of the backup.
+fn replace(path: &Path, content: &[u8]) -> io::Result<Option<String>> {
+    let backup = backup::take(path)?;
+    replace_file(path, |file| file.write_all(content))?;
+    Ok(backup)
+}
+
+/// Gives the file at `path` back the content it had before the last save
+/// made by todo, returning what the save did and how many earlier saves can
+/// still be undone. The content being replaced is backed up first, so that
+/// `redo` and `todo recover` can bring it back.
+pub fn undo_last(path: &Path) -> Result<(String, usize), TodoError> {
+    ensure_writable(path)?;
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let path = target.as_path();
+    let save_error = |source| TodoError::Save {
+        path: path.to_owned(),
+        source,
+    };
+    let mut journal = read(path);
+    let undo = journal.undo.pop().ok_or(TodoError::NothingToUndo)?;
+    let content = Some(current(path)?)
+        .filter(|_| Fingerprint::of(path) == Some(undo.after))
+        .and_then(|c| undo.before.read(path, &c));
+    let Some(content) = content else {
+        if !is_dry_run() {
+            write(path, &Journal::default()).map_err(save_error)?;
+        }
+        return Err(TodoError::ChangedElsewhere);
+    };
+    if is_dry_run() {
+        return Ok((undo.what, journal.undo.len()));
+    }
+    let backup = replace(path, &content).map_err(save_error)?;
+    let restored =
+        Fingerprint::of(path).ok_or_else(|| save_error(io::ErrorKind::NotFound.into()))?;
+    // The file has the content the save before left again, if not its
+    // modification time.
+    if let Some(last) = journal.undo.last_mut() {
+        last.after = restored;
+    }
+    journal.redo.push(Redo {
+        after: backup.map_or(Content::Prefix(0), Content::Backup),
+        before: restored,
+        undo: undo.clone(),
+    });
+    write(path, &journal).map_err(save_error)?;
+    Ok((undo.what, journal.undo.len()))
+}
+
+/// Makes the last save undone by `undo_last` again, returning what it did.
+pub fn redo(path: &Path) -> Result<String, TodoError> {
+    ensure_writable(path)?;
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let path = target.as_path();
+    let save_error = |source| TodoError::Save {
+        path: path.to_owned(),
+        source,
+    };
+    let mut journal = read(path);
+    let redo = journal.redo.pop().ok_or(TodoError::NothingToRedo)?;
+    let content = Some(current(path)?)
+        .filter(|_| Fingerprint::of(path) == Some(redo.before))
+        .and_then(|c| redo.after.read(path, &c));
+    let Some(content) = content else {
+        journal.redo.clear();
+        if !is_dry_run() {
+            write(path, &journal).map_err(save_error)?;
+        }
+        return Err(TodoError::ChangedElsewhere);
+    };
+    if is_dry_run() {
+        return Ok(redo.undo.what);
+    }
+    replace(path, &content).map_err(save_error)?;
+    let after = Fingerprint::of(path).ok_or_else(|| save_error(io::ErrorKind::NotFound.into()))?;
+    // Likewise, the file is as the undo before this one left it.
+    if let Some(last) = journal.redo.last_mut() {
+        last.before = after;
+    }
+    journal.undo.push(Undo {
+        after,
+        ..redo.undo.clone()
+    });
+    write(path, &journal).map_err(save_error)?;
+    Ok(redo.undo.what)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{stream, TodoList};
+
+    #[test]
+    fn test_undo_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        assert!(matches!(undo_last(&path), Err(TodoError::NothingToUndo)));
+
+        TodoList::load(&path).unwrap().add("buy milk").unwrap();
+        stream::append(&path, "call mom").unwrap();
+        TodoList::load(&path).unwrap().check(1).unwrap();
+        assert_eq!(len(&path), (3, 0));
+
+        assert_eq!(
+            undo_last(&path).unwrap(),
+            ("check 1 \"buy milk\"".to_owned(), 2)
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [ ] call mom\n"
+        );
+        assert_eq!(
+            undo_last(&path).unwrap(),
+            ("add 2 \"call mom\"".to_owned(), 1)
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- [ ] buy milk\n");
+        assert_eq!(undo_last(&path).unwrap().1, 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        assert!(matches!(undo_last(&path), Err(TodoError::NothingToUndo)));
+    }
+
+    #[test]
+    fn test_redo() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        assert!(matches!(redo(&path), Err(TodoError::NothingToRedo)));
+        TodoList::load(&path).unwrap().add("buy milk").unwrap();
+        TodoList::load(&path).unwrap().check(1).unwrap();
+        let checked = fs::read_to_string(&path).unwrap();
+
+        undo_last(&path).unwrap();
+        undo_last(&path).unwrap();
+        assert_eq!(len(&path), (0, 2));
+        assert_eq!(redo(&path).unwrap(), "add 1 \"buy milk\"");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- [ ] buy milk\n");
+        assert_eq!(redo(&path).unwrap(), "check 1 \"buy milk\"");
+        assert_eq!(fs::read_to_string(&path).unwrap(), checked);
+        assert!(matches!(redo(&path), Err(TodoError::NothingToRedo)));
+
+        // Undoing works as before, and a new save clears what could be
+        // redone.
+        undo_last(&path).unwrap();
+        TodoList::load(&path).unwrap().add("call mom").unwrap();
+        assert_eq!(len(&path), (2, 0));
+        assert!(matches!(redo(&path), Err(TodoError::NothingToRedo)));
+
+        undo_last(&path).unwrap();
+        fs::write(&path, "- [ ] buy bread\n").unwrap();
+        assert!(matches!(redo(&path), Err(TodoError::ChangedElsewhere)));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- [ ] buy bread\n");
+    }
+
+    #[test]
+    fn test_changed_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        TodoList::load(&path).unwrap().add("buy milk").unwrap();
+        TodoList::load(&path).unwrap().add("call mom").unwrap();
+
+        fs::write(&path, "- [ ] buy milk\n- [ ] call dad too\n").unwrap();
+        assert!(matches!(undo_last(&path), Err(TodoError::ChangedElsewhere)));
+        assert_eq!(len(&path), (0, 0));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [ ] call dad too\n"
+        );
+
+        // A save on top of the edit starts the journal over.
+        TodoList::load(&path).unwrap().add("water plants").unwrap();
+        assert_eq!(len(&path), (1, 0));
+        assert_eq!(undo_last(&path).unwrap().1, 0);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [ ] call dad too\n"
+        );
+    }
+
+    #[test]
+    fn test_stamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        fs::write(&path, "- [ ] buy milk\n").unwrap();
+        let fingerprint = Fingerprint::of(&path).unwrap();
+        let stamp = Stamp(&fingerprint).to_string();
+        assert!(stamp.starts_with("15:"), "{}", stamp);
+        assert_eq!(parse_stamp(&stamp), Some(fingerprint));
+        let unknown = parse_stamp("15:-").unwrap();
+        assert_eq!(Stamp(&unknown).to_string(), "15:-");
+        assert_eq!(parse_stamp("00c0ffee00c0ffee"), None);
+    }
+
+    #[test]
+    fn test_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        for i in 0..KEEP + 5 {
+            TodoList::load(&path).unwrap().add(&i.to_string()).unwrap();
+        }
+        assert_eq!(len(&path).0, KEEP);
+    }
+}
@@ -1,240 +1,6747 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error;
 use std::fmt;
-use std::fs::OpenOptions;
+use std::fs::{self, File, OpenOptions};
 use std::io;
 use std::io::prelude::*;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
-use regex::Regex;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::Deserialize;
 
-use ansi_term::Colour::*;
 use ansi_term::Style;
 
+use template::Template;
+use theme::Theme;
+
+pub mod activity;
+pub mod backup;
+pub mod cal;
+pub mod clipboard;
+pub mod config;
+pub mod conflicts;
+pub mod date;
+pub mod fsck;
+pub mod graph;
+pub mod hooks;
+pub mod ical;
+pub mod import;
+pub mod journal;
+pub mod links;
+pub mod lock;
+pub mod man;
+pub mod merge;
+pub mod org;
+pub mod pager;
+pub mod paths;
+#[cfg(all(feature = "picker", unix))]
+pub mod picker;
+pub mod plugins;
+pub mod renumber;
+pub mod report;
+pub mod review;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod status;
+pub mod stream;
+pub mod sync;
+pub mod template;
+pub mod theme;
+pub mod tidy;
+#[cfg(feature = "webdav")]
+pub mod webdav;
+pub mod wrap;
+
+/// How output looks: the colors, symbols and layout that the command line
+/// and the config chose, which everything that renders tasks is given.
+#[derive(Clone, Debug)]
+pub struct Render {
+    /// Whether output is colored.
+    pub color: bool,
+    /// Whether the terminal can show struck-through text; done tasks are
+    /// only dimmed when it can't.
+    pub strikethrough: bool,
+    /// Keeps the output to ASCII, for terminals that can't show anything
+    /// else: the ASCII status symbols whatever `symbols` says, and ASCII in
+    /// place of every other glyph.
+    pub ascii: bool,
+    pub symbols: Symbols,
+    /// The colors of listings.
+    pub theme: Theme,
+    /// Lists in the scripting layout, `Template::plain`, unless there's a
+    /// `template`.
+    pub plain: bool,
+    /// Ends the records of the scripting layout with NUL bytes rather than
+    /// newlines, for notes containing characters that shells split on.
+    pub print0: bool,
+    /// Lists each task as a line of this template.
+    pub template: Option<Template>,
+    /// Shows due dates as written rather than relative to today, as in
+    /// "due in 5d".
+    pub absolute_dates: bool,
+    /// The width that listed notes are wrapped to; 0 turns wrapping off.
+    pub width: usize,
+    /// Makes the URLs of listed notes links the terminal can open; see
+    /// `links::hyperlink`.
+    pub hyperlinks: bool,
+    /// Cuts listed notes to one line of `width`, ending in an ellipsis,
+    /// instead of wrapping them.
+    pub oneline: bool,
+    /// Shows the age of each task at the end of its line, as `format_age`
+    /// puts it, stressing the ages over this many days.
+    pub ages: Option<u32>,
+    /// The streaks of daily tasks, shown at the end of their lines.
+    pub streaks: Option<Streaks>,
+    /// Numbers tasks from 0 rather than 1 wherever they're shown. Indices
+    /// stay 1-based in the library; `shown_index` and `given_index`
+    /// translate them at the edges.
+    pub zero_indexed: bool,
+}
+
+impl Default for Render {
+    /// Colors and Unicode, with the dark theme and nothing wrapped.
+    fn default() -> Self {
+        Render {
+            color: true,
+            strikethrough: true,
+            ascii: false,
+            symbols: Symbols::Unicode,
+            theme: Theme::default(),
+            plain: false,
+            print0: false,
+            template: None,
+            absolute_dates: false,
+            width: 0,
+            hyperlinks: false,
+            oneline: false,
+            ages: None,
+            streaks: None,
+            zero_indexed: false,
+        }
+    }
+}
+
+impl Render {
+    /// Returns `s`, or the plain style when color is turned off.
+    pub fn style(&self, s: impl Into<Style>) -> Style {
+        if self.color {
+            s.into()
+        } else {
+            Style::new()
+        }
+    }
+
+    /// `unicode`, or `ascii` in ASCII output.
+    pub fn glyph(&self, unicode: &'static str, ascii: &'static str) -> &'static str {
+        if self.ascii {
+            ascii
+        } else {
+            unicode
+        }
+    }
+
+    /// The number the task at `index` is shown with.
+    pub fn shown_index(&self, index: usize) -> usize {
+        match self.zero_indexed {
+            true => index.saturating_sub(1),
+            false => index,
+        }
+    }
+
+    /// The index of the task shown as `number`.
+    pub fn given_index(&self, number: usize) -> usize {
+        match self.zero_indexed {
+            true => number + 1,
+            false => number,
+        }
+    }
+
+    /// The symbols listings use, which are ASCII in ASCII output.
+    fn symbols(&self) -> Symbols {
+        if self.ascii {
+            Symbols::Ascii
+        } else {
+            self.symbols
+        }
+    }
+}
+
+/// The sets of symbols that mark a task's status in listings.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Symbols {
+    /// `✓` and `✖`, `★` for pinned, `▶` for started, `🔒` for blocked and
+    /// `⌛` for waiting tasks.
+    #[default]
+    Unicode,
+    /// `[x]` and `[ ]`, `*` for pinned, `>` for started, `~` for blocked
+    /// and `w` for waiting tasks.
+    Ascii,
+    /// No status symbol, leaving only the colors, and the Unicode markers.
+    Minimal,
+}
+
+impl Symbols {
+    /// The done, pending, pinned, started, blocked and waiting symbols.
+    fn glyphs(self) -> [&'static str; 6] {
+        match self {
+            Symbols::Unicode => ["✓", "✖", "★", "▶", "🔒", "⌛"],
+            Symbols::Ascii => ["[x]", "[ ]", "*", ">", "~", "w"],
+            Symbols::Minimal => ["", "", "★", "▶", "🔒", "⌛"],
+        }
+    }
+}
+
+impl FromStr for Symbols {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unicode" => Ok(Symbols::Unicode),
+            "ascii" => Ok(Symbols::Ascii),
+            "minimal" => Ok(Symbols::Minimal),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The status symbol and markers printed before the note of `task`, each
+/// followed by a space.
+fn markers(render: &Render, task: &Task, blocked: bool) -> String {
+    let [done, pending, pinned, started, lock, hourglass] = render.symbols().glyphs();
+    let mut symbols = vec![match task {
+        Task::DoneTask(_) => render.style(render.theme.done).paint(done),
+        Task::TodoTask(_) => render.style(render.theme.pending).paint(pending),
+    }];
+    if task.data().is_pinned() {
+        symbols.push(render.style(render.theme.pinned).paint(pinned));
+    }
+    if task.data().is_started() {
+        symbols.push(render.style(render.theme.started).paint(started));
+    }
+    if blocked {
+        symbols.push(render.style(render.theme.muted).paint(lock));
+    }
+    if task.data().is_waiting() {
+        symbols.push(render.style(render.theme.muted).paint(hourglass));
+    }
+    symbols
+        .iter()
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("{} ", s))
+        .collect()
+}
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Makes saving leave every file as it is, for `--dry-run`. Commands still
+/// make their changes in memory, so they print what a real run would.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Makes every list be opened for reading only, for `--read-only`:
+/// anything that would save one of them, or write another file for it such
+/// as an export, fails with `TodoError::ReadOnly` before it writes a file.
+/// A list whose own file can't be written is read-only by itself, without
+/// making others so.
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// How a list file is written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// `- [ ] note` and `- [x] note` lines.
+    Markdown,
+    /// The `TODO` and `DONE` headlines of an Emacs org file; see `org`.
+    Org,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(0);
+
+/// Makes every list be read and written in `format`, rather than in the
+/// one its file's extension says.
+pub fn set_format(format: Option<Format>) {
+    let format = match format {
+        None => 0,
+        Some(Format::Markdown) => 1,
+        Some(Format::Org) => 2,
+    };
+    FORMAT.store(format, Ordering::Relaxed);
+}
+
+impl Format {
+    /// The format of the list file at `path`: org for a `.org` file, unless
+    /// `set_format` said otherwise.
+    pub fn of(path: &Path) -> Self {
+        match FORMAT.load(Ordering::Relaxed) {
+            1 => Format::Markdown,
+            2 => Format::Org,
+            _ if path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("org")) =>
+            {
+                Format::Org
+            }
+            _ => Format::Markdown,
+        }
+    }
+}
+
+/// The age of a task created `days` ago, in the largest unit that fits: days
+/// for the first two weeks, then weeks for the first year, then years, as
+/// in `3d`, `6w` and `1y`.
+pub fn format_age(days: i64) -> String {
+    match days.max(0) {
+        days @ 0..=13 => format!("{}d", days),
+        days @ 14..=364 => format!("{}w", days / 7),
+        days => format!("{}y", days / 365),
+    }
+}
+
+/// Renders a `[████░░░░] 4/8 done (50%)` bar that fits in `width` columns,
+/// keeping at least 10 cells for the bar itself.
+pub fn progress_bar(render: &Render, stats: &Stats, width: usize) -> String {
+    let ratio = stats.completion();
+    let label = format!(
+        " {}/{} done ({:.0}%)",
+        stats.done,
+        stats.pending + stats.done,
+        ratio * 100.0
+    );
+    let cells = width
+        .saturating_sub(wrap::display_width(&label) + 2)
+        .max(10);
+    let filled = ((ratio * cells as f64).round() as usize).min(cells);
+    format!(
+        "[{}{}]{}",
+        render
+            .style(render.theme.done)
+            .paint(render.glyph("█", "#").repeat(filled)),
+        render
+            .style(render.theme.muted)
+            .paint(render.glyph("░", "-").repeat(cells - filled)),
+        label
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ansi_term::Colour::*;
+
+    #[test]
+    fn test_progress_bar() {
+        let bar = |pending, done, width| {
+            let stats = Stats {
+                pending,
+                done,
+                overdue: 0,
+            };
+            progress_bar(&Render::default(), &stats, width)
+                .replace("\x1b[32m", "")
+                .replace("\x1b[2m", "")
+                .replace("\x1b[0m", "")
+        };
+        assert_eq!(bar(0, 0, 26), "[░░░░░░░░░░] 0/0 done (0%)");
+        assert_eq!(bar(2, 2, 29), "[██████░░░░░░] 2/4 done (50%)");
+        assert_eq!(bar(0, 3, 0), "[██████████] 3/3 done (100%)");
+    }
+
+    #[test]
+    fn test_completions() {
+        let list = list_of(&[
+            "- [x] buy milk done:2024-06-28",
+            "- [x] call mom done:2024-06-28",
+            "- [x] water plants",
+            "- [ ] pay rent done:2024-06-29",
+            "- [x] file taxes done:2024-07-01",
+        ]);
+        let day = |s| date::parse_date(s).unwrap();
+        assert_eq!(
+            list.completions(),
+            BTreeMap::from([(day("2024-06-28"), 2), (day("2024-07-01"), 1)])
+        );
+    }
+
+    #[test]
+    fn test_streaks() {
+        let list = list_of(&[
+            "- [ ] 2024-06-01 stretch rec:1d",
+            "- [x] stretch rec:1d done:2024-06-30",
+            "- [x] (A) read rec:1d done:2024-07-01",
+            "- [x] stretch done:2024-06-28",
+            "- [ ] water plants",
+        ]);
+        let archive = list_of(&[
+            "- [x] stretch rec:1d done:2024-06-29",
+            "- [x] stretch rec:1d done:2024-06-27",
+            "- [x] read rec:1d done:2024-06-29",
+        ]);
+        let today = date::parse_date("2024-07-01").unwrap();
+        let streaks = list.streaks(Some(&archive), today);
+        assert_eq!(
+            streaks.iter().collect::<Vec<_>>(),
+            [("read", 1), ("stretch", 2)]
+        );
+        assert_eq!(list.streaks(None, today).get("stretch"), Some(1));
+        let tomorrow = date::parse_date("2024-07-02").unwrap();
+        assert_eq!(
+            list.streaks(Some(&archive), tomorrow).get("stretch"),
+            Some(0)
+        );
+        assert!(list.has_daily_tasks());
+    }
+
+    #[test]
+    fn test_matching() {
+        let today = date::parse_date("2024-07-01").unwrap();
+        let list = list_of(&[
+            "- [ ] pay rent #home due:2024-06-28",
+            "- [x] buy milk #home",
+            "- [ ] call mom due:2024-07-03",
+            "- [ ] file taxes #home t:2024-08-01",
+        ]);
+        let matching = |filter: Filter| list.matching(&filter, today);
+        assert_eq!(matching(Filter::new(Scope::Pending)), vec![1, 3]);
+        assert_eq!(matching(Filter::new(Scope::Done)), vec![2]);
+        assert_eq!(matching(Filter::new(Scope::Any)), vec![1, 2, 3, 4]);
+        let home = Filter {
+            words: Some(Words::has("#home")),
+            ..Filter::new(Scope::Any)
+        };
+        assert_eq!(matching(home.clone()), vec![1, 2, 4]);
+        let home = Filter {
+            words: Some(Words::has("#Home")),
+            ..home
+        };
+        assert!(matching(home.clone()).is_empty());
+        let home = Filter {
+            case: Case::Ignore,
+            ..home
+        };
+        assert_eq!(matching(home), vec![1, 2, 4]);
+        let due = Filter {
+            due_by: Some(today),
+            ..Filter::new(Scope::Pending)
+        };
+        assert_eq!(matching(due), vec![1]);
+        let overdue = Filter {
+            overdue: true,
+            ..Filter::new(Scope::Pending)
+        };
+        assert_eq!(matching(overdue), vec![1]);
+    }
+
+    #[test]
+    fn test_words() {
+        let list = list_of(&[
+            "- [ ] fix the api #backend #urgent",
+            "- [ ] restyle #frontend #urgent @laptop",
+            "- [ ] add an index #backend +launch",
+            "- [ ] rewrite it all #backend #someday",
+        ]);
+        let today = date::today();
+        let matching = |words: Words| {
+            let filter = Filter {
+                words: Some(words),
+                ..Filter::new(Scope::Pending)
+            };
+            list.matching(&filter, today)
+        };
+        let tag = |t: &str| Words::has(&format!("#{}", t));
+        assert_eq!(matching(tag("backend").and(tag("urgent"))), vec![1]);
+        assert_eq!(matching(tag("backend").or(tag("urgent"))), vec![1, 2, 3, 4]);
+        // Negation binds to its word, and the alternatives are grouped
+        // before they're combined with the rest.
+        assert_eq!(
+            matching(tag("backend").or(tag("urgent")).and(!tag("someday"))),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            matching(tag("someday").or(tag("urgent").and(!Words::has("@laptop")))),
+            vec![1, 4]
+        );
+        assert_eq!(matching(tag("backend").and(Words::has("+launch"))), vec![3]);
+        assert_eq!(matching(!Words::text("IT")), vec![1, 2, 3, 4]);
+        assert_eq!(matching((!Words::text("it")).and(!tag("urgent"))), vec![3]);
+        assert_eq!(
+            tag("a").and(tag("b")).and(tag("c")),
+            Words::All(vec![tag("a"), tag("b"), tag("c")])
+        );
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let list = list_of(&[
+            "- [ ] water plants due:2024-07-03",
+            "- [ ] (B) 2024-06-01 call mom",
+            "- [ ] 2024-05-01 Buy milk due:2024-07-01 pin:1",
+            "- [ ] (A) file taxes",
+            "- [ ] pay rent pin:1",
+        ]);
+        let sorted = |key| {
+            let mut out = Vec::new();
+            let filter = Filter {
+                sort: Some(key),
+                ..Filter::new(Scope::Any)
+            };
+            filter_print_lines(
+                &Render::default(),
+                &mut out,
+                &list.list,
+                date::today(),
+                false,
+                &filter,
+            )
+            .unwrap()
+        };
+        assert_eq!(sorted(SortKey::Priority), vec![3, 5, 4, 2, 1]);
+        assert_eq!(sorted(SortKey::Due), vec![3, 5, 1, 2, 4]);
+        assert_eq!(sorted(SortKey::Age), vec![3, 5, 2, 1, 4]);
+        assert_eq!(sorted(SortKey::Alpha), vec![3, 5, 2, 4, 1]);
+    }
+
+    #[test]
+    fn test_paging() {
+        let list = list_of(&[
+            "- [ ] (B) one",
+            "- [ ] two",
+            "- [x] three",
+            "- [ ] (A) four",
+            "- [ ] five pin:1",
+            "- [ ] (C) six",
+        ]);
+        let shown = |filter: Filter| {
+            let mut out = Vec::new();
+            filter_print_lines(
+                &Render::default(),
+                &mut out,
+                &list.list,
+                date::today(),
+                false,
+                &filter,
+            )
+            .unwrap()
+        };
+        let page = |offset, limit| Filter {
+            offset,
+            limit,
+            ..Filter::new(Scope::Pending)
+        };
+        assert_eq!(shown(page(0, Some(3))), vec![5, 1, 2]);
+        assert_eq!(shown(page(3, Some(3))), vec![4, 6]);
+        assert_eq!(shown(page(9, None)), Vec::<usize>::new());
+        assert_eq!(
+            shown(Filter {
+                reverse: true,
+                sort: Some(SortKey::Priority),
+                ..page(1, Some(2))
+            }),
+            vec![2, 6]
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        let list = list_of(&[
+            "- [ ] deploy #backend #ops +launch",
+            "- [ ] buy milk",
+            "- [x] fix login #backend +launch",
+            "- [ ] rotate keys #ops #ops pin:1 +infra",
+        ]);
+        let today = date::today();
+        let groups = list.groups(&Filter::new(Scope::Pending), GroupKey::Tag, today);
+        assert_eq!(
+            groups.groups.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("#backend".to_owned(), vec![1]),
+                ("#ops".to_owned(), vec![4, 1]),
+            ]
+        );
+        assert_eq!(groups.rest, vec![2]);
+        let groups = list.groups(&Filter::new(Scope::Any), GroupKey::Tag, today);
+        assert_eq!(groups.groups["#backend"], vec![1, 3]);
+
+        let groups = list.groups(&Filter::new(Scope::Any), GroupKey::Project, today);
+        assert_eq!(groups.groups["+launch"], vec![1, 3]);
+        assert_eq!(groups.groups["+infra"], vec![4]);
+        assert_eq!(groups.rest, vec![2]);
+    }
+
+    #[test]
+    fn test_find_ignore_case() {
+        assert_eq!(
+            find_ignore_case("buy Milk and MILK", "milk"),
+            vec![(4, 8), (13, 17)]
+        );
+        assert_eq!(find_ignore_case("aaaa", "aa"), vec![(0, 2), (2, 4)]);
+        assert_eq!(find_ignore_case("Ünïcode", "ÜNÏ"), vec![(0, 5)]);
+        assert!(find_ignore_case("milk", "").is_empty());
+        assert!(find_ignore_case("mil", "milk").is_empty());
+    }
+
+    #[test]
+    fn test_find_with_case() {
+        assert_eq!(
+            find_with_case("Buy Milk and milk", "milk", Case::Smart),
+            vec![(4, 8), (13, 17)]
+        );
+        assert_eq!(
+            find_with_case("Buy Milk and milk", "Milk", Case::Smart),
+            vec![(4, 8)]
+        );
+        assert_eq!(
+            find_with_case("Buy Milk and milk", "milk", Case::Sensitive),
+            vec![(13, 17)]
+        );
+        assert_eq!(
+            find_with_case("Buy Milk and milk", "MILK", Case::Ignore).len(),
+            2
+        );
+        assert_eq!(find_with_case("Über", "über", Case::Smart), vec![(0, 5)]);
+        assert!(find_with_case("über", "Über", Case::Smart).is_empty());
+        assert!(Case::Smart.ignores(r"\S+ milk"));
+        assert!(!Case::Smart.ignores(r"\\Milk"));
+    }
+
+    #[test]
+    fn test_highlight() {
+        let on = |s: &str| Style::default().reverse().paint(s).to_string();
+        assert_eq!(
+            highlight(
+                &Render::default(),
+                "buy milk and more milk",
+                &[(4, 8), (18, 22)]
+            ),
+            format!("buy {} and more {}", on("milk"), on("milk"))
+        );
+        assert_eq!(
+            highlight(&Render::default(), "buy milk", &[(4, 7), (5, 8), (0, 3)]),
+            format!("{} {}", on("buy"), on("milk"))
+        );
+    }
+
+    #[test]
+    fn test_completion() {
+        let stats = Stats {
+            pending: 3,
+            done: 1,
+            overdue: 0,
+        };
+        assert_eq!(stats.completion(), 0.25);
+        let empty = Stats {
+            pending: 0,
+            done: 0,
+            overdue: 0,
+        };
+        assert_eq!(empty.completion(), 0.0);
+    }
+
+    #[test]
+    fn test_parse() {
+        for line in [
+            "- [ ] buy milk",
+            "- [x] buy milk",
+            "- [ ] ",
+            "- [ ]  two  spaces  ",
+            "- [x] - [ ] nested",
+            "- [ ] (A) 牛奶 due:2024-07-01",
+        ] {
+            let task: Task = line.parse().unwrap();
+            assert_eq!(format!("{:?}", task), line);
+        }
+        for line in [
+            "",
+            "- [ ]",
+            "- [X] buy milk",
+            "- [\t] buy milk",
+            "-  [ ] buy milk",
+            " - [ ] buy milk",
+        ] {
+            assert!(line.parse::<Task>().is_err(), "{:?}", line);
+        }
+
+        let content = "- [ ] buy milk\n- [x] call mom\r\n- [ ] no newline";
+        let list = TodoList::read(Path::new(""), content.as_bytes()).unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.note(2), Some("call mom"));
+        assert_eq!(list.note(3), Some("no newline"));
+        assert!(matches!(
+            TodoList::read(Path::new(""), "- [ ] fine\noops\n".as_bytes()),
+            Err(TodoError::Parse { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_checklist_lines() {
+        let content = "- [ ] pack\n  - [x] socks\n  - [ ] shirts\n- [x] call mom\n";
+        let list = TodoList::read(Path::new(""), content.as_bytes()).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(
+            list.checklist(1).unwrap(),
+            [(true, "socks".to_owned()), (false, "shirts".to_owned())]
+        );
+        assert!(list.checklist(2).unwrap().is_empty());
+
+        // Only items right under a task or its items belong to it; other
+        // indented lines aren't tasks.
+        for (content, line) in [
+            ("  - [ ] orphan\n", 1),
+            ("- [ ] pack\n  socks\n", 2),
+            ("- [ ] pack\n    - [ ] too deep\n", 2),
+            ("- [ ] pack\n\t- [ ] tab\n", 2),
+        ] {
+            assert!(
+                matches!(
+                    TodoList::read(Path::new(""), content.as_bytes()),
+                    Err(TodoError::Parse { line: l, .. }) if l == line
+                ),
+                "{:?}",
+                content
+            );
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] pack\n  - [ ] socks\nnotes\n  - [ ] after notes\n",
+        )
+        .unwrap();
+        let list = TodoList::load_skipping_invalid(&path).unwrap();
+        assert_eq!(list.checklist(1).unwrap().len(), 1);
+        assert_eq!(
+            list.invalid_lines().collect::<Vec<_>>(),
+            [(3, "notes"), (4, "  - [ ] after notes")]
+        );
+    }
+
+    #[test]
+    fn test_checklist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] pack\n- [ ] call mom\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        assert_eq!(list.add_item(1, "socks").unwrap(), 1);
+        assert_eq!(list.add_item(1, "shirts").unwrap(), 2);
+        assert_eq!(list.add_item(1, "toothbrush").unwrap(), 3);
+        list.set_item_done(1, 2, true).unwrap();
+        assert_eq!(list.remove_item(1, 3).unwrap(), "toothbrush");
+        assert!(matches!(
+            list.set_item_done(1, 3, true),
+            Err(TodoError::NoSuchItem {
+                item: 3,
+                len: 2,
+                ..
+            })
+        ));
+        assert!(matches!(
+            list.remove_item(2, 1),
+            Err(TodoError::NoSuchItem { len: 0, .. })
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] pack\n  - [ ] socks\n  - [x] shirts\n- [ ] call mom\n"
+        );
+
+        list.check(1).unwrap();
+        assert_eq!(list.check_items(1).unwrap(), 1);
+        assert_eq!(list.check_items(1).unwrap(), 0);
+        list.remove(2).unwrap();
+        let list = TodoList::load(&path).unwrap();
+        assert_eq!(
+            list.checklist(1).unwrap(),
+            [(true, "socks".to_owned()), (true, "shirts".to_owned())]
+        );
+        assert_eq!(
+            stream::stats(&path, date::today()).unwrap().done,
+            1,
+            "items aren't counted as tasks"
+        );
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] buy paint\n- [ ] paint fence\n- [ ] call mom\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        list.block(2, 1).unwrap();
+        list.block(3, 2).unwrap();
+        assert_eq!(list.blocked(), [2, 3]);
+        assert_eq!(list.dependents(1).unwrap(), [2]);
+        assert!(matches!(
+            list.block(1, 3),
+            Err(TodoError::DependencyCycle { index: 1, on: 3 })
+        ));
+        assert!(matches!(
+            list.block(2, 2),
+            Err(TodoError::DependencyCycle { index: 2, on: 2 })
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy paint id:1\n- [ ] paint fence dep:1 id:2\n- [ ] call mom dep:2\n"
+        );
+
+        list.check(1).unwrap();
+        assert_eq!(list.blocked(), [3]);
+        list.remove(2).unwrap();
+        assert_eq!(list.blocked(), Vec::<usize>::new());
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.ends_with("\n- [ ] call mom\n"), "{}", content);
+    }
+
+    #[test]
+    fn test_waiting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] send contract\n- [x] call mom\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        list.wait(1, Some("Alice Smith")).unwrap();
+        list.wait(2, None).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] send contract wait:Alice_Smith\n- [ ] call mom wait:1\n"
+        );
+        let details = list.details(1).unwrap();
+        assert_eq!(details.text, "send contract");
+        assert_eq!(details.waiting_for.as_deref(), Some("Alice Smith"));
+        assert_eq!(list.details(2).unwrap().waiting_for, None);
+        assert_eq!(list.next(date::today()), None);
+        let hide = Filter {
+            hide_waiting: true,
+            ..Filter::new(Scope::Pending)
+        };
+        assert!(list.matching(&hide, date::today()).is_empty());
+
+        list.undo(1).unwrap();
+        list.check(2).unwrap();
+        assert_eq!(list.next(date::today()), Some(1));
+        assert!(!list.details(2).unwrap().waiting);
+    }
+
+    #[test]
+    fn test_assign() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] buy milk\n- [ ] pay rent assignee:bob assignee:carol\n\
+             - [x] call mom assignee:alice\n- [ ] water plants\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        assert_eq!(
+            list.pending_by_assignee(),
+            [(Some("bob".to_owned()), 1), (None, 2)]
+        );
+        list.assign(1, Some("alice")).unwrap();
+        list.assign(1, Some("Alice Smith")).unwrap();
+        list.assign(2, Some("alice")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk assignee:Alice_Smith\n- [ ] pay rent assignee:alice\n\
+             - [x] call mom assignee:alice\n- [ ] water plants\n"
+        );
+        assert_eq!(list.display_note(2).as_deref(), Some("pay rent"));
+        assert_eq!(
+            list.details(1).unwrap().assignee.as_deref(),
+            Some("Alice_Smith")
+        );
+        let mine = Filter {
+            assignee: Some("alice".to_owned()),
+            ..Filter::new(Scope::Any)
+        };
+        assert_eq!(list.matching(&mine, date::today()), [2, 3]);
+        let unassigned = Filter {
+            unassigned: true,
+            ..Filter::new(Scope::Pending)
+        };
+        assert_eq!(list.matching(&unassigned, date::today()), [4]);
+        list.assign(3, Some("bob")).unwrap();
+        list.assign(4, Some("alice")).unwrap();
+        assert_eq!(
+            list.pending_by_assignee(),
+            [
+                (Some("alice".to_owned()), 2),
+                (Some("Alice_Smith".to_owned()), 1)
+            ]
+        );
+        list.assign(1, None).unwrap();
+        assert_eq!(
+            list.pending_by_assignee(),
+            [(Some("alice".to_owned()), 2), (None, 1)]
+        );
+        list.assign(2, Some(" ")).unwrap();
+        list.assign(4, None).unwrap();
+        assert_eq!(list.pending_by_assignee(), Vec::new());
+        assert!(matches!(
+            list.assign(1, Some("a\nb")),
+            Err(TodoError::MultilineNote { .. })
+        ));
+    }
+
+    #[test]
+    fn test_deferred_saves() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] buy milk\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        list.defer_saves();
+
+        list.add("call mom").unwrap();
+        list.check(1).unwrap();
+        list.pin(2).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "- [ ] buy milk\n");
+
+        assert_eq!(journal::len(&path), (0, 0));
+
+        list.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!(
+                "- [x] buy milk done:{}\n- [ ] call mom pin:1\n",
+                date::today()
+            )
+        );
+        // The three changes were one save, with one backup to undo it.
+        assert_eq!(journal::len(&path), (1, 0));
+        let backups = std::fs::read_dir(backup::backups_dir(&path)).unwrap();
+        let backups = backups.filter(|e| e.as_ref().unwrap().file_name() != "journal");
+        assert_eq!(backups.count(), 1);
+
+        // Nothing changed since, so committing again doesn't write.
+        std::fs::remove_file(&path).unwrap();
+        list.commit().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_failed_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] buy milk\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        // A directory in the way of the temporary file makes the save fail
+        // even for users that aren't bound by permissions.
+        std::fs::create_dir(dir.path().join(".todo.txt.tmp")).unwrap();
+        assert!(matches!(list.check(1), Err(TodoError::Save { .. })));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "- [ ] buy milk\n");
+
+        std::fs::remove_dir(dir.path().join(".todo.txt.tmp")).unwrap();
+        list.add("call mom").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("- [x] buy milk done:{}\n- [ ] call mom\n", date::today())
+        );
+        assert!(!dir.path().join(".todo.txt.tmp").exists());
+    }
+
+    #[test]
+    fn test_concurrent_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let content = "- [ ] one\n- [ ] two\n- [ ] three\n- [ ] four\n";
+        std::fs::write(&path, content).unwrap();
+
+        // Both sides add a task.
+        let mut a = TodoList::load(&path).unwrap();
+        let mut b = TodoList::load(&path).unwrap();
+        a.add("task A").unwrap();
+        b.add("task B").unwrap();
+        let list = TodoList::load(&path).unwrap();
+        assert_eq!(list.note(5), Some("task A"));
+        assert_eq!(list.note(6), Some("task B"));
+
+        // Both sides remove task 3: it's removed once, and task 4 stays.
+        std::fs::write(&path, content).unwrap();
+        let mut a = TodoList::load(&path).unwrap();
+        let mut b = TodoList::load(&path).unwrap();
+        a.remove(3).unwrap();
+        b.remove(3).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] one\n- [ ] two\n- [ ] four\n"
+        );
+
+        // A change to a task that was changed or removed meanwhile is
+        // dropped; changes to other tasks still apply.
+        std::fs::write(&path, content).unwrap();
+        let mut a = TodoList::load(&path).unwrap();
+        let mut b = TodoList::load(&path).unwrap();
+        a.check(2).unwrap();
+        a.remove(1).unwrap();
+        b.defer_saves();
+        b.pin(2).unwrap();
+        b.check(1).unwrap();
+        b.remove(4).unwrap();
+        b.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("- [x] two done:{}\n- [ ] three\n", date::today())
+        );
+
+        // Writing after a merge doesn't merge again.
+        b.add("five").unwrap();
+        b.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!(
+                "- [x] two done:{}\n- [ ] three\n- [ ] five\n",
+                date::today()
+            )
+        );
+
+        // A copy goes after its original wherever that's moved to.
+        std::fs::write(&path, content).unwrap();
+        let mut a = TodoList::load(&path).unwrap();
+        let mut b = TodoList::load(&path).unwrap();
+        a.remove(1).unwrap();
+        b.duplicate(3).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] two\n- [ ] three\n- [ ] three\n- [ ] four\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_keeps_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("synced.txt");
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&target, "- [ ] buy milk\n").unwrap();
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        TodoList::load(&path).unwrap().check(1).unwrap();
+        assert!(std::fs::symlink_metadata(&path).unwrap().is_symlink());
+        assert_eq!(
+            std::fs::read_to_string(&target).unwrap(),
+            format!("- [x] buy milk done:{}\n", date::today())
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    proptest::proptest! {
+        #[test]
+        fn test_parse_line_matches_regex(
+            line in r"(-( )?\[[ xX\t\n]?\]( |\n)?)?[^\r]{0,12}(\r?\n[a-z]{0,2})?|\PC{0,16}"
+        ) {
+            let re = regex::Regex::new(r"^- \[([\sx])\] (.*)$").unwrap();
+            let expected = re.captures(&line).and_then(|cap| {
+                let note = cap.get(2).unwrap().as_str();
+                match &cap[1] {
+                    "x" => Some((true, note)),
+                    " " => Some((false, note)),
+                    _ => None,
+                }
+            });
+            proptest::prop_assert_eq!(parse_line(&line), expected);
+        }
+    }
+
+    #[test]
+    fn test_notes_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let mut list = TodoList::load(&path).unwrap();
+        list.add("- [x] sneaky").unwrap();
+        assert!(matches!(
+            list.add("two\nlines"),
+            Err(TodoError::MultilineNote { .. })
+        ));
+        assert!(list.add_all(["fine", "carriage\rreturn"]).is_err());
+        assert!(matches!(list.add(" \t "), Err(TodoError::EmptyNote)));
+        assert_eq!(list.len(), 1);
+        // Surrounding whitespace is trimmed, but inner runs are kept.
+        list.add("  call  mom\r\n").unwrap();
+        assert_eq!(list.note(2), Some("call  mom"));
+        assert_eq!(squeeze_whitespace(" call \t mom "), "call mom");
+
+        let list = TodoList::load(&path).unwrap();
+        assert_eq!(list.note(1), Some("- [x] sneaky"));
+        assert_eq!(list.is_done(1), Some(false));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_add_round_trips(note in r"\PC*\S\PC*") {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("todo.txt");
+            TodoList::load(&path).unwrap().add(&note).unwrap();
+            let list = TodoList::load(&path).unwrap();
+            proptest::prop_assert_eq!(list.note(1), Some(note.trim()));
+        }
+
+        #[test]
+        fn test_add_round_trips_or_fails(note in proptest::prelude::any::<String>()) {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("todo.txt");
+            let added = TodoList::load(&path).unwrap().add(&note).is_ok();
+            let list = TodoList::load(&path).unwrap();
+            if added {
+                proptest::prop_assert_eq!(list.note(1), Some(note.trim()));
+            } else {
+                proptest::prop_assert!(list.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_skip_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "TODO: fix later\n- [ ] buy milk\n\n- [ ] call mom\n").unwrap();
+        assert!(matches!(
+            TodoList::load(&path),
+            Err(TodoError::Parse { line: 1, ref content }) if content == "TODO: fix later"
+        ));
+
+        let mut list = TodoList::load_skipping_invalid(&path).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(
+            list.invalid_lines().collect::<Vec<_>>(),
+            vec![(1, "TODO: fix later"), (3, "")]
+        );
+        list.check(2).unwrap();
+        list.add("pay rent").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!(
+                "TODO: fix later\n- [ ] buy milk\n\n- [x] call mom done:{}\n- [ ] pay rent\n",
+                date::today()
+            )
+        );
+        list.remove(1).unwrap();
+        list.remove(1).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "TODO: fix later\n- [ ] pay rent\n\n"
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_indices() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let mut list = TodoList::load(&path).unwrap();
+        assert!(matches!(
+            list.check(1),
+            Err(TodoError::NoSuchTask { index: 1, len: 0 })
+        ));
+
+        list.add("buy milk").unwrap();
+        for index in [0, 2] {
+            assert!(matches!(
+                list.check(index),
+                Err(TodoError::NoSuchTask { len: 1, .. })
+            ));
+            assert!(list.undo(index).is_err());
+            assert!(list.remove(index).is_err());
+            assert!(list.pin(index).is_err());
+            assert!(list.snooze(index, date::today()).is_err());
+            assert!(list.append_note(index, "PR #412").is_err());
+            assert!(list.prepend_note(index, "URGENT:").is_err());
+            assert!(list.duplicate(index).is_err());
+            assert!(list.details(index).is_err());
+            assert!(list.start(index, date::now()).is_err());
+            assert!(list
+                .print_task(&Render::default(), &mut Vec::new(), index)
+                .is_err());
+            assert_eq!(list.note(index), None);
+        }
+        assert_eq!(list.note(1), Some("buy milk"));
+    }
+
+    #[test]
+    fn test_append_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] buy milk\n- [x] review PR pin:1\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        list.append_note(2, "  #412 ").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [x] review PR pin:1 #412\n"
+        );
+        assert!(matches!(
+            list.append_note(1, " "),
+            Err(TodoError::EmptyNote)
+        ));
+        assert!(matches!(
+            list.append_note(1, "line\nbreak"),
+            Err(TodoError::MultilineNote { .. })
+        ));
+        assert_eq!(list.note(1), Some("buy milk"));
+    }
+
+    #[test]
+    fn test_attach_and_detach() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] read spec\n- [ ] buy milk\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        list.attach(1, "~/docs/my spec 100%.pdf").unwrap();
+        list.attach(1, "/tmp/notes.txt").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] read spec file:~/docs/my%20spec%20100%25.pdf file:/tmp/notes.txt\n\
+             - [ ] buy milk\n"
+        );
+        assert_eq!(list.display_note(1).unwrap(), "read spec");
+        assert_eq!(
+            list.details(1).unwrap().attachments,
+            ["~/docs/my spec 100%.pdf", "/tmp/notes.txt"]
+        );
+        assert_eq!(decode_path("50%off%2"), "50%off%2");
+
+        assert_eq!(list.detach(1, Some(2)).unwrap(), ["/tmp/notes.txt"]);
+        assert!(matches!(
+            list.detach(1, Some(2)),
+            Err(TodoError::NoSuchAttachment { len: 1, .. })
+        ));
+        list.attach(1, "b.txt").unwrap();
+        assert_eq!(
+            list.detach(1, None).unwrap(),
+            ["~/docs/my spec 100%.pdf", "b.txt"]
+        );
+        assert_eq!(list.note(1), Some("read spec"));
+        assert!(matches!(
+            list.detach(2, None),
+            Err(TodoError::NoSuchAttachment { len: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_edit_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] buy milk\n- [x] review PR pin:1 done:2024-07-01\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        list.edit_note(1, " buy oat milk +shop ").unwrap();
+        list.edit_note(2, "review the PR #412").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy oat milk +shop\n- [x] review the PR #412 pin:1 done:2024-07-01\n"
+        );
+        list.edit_note(2, "review PR done:2024-07-02").unwrap();
+        assert_eq!(list.note(2), Some("review PR done:2024-07-02 pin:1"));
+        assert!(matches!(list.edit_note(1, " "), Err(TodoError::EmptyNote)));
+        assert_eq!(list.note(1), Some("buy oat milk +shop"));
+    }
+
+    #[test]
+    fn test_prepend_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] buy milk\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        list.prepend_note(1, "URGENT:").unwrap();
+        assert_eq!(list.note(1), Some("URGENT: buy milk"));
+        list.prepend_note(1, " - [x] ").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] - [x] URGENT: buy milk\n"
+        );
+        let list = TodoList::load(&path).unwrap();
+        assert_eq!(list.note(1), Some("- [x] URGENT: buy milk"));
+        assert!(matches!(list.list[0], Task::TodoTask(_)));
+    }
+
+    #[test]
+    fn test_rewrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] acme docs t:2024-06-01\n- [ ] acme\n- [ ] acme-7 id:7\n\
+             - [x] acme site\n- [ ] call mom\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        let rename = |note: &str| note.replace("acme", "zenith").replace("id:7", "id:8");
+        let rewrites = list.rewrites(Scope::Pending, rename);
+        let shown: Vec<_> = rewrites
+            .iter()
+            .map(|r| (r.index, &r.before[..], &r.after[..], r.rejected))
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                (1, "acme docs", "zenith docs", None),
+                (2, "acme", "zenith", None),
+                (
+                    3,
+                    "acme-7",
+                    "zenith-7",
+                    Some("it would change tokens that todo keeps hidden")
+                ),
+            ]
+        );
+        let rewrites = list.rewrites(Scope::Any, |note| note.replace("acme", ""));
+        let rejected: Vec<_> = rewrites.iter().map(|r| r.rejected).collect();
+        assert_eq!(rejected[1], Some("it would be empty"));
+        assert_eq!(rewrites[3].after, "site");
+        assert_eq!(
+            list.rewrites(Scope::Any, |note| note.replace(' ', "\n"))[0].rejected,
+            Some("it would span lines")
+        );
+
+        let rewrites = list.rewrites(Scope::Pending, rename);
+        assert_eq!(list.rewrite(&rewrites).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] zenith docs t:2024-06-01\n- [ ] zenith\n- [ ] acme-7 id:7\n\
+             - [x] acme site\n- [ ] call mom\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] fix bug #WIP  soon\n- [x] docs #wip\n- [ ] site #in-progress #wip\n\
+             - [ ] ship #wipe #wip, later\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        assert_eq!(list.rename_tag("wip", "in-progress").unwrap(), 3);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] fix bug #in-progress  soon\n- [x] docs #in-progress\n\
+             - [ ] site #in-progress\n- [ ] ship #wipe #wip, later\n"
+        );
+        assert_eq!(list.rename_tag("wip", "done").unwrap(), 0);
+        assert_eq!(list.rename_tag("in-progress", "In-Progress").unwrap(), 3);
+        assert_eq!(list.note(3), Some("site #In-Progress"));
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [x] (A) water plants spent:1200\n- [ ] buy milk\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        assert_eq!(list.duplicate(1).unwrap(), 2);
+        assert_eq!(list.duplicate_to_end(2).unwrap(), 4);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [x] (A) water plants spent:1200\n- [ ] (A) water plants\n\
+             - [ ] buy milk\n- [ ] (A) water plants\n"
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] call mom\n- [ ] (B) plan trip #travel +summer due:2024-08-01 id:3\n\
+             \x20 - [ ] passports\n- [x] pay rent\n- [ ] water plants dep:3\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        assert_eq!(
+            list.split(2, &["book flights", "(A) book hotel #travel"], true, false)
+                .unwrap(),
+            [2, 3]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] call mom\n- [ ] (B) book flights #travel +summer id:3\n  - [ ] passports\n\
+             - [ ] (A) book hotel #travel +summer\n- [x] pay rent\n- [ ] water plants dep:3\n"
+        );
+
+        assert_eq!(list.split(1, &["ring mom"], false, true).unwrap(), [1, 2]);
+        assert_eq!(list.note(1), Some("call mom"));
+        assert_eq!(list.note(2), Some("ring mom"));
+        assert!(matches!(
+            list.split(5, &["pay"], true, false),
+            Err(TodoError::SplitDone { index: 5 })
+        ));
+        assert!(matches!(
+            list.split(1, &["fine", " "], true, false),
+            Err(TodoError::EmptyNote)
+        ));
+        assert_eq!(list.len(), 6);
+    }
+
+    #[test]
+    fn test_join() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] (C) 2024-06-10 plan trip #travel due:2024-08-01 id:1\n- [ ] call mom\n\
+             - [x] (A) 2024-06-01 book hotel #travel +summer due:2024-07-20 id:2 done:2024-06-30\n\
+             \x20 - [x] compare prices\n- [ ] pack dep:2\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        assert_eq!(list.join(1, 3, "; ").unwrap(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] (A) 2024-06-01 plan trip #travel due:2024-08-01 id:1; book hotel +summer\n\
+             \x20 - [x] compare prices\n- [ ] call mom\n- [ ] pack dep:1\n"
+        );
+        assert_eq!(list.join(3, 2, " / ").unwrap(), 2);
+        assert_eq!(list.note(2), Some("pack dep:1 / call mom"));
+        assert!(matches!(
+            list.join(2, 2, "; "),
+            Err(TodoError::JoinSame { index: 2 })
+        ));
+        assert!(matches!(
+            list.join(1, 9, "; "),
+            Err(TodoError::NoSuchTask { index: 9, .. })
+        ));
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] call mom\n- [ ] pay rent due:2024-07-03\n- [ ] a due:2024-06-20\n\
+             - [ ] water plants\n- [ ] b due:2024-07-03\n  - [ ] item\n- [ ] c due:2024-06-20\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        assert_eq!(list.sort_by(SortKey::Due).unwrap(), 4);
+        // Tasks due the same day, and those without a date, keep their order.
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] a due:2024-06-20\n- [ ] c due:2024-06-20\n- [ ] pay rent due:2024-07-03\n\
+             - [ ] b due:2024-07-03\n  - [ ] item\n- [ ] call mom\n- [ ] water plants\n"
+        );
+        assert_eq!(list.sort_by(SortKey::Due).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [x] (B) water plants #home done:2024-06-30 spent:15m\n- [ ] call mom\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        assert_eq!(list.reopen(1).unwrap(), 3);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [x] (B) water plants #home done:2024-06-30 spent:15m\n- [ ] call mom\n\
+             - [ ] (B) water plants #home\n"
+        );
+        assert!(list.duplicates().is_empty());
+        assert!(matches!(
+            list.reopen(2),
+            Err(TodoError::NotChecked { index: 2 })
+        ));
+
+        let archive =
+            TodoList::read(Path::new(""), &b"- [x] pay rent done:2024-05-01\n"[..]).unwrap();
+        assert_eq!(list.reopen_from(&archive, 1).unwrap(), 4);
+        assert_eq!(list.note(4), Some("pay rent"));
+        assert!(list.reopen_from(&archive, 2).is_err());
+    }
+
+    #[test]
+    fn test_move_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] one\n- [ ] two\n- [ ] three\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        list.move_task(3, 1).unwrap();
+        list.move_task(2, 3).unwrap();
+        list.move_task(2, 2).unwrap();
+        assert!(list.move_task(4, 1).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] three\n- [ ] two\n- [ ] one\n"
+        );
+
+        // Moving to the top still applies after another task was added.
+        let mut list = TodoList::load(&path).unwrap();
+        TodoList::load(&path).unwrap().add("four").unwrap();
+        list.move_task(3, 1).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] one\n- [ ] three\n- [ ] two\n- [ ] four\n"
+        );
+    }
+
+    #[test]
+    fn test_activity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [x] buy milk\n- [ ] call mom pin:1\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        list.check(2).unwrap();
+        list.add("water plants").unwrap();
+        list.cleanup().unwrap();
+        assert!(list.check(5).is_err());
+        assert_eq!(
+            list.take_activity(),
+            vec![
+                Activity::new("check", 2, "call mom"),
+                Activity::new("add", 3, "water plants"),
+                Activity::new("remove", 1, "buy milk"),
+                Activity::new("remove", 2, "call mom"),
+            ]
+        );
+        assert!(list.take_activity().is_empty());
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let list = list_of(&[
+            "- [ ] (B) 2024-06-01 pay rent +home due:2024-01-31 rec:+1m",
+            "- [ ] water plants due:2024-06-20 rec:3d",
+            "- [ ] stretch rec:1d",
+            "- [ ] file report due:2024-07-05 t:2024-07-03 rec:+1w wait:bob",
+            "- [ ] buy milk due:2024-07-01",
+            "- [ ] clean desk rec:often",
+            "- [x] call mom due:2024-06-01 rec:+1m",
+        ]);
+        let today = date::parse_date("2024-07-01").unwrap();
+        let next = |i| list.next_occurrence(i, today).map(|t| format!("{:?}", t));
+        assert_eq!(
+            next(0).as_deref(),
+            Some("- [ ] (B) 2024-07-01 pay rent +home due:2024-02-29 rec:+1m")
+        );
+        assert_eq!(
+            next(1).as_deref(),
+            Some("- [ ] water plants due:2024-07-04 rec:3d")
+        );
+        assert_eq!(
+            next(2).as_deref(),
+            Some("- [ ] stretch rec:1d due:2024-07-02")
+        );
+        assert_eq!(
+            next(3).as_deref(),
+            Some("- [ ] file report due:2024-07-12 t:2024-07-10 rec:+1w")
+        );
+        assert_eq!(next(4), None);
+        assert_eq!(next(5), None);
+        assert_eq!(next(6), None);
+    }
+
+    #[test]
+    fn test_check_recurring() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] pay rent due:2024-01-31 rec:+1m\n  - [x] transfer\n\
+             - [ ] buy milk\n- [ ] water plants due:2024-06-20 rec:+1w\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        for index in [1, 3, 1] {
+            list.check(index).unwrap();
+        }
+        let pending: Vec<String> = list
+            .list
+            .iter()
+            .filter(|t| matches!(t, Task::TodoTask(_)))
+            .map(|t| format!("{:?}", t))
+            .collect();
+        assert_eq!(
+            pending,
+            [
+                "- [ ] buy milk",
+                "- [ ] pay rent due:2024-02-29 rec:+1m",
+                "- [ ] water plants due:2024-06-27 rec:+1w",
+            ]
+        );
+        assert_eq!(list.checklist(4).unwrap(), [(false, "transfer".to_owned())]);
+        let verbs: Vec<&str> = list.take_activity().iter().map(|a| a.verb).collect();
+        assert_eq!(verbs, ["check", "add", "check", "add", "check"]);
+        assert_eq!(TodoList::load(&path).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_unwritable_file_is_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("todo.done.txt");
+        fs::write(&archive, "- [x] call mom\n").unwrap();
+        let mut permissions = fs::metadata(&archive).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&archive, permissions).unwrap();
+        let mut done = TodoList::load(&archive).unwrap();
+        assert!(done.is_read_only());
+        assert!(matches!(done.add("x"), Err(TodoError::ReadOnly { .. })));
+        assert_eq!(done.take_activity(), []);
+
+        // Other lists can still be saved.
+        let path = dir.path().join("todo.txt");
+        let mut list = TodoList::load(&path).unwrap();
+        assert!(!list.is_read_only());
+        list.add("buy milk").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- [ ] buy milk\n");
+        assert!(matches!(
+            write_file(&archive, b""),
+            Err(TodoError::ReadOnly { .. })
+        ));
+        assert_eq!(fs::read_to_string(&archive).unwrap(), "- [x] call mom\n");
+    }
+
+    #[test]
+    fn test_reset_habits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let archive = dir.path().join("archive.txt");
+        std::fs::write(
+            &path,
+            "- [x] stretch habit:1 done:2024-07-01\n- [x] buy milk done:2024-06-30\n\
+             - [ ] journal habit:1\n- [x] read habit:1 done:2024-06-29\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        let day = |s| date::parse_date(s).unwrap();
+        assert_eq!(list.reset_habits(day("2024-07-01"), &archive).unwrap(), 1);
+        assert_eq!(list.reset_habits(day("2024-07-01"), &archive).unwrap(), 0);
+        assert_eq!(list.reset_habits(day("2024-07-02"), &archive).unwrap(), 1);
+        assert_eq!(list.reset_habits(day("2024-07-02"), &archive).unwrap(), 0);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] stretch habit:1\n- [x] buy milk done:2024-06-30\n\
+             - [ ] journal habit:1\n- [ ] read habit:1\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&archive).unwrap(),
+            "- [x] read habit:1 done:2024-06-29\n- [x] stretch habit:1 done:2024-07-01\n"
+        );
+        let archived = TodoList::load(&archive).unwrap();
+        let streaks = list.streaks(Some(&archived), day("2024-07-02"));
+        assert_eq!(streaks.get("stretch"), Some(1));
+        assert_eq!(streaks.get("read"), Some(0));
+
+        let plain = dir.path().join("plain.txt");
+        std::fs::write(&plain, "- [x] buy milk done:2024-06-30\n").unwrap();
+        let none = dir.path().join("none.txt");
+        let mut list = TodoList::load(&plain).unwrap();
+        assert_eq!(list.reset_habits(day("2024-07-02"), &none).unwrap(), 0);
+        assert!(!none.exists());
+    }
+
+    #[test]
+    fn test_details() {
+        let list = list_of(&[
+            "- [ ] (B) call mom about +party @phone due:2024-07-01 pin:1 spent:90",
+            "- [x] buy milk",
+        ]);
+        assert_eq!(
+            list.details(1).unwrap(),
+            Details {
+                index: 1,
+                done: false,
+                text: "call mom about".to_owned(),
+                priority: Some('B'),
+                created: None,
+                completed: None,
+                tags: vec!["+party".to_owned(), "@phone".to_owned()],
+                due: date::parse_date("2024-07-01"),
+                estimate: None,
+                snoozed_until: None,
+                pinned: true,
+                waiting: false,
+                waiting_for: None,
+                assignee: None,
+                started: None,
+                spent: 90,
+                checklist: Vec::new(),
+                attachments: Vec::new(),
+                line: "- [ ] (B) call mom about +party @phone due:2024-07-01 pin:1 spent:90"
+                    .to_owned(),
+            }
+        );
+        let details = list.details(2).unwrap();
+        assert!(details.done);
+        assert_eq!(
+            details.to_json(&Render::default())["priority"],
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            details.to_json(&Render::default())["checklist"],
+            serde_json::json!([])
+        );
+    }
+
+    #[test]
+    fn test_taskdata_display() {
+        let note = "test note";
+        let task_data = TaskData {
+            note: note.to_string(),
+            outline: None,
+            checklist: Vec::new(),
+        };
+
+        let display_string = format!("{}", task_data);
+        assert_eq!(note, display_string);
+    }
+
+    fn list_of(notes: &[&str]) -> TodoList<'static> {
+        TodoList {
+            path: Path::new(""),
+            format: Format::Markdown,
+            list: notes.iter().map(|n| n.parse().unwrap()).collect(),
+            invalid: Vec::new(),
+            preamble: Vec::new(),
+            deferred: false,
+            dirty: false,
+            lenient: false,
+            fingerprint: None,
+            read_only: false,
+            changes: Vec::new(),
+            activity: Vec::new(),
+            journaled: 0,
+        }
+    }
+
+    #[test]
+    fn test_tokens() {
+        let mut task_data = TaskData {
+            note: "call mom t:2024-07-01 soon".to_string(),
+            outline: None,
+            checklist: Vec::new(),
+        };
+        assert_eq!(task_data.token("t"), Some("2024-07-01"));
+        assert_eq!(task_data.token("due"), None);
+
+        task_data.set_token("t", Some("2024-08-01"));
+        assert_eq!(task_data.note, "call mom t:2024-08-01 soon");
+        task_data.set_token("pin", Some("1"));
+        assert_eq!(task_data.note, "call mom t:2024-08-01 soon pin:1");
+        assert_eq!(format!("{}", task_data), "call mom soon");
+        task_data.set_token("t", None);
+        assert_eq!(task_data.note, "call mom soon pin:1");
+        task_data.set_token("pin", None);
+        assert_eq!(task_data.note, "call mom soon");
+
+        let mut task_data = TaskData {
+            note: "pin:1 first".to_string(),
+            outline: None,
+            checklist: Vec::new(),
+        };
+        task_data.set_token("pin", None);
+        assert_eq!(task_data.note, "first");
+    }
+
+    #[test]
+    fn test_pin_survives_check() {
+        let task: Task = "- [ ] inbox zero pin:1".parse().unwrap();
+        let task = task.check(date::parse_date("2024-07-01").unwrap());
+        assert!(task.data().is_pinned());
+        assert_eq!(
+            format!("{:?}", task),
+            "- [x] inbox zero pin:1 done:2024-07-01"
+        );
+        let task = task.undo();
+        assert!(task.data().is_pinned());
+        assert_eq!(format!("{:?}", task), "- [ ] inbox zero pin:1");
+    }
+
+    #[test]
+    fn test_snoozed() {
+        let today = date::parse_date("2024-07-01").unwrap();
+        let snoozed = |note: &str| {
+            TaskData {
+                note: note.to_string(),
+                outline: None,
+                checklist: Vec::new(),
+            }
+            .is_snoozed(today)
+        };
+        assert!(snoozed("call mom t:2024-07-02"));
+        assert!(!snoozed("call mom t:2024-07-01"));
+        assert!(!snoozed("call mom t:2024-06-30"));
+        assert!(!snoozed("call mom t:soon"));
+        assert!(!snoozed("call mom"));
+    }
+
+    #[test]
+    fn test_today_sections() {
+        let list = list_of(&[
+            "- [ ] pay rent due:2024-06-30",
+            "- [ ] buy milk",
+            "- [ ] inbox zero pin:1",
+            "- [ ] call mom t:2024-07-01",
+            "- [x] file taxes due:2024-07-01",
+            "- [ ] renew passport due:2024-07-01 pin:1 #errand",
+            "- [ ] water plants due:2024-07-02 #errand",
+        ]);
+        let today = date::parse_date("2024-07-01").unwrap();
+        assert_eq!(
+            list.today_sections(today, None, Case::Smart),
+            vec![
+                ("Due today or overdue", vec![1, 6]),
+                ("Snoozed until today", vec![4]),
+                ("Pinned", vec![3]),
+            ]
+        );
+        let words = Words::has("#errand");
+        assert_eq!(
+            list.today_sections(today, Some(&words), Case::Smart),
+            vec![
+                ("Due today or overdue", vec![6]),
+                ("Snoozed until today", vec![]),
+                ("Pinned", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let list = list_of(&[
+            "- [ ] pay rent due:2024-06-30",
+            "- [ ] buy milk due:2024-07-01",
+            "- [x] file taxes due:2024-06-01",
+            "- [ ] call mom",
+        ]);
+        let today = date::parse_date("2024-07-01").unwrap();
+        assert_eq!(
+            list.stats(today),
+            Stats {
+                pending: 3,
+                done: 1,
+                overdue: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_estimates() {
+        let estimate = |s| Estimate::parse(s);
+        assert_eq!(estimate("2h"), Some(Estimate::Minutes(120)));
+        assert_eq!(estimate("1.5h"), Some(Estimate::Minutes(90)));
+        assert_eq!(estimate("45m"), Some(Estimate::Minutes(45)));
+        assert_eq!(estimate("3p"), Some(Estimate::Points(3)));
+        for garbage in ["soon", "h", "2", "-1h", "2d", "1e3h", "1.5p", "infh"] {
+            assert_eq!(estimate(garbage), None, "{}", garbage);
+        }
+        assert_eq!(Estimate::Minutes(90).to_string(), "1h 30m");
+
+        // Wednesday; the week started on Monday the 1st.
+        let today = date::parse_date("2024-07-03").unwrap();
+        let list = list_of(&[
+            "- [ ] write report est:2h",
+            "- [ ] fix the login bug est:3p",
+            "- [ ] reply to Ann est:30m",
+            "- [ ] plan est:soon",
+            "- [x] review PR est:1h done:2024-07-01",
+            "- [x] deploy est:1h done:2024-06-30",
+        ]);
+        assert_eq!(
+            list.workloads(today),
+            Workloads {
+                pending: Workload {
+                    minutes: 150,
+                    points: 3
+                },
+                done_this_week: Workload {
+                    minutes: 60,
+                    points: 0
+                },
+            }
+        );
+        assert_eq!(list.workloads(today).pending.to_string(), "2h 30m and 3p");
+        assert_eq!(list.details(4).unwrap().text, "plan est:soon");
+
+        let mut out = Vec::new();
+        let filter = Filter {
+            sort: Some(SortKey::Estimate),
+            ..Filter::new(Scope::Any)
+        };
+        let order = filter_print_lines(
+            &Render::default(),
+            &mut out,
+            &list.list[..4],
+            today,
+            false,
+            &filter,
+        )
+        .unwrap();
+        assert_eq!(order, vec![3, 1, 2, 4]);
+    }
+
+    #[test]
+    fn test_zero_indexed() {
+        let render = Render {
+            zero_indexed: true,
+            ..Render::default()
+        };
+        assert_eq!(render.shown_index(1), 0);
+        assert_eq!(render.given_index(0), 1);
+        assert_eq!(Render::default().given_index(1), 1);
+        let error = TodoError::NoSuchTask { index: 6, len: 2 };
+        assert_eq!(
+            error.numbered(&render).to_string(),
+            "no task at index 5 (valid: 0-1)"
+        );
+        assert_eq!(error.to_string(), "no task at index 6 (valid: 1-2)");
+    }
+
+    #[test]
+    fn test_render_note() {
+        let today = date::parse_date("2024-07-01").unwrap();
+        let render = |line: &str| render_note(&Render::default(), &line.parse().unwrap(), today);
+        assert_eq!(
+            render("- [ ] pay rent due:2024-06-28"),
+            format!("pay rent {}", Red.bold().paint("overdue 3d"))
+        );
+        assert_eq!(
+            render("- [ ] pay rent due:2024-07-01"),
+            format!("pay rent {}", Yellow.paint("due today"))
+        );
+        assert_eq!(
+            render("- [ ] pay rent due:2024-07-02"),
+            format!("pay rent {}", Yellow.paint("due tomorrow"))
+        );
+        assert_eq!(
+            render("- [ ] pay rent due:2024-07-05 soon"),
+            format!(
+                "pay rent {} soon",
+                Style::default().dimmed().paint("due in 4d")
+            )
+        );
+        assert_eq!(
+            render("- [ ] (A) pay rent due:2024-06-30 at the bank"),
+            format!(
+                "{} pay rent {} at the bank",
+                Red.bold().paint("(A)"),
+                Red.bold().paint("overdue 1d")
+            )
+        );
+        assert_eq!(
+            render("- [x] pay rent due:2024-06-28"),
+            Style::default()
+                .dimmed()
+                .strikethrough()
+                .paint("pay rent due:2024-06-28")
+                .to_string()
+        );
+        assert_eq!(
+            render("- [ ] (A) pay rent"),
+            format!("{} pay rent", Red.bold().paint("(A)"))
+        );
+        assert_eq!(
+            render("- [ ] (B) pay rent due:2024-07-01"),
+            format!(
+                "{} pay rent {}",
+                Yellow.paint("(B)"),
+                Yellow.paint("due today")
+            )
+        );
+        assert_eq!(
+            render("- [ ] (D) pay rent"),
+            format!("{} pay rent", Style::default().dimmed().paint("(D)"))
+        );
+        assert_eq!(
+            render("- [x] (A) pay rent"),
+            Style::default()
+                .dimmed()
+                .strikethrough()
+                .paint("(A) pay rent")
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_priority() {
+        let priority = |note: &str| {
+            TaskData {
+                note: note.to_string(),
+                outline: None,
+                checklist: Vec::new(),
+            }
+            .priority()
+        };
+        assert_eq!(priority("(A) call mom"), Some('A'));
+        assert_eq!(priority("(Z) call mom"), Some('Z'));
+        assert_eq!(priority("(a) call mom"), None);
+        assert_eq!(priority("(A)call mom"), None);
+        assert_eq!(priority("call mom (A)"), None);
+    }
+
+    #[test]
+    fn test_format_age() {
+        let ages: Vec<String> = [-2, 0, 3, 13, 14, 42, 364, 365, 800]
+            .into_iter()
+            .map(format_age)
+            .collect();
+        assert_eq!(
+            ages,
+            ["0d", "0d", "3d", "13d", "2w", "6w", "52w", "1y", "2y"]
+        );
+    }
+
+    #[test]
+    fn test_set_priority() {
+        let mut list = list_of(&["- [ ] (A) 2024-06-21 call mom", "- [ ] buy milk"]);
+        list.defer_saves();
+        list.set_priority(1, Some('B')).unwrap();
+        list.set_priority(2, Some('C')).unwrap();
+        assert_eq!(list.note(1), Some("(B) 2024-06-21 call mom"));
+        assert_eq!(list.note(2), Some("(C) buy milk"));
+        list.set_priority(1, None).unwrap();
+        assert_eq!(list.note(1), Some("2024-06-21 call mom"));
+
+        let raised: Vec<Option<char>> = [None, Some('C'), Some('B'), Some('A'), Some('E')]
+            .into_iter()
+            .map(raised_priority)
+            .collect();
+        assert_eq!(
+            raised,
+            [Some('C'), Some('B'), Some('A'), Some('A'), Some('D')]
+        );
+        let lowered: Vec<Option<char>> = [Some('A'), Some('B'), Some('C'), Some('E'), None]
+            .into_iter()
+            .map(lowered_priority)
+            .collect();
+        assert_eq!(lowered, [Some('B'), Some('C'), None, None, None]);
+    }
+
+    #[test]
+    fn test_next() {
+        let today = date::parse_date("2024-07-01").unwrap();
+        let list = list_of(&[
+            "- [ ] buy milk",
+            "- [ ] pay rent due:2024-07-03",
+            "- [ ] (B) call mom",
+            "- [ ] (B) file taxes due:2024-07-05",
+            "- [x] (A) water plants",
+            "- [ ] (A) renew passport t:2024-07-02",
+        ]);
+        assert_eq!(list.next(today), Some(4));
+        assert_eq!(list_of(&["- [ ] a", "- [ ] b"]).next(today), Some(1));
+        assert_eq!(
+            list_of(&["- [ ] a", "- [ ] b due:2024-08-01"]).next(today),
+            Some(2)
+        );
+        assert_eq!(list_of(&["- [x] a"]).next(today), None);
+    }
+
+    #[test]
+    fn test_last_pending() {
+        let list = list_of(&["- [ ] a", "- [ ] b", "- [x] c"]);
+        assert_eq!(list.last_pending(), Some(2));
+        assert_eq!(list_of(&["- [x] a"]).last_pending(), None);
+    }
+
+    #[test]
+    fn test_random() {
+        let list = list_of(&[
+            "- [ ] a",
+            "- [x] b",
+            "- [ ] c t:2099-01-01",
+            "- [ ] d dep:e",
+            "- [ ] e id:e",
+            "- [ ] f wait:bob",
+            "- [ ] g #home",
+        ]);
+        let today = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let filter = Filter::new(Scope::Pending);
+        let mut picked: Vec<usize> = (0..100)
+            .filter_map(|seed| list.random(&filter, today, seed))
+            .collect();
+        assert_eq!(
+            list.random(&filter, today, 7),
+            list.random(&filter, today, 7)
+        );
+        picked.sort_unstable();
+        picked.dedup();
+        assert_eq!(picked, [1, 5, 7]);
+        let home = Filter {
+            words: Some(Words::has("#home")),
+            ..Filter::new(Scope::Any)
+        };
+        assert_eq!(list.random(&home, today, 3), Some(7));
+        let none = Filter {
+            words: Some(Words::has("#work")),
+            ..Filter::new(Scope::Pending)
+        };
+        assert_eq!(list.random(&none, today, 3), None);
+    }
+
+    #[test]
+    fn test_time_tracking() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] write report\n- [ ] review PR\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        let at = |s: &str| date::parse_timestamp(s).unwrap();
+
+        assert!(matches!(
+            list.stop(at("2024-06-02T10:00:00Z")),
+            Err(TodoError::NotStarted)
+        ));
+        assert_eq!(list.start(1, at("2024-06-02T10:00:00Z")).unwrap(), None);
+        assert!(matches!(
+            list.start(1, at("2024-06-02T10:05:00Z")),
+            Err(TodoError::AlreadyStarted { index: 1 })
+        ));
+        assert_eq!(
+            list.start(2, at("2024-06-02T10:30:00Z")).unwrap(),
+            Some((1, 1800))
+        );
+        assert!(matches!(
+            list.stop(at("2024-06-02T09:00:00Z")),
+            Err(TodoError::ClockSkew { index: 2, .. })
+        ));
+        assert_eq!(
+            list.tracked(at("2024-06-02T10:40:00Z")).unwrap(),
+            vec![(1, 1800), (2, 600)]
+        );
+        assert_eq!(list.stop(at("2024-06-02T11:00:00Z")).unwrap(), (2, 1800));
+        assert_eq!(list.started(), None);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] write report spent:1800\n- [ ] review PR spent:1800\n"
+        );
+    }
+
+    #[test]
+    fn test_due_by() {
+        let list = list_of(&[
+            "- [ ] pay rent due:2024-06-30",
+            "- [ ] buy milk due:2024-07-03",
+            "- [x] file taxes due:2024-06-01",
+            "- [ ] call mom",
+            "- [ ] renew passport due:2024-07-04",
+        ]);
+        let today = date::parse_date("2024-07-01").unwrap();
+        let until = date::parse_date("2024-07-03").unwrap();
+        assert_eq!(list.due_by(today, until), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_completed_on() {
+        let list = list_of(&[
+            "- [x] call mom done:2024-07-01",
+            "- [ ] pay rent due:2024-07-01",
+            "- [x] buy milk",
+            "- [x] file taxes done:2024-06-30",
+            "- [x] water plants done:2024-07-01",
+        ]);
+        let day = |s| date::parse_date(s).unwrap();
+        assert_eq!(list.completed_on(day("2024-07-01")), vec![1, 5]);
+        assert_eq!(list.completed_on(day("2024-06-30")), vec![4]);
+        assert!(list.completed_on(day("2024-06-29")).is_empty());
+    }
+
+    #[test]
+    fn test_export() {
+        let mut list = list_of(&[
+            "- [ ] fix login #work",
+            "- [ ] buy milk",
+            "- [x] ship #work",
+        ]);
+        list.list[0].data_mut().checklist = vec![(true, "reproduce".to_owned())];
+        assert_eq!(
+            list.export(&[3, 1]).unwrap(),
+            b"- [x] ship #work\n- [ ] fix login #work\n  - [x] reproduce\n"
+        );
+        assert!(list.export(&[]).unwrap().is_empty());
+        assert!(list.export(&[4]).is_err());
+    }
+
+    #[test]
+    fn test_agenda() {
+        let list = list_of(&[
+            "- [ ] pay rent due:2024-06-30",
+            "- [ ] buy milk due:2024-07-03",
+            "- [ ] file taxes due:2024-06-01",
+            "- [ ] call mom t:2024-07-01",
+            "- [ ] renew passport due:2024-07-09",
+            "- [x] water plants due:2024-07-01",
+            "- [ ] book flights t:2024-06-20",
+            "- [ ] send invoice due:2024-07-01 t:2024-07-05",
+        ]);
+        let today = date::parse_date("2024-07-01").unwrap();
+        let until = date::parse_date("2024-07-08").unwrap();
+        assert_eq!(
+            list.agenda(today, until),
+            vec![
+                (Bucket::Overdue, vec![3, 1]),
+                (Bucket::Today, vec![4, 8]),
+                (Bucket::Upcoming, vec![2]),
+            ]
+        );
+        assert_eq!(
+            list.agenda(today, today),
+            vec![(Bucket::Overdue, vec![3, 1]), (Bucket::Today, vec![4, 8]),]
+        );
+        assert!(list_of(&["- [ ] buy milk"]).agenda(today, until).is_empty());
+    }
+
+    #[test]
+    fn test_created_between() {
+        let list = list_of(&[
+            "- [ ] 2024-06-28 pay rent",
+            "- [ ] (A) 2024-05-01 renew passport",
+            "- [ ] call mom",
+            "- [ ] 2024-07-01 buy milk",
+        ]);
+        let today = date::parse_date("2024-07-01").unwrap();
+        let since = Filter {
+            created_since: date::parse_since("7d", today),
+            ..Filter::new(Scope::Pending)
+        };
+        assert_eq!(list.matching(&since, today), vec![1, 4]);
+        let before = Filter {
+            created_before: date::parse_date("2024-07-01"),
+            include_undated: true,
+            ..Filter::new(Scope::Pending)
+        };
+        assert_eq!(list.matching(&before, today), vec![1, 2, 3]);
+        let between = Filter {
+            created_before: date::parse_date("2024-06-30"),
+            ..since
+        };
+        assert_eq!(list.matching(&between, today), vec![1]);
+    }
+
+    #[test]
+    fn test_move_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("todo.txt");
+        let dest_path = dir.path().join("work.txt");
+        std::fs::write(
+            &source_path,
+            "- [ ] buy milk\n- [x] review PR due:2024-07-01\n",
+        )
+        .unwrap();
+        std::fs::write(&dest_path, "- [ ] deploy\n").unwrap();
+        let mut source = TodoList::load(&source_path).unwrap();
+        let mut dest = TodoList::load(&dest_path).unwrap();
+
+        assert_eq!(source.move_to(2, &mut dest).unwrap(), 2);
+        assert!(matches!(
+            source.move_to(2, &mut dest),
+            Err(TodoError::NoSuchTask { index: 2, len: 1 })
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&source_path).unwrap(),
+            "- [ ] buy milk\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&dest_path).unwrap(),
+            "- [ ] deploy\n- [x] review PR due:2024-07-01\n"
+        );
+    }
+
+    #[test]
+    fn test_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let archive_path = dir.path().join("todo.done.txt");
+        std::fs::write(
+            &path,
+            "- [x] file taxes done:2024-06-16\n- [ ] buy milk\n- [x] call mom\n\
+             - [x] water plants done:2024-06-17\n- [x] pay rent done:2024-05-01\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        let cutoff = date::parse_date("2024-06-17").unwrap();
+
+        assert_eq!(list.archive(cutoff, &archive_path).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [x] call mom\n- [x] water plants done:2024-06-17\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&archive_path).unwrap(),
+            "- [x] file taxes done:2024-06-16\n- [x] pay rent done:2024-05-01\n"
+        );
+        let archived: Vec<_> = list
+            .take_activity()
+            .iter()
+            .map(|a| (a.verb, a.index))
+            .collect();
+        assert_eq!(archived, [("archive", 1), ("archive", 5)]);
+
+        std::fs::remove_file(&archive_path).unwrap();
+        assert_eq!(list.archive(cutoff, &archive_path).unwrap(), 0);
+        assert!(!archive_path.exists());
+    }
+
+    #[test]
+    fn test_cleanup_before() {
+        let mut list = list_of(&[
+            "- [x] file taxes done:2024-06-16",
+            "- [ ] buy milk",
+            "- [x] call mom",
+            "- [x] water plants done:2024-06-17",
+        ]);
+        list.defer_saves();
+        let cutoff = date::parse_date("2024-06-17").unwrap();
+        assert_eq!(list.cleanup_before(cutoff, false).unwrap(), 1);
+        let notes: Vec<_> = list.list.iter().map(|t| t.data().note.clone()).collect();
+        assert_eq!(
+            notes,
+            ["buy milk", "call mom", "water plants done:2024-06-17"]
+        );
+        assert_eq!(list.cleanup_before(cutoff, true).unwrap(), 1);
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_text() {
+        let text = |note: &str| {
+            TaskData {
+                note: note.to_string(),
+                outline: None,
+                checklist: Vec::new(),
+            }
+            .text()
+        };
+        assert_eq!(
+            text("(A) buy  milk due:2024-07-01 #home +errands @shop"),
+            "buy milk"
+        );
+        assert_eq!(
+            text("read https://example.com # now"),
+            "read https://example.com # now"
+        );
+        assert_eq!(text("meet at 10:30"), "meet at 10:30");
+    }
+
+    #[test]
+    fn test_import_skips_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] buy milk\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        let tasks = vec![
+            (false, "(A) buy milk #home".to_owned()),
+            (true, "file taxes".to_owned()),
+            (false, "file taxes due:2024-07-01".to_owned()),
+        ];
+
+        assert_eq!(
+            list.import(tasks.clone(), true).unwrap(),
+            vec![(true, "file taxes".to_owned())]
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "- [ ] buy milk\n");
+        assert_eq!(list.import(tasks.clone(), false).unwrap().len(), 1);
+        assert!(list.import(tasks, false).unwrap().is_empty());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [x] file taxes\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [ ] buy milk\n- [ ] (A) 2024-06-01 pay rent due:2024-07-01\n\
+             - [x] call mom done:2024-06-20\n- [ ] fix login #work\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        let other = list_of(&[
+            "- [ ]   buy   milk ",
+            "- [x] (B) pay rent due:2024-07-03 done:2024-06-30",
+            "- [ ] call mom",
+            "- [ ] fix login #home",
+            "- [ ] Buy milk",
+            "- [ ] buy milk tomorrow",
+            "- [x] water plants done:2024-06-29",
+            "- [ ] buy milk tomorrow t:2024-07-02",
+        ]);
+        let merged = list.merge_import(&other).unwrap();
+        assert_eq!(
+            merged,
+            Merged {
+                added: vec![5, 6, 7, 8],
+                upgraded: vec![2],
+                skipped: 3,
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [x] (A) 2024-06-01 pay rent due:2024-07-01 done:2024-06-30\n\
+             - [x] call mom done:2024-06-20\n- [ ] fix login #work\n- [ ] fix login #home\n\
+             - [ ] Buy milk\n- [ ] buy milk tomorrow\n- [x] water plants done:2024-06-29\n"
+        );
+        assert_eq!(
+            list.merge_import(&other).unwrap().added,
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn test_dedup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [x] buy milk\n- [ ] call mom\n- [ ] buy milk \n- [ ] call mom\n\
+             - [x] buy milk\n- [x] water plants\n- [x] water plants\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+
+        assert_eq!(list.duplicates(), vec![4, 5, 7]);
+        let removed = list.dedup().unwrap();
+        assert_eq!(removed[0], (4, "call mom".to_string()));
+        assert_eq!(removed.len(), 3);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [x] buy milk\n- [ ] call mom\n- [ ] buy milk \n- [x] water plants\n"
+        );
+        assert!(list.dedup().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_selector() {
+        let list = list_of(&[
+            "- [ ] Buy milk",
+            "- [ ] call mom",
+            "- [x] buy bread",
+            "- [ ] make coffee",
+        ]);
+
+        assert_eq!(
+            list.resolve_selector("7", Scope::Pending, Case::Smart)
+                .unwrap(),
+            7
+        );
+        assert_eq!(
+            list.resolve_selector("milk", Scope::Pending, Case::Smart)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            list.resolve_selector("MILK", Scope::Pending, Case::Ignore)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            list.resolve_selector("Buy", Scope::Any, Case::Smart)
+                .unwrap(),
+            1
+        );
+        assert!(matches!(
+            list.resolve_selector("buy", Scope::Any, Case::Smart),
+            Err(TodoError::Ambiguous { .. })
+        ));
+        assert!(matches!(
+            list.resolve_selector("Call", Scope::Any, Case::Smart),
+            Err(TodoError::NoMatch { .. })
+        ));
+        assert!(matches!(
+            list.resolve_selector("bread", Scope::Any, Case::Sensitive),
+            Ok(3)
+        ));
+        assert_eq!(
+            list.resolve_selector("buy", Scope::Pending, Case::Smart)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            list.resolve_selector("buy", Scope::Done, Case::Smart)
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            list.resolve_selector("cm", Scope::Pending, Case::Smart)
+                .unwrap(),
+            2
+        );
+        assert!(matches!(
+            list.resolve_selector("mo", Scope::Pending, Case::Smart),
+            Err(TodoError::Ambiguous { .. })
+        ));
+        assert!(matches!(
+            list.resolve_selector("tea", Scope::Any, Case::Smart),
+            Err(TodoError::NoMatch { .. })
+        ));
+
+        let list = list_of(&[
+            "- [ ] water plants pin:1 id:ab",
+            "- [x] pay rent done:2024-07-01",
+        ]);
+        assert_eq!(
+            list.resolve_selector("plants", Scope::Any, Case::Smart)
+                .unwrap(),
+            1
+        );
+        for selector in ["pin", "id:ab", "done"] {
+            assert!(matches!(
+                list.resolve_selector(selector, Scope::Any, Case::Smart),
+                Err(TodoError::NoMatch { .. })
+            ));
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+struct TaskData {
+    note: String,
+    /// The task's place in an org file, for one read from such a file.
+    outline: Option<Box<org::Outline>>,
+    /// The items of its checklist, each checked or not, written as
+    /// `  - [ ] item` lines under the task's line.
+    checklist: Vec<(bool, String)>,
+}
+
+/// Tokens that only carry flags for the tool and are hidden when a note
+/// is displayed. Attachments are listed by `todo show` instead.
+const HIDDEN_TOKENS: &[&str] = &[
+    "pin", "t", "start", "spent", "done", "id", "dep", "wait", "file", "assignee",
+];
+
+impl fmt::Display for TaskData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut note = self.note.clone();
+        for key in HIDDEN_TOKENS {
+            while let Some(range) = find_token(&note, key) {
+                note.replace_range(token_removal(&note, range), "");
+            }
+        }
+        write!(f, "{}", note)
+    }
+}
+
+/// The hidden tokens of `note`, in order.
+fn hidden_tokens(note: &str) -> Vec<&str> {
+    let mut tokens: Vec<(usize, usize)> = HIDDEN_TOKENS
+        .iter()
+        .flat_map(|key| find_tokens(note, key))
+        .collect();
+    tokens.sort_unstable();
+    tokens
+        .iter()
+        .map(|&(start, end)| &note[start..end])
+        .collect()
+}
+
+/// Finds the byte range of the first `key:value` word in `note`.
+fn find_token(note: &str, key: &str) -> Option<(usize, usize)> {
+    find_tokens(note, key).next()
+}
+
+/// The byte ranges of the `key:value` words in `note`, in order.
+fn find_tokens<'a>(note: &'a str, key: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let mut start = 0;
+    note.split(' ').filter_map(move |word| {
+        let range = (start, start + word.len());
+        start = range.1 + 1;
+        let value = word.strip_prefix(key)?.strip_prefix(':')?;
+        (!value.is_empty()).then_some(range)
+    })
+}
+
+/// The value of a `file:` token for `path`: spaces, tabs and `%` are
+/// written as `%20`, `%09` and `%25`, so that the path stays one word.
+fn encode_path(path: &str) -> String {
+    path.replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('\t', "%09")
+}
+
+/// The path of a `file:` token's value; see `encode_path`.
+fn decode_path(value: &str) -> String {
+    let mut path = String::new();
+    let mut rest = value;
+    while let Some(i) = rest.find('%') {
+        path.push_str(&rest[..i]);
+        let (decoded, len) = match rest.get(i..i + 3) {
+            Some("%20") => (" ", 3),
+            Some("%09") => ("\t", 3),
+            Some("%25") => ("%", 3),
+            _ => ("%", 1),
+        };
+        path.push_str(decoded);
+        rest = &rest[i + len..];
+    }
+    path.push_str(rest);
+    path
+}
+
+/// Whether `word` carries metadata rather than text: a `key:value` token,
+/// a `#tag`, a `+project` or an `@context`.
+fn is_token_word(word: &str) -> bool {
+    if let Some(rest) = word.strip_prefix(['#', '+', '@']) {
+        return !rest.is_empty();
+    }
+    match word.split_once(':') {
+        // An estimate that doesn't read as one is part of the text.
+        Some(("est", value)) => Estimate::parse(value).is_some(),
+        // Attachments may have absolute paths, which other values can't
+        // for URLs not to read as tokens.
+        Some(("file", value)) => !value.is_empty() && !value.starts_with("//"),
+        Some((key, value)) => {
+            key.starts_with(|c: char| c.is_ascii_alphabetic())
+                && !value.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                && !value.starts_with('/')
+        }
+        None => false,
+    }
+}
+
+/// Widens a token range to swallow one of the spaces around it, so that
+/// removing it leaves the rest of the note untouched.
+fn token_removal(note: &str, (start, end): (usize, usize)) -> std::ops::Range<usize> {
+    if start > 0 {
+        start - 1..end
+    } else if end < note.len() {
+        start..end + 1
+    } else {
+        start..end
+    }
+}
+
+impl TaskData {
+    fn token(&self, key: &str) -> Option<&str> {
+        find_token(&self.note, key).map(|(start, end)| &self.note[start + key.len() + 1..end])
+    }
+
+    /// Replaces the value of the `key` token, appending the token if the
+    /// note doesn't carry it yet, or removes it when `value` is `None`.
+    fn set_token(&mut self, key: &str, value: Option<&str>) {
+        match (find_token(&self.note, key), value) {
+            (Some(range), Some(value)) => self
+                .note
+                .replace_range(range.0..range.1, &format!("{}:{}", key, value)),
+            (Some(range), None) => {
+                let removal = token_removal(&self.note, range);
+                self.note.replace_range(removal, "");
+            }
+            (None, Some(value)) => {
+                if !self.note.is_empty() {
+                    self.note.push(' ');
+                }
+                self.note.push_str(&format!("{}:{}", key, value));
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// The words of the note, without its priority and any tokens, tags,
+    /// projects or contexts.
+    fn text(&self) -> String {
+        let note = match self.priority() {
+            Some(_) => &self.note[4..],
+            None => &self.note[..],
+        };
+        note.split_whitespace()
+            .filter(|w| !is_token_word(w))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The text of a daily task, one with `rec:1d` or a `habit:` token,
+    /// without a creation date: what its streak goes by.
+    fn habit(&self) -> Option<String> {
+        if self.token("rec") != Some("1d") && !self.is_habit() {
+            return None;
+        }
+        let text = self.text();
+        Some(match self.created() {
+            Some(_) => text.split_once(' ').map_or("", |(_, rest)| rest).to_owned(),
+            None => text,
+        })
+    }
+
+    /// The words of the note without its priority, creation date and
+    /// `key:value` tokens, which tasks are matched by in `merge_import`.
+    fn merge_key(&self) -> String {
+        let mut words = self.note.split_whitespace().peekable();
+        if self.priority().is_some() {
+            words.next();
+        }
+        words.next_if(|w| date::parse_date(w).is_some());
+        words
+            .filter(|w| w.starts_with(['#', '+', '@']) || !is_token_word(w))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Whether the task is a habit that `reset_habits` makes pending again
+    /// every day, from its `habit:` token.
+    fn is_habit(&self) -> bool {
+        self.token("habit").is_some()
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.token("pin").is_some()
+    }
+
+    /// The `id:` other tasks name in their `dep:` token to depend on this
+    /// one.
+    fn id(&self) -> Option<&str> {
+        self.token("id")
+    }
+
+    /// The ids of the tasks this one depends on, from its `dep:1,4` token.
+    fn deps(&self) -> impl Iterator<Item = &str> {
+        self.token("dep")
+            .into_iter()
+            .flat_map(|deps| deps.split(','))
+            .filter(|id| !id.is_empty())
+    }
+
+    /// The date until which the task is snoozed, following todo.txt's
+    /// threshold date convention.
+    fn threshold(&self) -> Option<NaiveDate> {
+        self.token("t").and_then(date::parse_date)
+    }
+
+    /// The todo.txt priority of the task, an uppercase letter in
+    /// parentheses at the start of the note such as `(A) `.
+    fn priority(&self) -> Option<char> {
+        match self.note.as_bytes() {
+            [b'(', p @ b'A'..=b'Z', b')', b' ', ..] => Some(*p as char),
+            _ => None,
+        }
+    }
+
+    /// Replaces the priority with `priority`, or removes it.
+    fn set_priority(&mut self, priority: Option<char>) {
+        if self.priority().is_some() {
+            self.note.replace_range(..4, "");
+        }
+        if let Some(p) = priority {
+            self.note.insert_str(0, &format!("({}) ", p));
+        }
+    }
+
+    fn due(&self) -> Option<NaiveDate> {
+        self.token("due").and_then(date::parse_date)
+    }
+
+    /// The paths of the files attached with `file:` tokens, as given.
+    fn attachments(&self) -> Vec<String> {
+        find_tokens(&self.note, "file")
+            .map(|(start, end)| decode_path(&self.note[start + "file:".len()..end]))
+            .collect()
+    }
+
+    /// The size of the task from its `est:` token, if it reads as one.
+    fn estimate(&self) -> Option<Estimate> {
+        self.token("est").and_then(Estimate::parse)
+    }
+
+    /// The date the task was checked, stamped by `check`.
+    fn completed(&self) -> Option<NaiveDate> {
+        self.token("done").and_then(date::parse_date)
+    }
+
+    /// The todo.txt creation date, a date right after the priority or at
+    /// the start of the note.
+    fn created(&self) -> Option<NaiveDate> {
+        let note = match self.priority() {
+            Some(_) => &self.note[4..],
+            None => &self.note[..],
+        };
+        note.split(' ').next().and_then(date::parse_date)
+    }
+
+    fn is_started(&self) -> bool {
+        self.token("start").is_some()
+    }
+
+    /// Whether the task waits on someone, from its `wait:` token.
+    fn is_waiting(&self) -> bool {
+        self.token("wait").is_some()
+    }
+
+    /// Who the task waits on: the name in its `wait:Alice_Smith` token
+    /// with spaces for underscores, unless it's a bare `wait:1`.
+    fn waiting_for(&self) -> Option<String> {
+        self.token("wait")
+            .filter(|who| *who != "1")
+            .map(|who| who.replace('_', " "))
+    }
+
+    /// Who the task is assigned to, from its `assignee:` token.
+    fn assignee(&self) -> Option<&str> {
+        self.token("assignee")
+    }
+
+    /// Seconds tracked in closed intervals.
+    fn spent(&self) -> i64 {
+        self.token("spent")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0)
+    }
+
+    fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.due().is_some_and(|due| due < today)
+    }
+
+    fn is_snoozed(&self, today: NaiveDate) -> bool {
+        self.threshold().is_some_and(|t| t > today)
+    }
+
+    /// Whether the note has `word`, such as `#tag` or `+project`.
+    fn has_word(&self, word: &str, case: Case) -> bool {
+        let ignore = case.ignores(word);
+        self.note.split(' ').any(|w| match ignore {
+            true => w.to_lowercase() == word.to_lowercase(),
+            false => w == word,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Task {
+    DoneTask(TaskData),
+    TodoTask(TaskData),
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Task::DoneTask(task_data) => write!(f, "- [x] {}", task_data.note),
+            Task::TodoTask(task_data) => write!(f, "- [ ] {}", task_data.note),
+        }
+    }
+}
+
+/// Trims the note of a new task, rejecting one that's empty or wouldn't
+/// read back as written: every task is one line of the file, and a `\r`
+/// at its end would be taken for part of a `\r\n` line ending. Any other
+/// text round-trips, including text that looks like a task line itself.
+pub fn clean_note(note: &str) -> Result<&str, TodoError> {
+    let note = note.trim();
+    if note.is_empty() {
+        return Err(TodoError::EmptyNote);
+    }
+    if note.contains(['\n', '\r']) {
+        return Err(TodoError::MultilineNote {
+            note: note.to_owned(),
+        });
+    }
+    Ok(note)
+}
+
+/// Collapses every run of whitespace in `note` into a single space.
+pub fn squeeze_whitespace(note: &str) -> String {
+    note.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl Task {
+    fn new(note: &str) -> Self {
+        Task::TodoTask(TaskData {
+            note: note.to_owned(),
+            outline: None,
+            checklist: Vec::new(),
+        })
+    }
+
+    fn data(&self) -> &TaskData {
+        match self {
+            Task::DoneTask(task_data) | Task::TodoTask(task_data) => task_data,
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut TaskData {
+        match self {
+            Task::DoneTask(task_data) | Task::TodoTask(task_data) => task_data,
+        }
+    }
+
+    /// Checks the task, stamping it with the date it was done `on`.
+    fn check(self, on: NaiveDate) -> Self {
+        match self {
+            Task::TodoTask(mut task_data) => {
+                task_data.set_token("wait", None);
+                task_data.set_token("done", Some(&on.format("%Y-%m-%d").to_string()));
+                Task::DoneTask(task_data)
+            }
+            Task::DoneTask(_) => self,
+        }
+    }
+
+    /// Whether the task is a habit checked before `today`, which
+    /// `TodoList::reset_habits` makes pending again.
+    fn is_stale_habit(&self, today: NaiveDate) -> bool {
+        match self {
+            Task::DoneTask(data) => data.is_habit() && data.completed().is_some_and(|d| d < today),
+            Task::TodoTask(_) => false,
+        }
+    }
+
+    /// Whether the task was done before `cutoff`, which `TodoList::archive`
+    /// moves to the archive.
+    fn is_done_before(&self, cutoff: NaiveDate) -> bool {
+        matches!(self, Task::DoneTask(data) if data.completed().is_some_and(|d| d < cutoff))
+    }
+
+    /// Reopens a done task, or stops a pending one waiting.
+    fn undo(self) -> Self {
+        match self {
+            Task::DoneTask(mut task_data) => {
+                task_data.set_token("done", None);
+                Task::TodoTask(task_data)
+            }
+            Task::TodoTask(mut task_data) => {
+                task_data.set_token("wait", None);
+                Task::TodoTask(task_data)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TaskParseError;
+
+/// Splits a `- [ ] note` or `- [x] note` line into whether it's checked
+/// and its note.
+fn parse_line(line: &str) -> Option<(bool, &str)> {
+    let rest = line.strip_prefix("- [")?;
+    let (done, rest) = match rest.as_bytes().first()? {
+        b'x' => (true, &rest[1..]),
+        b' ' => (false, &rest[1..]),
+        _ => return None,
+    };
+    let note = rest.strip_prefix("] ")?;
+    if note.contains('\n') {
+        return None;
+    }
+    Some((done, note))
+}
+
+impl FromStr for Task {
+    type Err = TaskParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (done, note) = parse_line(s).ok_or(TaskParseError)?;
+        let task_data = TaskData {
+            note: note.to_owned(),
+            outline: None,
+            checklist: Vec::new(),
+        };
+        Ok(if done {
+            Task::DoneTask(task_data)
+        } else {
+            Task::TodoTask(task_data)
+        })
+    }
+}
+
+/// Parses the lines of `reader` into tasks, calling `f` with the 1-based
+/// line number and the task, or the content of a line that isn't one. One
+/// line buffer is recycled for the whole read.
+///
+/// A line of two spaces and then a task line is an item of the checklist
+/// of the task before it, if it directly follows that task or another of
+/// its items. Any other indented line isn't a task.
+fn read_lines(
+    mut reader: impl BufRead,
+    mut f: impl FnMut(usize, Result<Task, &str>) -> Result<(), TodoError>,
+) -> Result<usize, TodoError> {
+    let mut line = String::new();
+    let mut count = 0;
+    // The last task, with its line number, held back until the lines of
+    // its checklist are read.
+    let mut held: Option<(usize, Task)> = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            if let Some((n, task)) = held {
+                f(n, Ok(task))?;
+            }
+            return Ok(count);
+        }
+        count += 1;
+        // Line endings are stripped like `BufRead::lines` does.
+        let content = line.strip_suffix('\n').unwrap_or(&line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        let item = content.strip_prefix("  ").and_then(parse_line);
+        if let (Some((_, task)), Some((done, text))) = (&mut held, item) {
+            task.data_mut().checklist.push((done, text.to_owned()));
+            continue;
+        }
+        if let Some((n, task)) = held.take() {
+            f(n, Ok(task))?;
+        }
+        match content.parse() {
+            Ok(task) => held = Some((count, task)),
+            Err(_) => f(count, Err(content))?,
+        }
+    }
+}
+
+/// Like `read_lines`, failing on the first line that isn't a task, but
+/// calling `f` with the 1-based index of each task rather than its line.
+/// Returns the number of tasks.
+fn read_tasks(reader: impl BufRead, mut f: impl FnMut(usize, Task)) -> Result<usize, TodoError> {
+    let mut count = 0;
+    read_lines(reader, |line, task| match task {
+        Ok(task) => {
+            count += 1;
+            f(count, task);
+            Ok(())
+        }
+        Err(content) => Err(TodoError::Parse {
+            line,
+            content: content.to_owned(),
+        }),
+    })?;
+    Ok(count)
+}
+
+/// Writes `task` as a line of a Markdown list, followed by the lines of
+/// its checklist.
+fn write_task(file: &mut impl Write, task: &Task) -> io::Result<()> {
+    writeln!(file, "{:?}", task)?;
+    for (done, item) in &task.data().checklist {
+        writeln!(file, "  - [{}] {}", if *done { 'x' } else { ' ' }, item)?;
+    }
+    Ok(())
+}
+
+/// Replaces the file at `target` with what `contents` writes, through a
+/// temporary file next to it renamed over it, so that a failure leaves the
+/// file as it was.
+fn replace_file(
+    target: &Path,
+    contents: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp = target.with_file_name(format!(".{}.tmp", name));
+    let written = File::create(&temp).and_then(|file| {
+        let mut file = BufWriter::new(file);
+        contents(&mut file)?;
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()
+    });
+    if let Ok(metadata) = fs::metadata(target) {
+        let _ = fs::set_permissions(&temp, metadata.permissions());
+    }
+    written
+        .and_then(|()| fs::rename(&temp, target))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })
+}
+
+/// Fails with `TodoError::ReadOnly` if lists are read-only or the file at
+/// `path` has no write permission, before anything writes it; every write
+/// of a list, and of a file made from one, starts here, so that read-only
+/// mode can't be missed.
+pub fn ensure_writable(path: &Path) -> Result<(), TodoError> {
+    match is_read_only() || has_no_write_permission(path) {
+        true => Err(TodoError::ReadOnly {
+            path: path.to_owned(),
+        }),
+        false => Ok(()),
+    }
+}
+
+/// Whether the file at `path` is marked read-only, which doesn't stop
+/// root from writing it as the system would.
+fn has_no_write_permission(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.permissions().readonly())
+}
+
+/// Writes `content` to the file at `path`, such as an export, unless lists
+/// are read-only.
+pub fn write_file(path: &Path, content: &[u8]) -> Result<(), TodoError> {
+    ensure_writable(path)?;
+    Ok(fs::write(path, content)?)
+}
+
+/// Renames the file at `from` to `to`, like a conflict copy once it's
+/// merged, unless lists are read-only.
+pub fn rename_file(from: &Path, to: &Path) -> Result<(), TodoError> {
+    ensure_writable(from)?;
+    Ok(fs::rename(from, to)?)
+}
+
+/// Removes the file at `path`, unless lists are read-only.
+pub fn remove_file(path: &Path) -> Result<(), TodoError> {
+    ensure_writable(path)?;
+    Ok(fs::remove_file(path)?)
+}
+
+/// Whether `e` means a file can't be written where it is, as with files of
+/// other users or on read-only mounts.
+pub(crate) fn is_unwritable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+/// Shows `path` with the home directory abbreviated to `~`.
+fn tilde(path: &Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_owned)) {
+        Some(rest) => Path::new("~").join(rest).display().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub enum TodoError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        content: String,
+    },
+    Save {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A note with a line break, which the file format can't hold.
+    MultilineNote {
+        note: String,
+    },
+    EmptyNote,
+    /// A number that isn't in the last renumbered listing, or whose task
+    /// changed since.
+    StaleNumber {
+        number: usize,
+    },
+    NoSuchTask {
+        index: usize,
+        /// The number of tasks in the list.
+        len: usize,
+    },
+    NoSuchItem {
+        index: usize,
+        item: usize,
+        /// The number of items in the task's checklist.
+        len: usize,
+    },
+    NoSuchAttachment {
+        index: usize,
+        attachment: usize,
+        /// The number of files attached to the task.
+        len: usize,
+    },
+    /// Checklists are kept as indented lines, which org files don't have.
+    OrgChecklist,
+    /// Making the task at `index` wait for the one at `on` would make it
+    /// wait for itself.
+    DependencyCycle {
+        index: usize,
+        on: usize,
+    },
+    NoMatch {
+        selector: String,
+    },
+    Ambiguous {
+        selector: String,
+        candidates: Vec<(usize, String)>,
+    },
+    /// Only pending tasks can be split.
+    SplitDone {
+        index: usize,
+    },
+    /// Only checked tasks can be reopened.
+    NotChecked {
+        index: usize,
+    },
+    /// A task can't be joined with itself.
+    JoinSame {
+        index: usize,
+    },
+    NotStarted,
+    AlreadyStarted {
+        index: usize,
+    },
+    BadTimestamp {
+        index: usize,
+        value: String,
+    },
+    ClockSkew {
+        index: usize,
+        started: String,
+    },
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+    Import {
+        message: String,
+    },
+    /// Neither the environment nor the system tells where lists are kept.
+    NoHome {
+        /// The environment variables that would.
+        hint: &'static str,
+    },
+    /// The undo journal has no saves left.
+    NothingToUndo,
+    /// No save has been undone since the last one.
+    NothingToRedo,
+    /// The file was changed by something other than todo since its last
+    /// save, so that save can't be undone.
+    ChangedElsewhere,
+    /// A sync command from the configuration failed.
+    Sync {
+        command: String,
+        message: String,
+    },
+    /// The hook run after a change failed, or was stopped for taking too
+    /// long.
+    Hook {
+        command: String,
+        message: String,
+    },
+    /// A list on a WebDAV server couldn't be fetched or put back.
+    Remote {
+        url: String,
+        message: String,
+    },
+    /// A change to a list opened read-only; see `set_read_only`.
+    ReadOnly {
+        path: PathBuf,
+    },
+}
+
+impl TodoError {
+    /// The message of the error, with the tasks it names numbered as
+    /// `render` shows them.
+    pub fn numbered<'a>(&'a self, render: &'a Render) -> impl fmt::Display + 'a {
+        Numbered(self, render)
+    }
+}
+
+struct Numbered<'a>(&'a TodoError, &'a Render);
+
+impl fmt::Display for TodoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Numbered(self, &Render::default()).fmt(f)
+    }
+}
+
+impl fmt::Display for Numbered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Numbered(error, render) = self;
+        let shown_index = |index: usize| render.shown_index(index);
+        match error {
+            TodoError::Io(e) => write!(f, "{}", e),
+            TodoError::Parse { line, content } => {
+                write!(f, "line {} isn't a task: {:?}", line, content)
+            }
+            TodoError::Save { path, source } => {
+                write!(f, "could not save {}: {}", tilde(path), source.kind())
+            }
+            TodoError::MultilineNote { note } => {
+                write!(f, "a note can't contain line breaks: {:?}", note)
+            }
+            TodoError::EmptyNote => write!(f, "refusing to add an empty task"),
+            TodoError::StaleNumber { number } => write!(
+                f,
+                "no task is numbered {} by the last `todo ls --renumbered`; list again",
+                shown_index(*number)
+            ),
+            TodoError::NoSuchTask { index, len: 0 } => write!(
+                f,
+                "no task at index {} (the list is empty)",
+                shown_index(*index)
+            ),
+            TodoError::NoSuchTask { index, len } => write!(
+                f,
+                "no task at index {} (valid: {}-{})",
+                shown_index(*index),
+                shown_index(1),
+                shown_index(*len)
+            ),
+            TodoError::NoSuchItem {
+                index,
+                item,
+                len: 0,
+            } => write!(
+                f,
+                "no item {} (task {} has no checklist)",
+                item,
+                shown_index(*index)
+            ),
+            TodoError::NoSuchItem { index, item, len } => write!(
+                f,
+                "no item {} in task {} (valid: 1-{})",
+                item,
+                shown_index(*index),
+                len
+            ),
+            TodoError::NoSuchAttachment { index, len: 0, .. } => {
+                write!(f, "task {} has no attachments", shown_index(*index))
+            }
+            TodoError::NoSuchAttachment {
+                index,
+                attachment,
+                len,
+            } => write!(
+                f,
+                "no attachment {} in task {} (valid: 1-{})",
+                attachment,
+                shown_index(*index),
+                len
+            ),
+            TodoError::DependencyCycle { index, on } if index == on => {
+                write!(f, "task {} can't wait for itself", shown_index(*index))
+            }
+            TodoError::DependencyCycle { index, on } => write!(
+                f,
+                "task {} already waits for task {}, so it can't block it",
+                shown_index(*on),
+                shown_index(*index)
+            ),
+            TodoError::OrgChecklist => write!(
+                f,
+                "org lists can't keep checklists; use org's own checkboxes under the headline"
+            ),
+            TodoError::NoMatch { selector } => write!(f, "no task matches '{}'", selector),
+            TodoError::Ambiguous {
+                selector,
+                candidates,
+            } => {
+                write!(f, "'{}' matches several tasks:", selector)?;
+                for (i, note) in candidates {
+                    write!(f, "\n  {}. {}", shown_index(*i), note)?;
+                }
+                Ok(())
+            }
+            TodoError::NotStarted => write!(f, "no task is started"),
+            TodoError::AlreadyStarted { index } => {
+                write!(f, "task {} is already started", shown_index(*index))
+            }
+            TodoError::BadTimestamp { index, value } => write!(
+                f,
+                "task {} has an invalid start time '{}', remove its start: token",
+                shown_index(*index),
+                value
+            ),
+            TodoError::Import { message } => write!(f, "cannot import: {}", message),
+            TodoError::NoHome { hint } => {
+                write!(
+                    f,
+                    "can't find the home directory to keep lists in; set {}",
+                    hint
+                )
+            }
+            TodoError::Config { path, message } => {
+                write!(f, "invalid config {}: {}", path.display(), message)
+            }
+            TodoError::ClockSkew { index, started } => write!(
+                f,
+                "task {} was started at {}, which is in the future; check the system clock",
+                shown_index(*index),
+                started
+            ),
+            TodoError::SplitDone { index } => write!(
+                f,
+                "task {} is checked, and only pending tasks can be split",
+                shown_index(*index)
+            ),
+            TodoError::NotChecked { index } => write!(
+                f,
+                "task {} isn't checked, and only checked tasks can be reopened",
+                shown_index(*index)
+            ),
+            TodoError::JoinSame { index } => {
+                write!(
+                    f,
+                    "task {} can't be joined with itself",
+                    shown_index(*index)
+                )
+            }
+            TodoError::NothingToUndo => write!(f, "nothing to undo"),
+            TodoError::NothingToRedo => write!(f, "nothing to redo"),
+            TodoError::ChangedElsewhere => write!(
+                f,
+                "the list was changed by something other than todo since its last change, \
+                 so that can't be undone; `todo recover` lists the backups"
+            ),
+            TodoError::Sync { command, message } => {
+                write!(f, "sync command `{}` failed: {}", command, message)
+            }
+            TodoError::Hook { command, message } => {
+                write!(f, "hook `{}` failed: {}", command, message)
+            }
+            TodoError::Remote { url, message } => write!(f, "{}: {}", url, message),
+            TodoError::ReadOnly { path } => write!(
+                f,
+                "{} is read-only (--read-only, or the file can't be written); nothing was changed",
+                tilde(path)
+            ),
+        }
+    }
+}
+
+impl error::Error for TodoError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TodoError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TodoError {
+    fn from(e: io::Error) -> Self {
+        TodoError::Io(e)
+    }
+}
+
+/// Prints the tasks accepted by `f` with their 1-based indices, or numbered
+/// from 1 in listing order if `renumber` is set, pinned tasks first. Tasks
+/// snoozed past `today` are annotated. Returns the indices of the printed
+/// tasks in listing order.
+fn filter_print_lines(
+    render: &Render,
+    out: &mut dyn Write,
+    list: &[Task],
+    today: NaiveDate,
+    renumber: bool,
+    filter: &Filter,
+) -> io::Result<Vec<usize>> {
+    let shown: Vec<_> = listing_order(list, filter, today)
+        .into_iter()
+        .skip(filter.offset)
+        .take(filter.limit.unwrap_or(usize::MAX))
+        .collect();
+    let blockers = Blockers::of(list);
+    let width = if renumber {
+        index_width(render, 1..=shown.len())
+    } else {
+        index_width(render, shown.iter().map(|(i, _)| i + 1))
+    };
+    for (n, (i, t)) in shown.iter().enumerate() {
+        let number = if renumber { n + 1 } else { i + 1 };
+        write!(
+            out,
+            "{}",
+            listing_line(render, number, width, t, blockers.block(t), today, &[])
+        )?;
+    }
+    Ok(shown.into_iter().map(|(i, _)| i + 1).collect())
+}
+
+/// The tasks of `list` accepted by `filter`, with their positions, in the
+/// order they're listed: pinned ones first, each part in file order or
+/// sorted by `filter.sort`, and reversed with `filter.reverse`.
+fn listing_order<'a>(
+    list: &'a [Task],
+    filter: &Filter,
+    today: NaiveDate,
+) -> Vec<(usize, &'a Task)> {
+    let blockers = Blockers::of(list);
+    let (mut pinned, mut rest): (Vec<_>, Vec<_>) = list
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| filter.accepts(t, &blockers, today))
+        .partition(|(_, t)| t.data().is_pinned());
+    if let Some(key) = filter.sort {
+        pinned.sort_by(|(_, a), (_, b)| key.compare(a.data(), b.data()));
+        rest.sort_by(|(_, a), (_, b)| key.compare(a.data(), b.data()));
+    }
+    if filter.reverse {
+        pinned.reverse();
+        rest.reverse();
+    }
+    pinned.into_iter().chain(rest).collect()
+}
+
+/// Whether the `indices` of a listing, in ascending order, skip any task.
+pub fn has_gaps(indices: &[usize]) -> bool {
+    indices.last().is_some_and(|&last| last != indices.len())
+}
+
+/// The style of a `(A)` priority prefix, one for each of the top three
+/// priorities and one for those below.
+fn priority_style(theme: &Theme, priority: char) -> Style {
+    match priority {
+        'A' => theme.priority_a,
+        'B' => theme.priority_b,
+        'C' => theme.priority_c,
+        _ => theme.priority_low,
+    }
+}
+
+/// How a due date `days` from today reads in a listing.
+fn relative_due(days: i64) -> String {
+    match days {
+        ..=-1 => format!("overdue {}d", -days),
+        0 => "due today".to_owned(),
+        1 => "due tomorrow".to_owned(),
+        _ => format!("due in {}d", days),
+    }
+}
+
+/// Renders the note of `task` in the colors of the theme. Done tasks are
+/// struck through, or marked `[done]` without color. Pending tasks get
+/// their priority colored, their `due:` date relative to `today` unless
+/// absolute dates are on, and a color escalating as the due date
+/// approaches: `due_soon` when due `today`, `overdue` when overdue, and
+/// with just the date colored when due later (`due_soon` when due
+/// tomorrow).
+fn render_note(render: &Render, task: &Task, today: NaiveDate) -> String {
+    let note = task.data().to_string();
+    if let Task::DoneTask(_) = task {
+        return if !render.color {
+            format!("{} [done]", note)
+        } else if render.strikethrough {
+            render
+                .theme
+                .done_note
+                .strikethrough()
+                .paint(note)
+                .to_string()
+        } else {
+            render.theme.done_note.paint(note).to_string()
+        };
+    }
+    let (prefix, rest) = match task.data().priority() {
+        Some(p) if note.starts_with('(') => (
+            format!(
+                "{} ",
+                render
+                    .style(priority_style(&render.theme, p))
+                    .paint(&note[..3])
+            ),
+            &note[4..],
+        ),
+        _ => (String::new(), &note[..]),
+    };
+    let (due, (start, end)) = match (task.data().due(), find_token(rest, "due")) {
+        (Some(due), Some(range)) => (due, range),
+        _ => return format!("{}{}", prefix, render.style(render.theme.note).paint(rest)),
+    };
+
+    let days = (due - today).num_days();
+    let absolute = render.absolute_dates;
+    let token = if absolute {
+        rest[start..end].to_owned()
+    } else {
+        relative_due(days)
+    };
+    let (before, after) = (&rest[..start], &rest[end..]);
+    // Only the date is colored by how soon it's due, for the priority and
+    // the rest of the note to keep their colors.
+    let token_style = if days < 0 {
+        render.theme.overdue
+    } else if days == 0 || (days == 1 && !absolute) {
+        render.theme.due_soon
+    } else {
+        render.theme.due_later
+    };
+    let late = if days < 0 && absolute {
+        format!(
+            " {}",
+            render
+                .style(render.theme.overdue)
+                .paint(format!("(+{}d)", -days))
+        )
+    } else {
+        String::new()
+    };
+    let note = render.style(render.theme.note);
+    format!(
+        "{}{}{}{}{}",
+        prefix,
+        note.paint(before),
+        render.style(token_style).paint(token.as_str()),
+        note.paint(after),
+        late
+    )
+}
+
+/// The number of digits of the largest of `indices`, which listings
+/// right-align their indices to.
+fn index_width(render: &Render, indices: impl Iterator<Item = usize>) -> usize {
+    indices
+        .max()
+        .map_or(0, |index| render.shown_index(index))
+        .to_string()
+        .len()
+}
+
+/// Renders `note` with the byte ranges in `matches` in reverse video.
+/// Overlapping and adjacent ranges are merged so each highlighted run gets
+/// a single escape code.
+fn highlight(render: &Render, note: &str, matches: &[(usize, usize)]) -> String {
+    let mut ranges = matches.to_vec();
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let mut out = String::new();
+    let mut done = 0;
+    for (start, end) in merged {
+        out.push_str(&note[done..start]);
+        out.push_str(
+            &render
+                .style(render.theme.highlight)
+                .paint(&note[start..end])
+                .to_string(),
+        );
+        done = end;
+    }
+    out.push_str(&note[done..]);
+    out
+}
+
+/// The line `task` is listed as, with its line ending: at `index`, right
+/// aligned to `index_width` digits, with `matches` highlighted.
+fn listing_line(
+    render: &Render,
+    index: usize,
+    index_width: usize,
+    task: &Task,
+    blocked: bool,
+    today: NaiveDate,
+    matches: &[(usize, usize)],
+) -> String {
+    let index = render.shown_index(index);
+    let plain = render.plain.then(Template::plain);
+    if let Some(template) = render.template.as_ref().or(plain.as_ref()) {
+        let end = if render.print0 { '\0' } else { '\n' };
+        return format!(
+            "{}{}",
+            template.render(index, task, today, render.symbols()),
+            end
+        );
+    }
+    let snoozed = match task.data().threshold() {
+        Some(until) if until > today => render
+            .style(render.theme.muted)
+            .paint(format!(" (snoozed until {})", until))
+            .to_string(),
+        _ => String::new(),
+    };
+    let waiting = match task.data().waiting_for() {
+        Some(who) => render
+            .style(render.theme.muted)
+            .paint(format!(" (waiting for {})", who))
+            .to_string(),
+        None => String::new(),
+    };
+    let assignee = match task.data().assignee() {
+        Some(who) => render
+            .style(render.theme.assignee)
+            .paint(format!(" {} {}", render.glyph("→", "->"), who))
+            .to_string(),
+        None => String::new(),
+    };
+    let checklist = &task.data().checklist;
+    let progress = if checklist.is_empty() {
+        String::new()
+    } else {
+        let done = checklist.iter().filter(|(done, _)| *done).count();
+        render
+            .style(render.theme.muted)
+            .paint(format!(" ({}/{})", done, checklist.len()))
+            .to_string()
+    };
+    let prefix = format!(
+        " {} {}",
+        render
+            .style(render.theme.index)
+            .paint(&format!("{:>1$}.", index, index_width)[..]),
+        markers(render, task, blocked)
+    );
+    let mut note = if blocked && matches.is_empty() {
+        render
+            .style(render.theme.muted)
+            .paint(task.data().to_string())
+            .to_string()
+    } else if matches.is_empty() {
+        render_note(render, task, today)
+    } else if matches!(task, Task::DoneTask(_)) && !render.color {
+        format!("{} [done]", task.data())
+    } else {
+        highlight(render, &task.data().to_string(), matches)
+    };
+    if render.hyperlinks {
+        note = links::hyperlink(&note);
+    }
+    let columns = progress
+        + &assignee
+        + &waiting
+        + &snoozed
+        + &streak_column(render, task)
+        + &age_column(render, task, today);
+    let width = render.width;
+    if width > 0 && render.oneline {
+        let used = wrap::display_width(&prefix) + wrap::display_width(&columns);
+        note = wrap::truncate(
+            &note,
+            width.saturating_sub(used).max(1),
+            render.glyph("…", "."),
+        );
+    }
+    note += &columns;
+    if width > 0 && !render.oneline {
+        note = wrap::wrap(&note, wrap::display_width(&prefix), width);
+    }
+    format!("{}{}\n", prefix, note)
+}
+
+/// The priority one step above `priority`, for `todo bump`: none becomes
+/// `C` and any other letter the one before it, up to `A`.
+pub fn raised_priority(priority: Option<char>) -> Option<char> {
+    match priority {
+        None => Some('C'),
+        Some('A') => Some('A'),
+        Some(p) => Some((p as u8 - 1) as char),
+    }
+}
+
+/// The priority one step below `priority`, for `todo drop`: `A` becomes
+/// `B` and `B` becomes `C`, and `C` and the letters after it become none.
+pub fn lowered_priority(priority: Option<char>) -> Option<char> {
+    match priority {
+        Some('A') => Some('B'),
+        Some('B') => Some('C'),
+        _ => None,
+    }
+}
+
+/// A well-mixed number from `seed`, for choosing at random without a
+/// dependency (SplitMix64).
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The age of `task` for the end of its line, if `render` shows ages:
+/// dimmed, or stressed once it's stale, and a dash for a task without a
+/// creation date.
+fn age_column(render: &Render, task: &Task, today: NaiveDate) -> String {
+    let stale_after = match render.ages {
+        Some(days) => i64::from(days),
+        None => return String::new(),
+    };
+    match task.data().created() {
+        Some(created) => {
+            let days = (today - created).num_days();
+            let theme = &render.theme;
+            let age_style = if days > stale_after {
+                theme.stale
+            } else {
+                theme.age
+            };
+            format!(" {}", render.style(age_style).paint(format_age(days)))
+        }
+        None => format!(
+            " {}",
+            render.style(render.theme.age).paint(render.glyph("–", "-"))
+        ),
+    }
+}
+
+fn streak_column(render: &Render, task: &Task) -> String {
+    match task
+        .data()
+        .habit()
+        .and_then(|h| render.streaks.as_ref()?.get(&h))
+    {
+        Some(days) if days > 0 => format!(" {} {}", render.glyph("🔥", "streak"), days),
+        _ => String::new(),
+    }
+}
+
+/// How many days in a row each daily task, one with `rec:1d`, has been
+/// checked, by its text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Streaks(BTreeMap<String, u32>);
+
+impl Streaks {
+    /// The streaks of the daily tasks of `list` up to `today`: the days in
+    /// a row, ending today or yesterday, on which a task of `list` or `more`
+    /// with the same text was checked. Only the completion dates count, so
+    /// a missed day ends a streak without anything changing.
+    fn of<'a>(
+        list: &'a [Task],
+        more: impl IntoIterator<Item = &'a Task>,
+        today: NaiveDate,
+    ) -> Self {
+        let mut days: BTreeMap<String, HashSet<NaiveDate>> = list
+            .iter()
+            .filter_map(|t| t.data().habit())
+            .map(|h| (h, HashSet::new()))
+            .collect();
+        for task in list.iter().chain(more) {
+            if let (Task::DoneTask(data), Some(habit)) = (task, task.data().habit()) {
+                if let (Some(days), Some(day)) = (days.get_mut(&habit), data.completed()) {
+                    days.insert(day);
+                }
+            }
+        }
+        let streaks = days
+            .into_iter()
+            .map(|(habit, days)| {
+                let mut day = today;
+                if !days.contains(&day) {
+                    day = day.pred_opt().unwrap_or(day);
+                }
+                let mut streak = 0;
+                while days.contains(&day) {
+                    streak += 1;
+                    match day.pred_opt() {
+                        Some(pred) => day = pred,
+                        None => break,
+                    }
+                }
+                (habit, streak)
+            })
+            .collect();
+        Streaks(streaks)
+    }
+
+    /// The streak of the daily task with the text `habit`.
+    pub fn get(&self, habit: &str) -> Option<u32> {
+        self.0.get(habit).copied()
+    }
+
+    /// The daily tasks and their streaks, by text.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.0.iter().map(|(habit, &days)| (&habit[..], days))
+    }
+}
+
+/// Task counts reported by `todo stats`.
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub pending: usize,
+    pub done: usize,
+    pub overdue: usize,
+}
+
+impl Stats {
+    /// The share of tasks that are done, from 0.0 to 1.0; an empty list
+    /// counts as 0.0.
+    pub fn completion(&self) -> f64 {
+        match self.pending + self.done {
+            0 => 0.0,
+            total => self.done as f64 / total as f64,
+        }
+    }
+}
+
+/// How big a task is, from its `est:` token: a time such as `est:2h`,
+/// `est:1.5h` or `est:30m`, or story points such as `est:3p`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Estimate {
+    Minutes(u32),
+    Points(u32),
+}
+
+impl Estimate {
+    fn parse(s: &str) -> Option<Estimate> {
+        let unit = s.chars().last()?;
+        let amount = &s[..s.len() - unit.len_utf8()];
+        if amount.is_empty() || !amount.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return None;
+        }
+        match unit {
+            'h' => {
+                let hours: f64 = amount.parse().ok()?;
+                let minutes = (hours * 60.0).round();
+                (minutes <= u32::MAX as f64).then_some(Estimate::Minutes(minutes as u32))
+            }
+            'm' => amount.parse().ok().map(Estimate::Minutes),
+            'p' => amount.parse().ok().map(Estimate::Points),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Estimate::Minutes(minutes) => write!(f, "{}", format_minutes(minutes)),
+            Estimate::Points(points) => write!(f, "{}p", points),
+        }
+    }
+}
+
+/// `90` as `1h 30m`, `120` as `2h` and `45` as `45m`.
+fn format_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {}m", h, m),
+    }
+}
+
+/// Estimates added up, times and points apart since one doesn't convert
+/// to the other.
+#[derive(Debug, Default, PartialEq)]
+pub struct Workload {
+    pub minutes: u32,
+    pub points: u32,
+}
+
+impl Workload {
+    fn add(&mut self, estimate: Estimate) {
+        match estimate {
+            Estimate::Minutes(m) => self.minutes = self.minutes.saturating_add(m),
+            Estimate::Points(p) => self.points = self.points.saturating_add(p),
+        }
+    }
+}
+
+impl fmt::Display for Workload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.minutes, self.points) {
+            (0, 0) => write!(f, "none"),
+            (m, 0) => write!(f, "{}", format_minutes(m)),
+            (0, p) => write!(f, "{}p", p),
+            (m, p) => write!(f, "{} and {}p", format_minutes(m), p),
+        }
+    }
+}
+
+/// The estimates totaled by `todo stats`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Workloads {
+    pub pending: Workload,
+    /// Tasks checked since Monday.
+    pub done_this_week: Workload,
+}
+
+/// What's recorded about one task, shown by `todo show`.
+#[derive(Debug, PartialEq)]
+pub struct Details {
+    pub index: usize,
+    pub done: bool,
+    /// The words of the note, without its priority and tokens.
+    pub text: String,
+    pub priority: Option<char>,
+    /// The todo.txt creation date.
+    pub created: Option<NaiveDate>,
+    /// The date the task was checked.
+    pub completed: Option<NaiveDate>,
+    /// The `#tag`, `+project` and `@context` words of the note.
+    pub tags: Vec<String>,
+    pub due: Option<NaiveDate>,
+    pub estimate: Option<Estimate>,
+    pub snoozed_until: Option<NaiveDate>,
+    pub pinned: bool,
+    pub waiting: bool,
+    /// Who the task waits on, if a name was given.
+    pub waiting_for: Option<String>,
+    pub assignee: Option<String>,
+    /// When time tracking was started, if it's running.
+    pub started: Option<DateTime<Utc>>,
+    /// Seconds tracked in closed intervals.
+    pub spent: i64,
+    /// The items of its checklist, each checked or not.
+    pub checklist: Vec<(bool, String)>,
+    /// The paths of its `file:` tokens, as given.
+    pub attachments: Vec<String>,
+    /// The task's line in the file.
+    pub line: String,
+}
+
+impl Details {
+    pub fn to_json(&self, render: &Render) -> serde_json::Value {
+        serde_json::json!({
+            "index": render.shown_index(self.index),
+            "done": self.done,
+            "text": self.text,
+            "priority": self.priority.map(String::from),
+            "created": self.created.map(|d| d.to_string()),
+            "completed": self.completed.map(|d| d.to_string()),
+            "tags": self.tags,
+            "due": self.due.map(|d| d.to_string()),
+            "estimate": self.estimate.map(|e| e.to_string()),
+            "snoozed_until": self.snoozed_until.map(|d| d.to_string()),
+            "pinned": self.pinned,
+            "waiting": self.waiting,
+            "waiting_for": self.waiting_for,
+            "assignee": self.assignee,
+            "started": self.started.map(date::format_timestamp),
+            "spent": self.spent,
+            "checklist": self
+                .checklist
+                .iter()
+                .map(|(done, text)| serde_json::json!({ "done": done, "text": text }))
+                .collect::<Vec<_>>(),
+            "attachments": self.attachments,
+            "line": self.line,
+        })
+    }
+}
+
+/// Which tasks a selector is matched against.
+#[derive(Clone, Copy)]
+pub enum Scope {
+    Pending,
+    Done,
+    Any,
+}
+
+impl Scope {
+    fn contains(self, task: &Task) -> bool {
+        match self {
+            Scope::Pending => matches!(task, Task::TodoTask(_)),
+            Scope::Done => matches!(task, Task::DoneTask(_)),
+            Scope::Any => true,
+        }
+    }
+}
+
+/// What `TodoList::merge_import` did with the tasks of another list.
+#[derive(Debug, Default, PartialEq)]
+pub struct Merged {
+    /// The indices of the tasks added, at the end of the list.
+    pub added: Vec<usize>,
+    /// The indices of the pending tasks checked because they're checked in
+    /// the other list.
+    pub upgraded: Vec<usize>,
+    /// How many tasks the list had already.
+    pub skipped: usize,
+}
+
+/// A note that `todo sed` would rewrite, from `TodoList::rewrites`.
+#[derive(Debug, PartialEq)]
+pub struct Rewrite {
+    pub index: usize,
+    /// The note before and after, as listings show them.
+    pub before: String,
+    pub after: String,
+    /// Why the new note can't be written, if it can't.
+    pub rejected: Option<&'static str>,
+    note: String,
+}
+
+/// What `ls --group-by` puts tasks in sections by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupKey {
+    /// `#tag` words.
+    Tag,
+    /// todo.txt `+project` words.
+    Project,
+}
+
+impl GroupKey {
+    fn sigil(self) -> char {
+        match self {
+            GroupKey::Tag => '#',
+            GroupKey::Project => '+',
+        }
+    }
+
+    /// The heading of the tasks with no tag or project.
+    fn missing(self) -> &'static str {
+        match self {
+            GroupKey::Tag => "untagged",
+            GroupKey::Project => "no project",
+        }
+    }
+}
+
+/// The tasks of a listing by tag or project, from `TodoList::groups`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Groups {
+    /// The indices of the tasks with each tag or project; a task with
+    /// several is under each of them.
+    pub groups: BTreeMap<String, Vec<usize>>,
+    /// The indices of the tasks with none.
+    pub rest: Vec<usize>,
+}
+
+/// Where a date falls for `todo agenda`, `todo remind` and `todo
+/// due-check`, which share it for their windows to agree.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bucket {
+    Overdue,
+    Today,
+    Upcoming,
+}
+
+impl Bucket {
+    /// The bucket of `date` seen from `today`, or none if it's after
+    /// `until`, the last day of the window.
+    pub fn of(date: NaiveDate, today: NaiveDate, until: NaiveDate) -> Option<Bucket> {
+        if date < today {
+            Some(Bucket::Overdue)
+        } else if date == today {
+            Some(Bucket::Today)
+        } else if date <= until {
+            Some(Bucket::Upcoming)
+        } else {
+            None
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Bucket::Overdue => "Overdue",
+            Bucket::Today => "Today",
+            Bucket::Upcoming => "Upcoming",
+        }
+    }
+}
+
+/// An order to list tasks in. Tasks without the key, such as ones without
+/// a priority for `Priority`, go last in file order.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// `(A)` first.
+    Priority,
+    /// Earliest `due:` date first.
+    Due,
+    /// Oldest todo.txt creation date first.
+    Age,
+    /// Alphabetical by the words of the note, ignoring case.
+    Alpha,
+    /// Smallest `est:` first, times before points.
+    #[serde(rename = "est")]
+    Estimate,
+}
+
+impl SortKey {
+    /// The name `ls --sort` takes.
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Priority => "priority",
+            SortKey::Due => "due",
+            SortKey::Age => "age",
+            SortKey::Alpha => "alpha",
+            SortKey::Estimate => "est",
+        }
+    }
+
+    /// The order of `a` and `b` by this key, which `ls --sort`, `todo
+    /// sort`, `todo next` and `todo today` all go by.
+    fn compare(self, a: &TaskData, b: &TaskData) -> std::cmp::Ordering {
+        fn missing_last<T: Ord>(a: Option<T>, b: Option<T>) -> std::cmp::Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            }
+        }
+        match self {
+            SortKey::Priority => missing_last(a.priority(), b.priority()),
+            SortKey::Due => missing_last(a.due(), b.due()),
+            SortKey::Age => missing_last(a.created(), b.created()),
+            SortKey::Alpha => a.text().to_lowercase().cmp(&b.text().to_lowercase()),
+            SortKey::Estimate => missing_last(a.estimate(), b.estimate()),
+        }
+    }
+}
+
+/// How text given on the command line is matched against notes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Case {
+    /// Ignore case unless the pattern has an uppercase letter.
+    #[default]
+    Smart,
+    Ignore,
+    Sensitive,
+}
+
+impl Case {
+    /// Whether matching `pattern` ignores case. Letters after a backslash
+    /// don't count for `Smart`, so regex classes such as `\S` don't make
+    /// a pattern case-sensitive.
+    pub fn ignores(self, pattern: &str) -> bool {
+        match self {
+            Case::Ignore => true,
+            Case::Sensitive => false,
+            Case::Smart => {
+                let mut escaped = false;
+                !pattern.chars().any(|c| {
+                    let upper = !escaped && c.is_uppercase();
+                    escaped = !escaped && c == '\\';
+                    upper
+                })
+            }
+        }
+    }
+}
+
+/// The ids of the pending tasks of a list, which block the tasks that
+/// depend on them.
+#[derive(Default)]
+struct Blockers(HashSet<String>);
+
+impl Blockers {
+    fn of<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> Self {
+        let mut blockers = Blockers::default();
+        for task in tasks {
+            blockers.add(task);
+        }
+        blockers
+    }
+
+    fn add(&mut self, task: &Task) {
+        if let (Task::TodoTask(_), Some(id)) = (task, task.data().id()) {
+            self.0.insert(id.to_owned());
+        }
+    }
+
+    /// Whether `task` is pending and waits for one of them.
+    fn block(&self, task: &Task) -> bool {
+        matches!(task, Task::TodoTask(_)) && task.data().deps().any(|id| self.0.contains(id))
+    }
+}
+
+/// The tasks a listing shows.
+#[derive(Clone)]
+pub struct Filter {
+    /// Pending tasks snoozed past today are only shown with `Scope::Any`.
+    pub scope: Scope,
+    pub overdue: bool,
+    /// Only tasks whose `#tag`, `+project` and `@context` words match.
+    pub words: Option<Words>,
+    /// Only tasks due on or before this date.
+    pub due_by: Option<NaiveDate>,
+    /// Only tasks created on or after this date.
+    pub created_since: Option<NaiveDate>,
+    /// Only tasks created before this date.
+    pub created_before: Option<NaiveDate>,
+    /// Keep the tasks without a creation date when filtering by it.
+    pub include_undated: bool,
+    /// How `tag` is matched.
+    pub case: Case,
+    /// The order of the tasks, after the pinned ones; file order if `None`.
+    pub sort: Option<SortKey>,
+    /// Only tasks that don't wait for a pending task they depend on.
+    pub ready: bool,
+    /// Leave out the tasks waiting on someone.
+    pub hide_waiting: bool,
+    /// Only the tasks assigned to this name, as written.
+    pub assignee: Option<String>,
+    /// Only the tasks assigned to no one.
+    pub unassigned: bool,
+    /// List the tasks in the opposite order, pinned ones still first.
+    pub reverse: bool,
+    /// How many of the tasks to leave out from the start of the listing.
+    pub offset: usize,
+    /// How many of the tasks to list at most, after `offset`; all of them
+    /// if `None`.
+    pub limit: Option<usize>,
+}
+
+impl Filter {
+    pub fn new(scope: Scope) -> Self {
+        Filter {
+            scope,
+            overdue: false,
+            words: None,
+            due_by: None,
+            created_since: None,
+            created_before: None,
+            include_undated: false,
+            case: Case::Smart,
+            sort: None,
+            ready: false,
+            hide_waiting: false,
+            assignee: None,
+            unassigned: false,
+            reverse: false,
+            offset: 0,
+            limit: None,
+        }
+    }
+
+    fn accepts(&self, task: &Task, blockers: &Blockers, today: NaiveDate) -> bool {
+        let data = task.data();
+        self.scope.contains(task)
+            && !(self.ready && blockers.block(task))
+            && !(self.hide_waiting && data.is_waiting())
+            && self
+                .assignee
+                .as_ref()
+                .is_none_or(|who| data.assignee() == Some(who))
+            && !(self.unassigned && data.assignee().is_some())
+            && !(matches!(self.scope, Scope::Pending) && data.is_snoozed(today))
+            && (!self.overdue || matches!(task, Task::TodoTask(d) if d.is_overdue(today)))
+            && self
+                .words
+                .as_ref()
+                .is_none_or(|words| words.accepts(data, self.case))
+            && self
+                .due_by
+                .is_none_or(|until| data.due().is_some_and(|due| due <= until))
+            && self.accepts_created(data.created())
+    }
+
+    fn accepts_created(&self, created: Option<NaiveDate>) -> bool {
+        if self.created_since.is_none() && self.created_before.is_none() {
+            return true;
+        }
+        match created {
+            Some(created) => {
+                self.created_since.is_none_or(|since| created >= since)
+                    && self.created_before.is_none_or(|before| created < before)
+            }
+            None => self.include_undated,
+        }
+    }
+}
+
+/// A condition on the `#tag`, `+project` and `@context` words of a note,
+/// or on its text, built up from `Words::has` and `Words::text` with `and`,
+/// `or` and `!`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Words {
+    /// The note has this word, sigil included.
+    Has(String),
+    /// The note, as displayed, contains this text.
+    Text(String),
+    Not(Box<Words>),
+    All(Vec<Words>),
+    Any(Vec<Words>),
+}
+
+impl Words {
+    /// The note has `word`, such as `#backend` or `+launch`.
+    pub fn has(word: &str) -> Self {
+        Words::Has(word.to_owned())
+    }
+
+    /// The note contains `text`, as `search` finds it.
+    pub fn text(text: &str) -> Self {
+        Words::Text(text.to_owned())
+    }
+
+    /// Both this and `other` hold.
+    pub fn and(self, other: Words) -> Self {
+        match self {
+            Words::All(mut all) => {
+                all.push(other);
+                Words::All(all)
+            }
+            words => Words::All(vec![words, other]),
+        }
+    }
+
+    /// This or `other` holds.
+    pub fn or(self, other: Words) -> Self {
+        match self {
+            Words::Any(mut any) => {
+                any.push(other);
+                Words::Any(any)
+            }
+            words => Words::Any(vec![words, other]),
+        }
+    }
+
+    fn accepts(&self, data: &TaskData, case: Case) -> bool {
+        match self {
+            Words::Has(word) => data.has_word(word, case),
+            Words::Text(text) => !find_with_case(&data.to_string(), text, case).is_empty(),
+            Words::Not(words) => !words.accepts(data, case),
+            Words::All(all) => all.iter().all(|w| w.accepts(data, case)),
+            Words::Any(any) => any.iter().any(|w| w.accepts(data, case)),
+        }
+    }
+}
+
+impl std::ops::Not for Words {
+    type Output = Words;
+
+    fn not(self) -> Words {
+        Words::Not(Box::new(self))
+    }
+}
+
+/// The byte ranges of the non-overlapping occurrences of `needle` in
+/// `haystack`, ignoring case.
+pub fn find_ignore_case(haystack: &str, needle: &str) -> Vec<(usize, usize)> {
+    let needle: Vec<char> = needle.chars().flat_map(char::to_lowercase).collect();
+    let mut found = Vec::new();
+    if needle.is_empty() {
+        return found;
+    }
+    let mut from = 0;
+    for (start, _) in haystack.char_indices() {
+        if start < from {
+            continue;
+        }
+        let mut wanted = needle.iter();
+        let mut end = None;
+        for (i, c) in haystack[start..].char_indices() {
+            if !c.to_lowercase().all(|l| wanted.next() == Some(&l)) {
+                break;
+            }
+            if wanted.len() == 0 {
+                end = Some(start + i + c.len_utf8());
+                break;
+            }
+        }
+        if let Some(end) = end {
+            found.push((start, end));
+            from = end;
+        }
+    }
+    found
+}
+
+/// The byte ranges of the non-overlapping occurrences of `needle` in
+/// `haystack`, ignoring case if `case` says so for `needle`.
+pub fn find_with_case(haystack: &str, needle: &str, case: Case) -> Vec<(usize, usize)> {
+    if case.ignores(needle) {
+        return find_ignore_case(haystack, needle);
+    }
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    haystack
+        .match_indices(needle)
+        .map(|(start, m)| (start, start + m.len()))
+        .collect()
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|c| chars.any(|h| h == c))
+}
+
+/// A line of the todo file that isn't a task, kept to be saved back as is.
+struct InvalidLine {
+    /// The 1-based line number it was read from.
+    line: usize,
+    /// The number of tasks read before it. It's saved after as many tasks,
+    /// or at the end if the list got shorter.
+    after: usize,
+    content: String,
+}
+
+/// A change made to the list since it was loaded or last saved, kept to be
+/// re-applied if the file changed on disk in the meantime.
+enum Change {
+    Add(Task),
+    /// A task inserted right after another one, or appended if that one is
+    /// gone; at the start, after none.
+    Insert {
+        after: Option<Task>,
+        task: Task,
+    },
+    Update {
+        before: Task,
+        after: Task,
+    },
+    Remove(Task),
+}
+
+/// What a todo file looked like on disk, by its length and modification
+/// time, to tell whether someone else has written it since without reading
+/// it again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fingerprint {
+    len: u64,
+    modified: Option<std::time::SystemTime>,
+}
+
+impl Fingerprint {
+    /// The fingerprint of the file at `path`, none if it can't be read.
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Fingerprint {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+pub struct TodoList<'p> {
+    path: &'p Path,
+    format: Format,
+    list: Vec<Task>,
+    invalid: Vec<InvalidLine>,
+    /// The lines of an org file before its first task.
+    preamble: Vec<String>,
+    /// Whether changes wait for `commit` rather than being saved at once.
+    deferred: bool,
+    /// Whether there are changes waiting for `commit`.
+    dirty: bool,
+    /// Whether lines that aren't tasks are skipped rather than failing.
+    lenient: bool,
+    /// The state of the file when it was last read or written, unknown for
+    /// a list that was `read` from elsewhere.
+    fingerprint: Option<Fingerprint>,
+    /// Whether saving fails with `TodoError::ReadOnly`, for `--read-only`
+    /// or a file that can't be written.
+    read_only: bool,
+    changes: Vec<Change>,
+    /// The operations made on the list, for the activity log.
+    activity: Vec<Activity>,
+    /// How many of `activity` were saved, and described in the undo
+    /// journal, already.
+    journaled: usize,
+}
+
+/// An operation on a task, as recorded in the activity log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Activity {
+    pub verb: &'static str,
+    pub index: usize,
+    /// The note as listed, after the operation, or before it for removals.
+    pub note: String,
+}
+
+impl Activity {
+    /// `verb` done to the task at `index` with the note `note`, which is
+    /// recorded as it's listed.
+    pub fn new(verb: &'static str, index: usize, note: &str) -> Self {
+        let note = TaskData {
+            note: note.to_owned(),
+            outline: None,
+            checklist: Vec::new(),
+        }
+        .to_string();
+        Activity { verb, index, note }
+    }
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let note = serde_json::Value::from(&self.note[..]);
+        write!(f, "{} {} {}", self.verb, self.index, note)
+    }
+}
+
+impl<'p> TodoList<'p> {
+    pub fn load(path: &'p Path) -> Result<Self, TodoError> {
+        Self::open(path, false)
+    }
+
+    /// Parses the lines of `reader` as the content of the todo file at
+    /// `path`.
+    pub fn read(path: &'p Path, reader: impl BufRead) -> Result<Self, TodoError> {
+        let mut list = Vec::new();
+        read_tasks(reader, |_, task| list.push(task))?;
+        Ok(TodoList {
+            path,
+            format: Format::Markdown,
+            list,
+            invalid: Vec::new(),
+            preamble: Vec::new(),
+            deferred: false,
+            dirty: false,
+            lenient: false,
+            fingerprint: None,
+            read_only: is_read_only(),
+            changes: Vec::new(),
+            activity: Vec::new(),
+            journaled: 0,
+        })
+    }
+
+    /// Like `load`, but lines that aren't tasks are left out of the list
+    /// rather than failing, and saved back unchanged.
+    pub fn load_skipping_invalid(path: &'p Path) -> Result<Self, TodoError> {
+        Self::open(path, true)
+    }
+
+    /// Opens the file at `path`, creating it if it's missing, unless lists
+    /// are read-only: then a missing file is an empty list and the file is
+    /// only opened for reading, as it is when it turns out not to be
+    /// writable, which makes this list read-only.
+    fn open(path: &'p Path, lenient: bool) -> Result<Self, TodoError> {
+        let mut read_only = is_read_only() || has_no_write_permission(path);
+        let writable = match read_only {
+            true => None,
+            false => Some(
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path),
+            ),
+        };
+        let file: Box<dyn Read> = match writable {
+            Some(Ok(file)) => Box::new(file),
+            Some(Err(e)) if !is_unwritable(&e) => return Err(e.into()),
+            _ => {
+                read_only = true;
+                match File::open(path) {
+                    Ok(file) => Box::new(file),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Box::new(io::empty()),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+        let fingerprint = Fingerprint::of(path);
+        let format = Format::of(path);
+
+        let mut list = Vec::new();
+        let mut invalid = Vec::new();
+        let mut preamble = Vec::new();
+        if format == Format::Org {
+            let mut content = String::new();
+            BufReader::new(file).read_to_string(&mut content)?;
+            (preamble, list) = org::parse(content.lines());
+        } else {
+            read_lines(BufReader::new(file), |line, task| {
+                match task {
+                    Ok(task) => list.push(task),
+                    Err(content) if lenient => invalid.push(InvalidLine {
+                        line,
+                        after: list.len(),
+                        content: content.to_owned(),
+                    }),
+                    Err(content) => {
+                        return Err(TodoError::Parse {
+                            line,
+                            content: content.to_owned(),
+                        })
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Ok(TodoList {
+            path,
+            format,
+            list,
+            invalid,
+            preamble,
+            deferred: false,
+            dirty: false,
+            lenient,
+            fingerprint,
+            read_only,
+            changes: Vec::new(),
+            activity: Vec::new(),
+            journaled: 0,
+        })
+    }
+
+    /// The line numbers and content of the lines skipped by
+    /// `load_skipping_invalid`.
+    pub fn invalid_lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.invalid.iter().map(|l| (l.line, &l.content[..]))
+    }
+
+    /// Makes changes wait for `commit` instead of each rewriting the file,
+    /// so that a compound operation saves once.
+    pub fn defer_saves(&mut self) {
+        self.deferred = true;
+    }
+
+    /// Whether there are changes waiting for `commit`.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.dirty
+    }
+
+    /// Saves the changes made since `defer_saves`, if there are any.
+    pub fn commit(&mut self) -> Result<(), TodoError> {
+        if self.dirty {
+            self.write()?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Whether the list can't be saved; see `set_read_only`.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn save(&mut self) -> Result<(), TodoError> {
+        if self.read_only {
+            // The changes won't be saved, so they aren't activity either.
+            self.activity.truncate(self.journaled);
+            return Err(TodoError::ReadOnly {
+                path: self.path.to_owned(),
+            });
+        }
+        if self.deferred {
+            self.dirty = true;
+            Ok(())
+        } else {
+            self.write()
+        }
+    }
+
+    /// Writes the list to a temporary file next to the todo file, then
+    /// renames it over the original, so a failure leaves the original
+    /// intact. A symlinked todo file is replaced at its target.
+    ///
+    /// If the file was written by someone else since it was read, it's
+    /// read again and the changes made here are re-applied on top, so that
+    /// neither side's changes are lost; see `rebase`.
+    fn write(&mut self) -> Result<(), TodoError> {
+        if is_dry_run() {
+            self.changes.clear();
+            self.journaled = self.activity.len();
+            return Ok(());
+        }
+        if self.fingerprint.is_some() && Fingerprint::of(self.path) != self.fingerprint {
+            self.rebase()?;
+        }
+        let target = fs::canonicalize(self.path).unwrap_or_else(|_| self.path.to_owned());
+        let previous = Fingerprint::of(&target);
+        let backup = backup::take(&target)
+            .and_then(|backup| {
+                replace_file(&target, |file| self.write_to(file))?;
+                Ok(backup)
+            })
+            .map_err(|source| TodoError::Save {
+                path: self.path.to_owned(),
+                source,
+            })?;
+        journal::record_save(
+            &target,
+            backup,
+            previous,
+            &journal::describe(&self.activity[self.journaled..]),
+        );
+        self.journaled = self.activity.len();
+        self.changes.clear();
+        if self.fingerprint.is_some() {
+            self.fingerprint = Fingerprint::of(&target);
+        }
+        Ok(())
+    }
+
+    /// Replaces the list with the current content of the file, then applies
+    /// the changes made here to it. Tasks are found by their full line, not
+    /// their index, since that may have shifted:
+    ///
+    /// - added tasks are appended;
+    /// - a change to a task is applied to the first task with the line it
+    ///   had before, and dropped if there's none because the task was
+    ///   changed or removed in the meantime;
+    /// - a removal removes the first task with the same line, if any, so a
+    ///   task removed on both sides is removed once and no other task goes.
+    fn rebase(&mut self) -> Result<(), TodoError> {
+        let current = Self::open(self.path, self.lenient)?;
+        self.list = current.list;
+        self.invalid = current.invalid;
+        self.preamble = current.preamble;
+        for change in self.changes.drain(..) {
+            match change {
+                Change::Add(task) => self.list.push(task),
+                Change::Insert { after: None, task } => self.list.insert(0, task),
+                Change::Insert {
+                    after: Some(after),
+                    task,
+                } => match self.list.iter().position(|t| *t == after) {
+                    Some(i) => self.list.insert(i + 1, task),
+                    None => self.list.push(task),
+                },
+                Change::Update { before, after } => {
+                    if let Some(t) = self.list.iter_mut().find(|t| **t == before) {
+                        *t = after;
+                    }
+                }
+                Change::Remove(task) => {
+                    if let Some(i) = self.list.iter().position(|t| *t == task) {
+                        self.list.remove(i);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records `verb` as done to the task at `index` for the activity log.
+    fn log(&mut self, verb: &'static str, index: usize) {
+        let activity = Activity::new(verb, index, &self.list[index - 1].data().note);
+        self.activity.push(activity);
+    }
+
+    /// Hands over the operations recorded since the last call, to be
+    /// written to the activity log once they're saved.
+    pub fn take_activity(&mut self) -> Vec<Activity> {
+        self.journaled = 0;
+        std::mem::take(&mut self.activity)
+    }
+
+    fn push(&mut self, task: Task) {
+        self.changes.push(Change::Add(task.clone()));
+        self.list.push(task);
+    }
+
+    /// Inserts `task` right after the task at position `i`.
+    fn insert_after(&mut self, i: usize, task: Task) {
+        self.insert(i + 1, task);
+    }
+
+    /// Inserts `task` at position `i`.
+    fn insert(&mut self, i: usize, task: Task) {
+        self.changes.push(Change::Insert {
+            after: i.checked_sub(1).map(|before| self.list[before].clone()),
+            task: task.clone(),
+        });
+        self.list.insert(i, task);
+    }
+
+    /// Changes the task at position `i` with `f`.
+    fn update(&mut self, i: usize, f: impl FnOnce(&mut Task)) {
+        let before = self.list[i].clone();
+        f(&mut self.list[i]);
+        if self.list[i] != before {
+            let after = self.list[i].clone();
+            self.changes.push(Change::Update { before, after });
+        }
+    }
+
+    /// Removes the task at position `i`.
+    fn take(&mut self, i: usize) -> Task {
+        let task = self.list.remove(i);
+        self.changes.push(Change::Remove(task.clone()));
+        task
+    }
+
+    fn write_to(&self, file: &mut impl Write) -> io::Result<()> {
+        if self.format == Format::Org {
+            for line in &self.preamble {
+                writeln!(file, "{}", line)?;
+            }
+            for task in &self.list {
+                org::write_task(file, task)?;
+            }
+            return Ok(());
+        }
+        let mut invalid = self.invalid.iter().peekable();
+        for (i, l) in self.list.iter().enumerate() {
+            while let Some(line) = invalid.next_if(|line| line.after <= i) {
+                writeln!(file, "{}", line.content)?;
+            }
+            write_task(file, l)?;
+        }
+        for line in invalid {
+            writeln!(file, "{}", line.content)?;
+        }
+        Ok(())
+    }
+
+    /// The tasks at `indices`, in that order, as a Markdown list file of
+    /// their own with their checklists: what `todo export --output` writes.
+    pub fn export(&self, indices: &[usize]) -> Result<Vec<u8>, TodoError> {
+        let mut content = Vec::new();
+        for &index in indices {
+            write_task(&mut content, &self.list[self.position(index)?])?;
+        }
+        Ok(content)
+    }
+
+    /// The position in the list of the task at the 1-based `index`.
+    fn position(&self, index: usize) -> Result<usize, TodoError> {
+        match index {
+            1.. if index <= self.list.len() => Ok(index - 1),
+            _ => Err(TodoError::NoSuchTask {
+                index,
+                len: self.list.len(),
+            }),
+        }
+    }
+
+    /// Resolves a command line selector to a 1-based index.
+    ///
+    /// Numbers are taken as indices as-is. Anything else is matched
+    /// case-insensitively against the notes of the tasks in `scope`, as
+    /// listings show them without their hidden tokens: a
+    /// unique prefix match wins, otherwise the note must contain the
+    /// selector as a subsequence, and exactly one task may match.
+    pub fn resolve_selector(
+        &self,
+        selector: &str,
+        scope: Scope,
+        case: Case,
+    ) -> Result<usize, TodoError> {
+        if let Ok(number) = selector.parse() {
+            return Ok(number);
+        }
+
+        let ignore = case.ignores(selector);
+        let fold = |text: &str| {
+            if ignore {
+                text.to_lowercase()
+            } else {
+                text.to_owned()
+            }
+        };
+        let needle = fold(selector);
+        let notes: Vec<(usize, String)> = self
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| scope.contains(t))
+            .map(|(i, t)| (i + 1, fold(&t.data().to_string())))
+            .collect();
+
+        let prefixed: Vec<usize> = notes
+            .iter()
+            .filter(|(_, n)| n.starts_with(&needle))
+            .map(|(i, _)| *i)
+            .collect();
+        let found = if prefixed.is_empty() {
+            notes
+                .iter()
+                .filter(|(_, n)| is_subsequence(&needle, n))
+                .map(|(i, _)| *i)
+                .collect()
+        } else {
+            prefixed
+        };
+
+        match found[..] {
+            [] => Err(TodoError::NoMatch {
+                selector: selector.to_owned(),
+            }),
+            [index] => Ok(index),
+            _ => Err(TodoError::Ambiguous {
+                selector: selector.to_owned(),
+                candidates: found
+                    .into_iter()
+                    .map(|i| (i, self.list[i - 1].data().to_string()))
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Adds a pending task for each of `notes` with a single save,
+    /// returning how many were added.
+    pub fn add_all<I>(&mut self, notes: I) -> Result<usize, TodoError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let notes: Vec<I::Item> = notes.into_iter().collect();
+        let notes = notes
+            .iter()
+            .map(|n| clean_note(n.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        for note in &notes {
+            self.push(Task::new(note));
+            self.log("add", self.len());
+        }
+        self.save()?;
+        Ok(notes.len())
+    }
+
+    /// Merges the tasks of `other`, another list such as a copy from a
+    /// second machine, with a single save. A task matches one of this list
+    /// when their notes are the same words without their priorities,
+    /// creation dates and `key:value` tokens; tags, projects, contexts and
+    /// the case of letters still count. A match is skipped, but a task
+    /// checked in `other` checks the pending one it matches, on the day it
+    /// was checked there; other tasks are appended with their checklists,
+    /// each once.
+    pub fn merge_import(&mut self, other: &TodoList) -> Result<Merged, TodoError> {
+        let mut merged = Merged::default();
+        for task in &other.list {
+            let key = task.data().merge_key();
+            let matches: Vec<usize> = (0..self.len())
+                .filter(|&i| self.list[i].data().merge_key() == key)
+                .collect();
+            let pending = matches
+                .iter()
+                .copied()
+                .find(|&i| matches!(self.list[i], Task::TodoTask(_)));
+            match (task, pending) {
+                (Task::DoneTask(data), Some(i)) => {
+                    let on = data.completed().unwrap_or_else(date::today);
+                    self.update(i, |t| *t = t.clone().check(on));
+                    self.log("check", i + 1);
+                    merged.upgraded.push(i + 1);
+                }
+                _ if !matches.is_empty() => merged.skipped += 1,
+                _ => {
+                    self.push(task.clone());
+                    self.log("import", self.len());
+                    merged.added.push(self.len());
+                }
+            }
+        }
+        if !merged.added.is_empty() || !merged.upgraded.is_empty() {
+            self.save()?;
+        }
+        Ok(merged)
+    }
+
+    /// Appends `(done, note)` tasks with a single save, skipping those whose
+    /// text (see `TaskData::text`) matches a task already in the list.
+    /// Returns the tasks that were added.
+    pub fn import(
+        &mut self,
+        tasks: Vec<(bool, String)>,
+        dry_run: bool,
+    ) -> Result<Vec<(bool, String)>, TodoError> {
+        let mut seen: Vec<String> = self.list.iter().map(|t| t.data().text()).collect();
+        let mut added = Vec::new();
+        for (done, note) in tasks {
+            let note = clean_note(&note)?.to_owned();
+            let task_data = TaskData {
+                note,
+                outline: None,
+                checklist: Vec::new(),
+            };
+            let text = task_data.text();
+            if seen.contains(&text) {
+                continue;
+            }
+            seen.push(text);
+            added.push((done, task_data.note));
+        }
+        if !dry_run && !added.is_empty() {
+            for (done, note) in added.iter().cloned() {
+                let task_data = TaskData {
+                    note,
+                    outline: None,
+                    checklist: Vec::new(),
+                };
+                self.push(if done {
+                    Task::DoneTask(task_data)
+                } else {
+                    Task::TodoTask(task_data)
+                });
+                self.log("import", self.len());
+            }
+            self.save()?;
+        }
+        Ok(added)
+    }
+
+    pub fn add(&mut self, note: &str) -> Result<(), TodoError> {
+        let note = clean_note(note)?;
+        self.push(Task::new(note));
+        self.log("add", self.len());
+        self.save()
+    }
+
+    /// The index of the task whose time is being tracked.
+    pub fn started(&self) -> Option<usize> {
+        self.list
+            .iter()
+            .position(|t| t.data().is_started())
+            .map(|i| i + 1)
+    }
+
+    /// The seconds since the task at `index` was started.
+    fn running(&self, index: usize, now: DateTime<Utc>) -> Result<i64, TodoError> {
+        let value = self.list[index - 1]
+            .data()
+            .token("start")
+            .unwrap_or_default();
+        let started = date::parse_timestamp(value).ok_or_else(|| TodoError::BadTimestamp {
+            index,
+            value: value.to_owned(),
+        })?;
+        if started > now {
+            return Err(TodoError::ClockSkew {
+                index,
+                started: value.to_owned(),
+            });
+        }
+        Ok((now - started).num_seconds())
+    }
+
+    /// Closes the open interval of the task at `index` without saving,
+    /// returning its length in seconds.
+    fn close_interval(&mut self, index: usize, now: DateTime<Utc>) -> Result<i64, TodoError> {
+        let seconds = self.running(index, now)?;
+        self.update(index - 1, |t| {
+            let task_data = t.data_mut();
+            let spent = task_data.spent() + seconds;
+            task_data.set_token("start", None);
+            task_data.set_token("spent", Some(&spent.to_string()));
+        });
+        Ok(seconds)
+    }
+
+    /// Starts tracking time on the task at `index`. A task that was already
+    /// started is stopped first and returned with its interval in seconds.
+    pub fn start(
+        &mut self,
+        index: usize,
+        now: DateTime<Utc>,
+    ) -> Result<Option<(usize, i64)>, TodoError> {
+        self.position(index)?;
+        let stopped = match self.started() {
+            Some(running) if running == index => {
+                return Err(TodoError::AlreadyStarted { index });
+            }
+            Some(running) => Some((running, self.close_interval(running, now)?)),
+            None => None,
+        };
+        self.update(index - 1, |t| {
+            t.data_mut()
+                .set_token("start", Some(&date::format_timestamp(now)));
+        });
+        self.log("start", index);
+        self.save()?;
+        Ok(stopped)
+    }
+
+    /// Stops tracking time, returning the stopped task and its interval in
+    /// seconds.
+    pub fn stop(&mut self, now: DateTime<Utc>) -> Result<(usize, i64), TodoError> {
+        let index = self.started().ok_or(TodoError::NotStarted)?;
+        let seconds = self.close_interval(index, now)?;
+        self.log("stop", index);
+        self.save()?;
+        Ok((index, seconds))
+    }
+
+    /// Total tracked seconds of every task that has any, including the
+    /// interval still running.
+    pub fn tracked(&self, now: DateTime<Utc>) -> Result<Vec<(usize, i64)>, TodoError> {
+        let mut tracked = Vec::new();
+        for (i, t) in self.list.iter().enumerate() {
+            let mut seconds = t.data().spent();
+            if t.data().is_started() {
+                seconds += self.running(i + 1, now)?;
+            }
+            if seconds > 0 || t.data().is_started() {
+                tracked.push((i + 1, seconds));
+            }
+        }
+        Ok(tracked)
+    }
+
+    /// Checks the task at `index`, stopping its time tracking if it was
+    /// started. A pending task that recurs gets its next occurrence added
+    /// to the end of the list; see `next_occurrence`.
+    pub fn check(&mut self, index: usize) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        if self.list[i].data().is_started() {
+            self.close_interval(index, date::now())?;
+        }
+        let next = self.next_occurrence(i, date::today());
+        self.update(i, |t| *t = t.clone().check(date::today()));
+        self.log("check", index);
+        if let Some(next) = next {
+            self.push(next);
+            self.log("add", self.len());
+        }
+        self.save()
+    }
+
+    /// The task to follow the pending task at position `i` when it's
+    /// checked on `today`, if it has a `rec:` token: a copy due that long
+    /// after today, or after its due date for a strict recurrence written
+    /// as in `rec:+1m`, as todo.txt has them. A `t:` snooze keeps its
+    /// distance to the due date, and a creation date becomes today's; the
+    /// checklist starts over. Adding months stops at the end of a shorter
+    /// month, so a task due on Jan 31 with `rec:+1m` is next due on Feb 29
+    /// in a leap year.
+    fn next_occurrence(&self, i: usize, today: NaiveDate) -> Option<Task> {
+        let Task::TodoTask(data) = &self.list[i] else {
+            return None;
+        };
+        let rec = data.token("rec")?;
+        let (strict, span) = match rec.strip_prefix('+') {
+            Some(span) => (true, span),
+            None => (false, rec),
+        };
+        let base = match data.due() {
+            Some(due) if strict => due,
+            _ => today,
+        };
+        let due = date::Span::parse(span)?.after(base)?;
+        let mut next = self.copy_of(i);
+        let data = next.data_mut();
+        if let Some(snooze) = data.threshold() {
+            let shifted = snooze + (due - data.due().unwrap_or(today));
+            data.set_token("t", Some(&shifted.format("%Y-%m-%d").to_string()));
+        }
+        data.set_token("due", Some(&due.format("%Y-%m-%d").to_string()));
+        data.set_token("wait", None);
+        if data.created().is_some() {
+            let at = if data.priority().is_some() { 4 } else { 0 };
+            let end = data.note[at..]
+                .find(' ')
+                .map_or(data.note.len(), |n| at + n);
+            data.note
+                .replace_range(at..end, &today.format("%Y-%m-%d").to_string());
+        }
+        for (done, _) in &mut data.checklist {
+            *done = false;
+        }
+        Some(next)
+    }
+
+    pub fn undo(&mut self, index: usize) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        self.update(i, |t| *t = t.clone().undo());
+        self.log("undo", index);
+        self.save()
+    }
+
+    /// The checklist of the task at `index`, each item checked or not.
+    pub fn checklist(&self, index: usize) -> Result<&[(bool, String)], TodoError> {
+        Ok(&self.list[self.position(index)?].data().checklist)
+    }
+
+    /// Adds `text` to the end of the checklist of the task at `index`,
+    /// returning the item's number.
+    pub fn add_item(&mut self, index: usize, text: &str) -> Result<usize, TodoError> {
+        let i = self.position(index)?;
+        if self.format == Format::Org {
+            return Err(TodoError::OrgChecklist);
+        }
+        let text = clean_note(text)?.to_owned();
+        self.update(i, |t| t.data_mut().checklist.push((false, text)));
+        self.log("add item", index);
+        self.save()?;
+        Ok(self.list[i].data().checklist.len())
+    }
+
+    /// The position in the checklist of the task at position `i` of the
+    /// 1-based `item`.
+    fn item_position(&self, i: usize, item: usize) -> Result<usize, TodoError> {
+        let len = self.list[i].data().checklist.len();
+        match item {
+            1.. if item <= len => Ok(item - 1),
+            _ => Err(TodoError::NoSuchItem {
+                index: i + 1,
+                item,
+                len,
+            }),
+        }
+    }
+
+    /// Checks, or unchecks unless `done`, the 1-based `item` of the
+    /// checklist of the task at `index`.
+    pub fn set_item_done(
+        &mut self,
+        index: usize,
+        item: usize,
+        done: bool,
+    ) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let j = self.item_position(i, item)?;
+        self.update(i, |t| t.data_mut().checklist[j].0 = done);
+        self.log(if done { "check item" } else { "undo item" }, index);
+        self.save()
+    }
+
+    /// Checks the items of the checklist of the task at `index` that aren't
+    /// yet, returning how many there were.
+    pub fn check_items(&mut self, index: usize) -> Result<usize, TodoError> {
+        let i = self.position(index)?;
+        let open = self.list[i]
+            .data()
+            .checklist
+            .iter()
+            .filter(|(done, _)| !done)
+            .count();
+        if open > 0 {
+            self.update(i, |t| {
+                for (done, _) in &mut t.data_mut().checklist {
+                    *done = true;
+                }
+            });
+            self.log("check items", index);
+            self.save()?;
+        }
+        Ok(open)
+    }
+
+    /// Removes the 1-based `item` from the checklist of the task at
+    /// `index`, returning its text.
+    pub fn remove_item(&mut self, index: usize, item: usize) -> Result<String, TodoError> {
+        let i = self.position(index)?;
+        let j = self.item_position(i, item)?;
+        let (_, text) = self.list[i].data().checklist[j].clone();
+        self.update(i, |t| {
+            t.data_mut().checklist.remove(j);
+        });
+        self.log("remove item", index);
+        self.save()?;
+        Ok(text)
+    }
+
+    /// Attaches the file at `path` to the task at `index` with a `file:`
+    /// token, keeping the path as given.
+    pub fn attach(&mut self, index: usize, path: &str) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let token = format!("file:{}", encode_path(clean_note(path)?));
+        self.update(i, |t| {
+            let data = t.data_mut();
+            data.note = format!("{} {}", data.note, token);
+        });
+        self.log("attach", index);
+        self.save()
+    }
+
+    /// Removes the 1-based `attachment` of the task at `index`, or all of
+    /// them if `None`, returning their paths.
+    pub fn detach(
+        &mut self,
+        index: usize,
+        attachment: Option<usize>,
+    ) -> Result<Vec<String>, TodoError> {
+        let i = self.position(index)?;
+        let data = self.list[i].data();
+        let tokens: Vec<(usize, usize)> = find_tokens(&data.note, "file").collect();
+        let attachments = data.attachments();
+        let chosen: Vec<usize> = match attachment {
+            None if !tokens.is_empty() => (0..tokens.len()).rev().collect(),
+            Some(n) if (1..=tokens.len()).contains(&n) => vec![n - 1],
+            _ => {
+                return Err(TodoError::NoSuchAttachment {
+                    index,
+                    attachment: attachment.unwrap_or(1),
+                    len: tokens.len(),
+                })
+            }
+        };
+        self.update(i, |t| {
+            let note = &mut t.data_mut().note;
+            // From the last, so that the earlier ranges stay right.
+            for &j in &chosen {
+                let removal = token_removal(note, tokens[j]);
+                note.replace_range(removal, "");
+            }
+        });
+        self.log("detach", index);
+        self.save()?;
+        Ok(chosen
+            .into_iter()
+            .rev()
+            .map(|j| attachments[j].clone())
+            .collect())
+    }
 
-    #[test]
-    fn test_taskdata_display() {
-        let note = "test note";
-        let task_data = TaskData {
-            note: note.to_string(),
+    pub fn pin(&mut self, index: usize) -> Result<(), TodoError> {
+        self.set_pinned(index, true)
+    }
+
+    pub fn unpin(&mut self, index: usize) -> Result<(), TodoError> {
+        self.set_pinned(index, false)
+    }
+
+    fn set_pinned(&mut self, index: usize, pinned: bool) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        self.update(i, |t| {
+            t.data_mut()
+                .set_token("pin", if pinned { Some("1") } else { None });
+        });
+        self.log(if pinned { "pin" } else { "unpin" }, index);
+        self.save()
+    }
+
+    /// Sets the todo.txt priority of the task at `index`, or removes it.
+    pub fn set_priority(&mut self, index: usize, priority: Option<char>) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        self.update(i, |t| t.data_mut().set_priority(priority));
+        self.log("priority", index);
+        self.save()
+    }
+
+    /// Hides the task from the default listing until `until`.
+    pub fn snooze(&mut self, index: usize, until: NaiveDate) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        self.update(i, |t| {
+            t.data_mut()
+                .set_token("t", Some(&until.format("%Y-%m-%d").to_string()));
+        });
+        self.log("snooze", index);
+        self.save()
+    }
+
+    /// Sets the due date of the task at `index` to `due`.
+    pub fn set_due(&mut self, index: usize, due: NaiveDate) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        self.update(i, |t| {
+            t.data_mut()
+                .set_token("due", Some(&due.format("%Y-%m-%d").to_string()));
+        });
+        self.log("due", index);
+        self.save()
+    }
+
+    /// Marks the task at `index` as waiting on someone, or on `who`,
+    /// reopening it if it's done.
+    pub fn wait(&mut self, index: usize, who: Option<&str>) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let who = match who.map(str::trim) {
+            Some(who) if !who.is_empty() => clean_note(who)?.replace(' ', "_"),
+            _ => "1".to_owned(),
         };
+        self.update(i, |t| {
+            *t = t.clone().undo();
+            t.data_mut().set_token("wait", Some(&who));
+        });
+        self.log("wait", index);
+        self.save()
+    }
 
-        let display_string = format!("{}", task_data);
-        assert_eq!(note, display_string);
+    /// Assigns the task at `index` to `who`, replacing whoever it was
+    /// assigned to, or to no one.
+    pub fn assign(&mut self, index: usize, who: Option<&str>) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let who = match who.map(str::trim) {
+            Some(who) if !who.is_empty() => Some(clean_note(who)?.replace(' ', "_")),
+            _ => None,
+        };
+        self.update(i, |t| {
+            let data = t.data_mut();
+            while data.assignee().is_some() {
+                data.set_token("assignee", None);
+            }
+            data.set_token("assignee", who.as_deref());
+        });
+        self.log("assign", index);
+        self.save()
     }
-}
 
-struct TaskData {
-    note: String,
-}
+    /// Adds a space and `text` to the end of the note of the task at
+    /// `index`, keeping its state and position.
+    pub fn append_note(&mut self, index: usize, text: &str) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let text = clean_note(text)?;
+        self.update(i, |t| {
+            let data = t.data_mut();
+            data.note = format!("{} {}", data.note, text);
+        });
+        self.log("append", index);
+        self.save()
+    }
 
-impl fmt::Display for TaskData {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.note)
+    /// Adds `text` and a space to the start of the note of the task at
+    /// `index`, keeping its state and position.
+    pub fn prepend_note(&mut self, index: usize, text: &str) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let text = clean_note(text)?;
+        self.update(i, |t| {
+            let data = t.data_mut();
+            data.note = format!("{} {}", text, data.note);
+        });
+        self.log("prepend", index);
+        self.save()
     }
-}
 
-enum Task {
-    DoneTask(TaskData),
-    TodoTask(TaskData),
-}
+    /// Replaces the note of the task at `index` with `text`, keeping its
+    /// state and position, and the tokens hidden in listings unless `text`
+    /// has its own: a new `pin:1` replaces the old one, and so on.
+    pub fn edit_note(&mut self, index: usize, text: &str) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let text = clean_note(text)?;
+        let key = |token: &str| token.split(':').next().unwrap_or_default().to_owned();
+        let given: Vec<String> = hidden_tokens(text).into_iter().map(key).collect();
+        self.update(i, |t| {
+            let data = t.data_mut();
+            let mut note = text.to_owned();
+            for token in hidden_tokens(&data.note) {
+                if !given.contains(&key(token)) {
+                    note.push(' ');
+                    note.push_str(token);
+                }
+            }
+            data.note = note;
+        });
+        self.log("edit", index);
+        self.save()
+    }
 
-impl fmt::Debug for Task {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Task::DoneTask(task_data) => write!(f, "- [x] {}", task_data),
-            Task::TodoTask(task_data) => write!(f, "- [ ] {}", task_data),
+    /// The notes of the tasks in `scope` that `f` changes, as `todo sed`
+    /// would rewrite them. A new note is rejected if it would be empty,
+    /// span lines or change the tokens that are hidden in listings, which
+    /// would lose the task's dates, time or dependencies.
+    pub fn rewrites(&self, scope: Scope, f: impl Fn(&str) -> String) -> Vec<Rewrite> {
+        let mut rewrites = Vec::new();
+        for (i, task) in self.list.iter().enumerate() {
+            if !scope.contains(task) {
+                continue;
+            }
+            let data = task.data();
+            let new = f(&data.note);
+            let note = new.trim();
+            if note == data.note {
+                continue;
+            }
+            let rejected = if note.contains(['\n', '\r']) {
+                Some("it would span lines")
+            } else if hidden_tokens(note) != hidden_tokens(&data.note) {
+                Some("it would change tokens that todo keeps hidden")
+            } else {
+                None
+            };
+            let after = TaskData {
+                note: note.to_owned(),
+                outline: None,
+                checklist: Vec::new(),
+            }
+            .to_string();
+            let rejected = match rejected {
+                None if after.trim().is_empty() => Some("it would be empty"),
+                rejected => rejected,
+            };
+            rewrites.push(Rewrite {
+                index: i + 1,
+                before: data.to_string(),
+                after,
+                rejected,
+                note: note.to_owned(),
+            });
         }
+        rewrites
     }
-}
 
-impl fmt::Display for Task {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Task::DoneTask(task_data) => write!(f, "{} {}", Green.paint("✓"), task_data),
-            Task::TodoTask(task_data) => write!(f, "{} {}", Red.paint("✖"), task_data),
+    /// Gives the tasks of `rewrites` that weren't rejected their new notes,
+    /// with a single save. Returns how many were rewritten.
+    pub fn rewrite(&mut self, rewrites: &[Rewrite]) -> Result<usize, TodoError> {
+        let mut count = 0;
+        for rewrite in rewrites.iter().filter(|r| r.rejected.is_none()) {
+            let i = self.position(rewrite.index)?;
+            let note = clean_note(&rewrite.note)?.to_owned();
+            self.update(i, |t| t.data_mut().note = note);
+            self.log("rewrite", rewrite.index);
+            count += 1;
         }
+        if count > 0 {
+            self.save()?;
+        }
+        Ok(count)
     }
-}
 
-impl Task {
-    fn new(note: &str) -> Self {
-        Task::TodoTask(TaskData {
-            note: note.to_owned(),
-        })
+    /// Renames the `#old` tag to `#new` in the notes of all tasks, matching
+    /// `old` regardless of case. A task that has `#new` already keeps the
+    /// first of them only. Returns how many tasks changed.
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> Result<usize, TodoError> {
+        let old = format!("#{}", old.to_lowercase());
+        let new = format!("#{}", new);
+        let mut count = 0;
+        for i in 0..self.list.len() {
+            let note = &self.list[i].data().note;
+            if !note.split(' ').any(|w| w.to_lowercase() == old) {
+                continue;
+            }
+            let mut renamed = false;
+            let mut words = Vec::new();
+            for word in note.split(' ') {
+                let lower = word.to_lowercase();
+                if lower != old && lower != new.to_lowercase() {
+                    words.push(word);
+                } else if !renamed {
+                    words.push(if lower == old { &new } else { word });
+                    renamed = true;
+                }
+            }
+            let note = words.join(" ");
+            if note != self.list[i].data().note {
+                self.update(i, |t| t.data_mut().note = note);
+                self.log("retag", i + 1);
+                count += 1;
+            }
+        }
+        if count > 0 {
+            self.save()?;
+        }
+        Ok(count)
     }
 
-    fn check(self) -> Self {
-        match self {
-            Task::TodoTask(task_data) => Task::DoneTask(task_data),
-            Task::DoneTask(_) => self,
+    /// Inserts a pending copy of the task at `index` right after it,
+    /// returning the copy's index. Time tracked on the original isn't
+    /// copied.
+    pub fn duplicate(&mut self, index: usize) -> Result<usize, TodoError> {
+        let i = self.position(index)?;
+        let copy = self.copy_of(i);
+        self.insert_after(i, copy);
+        self.log("dup", index + 1);
+        self.save()?;
+        Ok(index + 1)
+    }
+
+    /// Adds a pending copy of the task at `index`, as `duplicate` does, to
+    /// the end of the list.
+    pub fn duplicate_to_end(&mut self, index: usize) -> Result<usize, TodoError> {
+        let i = self.position(index)?;
+        let copy = self.copy_of(i);
+        self.push(copy);
+        self.log("dup", self.len());
+        self.save()?;
+        Ok(self.len())
+    }
+
+    /// Replaces the pending task at `index` with a task for each of
+    /// `parts`, at its position: the first takes over its id and checklist.
+    /// With `inherit`, each part without a priority of its own takes the
+    /// task's, and each gets the task's tags, projects and contexts. With
+    /// `keep`, the task stays, before the parts. Returns the indices of the
+    /// tasks now in its place.
+    pub fn split(
+        &mut self,
+        index: usize,
+        parts: &[&str],
+        inherit: bool,
+        keep: bool,
+    ) -> Result<Vec<usize>, TodoError> {
+        let i = self.position(index)?;
+        let Task::TodoTask(original) = self.list[i].clone() else {
+            return Err(TodoError::SplitDone { index });
+        };
+        let mut tasks = Vec::new();
+        for part in parts {
+            let mut data = TaskData {
+                note: clean_note(part)?.to_owned(),
+                outline: None,
+                checklist: Vec::new(),
+            };
+            if inherit {
+                if data.priority().is_none() {
+                    data.set_priority(original.priority());
+                }
+                let words = original
+                    .note
+                    .split(' ')
+                    .filter(|w| w.len() > 1 && w.starts_with(['#', '+', '@']));
+                for word in words {
+                    if !data.note.split(' ').any(|w| w == word) {
+                        data.note = format!("{} {}", data.note, word);
+                    }
+                }
+            }
+            tasks.push(data);
         }
+        if tasks.is_empty() {
+            return Err(TodoError::EmptyNote);
+        }
+        self.log("split", index);
+        let mut at = i + 1;
+        if !keep {
+            self.take(i);
+            at = i;
+            let first = &mut tasks[0];
+            if let Some(id) = original.id() {
+                first.set_token("id", Some(id));
+            }
+            first.checklist = original.checklist;
+        }
+        for data in tasks {
+            self.insert(at, Task::TodoTask(data));
+            at += 1;
+            self.log("add", at);
+        }
+        self.save()?;
+        Ok((index..=at).collect())
     }
 
-    fn undo(self) -> Self {
-        match self {
-            Task::DoneTask(task_data) => Task::TodoTask(task_data),
-            Task::TodoTask(_) => self,
+    /// Joins the task at `other` into the one at `index`: its note goes
+    /// after `separator`, without the tags, projects, contexts and tokens
+    /// the task has already, and its checklist after the task's. The task
+    /// keeps the higher priority and the earlier creation date of the two,
+    /// and stays checked only if both are. Tasks waiting for the other wait
+    /// for the task instead. Returns the task's index once the other is
+    /// removed.
+    pub fn join(
+        &mut self,
+        index: usize,
+        other: usize,
+        separator: &str,
+    ) -> Result<usize, TodoError> {
+        let i = self.position(index)?;
+        let j = self.position(other)?;
+        if i == j {
+            return Err(TodoError::JoinSame { index });
+        }
+        let (a, b) = (self.list[i].data(), self.list[j].data());
+        let done = matches!(
+            (&self.list[i], &self.list[j]),
+            (Task::DoneTask(_), Task::DoneTask(_))
+        );
+        let priority = match (a.priority(), b.priority()) {
+            (Some(p), Some(q)) => Some(p.min(q)),
+            (p, q) => p.or(q),
+        };
+        let created = match (a.created(), b.created()) {
+            (Some(c), Some(d)) => Some(c.min(d)),
+            (c, d) => c.or(d),
+        };
+        let body = |data: &TaskData| -> String {
+            let words: Vec<&str> = data.note.split(' ').collect();
+            let skip =
+                usize::from(data.priority().is_some()) + usize::from(data.created().is_some());
+            words[skip..].join(" ")
+        };
+        let rest = body(b);
+        let rest: Vec<&str> = rest
+            .split(' ')
+            .filter(|w| match w.split_once(':') {
+                _ if w.len() > 1 && w.starts_with(['#', '+', '@']) => {
+                    !a.has_word(w, Case::Sensitive)
+                }
+                Some((key, _)) if is_token_word(w) => a.token(key).is_none(),
+                _ => true,
+            })
+            .collect();
+        let mut note = String::new();
+        if let Some(p) = priority {
+            note.push_str(&format!("({}) ", p));
         }
+        if let Some(created) = created {
+            note.push_str(&format!("{} ", created.format("%Y-%m-%d")));
+        }
+        note.push_str(&body(a));
+        let rest = rest.join(" ");
+        if !rest.trim().is_empty() {
+            note.push_str(separator);
+            note.push_str(&rest);
+        }
+        let mut data = TaskData {
+            note,
+            outline: a.outline.clone(),
+            checklist: a.checklist.iter().chain(&b.checklist).cloned().collect(),
+        };
+        if !done {
+            data.set_token("done", None);
+        }
+        let ids = (a.id().map(str::to_owned), b.id().map(str::to_owned));
+        self.update(i, |t| {
+            *t = if done {
+                Task::DoneTask(data)
+            } else {
+                Task::TodoTask(data)
+            }
+        });
+        if let (Some(id), Some(other_id)) = ids {
+            for k in 0..self.list.len() {
+                if !self.list[k].data().deps().any(|dep| dep == other_id) {
+                    continue;
+                }
+                self.update(k, |t| {
+                    let data = t.data_mut();
+                    let mut deps: Vec<&str> = data
+                        .deps()
+                        .map(|dep| if dep == other_id { &id[..] } else { dep })
+                        .collect();
+                    deps.dedup();
+                    let deps = deps.join(",");
+                    data.set_token("dep", Some(&deps));
+                });
+            }
+        }
+        self.log("join", index);
+        self.log("remove", other);
+        self.take(j);
+        self.save()?;
+        Ok(if j < i { index - 1 } else { index })
     }
-}
 
-#[derive(Debug)]
-struct TaskParseError;
+    /// Adds a pending copy of the checked task at `index`, as `duplicate`
+    /// makes, to the end of the list, leaving the task checked for the
+    /// record. Returns the copy's index.
+    pub fn reopen(&mut self, index: usize) -> Result<usize, TodoError> {
+        let copy = self.reopened(index)?;
+        self.push(copy);
+        self.log("reopen", self.len());
+        self.save()?;
+        Ok(self.len())
+    }
 
-impl FromStr for Task {
-    type Err = TaskParseError;
+    /// Adds a pending copy of the task at `index` in `archive`, as `reopen`
+    /// does, to the end of the list.
+    pub fn reopen_from(&mut self, archive: &TodoList, index: usize) -> Result<usize, TodoError> {
+        let copy = archive.reopened(index)?;
+        self.push(copy);
+        self.log("reopen", self.len());
+        self.save()?;
+        Ok(self.len())
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"^- \[([\sx])\] (.*)$").unwrap();
-        match re.captures(s) {
-            Some(cap) => cap
-                .get(2)
-                .map(|n| TaskData {
-                    note: n.as_str().to_string(),
-                })
-                .and_then(|task_data| match cap.get(1).map(|m| m.as_str()) {
-                    Some("x") => Some(Task::DoneTask(task_data)),
-                    Some(" ") => Some(Task::TodoTask(task_data)),
-                    _ => None,
-                })
-                .ok_or(TaskParseError),
-            None => Err(TaskParseError),
+    fn reopened(&self, index: usize) -> Result<Task, TodoError> {
+        let i = self.position(index)?;
+        match self.list[i] {
+            Task::DoneTask(_) => Ok(self.copy_of(i)),
+            Task::TodoTask(_) => Err(TodoError::NotChecked { index }),
         }
     }
-}
 
-fn filter_print_lines<I, F>(iter: I, f: F)
-where
-    I: Iterator,
-    I::Item: fmt::Display,
-    F: Fn(&I::Item) -> bool,
-{
-    for (i, t) in iter.enumerate().filter(|pair| match pair {
-        (_, t) => f(t),
-    }) {
-        println!(
-            " {} {}",
-            Style::default().dimmed().paint(&format!("{}.", i + 1)[..]),
-            t
-        );
+    fn copy_of(&self, i: usize) -> Task {
+        let mut data = self.list[i].data().clone();
+        data.set_token("start", None);
+        data.set_token("spent", None);
+        data.set_token("done", None);
+        // Tasks depending on the original don't wait for the copy.
+        data.set_token("id", None);
+        Task::TodoTask(data)
     }
-}
 
-fn vec_try_remove<T>(v: &mut Vec<T>, index: usize) -> Option<T> {
-    if index < v.len() {
-        Some(v.remove(index))
-    } else {
-        None
+    /// Reorders the list by `key`, as `ls --sort` lists it, with the tasks
+    /// that compare equal in their order. Returns how many tasks moved.
+    pub fn sort_by(&mut self, key: SortKey) -> Result<usize, TodoError> {
+        let mut order: Vec<usize> = (0..self.list.len()).collect();
+        order.sort_by(|&a, &b| key.compare(self.list[a].data(), self.list[b].data()));
+        // The original position of the task at each position.
+        let mut current: Vec<usize> = (0..self.list.len()).collect();
+        let mut moved = 0;
+        for (p, &wanted) in order.iter().enumerate() {
+            let at = current.iter().position(|&o| o == wanted).unwrap();
+            if at != p {
+                let task = self.take(at);
+                self.insert(p, task);
+                current.remove(at);
+                current.insert(p, wanted);
+                self.log("reorder", p + 1);
+                moved += 1;
+            }
+        }
+        if moved > 0 {
+            self.save()?;
+        }
+        Ok(moved)
     }
-}
 
-pub struct TodoList<'p> {
-    path: &'p Path,
-    list: Vec<Task>,
-}
+    /// Moves the task at `index` to `to`, so that the tasks in between move
+    /// by one.
+    pub fn move_task(&mut self, index: usize, to: usize) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let to = self.position(to)?;
+        if i == to {
+            return Ok(());
+        }
+        let task = self.take(i);
+        self.insert(to, task);
+        self.log("reorder", to + 1);
+        self.save()
+    }
 
-impl<'p> TodoList<'p> {
-    pub fn load(path: &'p Path) -> Result<Self, io::Error> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&path)?;
-
-        let reader = BufReader::new(file);
-        let list: Vec<Task> = reader
-            .lines()
+    /// Moves the task at `index` to the end of `dest`, returning its index
+    /// there. `dest` is saved before the task is removed from this list, so
+    /// a failure can't lose the task.
+    pub fn move_to(&mut self, index: usize, dest: &mut TodoList) -> Result<usize, TodoError> {
+        let task = self.list[self.position(index)?].clone();
+        dest.push(task);
+        dest.save()?;
+        self.log("move", index);
+        let i = self.position(index)?;
+        self.take(i);
+        self.save()?;
+        Ok(dest.len())
+    }
+
+    /// Moves the done tasks completed before `cutoff` to the end of the
+    /// archive at `to`, which is only read when there are some, returning
+    /// how many. Done tasks without a completion date stay.
+    pub fn archive(&mut self, cutoff: NaiveDate, to: &Path) -> Result<usize, TodoError> {
+        let old = |task: &Task| task.is_done_before(cutoff);
+        let count = self.list.iter().filter(|t| old(t)).count();
+        if count == 0 {
+            return Ok(0);
+        }
+        let mut archive = TodoList::load(to)?;
+        for task in self.list.iter().filter(|t| old(t)) {
+            archive.push(task.clone());
+        }
+        archive.save()?;
+        for i in 0..self.len() {
+            if old(&self.list[i]) {
+                self.log("archive", i + 1);
+            }
+        }
+        let (archived, kept): (Vec<Task>, Vec<Task>) =
+            std::mem::take(&mut self.list).into_iter().partition(old);
+        self.list = kept;
+        self.changes
+            .extend(archived.into_iter().map(Change::Remove));
+        self.save()?;
+        Ok(count)
+    }
+
+    /// Makes the habits checked before `today`, the tasks with a `habit:`
+    /// token, pending again where they are, each with a checked copy added
+    /// to the end of the archive at `to` for its streak to remember the
+    /// day. A habit checked today stays checked, so it's reset once a day
+    /// at most; the archive is only read when there's one to reset.
+    /// Returns how many were reset.
+    pub fn reset_habits(&mut self, today: NaiveDate, to: &Path) -> Result<usize, TodoError> {
+        let stale = |task: &Task| task.is_stale_habit(today);
+        let count = self.list.iter().filter(|t| stale(t)).count();
+        if count == 0 {
+            return Ok(0);
+        }
+        let mut archive = TodoList::load(to)?;
+        for task in self.list.iter().filter(|t| stale(t)) {
+            archive.push(task.clone());
+        }
+        archive.save()?;
+        for i in 0..self.len() {
+            if stale(&self.list[i]) {
+                self.update(i, |t| *t = t.clone().undo());
+                self.log("reset", i + 1);
+            }
+        }
+        self.save()?;
+        Ok(count)
+    }
+
+    /// Removes the task at `index`, and the dependencies of other tasks on
+    /// it.
+    pub fn remove(&mut self, index: usize) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let dependents = self.dependents(index)?;
+        self.log("remove", index);
+        let task = self.take(i);
+        if let Some(id) = task.data().id() {
+            for d in dependents.into_iter().filter(|&d| d != index) {
+                let d = if d > index { d - 2 } else { d - 1 };
+                self.update(d, |t| {
+                    let data = t.data_mut();
+                    let deps: Vec<&str> = data.deps().filter(|dep| *dep != id).collect();
+                    let deps = (!deps.is_empty()).then(|| deps.join(","));
+                    data.set_token("dep", deps.as_deref());
+                });
+            }
+        }
+        self.save()
+    }
+
+    /// The indices of the tasks that depend on the one at `index`.
+    pub fn dependents(&self, index: usize) -> Result<Vec<usize>, TodoError> {
+        let Some(id) = self.list[self.position(index)?].data().id() else {
+            return Ok(Vec::new());
+        };
+        Ok(self
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.data().deps().any(|dep| dep == id))
+            .map(|(i, _)| i + 1)
+            .collect())
+    }
+
+    /// The indices of the pending tasks waiting for a pending task they
+    /// depend on.
+    pub fn blocked(&self) -> Vec<usize> {
+        let blockers = Blockers::of(&self.list);
+        self.list
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| blockers.block(t))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+
+    /// Makes the task at `index` wait until the one at `on` is done, by
+    /// giving that one an `id:` token if it has none and adding the id to
+    /// the `dep:` token of this one. A dependency that would make a task
+    /// wait for itself, directly or through others, is refused.
+    pub fn block(&mut self, index: usize, on: usize) -> Result<(), TodoError> {
+        let i = self.position(index)?;
+        let j = self.position(on)?;
+        if i == j || self.waits_for(j, i) {
+            return Err(TodoError::DependencyCycle { index, on });
+        }
+        let id = match self.list[j].data().id() {
+            Some(id) => id.to_owned(),
+            None => {
+                let id = self.next_id().to_string();
+                self.update(j, |t| t.data_mut().set_token("id", Some(&id)));
+                id
+            }
+        };
+        let mut deps: Vec<String> = self.list[i].data().deps().map(str::to_owned).collect();
+        if !deps.contains(&id) {
+            deps.push(id);
+            self.update(i, |t| t.data_mut().set_token("dep", Some(&deps.join(","))));
+        }
+        self.log("block", index);
+        self.save()
+    }
+
+    /// Whether the task at position `from` depends on the one at position
+    /// `to`, directly or through other tasks.
+    fn waits_for(&self, from: usize, to: usize) -> bool {
+        let Some(target) = self.list[to].data().id() else {
+            return false;
+        };
+        let mut seen = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(k) = stack.pop() {
+            if !seen.insert(k) {
+                continue;
+            }
+            for dep in self.list[k].data().deps() {
+                if dep == target {
+                    return true;
+                }
+                stack.extend(
+                    self.list
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, t)| t.data().id() == Some(dep))
+                        .map(|(i, _)| i),
+                );
+            }
+        }
+        false
+    }
+
+    /// The lowest number above those of the numeric ids in the list.
+    fn next_id(&self) -> u64 {
+        self.list
+            .iter()
+            .filter_map(|t| t.data().id()?.parse::<u64>().ok())
+            .max()
+            .map_or(1, |id| id + 1)
+    }
+
+    /// Returns the 1-based indices of tasks that repeat an earlier note.
+    ///
+    /// Notes are compared after trimming, and only between tasks that are
+    /// both checked or both unchecked: a checked task and its pending copy,
+    /// as `reopen` makes, are a record and a new task. Of each group of
+    /// duplicates the earliest task is kept.
+    pub fn duplicates(&self) -> Vec<usize> {
+        let mut seen: HashSet<(bool, &str)> = HashSet::new();
+        let mut duplicates = Vec::new();
+        for (i, t) in self.list.iter().enumerate() {
+            let done = matches!(t, Task::DoneTask(_));
+            if !seen.insert((done, t.data().note.trim())) {
+                duplicates.push(i + 1);
+            }
+        }
+        duplicates
+    }
+
+    /// Removes the tasks reported by `duplicates`, returning their original
+    /// indices and notes.
+    pub fn dedup(&mut self) -> Result<Vec<(usize, String)>, TodoError> {
+        let duplicates = self.duplicates();
+        let mut removed = Vec::new();
+        if duplicates.is_empty() {
+            return Ok(removed);
+        }
+        for &index in duplicates.iter().rev() {
+            self.log("remove", index);
+            removed.push((index, self.take(index - 1).data().to_string()));
+        }
+        self.save()?;
+        removed.reverse();
+        Ok(removed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn note(&self, index: usize) -> Option<&str> {
+        self.list
+            .get(index.wrapping_sub(1))
+            .map(|t| &t.data().note[..])
+    }
+
+    /// The note as shown in listings, without the tokens that are only
+    /// meant for the tool.
+    pub fn display_note(&self, index: usize) -> Option<String> {
+        self.list
+            .get(index.wrapping_sub(1))
+            .map(|t| t.data().to_string())
+    }
+
+    pub fn cleanup(&mut self) -> Result<(), TodoError> {
+        self.remove_done(|_| true).map(drop)
+    }
+
+    /// Removes the done tasks completed before `cutoff`, and those without
+    /// a completion date if `undated`, returning how many.
+    pub fn cleanup_before(&mut self, cutoff: NaiveDate, undated: bool) -> Result<usize, TodoError> {
+        self.remove_done(|data| data.completed().map_or(undated, |d| d < cutoff))
+    }
+
+    fn remove_done(&mut self, which: impl Fn(&TaskData) -> bool) -> Result<usize, TodoError> {
+        let removed = |task: &Task| matches!(task, Task::DoneTask(_)) && which(task.data());
+        for i in 0..self.len() {
+            if removed(&self.list[i]) {
+                self.log("remove", i + 1);
+            }
+        }
+        let (done, kept): (Vec<Task>, Vec<Task>) = std::mem::take(&mut self.list)
+            .into_iter()
+            .partition(removed);
+        let count = done.len();
+        self.list = kept;
+        self.changes.extend(done.into_iter().map(Change::Remove));
+        self.save()?;
+        Ok(count)
+    }
+
+    /// Removes all but the last `n` tasks, returning how many.
+    pub fn keep_last(&mut self, n: usize) -> Result<usize, TodoError> {
+        let count = self.len().saturating_sub(n);
+        for i in 0..count {
+            self.log("remove", i + 1);
+        }
+        self.changes
+            .extend(self.list.drain(..count).map(Change::Remove));
+        self.save()?;
+        Ok(count)
+    }
+
+    pub fn clear(&mut self) -> Result<(), TodoError> {
+        for i in 0..self.len() {
+            self.log("remove", i + 1);
+        }
+        self.changes.extend(self.list.drain(..).map(Change::Remove));
+        self.save()
+    }
+
+    pub fn print_unchecked(&self, render: &Render, out: &mut impl Write) -> Result<(), TodoError> {
+        self.print_matching(render, out, &Filter::new(Scope::Pending))
+    }
+
+    pub fn print_all(&self, render: &Render, out: &mut impl Write) -> Result<(), TodoError> {
+        self.print_matching(render, out, &Filter::new(Scope::Any))
+    }
+
+    pub fn print_overdue(&self, render: &Render, out: &mut impl Write) -> Result<(), TodoError> {
+        let filter = Filter {
+            overdue: true,
+            ..Filter::new(Scope::Pending)
+        };
+        self.print_matching(render, out, &filter)
+    }
+
+    pub fn print_matching(
+        &self,
+        render: &Render,
+        out: &mut impl Write,
+        filter: &Filter,
+    ) -> Result<(), TodoError> {
+        filter_print_lines(render, out, &self.list, date::today(), false, filter)?;
+        Ok(())
+    }
+
+    /// The indices of the tasks accepted by `filter` under each of their
+    /// tags or projects, in listing order, and of those with none.
+    pub fn groups(&self, filter: &Filter, key: GroupKey, today: NaiveDate) -> Groups {
+        let mut groups = Groups::default();
+        for (i, t) in listing_order(&self.list, filter, today) {
+            let mut names: Vec<&str> = t
+                .data()
+                .note
+                .split(' ')
+                .filter(|w| w.len() > 1 && w.starts_with(key.sigil()))
+                .collect();
+            names.sort_unstable();
+            names.dedup();
+            if names.is_empty() {
+                groups.rest.push(i + 1);
+            }
+            for name in names {
+                groups
+                    .groups
+                    .entry(name.to_owned())
+                    .or_default()
+                    .push(i + 1);
+            }
+        }
+        groups
+    }
+
+    /// Prints the tasks accepted by `filter` in a section per tag or
+    /// project, under a dimmed heading with its task counts, and the tasks
+    /// with none last. Sections are in alphabetical order, or from the
+    /// biggest with `by_count`.
+    pub fn print_grouped(
+        &self,
+        render: &Render,
+        out: &mut impl Write,
+        filter: &Filter,
+        key: GroupKey,
+        by_count: bool,
+    ) -> Result<(), TodoError> {
+        let today = date::today();
+        let groups = self.groups(filter, key, today);
+        let blockers = Blockers::of(&self.list);
+        let width = index_width(
+            render,
+            groups
+                .groups
+                .values()
+                .chain([&groups.rest])
+                .flatten()
+                .copied(),
+        );
+        let mut sections: Vec<(&str, &Vec<usize>)> = groups
+            .groups
+            .iter()
+            .map(|(name, indices)| (&name[..], indices))
+            .collect();
+        if by_count {
+            sections.sort_by_key(|(_, indices)| std::cmp::Reverse(indices.len()));
+        }
+        if !groups.rest.is_empty() {
+            sections.push((key.missing(), &groups.rest));
+        }
+        for (n, (name, indices)) in sections.into_iter().enumerate() {
+            if n > 0 {
+                writeln!(out)?;
+            }
+            let done = indices
+                .iter()
+                .filter(|&&i| matches!(self.list[i - 1], Task::DoneTask(_)))
+                .count();
+            let mut heading = format!(
+                "{} {} {} pending",
+                name,
+                render.glyph("—", "-"),
+                indices.len() - done
+            );
+            if done > 0 {
+                heading += &format!(", {} done", done);
+            }
+            writeln!(out, "{}", render.style(render.theme.muted).paint(heading))?;
+            for &i in indices {
+                let t = &self.list[i - 1];
+                write!(
+                    out,
+                    "{}",
+                    listing_line(render, i, width, t, blockers.block(t), today, &[])
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the tasks in `scope` with the `words`, if given, whose
+    /// displayed note has matches, as byte ranges returned by `find`, with
+    /// every match highlighted. Returns how many were printed.
+    pub fn print_search(
+        &self,
+        render: &Render,
+        out: &mut impl Write,
+        find: impl Fn(&str) -> Vec<(usize, usize)>,
+        scope: Scope,
+        words: Option<&Words>,
+        case: Case,
+    ) -> Result<usize, TodoError> {
+        let today = date::today();
+        let blockers = Blockers::of(&self.list);
+        let found: Vec<_> = self
+            .list
+            .iter()
             .enumerate()
-            .map(|(i, l)| match l {
-                Ok(s) => s
-                    .parse::<Task>()
-                    .expect(&format!("Failed to parse line {}", i)),
-                Err(e) => panic!("{:#?}", e),
+            .filter(|(_, t)| scope.contains(t))
+            .filter(|(_, t)| words.is_none_or(|words| words.accepts(t.data(), case)))
+            .filter_map(|(i, t)| {
+                let note = t.data().to_string();
+                let matches: Vec<_> = find(&note)
+                    .into_iter()
+                    .filter(|(start, end)| start < end)
+                    .collect();
+                (!matches.is_empty()).then_some((i + 1, t, matches))
             })
             .collect();
-        Ok(TodoList {
-            path: path,
-            list: list,
-        })
+        let width = index_width(render, found.iter().map(|(i, _, _)| *i));
+        for (i, t, matches) in &found {
+            write!(
+                out,
+                "{}",
+                listing_line(render, *i, width, t, blockers.block(t), today, matches)
+            )?;
+        }
+        Ok(found.len())
     }
 
-    fn save(&self) {
-        let mut file = OpenOptions::new()
-            .truncate(true)
-            .create(true)
-            .write(true)
-            .open(self.path)
-            .unwrap();
+    /// The indices of the tasks accepted by `filter`.
+    pub fn matching(&self, filter: &Filter, today: NaiveDate) -> Vec<usize> {
+        let blockers = Blockers::of(&self.list);
+        self.list
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| filter.accepts(t, &blockers, today))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
 
-        for l in &self.list {
-            writeln!(file, "{:?}", l).unwrap();
+    /// A task chosen uniformly at random from those `filter` accepts that
+    /// can be worked on, as with `next`: neither snoozed, blocked nor
+    /// waiting. The same `seed` chooses the same task from the same list.
+    pub fn random(&self, filter: &Filter, today: NaiveDate, seed: u64) -> Option<usize> {
+        let filter = Filter {
+            scope: Scope::Pending,
+            ready: true,
+            hide_waiting: true,
+            ..filter.clone()
+        };
+        let pool = self.matching(&filter, today);
+        if pool.is_empty() {
+            return None;
         }
+        Some(pool[(splitmix64(seed) % pool.len() as u64) as usize])
     }
 
-    fn modify(&mut self, action: impl FnOnce(&mut Vec<Task>)) {
-        action(&mut self.list);
-        self.save();
+    /// The last pending task in the file, for `--last`: the one added most
+    /// recently, since tasks are added at the end, unless the file was
+    /// reordered by hand.
+    pub fn last_pending(&self) -> Option<usize> {
+        self.list
+            .iter()
+            .rposition(|t| matches!(t, Task::TodoTask(_)))
+            .map(|i| i + 1)
     }
 
-    pub fn add(&mut self, note: &str) {
-        self.modify(|l| {
-            let task = Task::new(note);
-            l.push(task);
-        })
+    /// Suggests the pending task to work on next: the one with the highest
+    /// priority, then the earliest due date, then the lowest index. Tasks
+    /// without a priority or due date rank after those having one, and
+    /// snoozed, blocked or waiting tasks are never suggested.
+    pub fn next(&self, today: NaiveDate) -> Option<usize> {
+        let blockers = Blockers::of(&self.list);
+        self.list
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| match t {
+                Task::TodoTask(d)
+                    if !d.is_snoozed(today) && !blockers.block(t) && !d.is_waiting() =>
+                {
+                    Some((i, d))
+                }
+                _ => None,
+            })
+            .min_by(|(_, a), (_, b)| {
+                SortKey::Priority
+                    .compare(a, b)
+                    .then_with(|| SortKey::Due.compare(a, b))
+            })
+            .map(|(i, _)| i + 1)
+    }
+
+    pub fn print_task(
+        &self,
+        render: &Render,
+        out: &mut impl Write,
+        index: usize,
+    ) -> Result<(), TodoError> {
+        let task = &self.list[self.position(index)?];
+        let blocked = Blockers::of(&self.list).block(task);
+        write!(
+            out,
+            "{}",
+            listing_line(render, index, 0, task, blocked, date::today(), &[])
+        )?;
+        Ok(())
     }
 
-    pub fn check(&mut self, index: usize) {
-        let i = index - 1;
-        if let Some(t) = vec_try_remove(&mut self.list, i) {
-            self.modify(|l| {
-                l.insert(i, t.check());
+    /// The pending tasks due on or before `until`, overdue ones included.
+    pub fn due_by(&self, today: NaiveDate, until: NaiveDate) -> Vec<usize> {
+        self.list
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| match t {
+                Task::TodoTask(d) => d
+                    .due()
+                    .is_some_and(|due| Bucket::of(due, today, until).is_some()),
+                Task::DoneTask(_) => false,
             })
-        }
+            .map(|(i, _)| i + 1)
+            .collect()
     }
 
-    pub fn undo(&mut self, index: usize) {
-        let i = index - 1;
-        if let Some(t) = vec_try_remove(&mut self.list, i) {
-            self.modify(|l| {
-                l.insert(i, t.undo());
+    /// The pending tasks due by `until`, or snoozed until a day from
+    /// `today` to then, in their buckets: the bucket of the due date, or of
+    /// the snooze for a task that isn't due. Each bucket is in date order,
+    /// the oldest first, and empty ones are left out.
+    pub fn agenda(&self, today: NaiveDate, until: NaiveDate) -> Vec<(Bucket, Vec<usize>)> {
+        let mut dated: Vec<(NaiveDate, usize)> = self
+            .list
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| match t {
+                Task::TodoTask(d) => d
+                    .due()
+                    .or_else(|| d.threshold().filter(|&t| t >= today))
+                    .map(|date| (date, i + 1)),
+                Task::DoneTask(_) => None,
             })
+            .collect();
+        dated.sort_by_key(|&(date, _)| date);
+        let mut buckets: Vec<(Bucket, Vec<usize>)> = Vec::new();
+        for (date, i) in dated {
+            let Some(bucket) = Bucket::of(date, today, until) else {
+                continue;
+            };
+            match buckets.last_mut() {
+                Some((last, indices)) if *last == bucket => indices.push(i),
+                _ => buckets.push((bucket, vec![i])),
+            }
+        }
+        buckets
+    }
+
+    /// Prints the buckets of `agenda` under their labels, returning them.
+    pub fn print_agenda(
+        &self,
+        render: &Render,
+        out: &mut impl Write,
+        today: NaiveDate,
+        until: NaiveDate,
+    ) -> Result<Vec<(Bucket, Vec<usize>)>, TodoError> {
+        let buckets = self.agenda(today, until);
+        let blockers = Blockers::of(&self.list);
+        let width = index_width(
+            render,
+            buckets
+                .iter()
+                .flat_map(|(_, indices)| indices.iter().copied()),
+        );
+        for (n, (bucket, indices)) in buckets.iter().enumerate() {
+            if !render.plain {
+                if n > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(
+                    out,
+                    "{}",
+                    render.style(Style::new().bold()).paint(bucket.label())
+                )?;
+            }
+            for &i in indices {
+                let t = &self.list[i - 1];
+                write!(
+                    out,
+                    "{}",
+                    listing_line(render, i, width, t, blockers.block(t), today, &[])
+                )?;
+            }
         }
+        Ok(buckets)
     }
 
-    pub fn remove(&mut self, index: usize) {
-        let i = index - 1;
-        if let Some(_) = vec_try_remove(&mut self.list, i) {
-            self.save();
+    /// The pending tasks due from `first` until before `end`, as their
+    /// indices and notes by due date.
+    pub fn due_between(
+        &self,
+        first: NaiveDate,
+        end: NaiveDate,
+    ) -> BTreeMap<NaiveDate, Vec<(usize, String)>> {
+        let mut days: BTreeMap<NaiveDate, Vec<(usize, String)>> = BTreeMap::new();
+        for (i, t) in self.list.iter().enumerate() {
+            if let Task::TodoTask(data) = t {
+                if let Some(due) = data.due().filter(|due| (first..end).contains(due)) {
+                    days.entry(due).or_default().push((i + 1, data.to_string()));
+                }
+            }
         }
+        days
     }
 
-    pub fn cleanup(&mut self) {
-        self.modify(|l| {
-            l.retain(|task| match task {
-                Task::TodoTask(_) => true,
-                _ => false,
-            });
-        })
+    /// Renders the list as an iCalendar document with one VTODO per task
+    /// accepted by `filter`, or per such task with a due date when
+    /// `due_only` is set. UIDs are derived
+    /// from the note so that re-exports update the same entries.
+    pub fn to_ical(&self, filter: &Filter, due_only: bool, now: DateTime<Utc>) -> String {
+        let mut seen: HashMap<u64, usize> = HashMap::new();
+        let blockers = Blockers::of(&self.list);
+        let today = date::today();
+        let summaries: Vec<(&Task, String)> = self
+            .list
+            .iter()
+            .filter(|t| filter.accepts(t, &blockers, today))
+            .filter(|t| !due_only || t.data().due().is_some())
+            .map(|t| (t, t.data().to_string()))
+            .collect();
+        let todos: Vec<ical::Todo> = summaries
+            .iter()
+            .map(|(t, summary)| {
+                let hash = ical::fnv1a(t.data().note.as_bytes());
+                let n = seen.entry(hash).or_insert(0);
+                *n += 1;
+                let uid = match n {
+                    1 => format!("{:016x}@todo", hash),
+                    n => format!("{:016x}-{}@todo", hash, n),
+                };
+                ical::Todo {
+                    uid,
+                    summary,
+                    done: matches!(t, Task::DoneTask(_)),
+                    due: t.data().due(),
+                }
+            })
+            .collect();
+        ical::calendar(&todos, now)
     }
 
-    pub fn clear(&mut self) {
-        self.modify(|l| {
-            l.clear();
+    /// Whether the task at `index` is checked, or `None` if there's no such
+    /// task.
+    pub fn is_done(&self, index: usize) -> Option<bool> {
+        self.list
+            .get(index.wrapping_sub(1))
+            .map(|t| matches!(t, Task::DoneTask(_)))
+    }
+
+    pub fn details(&self, index: usize) -> Result<Details, TodoError> {
+        let task = &self.list[self.position(index)?];
+        let data = task.data();
+        Ok(Details {
+            index,
+            done: matches!(task, Task::DoneTask(_)),
+            text: data.text(),
+            priority: data.priority(),
+            created: data.created(),
+            completed: data.completed(),
+            tags: data
+                .note
+                .split_whitespace()
+                .filter(|w| w.len() > 1 && w.starts_with(['#', '+', '@']))
+                .map(str::to_owned)
+                .collect(),
+            due: data.due(),
+            estimate: data.estimate(),
+            snoozed_until: data.threshold(),
+            pinned: data.is_pinned(),
+            waiting: data.is_waiting(),
+            waiting_for: data.waiting_for(),
+            assignee: data.assignee().map(str::to_owned),
+            started: data.token("start").and_then(date::parse_timestamp),
+            spent: data.spent(),
+            checklist: data.checklist.clone(),
+            attachments: data.attachments(),
+            line: format!("{:?}", task),
         })
     }
 
-    pub fn print_unchecked(&self) {
-        filter_print_lines(self.list.iter(), |t| match t {
-            Task::TodoTask(_) => true,
-            _ => false,
-        });
+    /// The tasks as a JSON array of `{"index", "done", "note"}` objects.
+    pub fn to_json(&self, render: &Render) -> serde_json::Value {
+        self.list
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                serde_json::json!({
+                    "index": render.shown_index(i + 1),
+                    "done": matches!(t, Task::DoneTask(_)),
+                    "note": t.data().note,
+                })
+            })
+            .collect()
+    }
+
+    pub fn stats(&self, today: NaiveDate) -> Stats {
+        let mut stats = Stats {
+            pending: 0,
+            done: 0,
+            overdue: 0,
+        };
+        for t in &self.list {
+            match t {
+                Task::TodoTask(task_data) => {
+                    stats.pending += 1;
+                    if task_data.is_overdue(today) {
+                        stats.overdue += 1;
+                    }
+                }
+                Task::DoneTask(_) => stats.done += 1,
+            }
+        }
+        stats
+    }
+
+    /// The totals of the estimates of the pending tasks and of the tasks
+    /// checked this week, up to `today`.
+    /// The streaks of the daily tasks of the list, counting the tasks
+    /// checked in `archive` too.
+    pub fn streaks(&self, archive: Option<&TodoList>, today: NaiveDate) -> Streaks {
+        let archived = archive.into_iter().flat_map(|a| &a.list);
+        Streaks::of(&self.list, archived, today)
+    }
+
+    /// Whether the list has daily tasks, which have streaks.
+    pub fn has_daily_tasks(&self) -> bool {
+        self.list.iter().any(|t| t.data().habit().is_some())
+    }
+
+    /// The done tasks checked on `day`, going by their completion dates, in
+    /// the order of the file; those without a date aren't any.
+    pub fn completed_on(&self, day: NaiveDate) -> Vec<usize> {
+        self.list
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| matches!(t, Task::DoneTask(d) if d.completed() == Some(day)))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+
+    /// How many of the done tasks were completed on each day, going by
+    /// their completion dates.
+    pub fn completions(&self) -> BTreeMap<NaiveDate, usize> {
+        let mut counts = BTreeMap::new();
+        for t in &self.list {
+            if let Task::DoneTask(data) = t {
+                if let Some(day) = data.completed() {
+                    *counts.entry(day).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// The number of pending tasks of each assignee, the most first, then
+    /// of those assigned to no one; empty if no pending task is assigned.
+    pub fn pending_by_assignee(&self) -> Vec<(Option<String>, usize)> {
+        let mut counts: BTreeMap<Option<&str>, usize> = BTreeMap::new();
+        for t in &self.list {
+            if let Task::TodoTask(data) = t {
+                *counts.entry(data.assignee()).or_insert(0) += 1;
+            }
+        }
+        if counts.keys().all(Option::is_none) {
+            return Vec::new();
+        }
+        let mut counts: Vec<(Option<String>, usize)> = counts
+            .into_iter()
+            .map(|(who, n)| (who.map(str::to_owned), n))
+            .collect();
+        counts.sort_by(|(a, m), (b, n)| a.is_none().cmp(&b.is_none()).then(n.cmp(m)));
+        counts
+    }
+
+    pub fn workloads(&self, today: NaiveDate) -> Workloads {
+        let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+        let mut workloads = Workloads::default();
+        for t in &self.list {
+            let Some(estimate) = t.data().estimate() else {
+                continue;
+            };
+            match t {
+                Task::TodoTask(_) => workloads.pending.add(estimate),
+                Task::DoneTask(data) => {
+                    if data.completed().is_some_and(|d| d >= monday && d <= today) {
+                        workloads.done_this_week.add(estimate);
+                    }
+                }
+            }
+        }
+        workloads
+    }
+
+    /// Splits the pending tasks that need attention on `today` into labeled
+    /// sections: due today or overdue, snoozed until today, and pinned.
+    /// A task is only listed in the first section it belongs to, and only
+    /// if it has the `words` asked for.
+    pub fn today_sections(
+        &self,
+        today: NaiveDate,
+        words: Option<&Words>,
+        case: Case,
+    ) -> Vec<(&'static str, Vec<usize>)> {
+        let pending: Vec<(usize, &TaskData)> = self
+            .list
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| match t {
+                Task::TodoTask(task_data) => Some((i + 1, task_data)),
+                Task::DoneTask(_) => None,
+            })
+            .filter(|(_, d)| words.is_none_or(|words| words.accepts(d, case)))
+            .collect();
+
+        let mut due: Vec<(usize, &TaskData)> = pending
+            .iter()
+            .filter(|(_, d)| d.due().is_some_and(|due| due <= today))
+            .copied()
+            .collect();
+        due.sort_by(|(_, a), (_, b)| SortKey::Due.compare(a, b));
+        let due: Vec<usize> = due.into_iter().map(|(i, _)| i).collect();
+        let snoozed: Vec<usize> = pending
+            .iter()
+            .filter(|(i, d)| !due.contains(i) && d.threshold() == Some(today))
+            .map(|(i, _)| *i)
+            .collect();
+        let pinned: Vec<usize> = pending
+            .iter()
+            .filter(|(i, d)| !due.contains(i) && !snoozed.contains(i) && d.is_pinned())
+            .map(|(i, _)| *i)
+            .collect();
+
+        vec![
+            ("Due today or overdue", due),
+            ("Snoozed until today", snoozed),
+            ("Pinned", pinned),
+        ]
     }
 
-    pub fn print_all(&self) {
-        filter_print_lines(self.list.iter(), |_| true);
+    /// Prints the non-empty sections of `today_sections`, returning how many
+    /// tasks were shown.
+    pub fn print_today(
+        &self,
+        render: &Render,
+        out: &mut impl Write,
+        words: Option<&Words>,
+        case: Case,
+    ) -> Result<usize, TodoError> {
+        let today = date::today();
+        let mut shown = 0;
+        let sections = self.today_sections(today, words, case);
+        let blockers = Blockers::of(&self.list);
+        let width = index_width(
+            render,
+            sections
+                .iter()
+                .flat_map(|(_, indices)| indices.iter().copied()),
+        );
+        for (label, indices) in sections {
+            if indices.is_empty() {
+                continue;
+            }
+            if !render.plain {
+                if shown > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "{}", render.style(Style::new().bold()).paint(label))?;
+            }
+            for i in &indices {
+                let t = &self.list[i - 1];
+                write!(
+                    out,
+                    "{}",
+                    listing_line(render, *i, width, t, blockers.block(t), today, &[])
+                )?;
+            }
+            shown += indices.len();
+        }
+        Ok(shown)
     }
 }
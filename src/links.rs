@@ -0,0 +1,216 @@
+//! Clickable links in listings: the `http` and `https` URLs of notes in the
+//! OSC 8 escape sequences of terminals that support them. Also opening
+//! them and attached files, for `todo open`.
+
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+/// Whether the terminal, going by the variables it sets, shows OSC 8
+/// links: iTerm2, WezTerm, kitty, VS Code, Windows Terminal, and those
+/// built on VTE 0.50 or later such as GNOME Terminal.
+pub fn supported() -> bool {
+    let var = |name| std::env::var(name).ok();
+    supported_by(
+        var("TERM_PROGRAM").as_deref(),
+        var("TERM").as_deref(),
+        var("VTE_VERSION").as_deref(),
+        var("WT_SESSION").is_some() || var("KITTY_WINDOW_ID").is_some(),
+    )
+}
+
+fn supported_by(program: Option<&str>, term: Option<&str>, vte: Option<&str>, known: bool) -> bool {
+    known
+        || matches!(program, Some("iTerm.app" | "WezTerm" | "vscode"))
+        || term.is_some_and(|term| term.contains("kitty") || term.starts_with("wezterm"))
+        || vte
+            .and_then(|version| version.parse::<u32>().ok())
+            .is_some_and(|version| version >= 5000)
+}
+
+/// Where the URLs of `text` are. A URL starts with `http://` or
+/// `https://` after anything but a letter or digit and runs to the next
+/// space, quote or angle bracket, leaving out the punctuation that ends a
+/// sentence and closing brackets it doesn't open, as in `(see
+/// https://example.com/a_(b))`.
+pub fn urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut from = 0;
+    while let Some(found) = ["http://", "https://"]
+        .iter()
+        .filter_map(|scheme| Some((text[from..].find(scheme)? + from, scheme.len())))
+        .min()
+    {
+        let (start, scheme) = found;
+        from = start + scheme;
+        if without_color(&text[..start])
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+        {
+            continue;
+        }
+        let mut end = text[start..]
+            .find(|c: char| c.is_whitespace() || c.is_control() || "\"'<>`".contains(c))
+            .map_or(text.len(), |len| start + len);
+        while end > start + scheme {
+            let url = &text[start..end];
+            let last = url.chars().next_back().unwrap();
+            let unopened = match last {
+                ')' => url.matches('(').count() < url.matches(')').count(),
+                ']' => url.matches('[').count() < url.matches(']').count(),
+                '}' => url.matches('{').count() < url.matches('}').count(),
+                _ => false,
+            };
+            if unopened || ".,:;!?*".contains(last) {
+                end -= last.len_utf8();
+            } else {
+                break;
+            }
+        }
+        if end > start + scheme {
+            urls.push(start..end);
+            from = end;
+        }
+    }
+    urls
+}
+
+/// `text` without the color codes at its end.
+fn without_color(mut text: &str) -> &str {
+    while let Some(code) = text.rfind('\x1b').filter(|&code| {
+        let sgr = &text[code + 1..];
+        sgr.strip_prefix('[')
+            .and_then(|sgr| sgr.strip_suffix('m'))
+            .is_some_and(|params| params.chars().all(|c| c.is_ascii_digit() || c == ';'))
+    }) {
+        text = &text[..code];
+    }
+    text
+}
+
+/// `text` with its URLs made links, the visible text staying the same.
+pub fn hyperlink(text: &str) -> String {
+    let mut linked = String::new();
+    let mut last = 0;
+    for url in urls(text) {
+        let target = &text[url.clone()];
+        linked.push_str(&text[last..url.start]);
+        linked.push_str(&format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", target, target));
+        last = url.end;
+    }
+    linked.push_str(&text[last..]);
+    linked
+}
+
+/// Opens `url` in `$BROWSER` if it's set, else like `open_file`.
+pub fn open_url(url: &str) -> Result<(), String> {
+    match std::env::var("BROWSER").ok().filter(|b| !b.is_empty()) {
+        Some(browser) => run(&browser, &[], url.as_ref()),
+        None => open_file(url.as_ref()),
+    }
+}
+
+/// Opens `target`, a file or URL, with the platform's opener: `open` on
+/// macOS, `start` on Windows and `xdg-open` elsewhere.
+pub fn open_file(target: &OsStr) -> Result<(), String> {
+    if cfg!(target_os = "macos") {
+        run("open", &[], target)
+    } else if cfg!(windows) {
+        // `start` takes a quoted first argument as the window's title.
+        run("cmd", &["/C", "start", ""], target)
+    } else {
+        run("xdg-open", &[], target)
+    }
+}
+
+fn run(program: &str, args: &[&str], target: &OsStr) -> Result<(), String> {
+    let status = Command::new(program)
+        .args(args)
+        .arg(target)
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|e| format!("couldn't run {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!(
+            "{} couldn't open {}",
+            program,
+            target.to_string_lossy()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(text: &str) -> Vec<&str> {
+        urls(text).into_iter().map(|url| &text[url]).collect()
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(
+            found("read https://example.com/a?b=c#d"),
+            ["https://example.com/a?b=c#d"]
+        );
+        assert_eq!(found("see https://example.com."), ["https://example.com"]);
+        assert_eq!(
+            found("(see https://example.com/a)"),
+            ["https://example.com/a"]
+        );
+        assert_eq!(
+            found("(see https://en.wikipedia.org/wiki/Rust_(language))"),
+            ["https://en.wikipedia.org/wiki/Rust_(language)"]
+        );
+        assert_eq!(found("[docs](https://docs.rs/x)"), ["https://docs.rs/x"]);
+        assert_eq!(
+            found("\"http://a.example\", <https://b.example>!"),
+            ["http://a.example", "https://b.example"]
+        );
+        assert_eq!(
+            found("mail http://x.example/, then http://y.example;"),
+            ["http://x.example/", "http://y.example"]
+        );
+        assert!(found("xhttps://example.com and https:// and http").is_empty());
+        assert_eq!(
+            found("\x1b[2mhttps://example.com\x1b[0m"),
+            ["https://example.com"]
+        );
+    }
+
+    #[test]
+    fn test_hyperlink() {
+        assert_eq!(
+            hyperlink("read https://example.com."),
+            "read \x1b]8;;https://example.com\x1b\\https://example.com\x1b]8;;\x1b\\."
+        );
+        assert_eq!(hyperlink("buy milk"), "buy milk");
+    }
+
+    #[test]
+    fn test_supported() {
+        assert!(supported_by(Some("iTerm.app"), None, None, false));
+        assert!(supported_by(None, Some("xterm-kitty"), None, false));
+        assert!(supported_by(
+            None,
+            Some("xterm-256color"),
+            Some("6003"),
+            false
+        ));
+        assert!(!supported_by(
+            None,
+            Some("xterm-256color"),
+            Some("4205"),
+            false
+        ));
+        assert!(!supported_by(
+            Some("Apple_Terminal"),
+            Some("xterm-256color"),
+            None,
+            false
+        ));
+        assert!(supported_by(None, None, None, true));
+    }
+}
@@ -0,0 +1,75 @@
+//! Advisory locking that serializes processes operating on the same list.
+
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use crate::{is_read_only, is_unwritable, TodoError};
+
+/// An exclusive lock on a todo file, held until dropped.
+///
+/// The lock is taken on a `.<name>.lock` file next to the todo file rather
+/// than on the todo file itself, which is replaced when saving.
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Blocks until no other process holds the lock for `path`.
+    pub fn acquire(path: &Path) -> Result<Self, TodoError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(lock_path(path))?;
+        file.lock()?;
+        Ok(FileLock { _file: file })
+    }
+
+    /// Like `acquire`, unless lists are read-only: then, or if the lock
+    /// can't be created because the directory can't be written, so that the
+    /// list couldn't be saved there either, there's no lock and the list is
+    /// just read.
+    pub fn acquire_unless_read_only(path: &Path) -> Result<Option<Self>, TodoError> {
+        if is_read_only() {
+            return Ok(None);
+        }
+        match FileLock::acquire(path) {
+            Ok(lock) => Ok(Some(lock)),
+            Err(TodoError::Io(e)) if is_unwritable(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.lock", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_path() {
+        assert_eq!(
+            lock_path(Path::new("/home/me/todo.txt")),
+            Path::new("/home/me/.todo.txt.lock")
+        );
+    }
+
+    #[test]
+    fn test_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let lock = FileLock::acquire(&path).unwrap();
+
+        let other = File::open(lock_path(&path)).unwrap();
+        assert!(other.try_lock().is_err());
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+    }
+}
@@ -1,81 +1,4720 @@
 #![cfg_attr(feature = "clippy", feature(plugin))]
 #![cfg_attr(feature = "clippy", plugin(clippy))]
 
-#[macro_use]
-extern crate clap;
-use clap::{App, AppSettings, Arg, SubCommand};
+use ansi_term::Style;
+use chrono::{DateTime, Datelike, Local, Months};
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use todo::activity;
+use todo::backup;
+use todo::cal::WeekStart;
+use todo::config::{AutoSync, Config, View};
+use todo::journal;
+use todo::lock::FileLock;
+use todo::merge::{self, Merged};
+use todo::pager::{Pager, Stdout};
+use todo::paths::ListSource;
+use todo::plugins;
+use todo::report::Report;
+use todo::template::CountTemplate;
 use todo::*;
 
-fn main() {
-    let args = App::new("todo")
+/// Exit codes of the CLI: 0 on success, `NOT_FOUND` when an index or
+/// selection matched nothing, `USAGE` for bad invocations and `FAILURE`
+/// for IO and parse errors.
+const NOT_FOUND: i32 = 1;
+const USAGE: i32 = 2;
+const FAILURE: i32 = 3;
+
+enum CliError {
+    Usage(String),
+    Todo(TodoError),
+    /// Nothing to report; exits with `NOT_FOUND` without a message.
+    Empty,
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => USAGE,
+            CliError::Todo(
+                TodoError::MultilineNote { .. }
+                | TodoError::EmptyNote
+                | TodoError::DependencyCycle { .. }
+                | TodoError::JoinSame { .. },
+            ) => USAGE,
+            CliError::Empty => NOT_FOUND,
+            CliError::Todo(
+                TodoError::NoSuchTask { .. }
+                | TodoError::NoSuchItem { .. }
+                | TodoError::NoSuchAttachment { .. }
+                | TodoError::NoMatch { .. }
+                | TodoError::Ambiguous { .. }
+                | TodoError::NotStarted
+                | TodoError::AlreadyStarted { .. }
+                | TodoError::StaleNumber { .. }
+                | TodoError::NothingToUndo
+                | TodoError::NothingToRedo,
+            ) => NOT_FOUND,
+            CliError::Todo(_) => FAILURE,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) => write!(f, "{}", msg),
+            CliError::Todo(e) => write!(f, "{}", e),
+            CliError::Empty => Ok(()),
+        }
+    }
+}
+
+impl From<TodoError> for CliError {
+    fn from(e: TodoError) -> Self {
+        CliError::Todo(e)
+    }
+}
+
+/// `print!` and `println!` to `Stdout`, which stop writing once stdout is
+/// closed rather than panicking.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        write_stdout(format_args!($($arg)*))
+    };
+}
+
+macro_rules! outln {
+    () => {
+        out!("\n")
+    };
+    ($($arg:tt)*) => {
+        write_stdout(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+fn write_stdout(args: fmt::Arguments) {
+    if let Err(e) = Stdout.write_fmt(args) {
+        panic!("failed printing to stdout: {}", e);
+    }
+}
+
+/// The command line interface, for parsing the arguments and for the man
+/// page.
+fn app() -> App<'static, 'static> {
+    App::new("todo")
         .version("0.2.0")
         .about("CLI Todo-List Tool")
-        .settings(&[
-            AppSettings::SubcommandsNegateReqs,
-            AppSettings::VersionlessSubcommands,
-        ])
+        .setting(AppSettings::VersionlessSubcommands)
         .arg(
             Arg::with_name("task")
-                .required(true)
                 .index(1)
-                .help("Add a new task"),
+                .help("Add a new task; without one, pending tasks are listed"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .takes_value(true)
+                .global(true)
+                .help("The named list to use [default: default]"),
+        )
+        .arg(
+            Arg::with_name("global")
+                .long("global")
+                .global(true)
+                .help("Use the list of the config or the default one, even inside a project with its own"),
+        )
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .takes_value(true)
+                .global(true)
+                .help("Wrap notes to this many columns [default: terminal width]"),
+        )
+        .arg(
+            Arg::with_name("no pager")
+                .long("no-pager")
+                .global(true)
+                .help("Don't page listings taller than the terminal"),
+        )
+        .arg(
+            Arg::with_name("symbols")
+                .long("symbols")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["unicode", "ascii", "minimal"])
+                .help("The status symbols to list tasks with [default: unicode]"),
+        )
+        .arg(
+            Arg::with_name("ascii")
+                .long("ascii")
+                .global(true)
+                .help("Print only ASCII, with the ASCII status symbols"),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .long("theme")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["dark", "light", "colorblind"])
+                .help(
+                    "The colors to list tasks with, for a dark or light background or red-green \
+                     color blindness [default: dark]",
+                ),
+        )
+        .arg(
+            Arg::with_name("colorblind")
+                .long("colorblind")
+                .global(true)
+                .conflicts_with("theme")
+                .help("Use the colorblind theme, like --theme colorblind"),
+        )
+        .arg(
+            Arg::with_name("plain")
+                .long("plain")
+                .global(true)
+                .help("List tasks as tab-separated index, todo|done and note"),
+        )
+        .arg(
+            Arg::with_name("print0")
+                .short("0")
+                .long("print0")
+                .global(true)
+                .help("Like --plain, but end each task with a NUL byte"),
+        )
+        .arg(
+            Arg::with_name("skip invalid")
+                .long("skip-invalid")
+                .global(true)
+                .help("Skip lines that aren't tasks, keeping them in the file"),
+        )
+        .arg(
+            Arg::with_name("zero indexed")
+                .long("zero-indexed")
+                .global(true)
+                .help("Number tasks from 0, in listings and in the indices commands take"),
+        )
+        .arg(
+            Arg::with_name("no hooks")
+                .long("no-hooks")
+                .global(true)
+                .help("Don't run the configured hook after changes"),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["markdown", "org"])
+                .help("The format of the list file [default: org for a .org file, else markdown]"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["auto", "always", "never"])
+                .conflicts_with("no color")
+                .help("When to print colors: auto for a terminal unless NO_COLOR is set [default: auto]"),
+        )
+        .arg(
+            Arg::with_name("no color")
+                .long("no-color")
+                .global(true)
+                .help("Print without colors, like --color never"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .global(true)
+                .help("Explain on stderr which file is used and what's done to it; twice to trace each operation"),
+        )
+        .arg(
+            Arg::with_name("dry run")
+                .long("dry-run")
+                .global(true)
+                .help("Show what a command would change without saving anything"),
+        )
+        .arg(
+            Arg::with_name("read only")
+                .long("read-only")
+                .global(true)
+                .help("Only read the list, failing any command that would change it; the default for files that can't be written"),
+        )
+        .arg(
+            Arg::with_name("absolute dates")
+                .long("absolute-dates")
+                .global(true)
+                .help("List due dates as written instead of e.g. \"due in 5d\""),
+        )
+        .arg(
+            Arg::with_name("ignore case")
+                .long("ignore-case")
+                .global(true)
+                .conflicts_with("case sensitive")
+                .help("Match searches, notes and tags ignoring case"),
+        )
+        .arg(
+            Arg::with_name("case sensitive")
+                .long("case-sensitive")
+                .global(true)
+                .help(
+                    "Match searches, notes and tags exactly [default: ignore case unless \
+                     there's an uppercase letter]",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("add")
+                .about("Add a new task")
+                .arg(Arg::with_name("task").required(true))
+                .arg(
+                    Arg::with_name("squeeze")
+                        .short("s")
+                        .long("squeeze")
+                        .help("Collapse runs of whitespace in the note into single spaces"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("ls")
+                .visible_alias("l")
                 .about("List unchecked tasks")
+                .arg(
+                    Arg::with_name("view")
+                        .long("view")
+                        .takes_value(true)
+                        .value_name("name")
+                        .help("Start from a view of the config file; other options override it"),
+                )
                 .arg(
                     Arg::with_name("list all")
                         .long("all")
                         .help("List all tasks"),
+                )
+                .arg(
+                    Arg::with_name("overdue")
+                        .long("overdue")
+                        .conflicts_with("list all")
+                        .help("List only overdue tasks"),
+                )
+                .arg(
+                    Arg::with_name("ready")
+                        .long("ready")
+                        .conflicts_with_all(&["list all", "done"])
+                        .help("List only pending tasks that don't wait for another"),
+                )
+                .arg(
+                    Arg::with_name("since")
+                        .long("since")
+                        .takes_value(true)
+                        .value_name("when")
+                        .help("List only tasks created on or after a date, or e.g. 7d ago"),
+                )
+                .arg(
+                    Arg::with_name("before")
+                        .long("before")
+                        .takes_value(true)
+                        .value_name("when")
+                        .help("List only tasks created before a date, or e.g. 2w ago"),
+                )
+                .arg(
+                    Arg::with_name("include undated")
+                        .long("include-undated")
+                        .help("With --since or --before, also list tasks without a creation date"),
+                )
+                .arg(
+                    Arg::with_name("hide waiting")
+                        .long("hide-waiting")
+                        .help("Leave out the tasks waiting on someone"),
+                )
+                .arg(
+                    Arg::with_name("assignee")
+                        .long("assignee")
+                        .takes_value(true)
+                        .value_name("who")
+                        .help("List only the tasks assigned to someone"),
+                )
+                .arg(
+                    Arg::with_name("unassigned")
+                        .long("unassigned")
+                        .conflicts_with("assignee")
+                        .help("List only the tasks assigned to no one"),
+                )
+                .arg(
+                    Arg::with_name("done")
+                        .long("done")
+                        .conflicts_with_all(&["list all", "overdue"])
+                        .help("List only checked tasks"),
+                )
+                .args(&word_args())
+                .arg(
+                    Arg::with_name("due")
+                        .long("due")
+                        .takes_value(true)
+                        .value_name("when")
+                        .help("List only tasks due by a date or within e.g. 3d"),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .takes_value(true)
+                        .value_name("key")
+                        .possible_values(&["priority", "due", "age", "alpha", "est"])
+                        .help("List in this order, keeping the tasks' numbers"),
+                )
+                .arg(
+                    Arg::with_name("group by")
+                        .long("group-by")
+                        .takes_value(true)
+                        .value_name("field")
+                        .possible_values(&["tag", "project"])
+                        .conflicts_with("renumbered")
+                        .help("List the tasks in a section per #tag or +project"),
+                )
+                .arg(
+                    Arg::with_name("by count")
+                        .long("by-count")
+                        .requires("group by")
+                        .help("Order the sections from the biggest instead of by name"),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .long("count")
+                        .help("Print only the number of matching tasks"),
+                )
+                .arg(
+                    Arg::with_name("progress")
+                        .long("progress")
+                        .conflicts_with("overdue")
+                        .help("Show a completion bar below the tasks"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("line")
+                        .conflicts_with("group by")
+                        .help(
+                            "List each task as this line, with {index}, {status}, {symbol}, \
+                             {note}, {priority}, {due}, {tags}, {created} or {age}; \
+                             {{ and }} are braces",
+                        ),
+                )
+                .arg(Arg::with_name("oneline").long("oneline").help(
+                    "Cut each note to fit on one line of the terminal, or of --width, ending \
+                     in an ellipsis, instead of wrapping it",
+                ))
+                .arg(Arg::with_name("age").long("age").help(
+                    "Show how old each task is, from its todo.txt creation date, as in 3d, 6w \
+                     or 1y",
+                ))
+                .arg(Arg::with_name("renumbered").long("renumbered").help(
+                    "Number the listed tasks from 1, for `todo check --renumbered`, \
+                             instead of by position in the whole list",
+                ))
+                .arg(
+                    Arg::with_name("reverse")
+                        .long("reverse")
+                        .help("List in the opposite order, pinned tasks still first"),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .takes_value(true)
+                        .value_name("n")
+                        .conflicts_with("group by")
+                        .help("List at most this many tasks; 0 lists all"),
+                )
+                .arg(
+                    Arg::with_name("offset")
+                        .long("offset")
+                        .takes_value(true)
+                        .value_name("n")
+                        .conflicts_with("group by")
+                        .help("Leave out this many tasks from the start of the listing"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("List tasks containing a text")
+                .arg(Arg::with_name("pattern").required(true))
+                .arg(
+                    Arg::with_name("regex")
+                        .long("regex")
+                        .short("e")
+                        .help("Treat the pattern as a regular expression"),
+                )
+                .arg(
+                    Arg::with_name("list all")
+                        .long("all")
+                        .help("Search checked tasks too"),
+                )
+                .args(&word_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("sed")
+                .about("Replace a regular expression in the notes of tasks")
+                .arg(Arg::with_name("pattern").required(true))
+                .arg(
+                    Arg::with_name("replacement")
+                        .required(true)
+                        .help("The text to put in place of each match; $1 or ${name} is a group"),
+                )
+                .arg(
+                    Arg::with_name("list all")
+                        .long("all")
+                        .help("Rewrite checked tasks too"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Rewrite without asking for confirmation"),
                 ),
         )
         .subcommand(
             SubCommand::with_name("remove")
-                .about("Remove a task by index")
-                .arg(Arg::with_name("index").required(true)),
+                .visible_alias("rm")
+                .about("Remove a task by index or note")
+                .arg(Arg::with_name("index").help(PICKED_INDEX))
+                .arg(last_arg().conflicts_with("index")),
         )
         .subcommand(
             SubCommand::with_name("check")
-                .about("Check a task by index")
-                .arg(Arg::with_name("index").required(true)),
+                .visible_alias("done")
+                .about("Check a task by index or note")
+                .arg(Arg::with_name("index").help(PICKED_INDEX))
+                .arg(last_arg().conflicts_with("index"))
+                .arg(
+                    Arg::with_name("renumbered")
+                        .long("renumbered")
+                        .conflicts_with("last")
+                        .requires("index")
+                        .help("Take the number from the last `todo ls --renumbered`"),
+                )
+                .arg(
+                    Arg::with_name("items")
+                        .long("items")
+                        .help("Check the open items of its checklist too, without asking"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("item")
+                .about("List or change the checklist of a task")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("action")
+                        .possible_values(&["add", "check", "undo", "remove"])
+                        .requires("item")
+                        .help("What to do; without it, the checklist is listed"),
+                )
+                .arg(
+                    Arg::with_name("item")
+                        .help("The text of the item to add, or the number of the item"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("wait")
+                .about("Mark a task as waiting on someone; undo it to stop waiting")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("for")
+                        .long("for")
+                        .takes_value(true)
+                        .value_name("who")
+                        .help("Who it waits on"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("assign")
+                .about("Assign a task to someone, replacing whoever it was assigned to")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("who")
+                        .required_unless("clear")
+                        .help("Who it's assigned to"),
+                )
+                .arg(
+                    Arg::with_name("clear")
+                        .long("clear")
+                        .conflicts_with("who")
+                        .help("Assign it to no one"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("block")
+                .about("Make a task wait until another one is done")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("on")
+                        .long("on")
+                        .takes_value(true)
+                        .value_name("index")
+                        .required(true)
+                        .help("The task it waits for"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("undo")
-                .about("Undo a task by index")
+                .about("Undo a task by index or note")
                 .arg(Arg::with_name("index").required(true)),
         )
-        .subcommand(SubCommand::with_name("cleanup").about("Clear checked tasks"))
-        .subcommand(SubCommand::with_name("clear").about("Clear all tasks"))
-        .get_matches();
-
-    let path = dirs::home_dir().unwrap().join("todo.txt");
-    let mut todo_list = TodoList::load(&path).unwrap();
-
-    if let Some(task) = args.value_of("task") {
-        todo_list.add(task);
-    }
-
-    match args.subcommand() {
-        ("ls", Some(matches)) => {
-            if matches.is_present("list all") {
-                todo_list.print_all();
-                return;
-            }
-        }
-        ("cleanup", Some(_)) => todo_list.cleanup(),
-        ("clear", Some(_)) => todo_list.clear(),
-        (action, Some(matches)) => {
-            let i = value_t_or_exit!(matches.value_of("index"), usize);
-            match action {
-                "remove" => todo_list.remove(i),
-                "check" => todo_list.check(i),
-                "undo" => todo_list.undo(i),
-                _ => (),
-            }
-        }
+        .subcommand(
+            SubCommand::with_name("reopen")
+                .about("Add a pending copy of a checked task, keeping the checked one")
+                .arg(Arg::with_name("index").required_unless("from archive"))
+                .arg(
+                    Arg::with_name("from archive")
+                        .long("from-archive")
+                        .help("Take the task from the archive; without an index, list it"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pin")
+                .about("Pin a task to the top of the listing")
+                .arg(Arg::with_name("index").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("unpin")
+                .about("Unpin a task")
+                .arg(Arg::with_name("index").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Show everything recorded about a task")
+                .arg(Arg::with_name("index").required(true))
+                .arg(Arg::with_name("json").long("json").help("Print JSON")),
+        )
+        .subcommand(
+            SubCommand::with_name("yank")
+                .about("Copy a task's note to the clipboard")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("with index")
+                        .long("with-index")
+                        .help("Copy the note with its \"4. \" prefix"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("open")
+                .about("Open the links in a task's note")
+                .arg(Arg::with_name("index").help(PICKED_INDEX))
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("Open every link of the note, rather than asking which"),
+                )
+                .arg(
+                    Arg::with_name("attachment")
+                        .long("attachment")
+                        .takes_value(true)
+                        .value_name("n")
+                        .conflicts_with("all")
+                        .help("Open the task's nth attached file, even if it has links"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("attach")
+                .about("Attach a file to a task, keeping its path as given")
+                .arg(Arg::with_name("index").required(true))
+                .arg(Arg::with_name("path").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("detach")
+                .about("Remove an attached file from a task, or all of them")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("attachment")
+                        .help("The number `todo show` lists it with [default: all of them]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("append")
+                .about("Add text to the end of a task's note")
+                .arg(Arg::with_name("index").required_unless("last"))
+                .arg(Arg::with_name("text").required_unless("last"))
+                .arg(last_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("edit")
+                .about("Replace the note of a task, keeping the tokens hidden in listings")
+                .arg(Arg::with_name("index").required_unless("last"))
+                .arg(Arg::with_name("text").required_unless("last"))
+                .arg(last_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("prepend")
+                .about("Add text to the start of a task's note")
+                .arg(Arg::with_name("index").required_unless("last"))
+                .arg(Arg::with_name("text").required_unless("last"))
+                .arg(last_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("dup")
+                .about("Add a pending copy of a task right after it")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("end")
+                        .long("end")
+                        .help("Add the copy at the end of the list instead"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("split")
+                .about("Replace a task with several, written in $VISUAL or $EDITOR or given")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("into")
+                        .long("into")
+                        .takes_value(true)
+                        .value_name("note")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("A task to split it into, instead of writing them in the editor"),
+                )
+                .arg(
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .help("Keep the task itself, as the first of them"),
+                )
+                .arg(
+                    Arg::with_name("bare")
+                        .long("bare")
+                        .help("Don't give the new tasks the task's priority and tags"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sort")
+                .about("Reorder the list in the file, as ls --sort lists it")
+                .arg(
+                    Arg::with_name("by")
+                        .long("by")
+                        .takes_value(true)
+                        .value_name("key")
+                        .required(true)
+                        .possible_values(&["priority", "due", "age", "alpha", "est"])
+                        .help("The order to put the tasks in; tasks without the key go last"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("join")
+                .about("Join a task into another, the inverse of split")
+                .arg(
+                    Arg::with_name("index")
+                        .required(true)
+                        .help("The task that stays, with the other's note after its own"),
+                )
+                .arg(
+                    Arg::with_name("other")
+                        .required(true)
+                        .help("The task to join into it, which is removed"),
+                )
+                .arg(
+                    Arg::with_name("separator")
+                        .long("separator")
+                        .takes_value(true)
+                        .value_name("text")
+                        .default_value("; ")
+                        .help("What goes between the two notes"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("snooze")
+                .about("Hide a task from the listing until a date")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("until")
+                        .required(true)
+                        .help("A date: 2024-07-01, tomorrow, next friday, in 2 weeks, 3d"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("due")
+                .about("Set the due date of a task")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("date")
+                        .required(true)
+                        .help("A date: 2024-07-01, tomorrow, next friday, in 2 weeks, 3d"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("today")
+                .about("List tasks due today or overdue, snoozed until today, and pinned")
+                .args(&word_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("cal")
+                .about("Show a month's calendar with the tasks due each day")
+                .arg(
+                    Arg::with_name("month")
+                        .long("month")
+                        .takes_value(true)
+                        .value_name("YYYY-MM")
+                        .help("The month to show [default: this month]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("done-today")
+                .about("List the tasks checked today, without changing any file")
+                .arg(
+                    Arg::with_name("yesterday")
+                        .long("yesterday")
+                        .help("List those checked yesterday instead"),
+                )
+                .arg(
+                    Arg::with_name("on")
+                        .long("on")
+                        .takes_value(true)
+                        .value_name("date")
+                        .conflicts_with("yesterday")
+                        .help("List those checked on a day: 2024-07-01, last friday, 3d"),
+                )
+                .arg(
+                    Arg::with_name("include archive")
+                        .long("include-archive")
+                        .help("Also list the tasks of the archive checked that day"),
+                )
+                .arg(Arg::with_name("json").long("json").help("Print JSON")),
+        )
+        .subcommand(
+            SubCommand::with_name("agenda")
+                .about("List overdue tasks, those of today and those coming up; exit with 1 if none is overdue or of today")
+                .arg(
+                    Arg::with_name("days")
+                        .long("days")
+                        .takes_value(true)
+                        .value_name("n")
+                        .help("How many days after today are coming up [default: 7]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("next")
+                .about("Suggest the task to work on next")
+                .arg(
+                    Arg::with_name("start")
+                        .long("start")
+                        .help("Start tracking time on the suggested task"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("random")
+                .about("Print a pending task chosen at random")
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Check the chosen task too"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .help("Choose with this number, the same way each time"),
+                )
+                .args(&word_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("pop")
+                .about("Check the last pending task and print its note")
+                .arg(
+                    Arg::with_name("remove")
+                        .long("remove")
+                        .help("Remove the task instead of checking it"),
+                ),
+        )
+        .subcommand(priority_command(
+            "bump",
+            "Raise the priority of tasks a step: none, (C), (B), (A)",
+        ))
+        .subcommand(priority_command(
+            "drop",
+            "Lower the priority of tasks a step: (A), (B), (C), none",
+        ))
+        .subcommand(ends_command("top", "Move tasks to the top of the list"))
+        .subcommand(ends_command("bottom", "Move tasks to the bottom of the list"))
+        .subcommand(
+            SubCommand::with_name("start")
+                .about("Start tracking time on a task")
+                .arg(Arg::with_name("index").required(true)),
+        )
+        .subcommand(SubCommand::with_name("stop").about("Stop tracking time"))
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Show task counts")
+                .arg(
+                    Arg::with_name("time")
+                        .long("time")
+                        .help("Show the time tracked per task"),
+                )
+                .arg(
+                    Arg::with_name("graph")
+                        .long("graph")
+                        .conflicts_with("time")
+                        .help("Chart the tasks checked per day, the archived ones included"),
+                )
+                .arg(
+                    Arg::with_name("days")
+                        .long("days")
+                        .takes_value(true)
+                        .value_name("n")
+                        .requires("graph")
+                        .help("How many days the chart covers, up to today [default: 30]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("remind")
+                .about("Send a desktop notification listing due and overdue tasks")
+                .arg(
+                    Arg::with_name("within")
+                        .long("within")
+                        .takes_value(true)
+                        .help("Also include tasks due within a duration (e.g. 2d)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lists")
+                .about("Show the available lists")
+                .arg(Arg::with_name("json").long("json").help("Print JSON")),
+        )
+        .subcommand(
+            SubCommand::with_name("path")
+                .about("Print the file of the list, as the other commands find it")
+                .arg(
+                    Arg::with_name("source")
+                        .long("source")
+                        .help("Also print what chose it: --list, TODO_FILE, the project, the config or the default"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Start a list for the project of the current directory, in a .todo.txt file"),
+        )
+        .subcommand(
+            SubCommand::with_name("log")
+                .about("Show the latest operations from the activity log")
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .short("n")
+                        .takes_value(true)
+                        .default_value("20")
+                        .help("How many entries to show"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("move")
+                .about("Move a task to another list")
+                .arg(Arg::with_name("index").required(true))
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The list to move the task to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("template")
+                .about("Add the tasks of a template, or list the templates")
+                .arg(Arg::with_name("name").help("The template from the config file")),
+        )
+        .subcommand(
+            SubCommand::with_name("view")
+                .about("List the tasks of a view from the config file")
+                .arg(
+                    Arg::with_name("view")
+                        .required(true)
+                        .help("The name of the view"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("views").about("List the views of the config file"))
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Import tasks from another tool or a markdown checklist")
+                .arg(Arg::with_name("file").required(true))
+                .arg(
+                    Arg::with_name("taskwarrior")
+                        .long("taskwarrior")
+                        .conflicts_with("markdown")
+                        .help("Read a Taskwarrior `task export` JSON file"),
+                )
+                .arg(
+                    Arg::with_name("merge")
+                        .long("merge")
+                        .conflicts_with_all(&["taskwarrior", "markdown"])
+                        .help("Read another todo list, skipping the tasks this one has and checking those done there"),
+                )
+                .arg(Arg::with_name("markdown").long("markdown").help(
+                    "Read the `- [ ]` checkboxes of a markdown file (the default for .md files)",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Summarize what was done and added in a period")
+                .arg(
+                    Arg::with_name("since")
+                        .long("since")
+                        .takes_value(true)
+                        .default_value("7d")
+                        .help("The first day: a date, a weekday (monday) or e.g. 7d ago"),
+                )
+                .arg(
+                    Arg::with_name("until")
+                        .long("until")
+                        .takes_value(true)
+                        .default_value("today")
+                        .help("The last day, in the same forms"),
+                )
+                .arg(Arg::with_name("json").long("json").help("Print JSON")),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Export the tasks to another format, or some of them to a list file")
+                .arg(
+                    Arg::with_name("ical")
+                        .long("ical")
+                        .required_unless("output")
+                        .conflicts_with("output")
+                        .help("Write an iCalendar document with a VTODO per task"),
+                )
+                .arg(
+                    Arg::with_name("due only")
+                        .long("due-only")
+                        .conflicts_with("output")
+                        .help("Only export tasks with a due date"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("file")
+                        .help("Write the tasks to a new list file, or to stdout with -"),
+                )
+                .arg(
+                    Arg::with_name("pending")
+                        .long("pending")
+                        .help("Only export pending tasks"),
+                )
+                .arg(
+                    Arg::with_name("done")
+                        .long("done")
+                        .conflicts_with("pending")
+                        .help("Only export checked tasks"),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .requires("output")
+                        .help("Replace the output file if it exists"),
+                )
+                .arg(
+                    Arg::with_name("move")
+                        .long("move")
+                        .requires("output")
+                        .help("Remove the exported tasks from the list once they're written"),
+                )
+                .args(&word_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serve the list over a local HTTP JSON API")
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .default_value("7890"),
+                )
+                .arg(
+                    Arg::with_name("host")
+                        .long("host")
+                        .takes_value(true)
+                        .default_value("127.0.0.1")
+                        .help("The address to bind to"),
+                )
+                .arg(
+                    Arg::with_name("token")
+                        .long("token")
+                        .takes_value(true)
+                        .env("TODO_SERVE_TOKEN")
+                        .help("Require this secret in the X-Todo-Token header"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cleanup")
+                .about("Clear checked tasks")
+                .arg(
+                    Arg::with_name("older than")
+                        .long("older-than")
+                        .takes_value(true)
+                        .value_name("span")
+                        .help("Only clear tasks checked longer ago than this, such as 30d"),
+                )
+                .arg(
+                    Arg::with_name("include undated")
+                        .long("include-undated")
+                        .requires("older than")
+                        .help("Also clear checked tasks without a completion date"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("autoclean")
+                .about("Move old checked tasks to the list's archive")
+                .arg(
+                    Arg::with_name("after")
+                        .long("after")
+                        .takes_value(true)
+                        .value_name("span")
+                        .help(
+                            "How long after their completion tasks are archived, such as 14d; \
+                             the config's auto_archive_after by default",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("archive")
+                .about("Manage the list's archive, where autoclean moves checked tasks")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("purge")
+                        .about("Remove old tasks from the archive")
+                        .arg(
+                            Arg::with_name("older than")
+                                .long("older-than")
+                                .takes_value(true)
+                                .value_name("span")
+                                .required_unless("keep last")
+                                .help(
+                                    "Remove the tasks checked longer ago than this, such as 1y",
+                                ),
+                        )
+                        .arg(
+                            Arg::with_name("keep last")
+                                .long("keep-last")
+                                .takes_value(true)
+                                .value_name("n")
+                                .help("Keep only the last n tasks, the ones archived most recently"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tag")
+                .about("Manage the tags of the list's tasks")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("rename")
+                        .about("Rename a #tag in every task that has it, checked ones included")
+                        .arg(Arg::with_name("old").required(true))
+                        .arg(Arg::with_name("new").required(true)),
+                ),
+        )
+        .subcommand(SubCommand::with_name("clear").about("Clear all tasks"))
+        .subcommand(SubCommand::with_name("dedupe").about("Remove duplicate tasks"))
+        .subcommand(
+            SubCommand::with_name("prompt")
+                .about("Print the number of pending tasks for a shell prompt, or nothing without any")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("text")
+                        .help(
+                            "Print this, with {pending}, {done} or {overdue}; {{ and }} are \
+                             braces [default: {pending}]",
+                        ),
+                )
+                .arg(no_config_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("due-check")
+                .about(
+                    "Exit with 0 if a task is due within the window, 1 if none is, 2 on errors",
+                )
+                .arg(
+                    Arg::with_name("within")
+                        .long("within")
+                        .takes_value(true)
+                        .value_name("duration")
+                        .help("The window, such as 1d or 2w [default: today]"),
+                )
+                .arg(
+                    Arg::with_name("print")
+                        .long("print")
+                        .help("List the tasks due in the window, in the format of --plain"),
+                )
+                .arg(no_config_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("Print the pending and overdue counts for a status bar")
+                .arg(
+                    Arg::with_name("tmux")
+                        .long("tmux")
+                        .help("Highlight the overdue count with tmux's #[...] markup"),
+                )
+                .arg(
+                    Arg::with_name("waybar")
+                        .long("waybar")
+                        .conflicts_with_all(&["tmux", "i3blocks"])
+                        .help("Print the JSON object of a waybar custom module"),
+                )
+                .arg(
+                    Arg::with_name("i3blocks")
+                        .long("i3blocks")
+                        .conflicts_with("tmux")
+                        .help("Print the full text, short text and color lines of an i3blocks block"),
+                )
+                .arg(
+                    Arg::with_name("tooltip tasks")
+                        .long("tooltip-tasks")
+                        .takes_value(true)
+                        .value_name("n")
+                        .requires("waybar")
+                        .help("List this many pending tasks in the waybar tooltip [default: 5]"),
+                )
+                .arg(
+                    Arg::with_name("max width")
+                        .long("max-width")
+                        .takes_value(true)
+                        .value_name("n")
+                        .help("Cut the text to this many columns, ending in an ellipsis"),
+                )
+                .arg(no_config_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("fsck")
+                .about("Check the file strictly, reporting every problem by line")
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .help("Repair what can be repaired safely, leaving the rest"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("tidy").about(
+            "Rewrite the file in todo's own form: checkboxes, whitespace and blank lines",
+        ))
+        .subcommand(
+            SubCommand::with_name("recover")
+                .about("List the automatic backups, or restore the list from one")
+                .arg(
+                    Arg::with_name("latest")
+                        .long("latest")
+                        .conflicts_with("from")
+                        .help("Restore the latest backup"),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .value_name("n")
+                        .help("Restore the backup numbered n in the listing"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Restore without asking for confirmation"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("undo-last").about("Undo the last change todo made to the list"),
+        )
+        .subcommand(SubCommand::with_name("review").about(
+            "Edit the whole list in $VISUAL or $EDITOR, like `git rebase -i`, then apply the changes",
+        ))
+        .subcommand(
+            SubCommand::with_name("edit-file")
+                .about("Open the list file in $VISUAL or $EDITOR, then check it"),
+        )
+        .subcommand(
+            SubCommand::with_name("redo").about("Make the last change undone by undo-last again"),
+        )
+        .subcommand(
+            SubCommand::with_name("conflicts")
+                .about("List the copies of the list that sync clients left after a conflict")
+                .arg(
+                    Arg::with_name("merge")
+                        .long("merge")
+                        .takes_value(true)
+                        .value_name("file")
+                        .help("Add the tasks of this copy that the list doesn't have, then rename it"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Merge another copy of the list into it, against the copy both came from")
+                .arg(
+                    Arg::with_name("theirs")
+                        .required(true)
+                        .help("The other copy of the list"),
+                )
+                .arg(
+                    Arg::with_name("base")
+                        .long("base")
+                        .takes_value(true)
+                        .value_name("file")
+                        .required(true)
+                        .help("The copy both were changed from"),
+                )
+                .arg(
+                    Arg::with_name("resolve")
+                        .long("resolve")
+                        .takes_value(true)
+                        .possible_values(&["ours", "theirs", "both"])
+                        .help("Which side of every conflict to keep, instead of asking"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about("Run the pull command, then the push command, of the config's [sync]"),
+        )
+        .subcommand(
+            SubCommand::with_name("man")
+                .setting(AppSettings::Hidden)
+                .about("Write the man page")
+                .arg(
+                    Arg::with_name("output")
+                        .help("The file to write it to [default: stdout]"),
+                ),
+        )
+}
+
+/// Exits with the message and code of `e`.
+fn fail(e: CliError) -> ! {
+    fail_numbered(e, &Render::default())
+}
+
+/// Exits with the message and code of `e`, numbering the tasks it names as
+/// `render` shows them.
+fn fail_numbered(e: CliError, render: &Render) -> ! {
+    match &e {
+        CliError::Empty => {}
+        CliError::Todo(error) => eprintln!("todo: {}", error.numbered(render)),
+        e => eprintln!("todo: {}", e),
+    }
+    process::exit(e.exit_code());
+}
+
+/// Loads the config file, refusing aliases named like a command.
+fn load_config(app: &App) -> Result<Config, CliError> {
+    let Some(path) = Config::path() else {
+        return Ok(Config::default());
+    };
+    let config = Config::load(&path)?;
+    if let Some(name) = command_names(app).find(|name| config.aliases.contains_key(*name)) {
+        return Err(TodoError::Config {
+            path,
+            message: format!("alias '{}' has the name of a command", name),
+        }
+        .into());
+    }
+    if let Some(name) = &config.timezone {
+        date::set_timezone(name);
+    }
+    Ok(config)
+}
+
+/// The names of the subcommands of `app`, with their aliases.
+fn command_names<'a>(app: &'a App) -> impl Iterator<Item = &'a str> {
+    let commands = app.p.subcommands.iter().flat_map(|command| {
+        let aliases = command.p.meta.aliases.iter().flatten();
+        std::iter::once(&command.p.meta.name[..]).chain(aliases.map(|(alias, _)| *alias))
+    });
+    commands.chain(["help"])
+}
+
+/// The command that `name`, in the command position but not a command,
+/// looks like a misspelling of, the way clap suggests one. Words with
+/// spaces are tasks, as no command has any.
+fn similar_command<'a>(app: &'a App, name: &str) -> Option<&'a str> {
+    if name.contains(char::is_whitespace) {
+        return None;
+    }
+    command_names(app)
+        .map(|command| (strsim::jaro_winkler(name, command), command))
+        .filter(|&(similarity, _)| similarity > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, command)| command)
+}
+
+/// Where the subcommand is in `args`: after the program and the options
+/// before it, with their values.
+fn command_position(app: &App, args: &[OsString]) -> usize {
+    let takes_value = |arg: &str| {
+        app.p.opts.iter().any(|opt| {
+            arg.strip_prefix("--") == opt.s.long
+                || (arg.len() == 2
+                    && opt.s.short.map(|c| format!("-{}", c)).as_deref() == Some(arg))
+        })
+    };
+    let mut i = 1;
+    while let Some(arg) = args.get(i).and_then(|arg| arg.to_str()) {
+        if arg == "--" || !arg.starts_with('-') || arg == "-" {
+            break;
+        }
+        i += if takes_value(arg) { 2 } else { 1 };
+    }
+    i
+}
+
+/// Replaces an alias in the command position of `args` with the words it
+/// stands for, and so on while they start with another alias.
+fn expand_aliases(
+    app: &App,
+    aliases: &BTreeMap<String, String>,
+    mut args: Vec<OsString>,
+) -> Result<Vec<OsString>, CliError> {
+    let i = command_position(app, &args);
+    let mut seen: Vec<String> = Vec::new();
+    while let Some((name, words)) = args
+        .get(i)
+        .and_then(|arg| arg.to_str())
+        .and_then(|arg| aliases.get_key_value(arg))
+    {
+        seen.push(name.clone());
+        if seen[..seen.len() - 1].contains(name) {
+            return Err(CliError::Usage(format!(
+                "alias cycle: {}",
+                seen.join(" -> ")
+            )));
+        }
+        args.splice(i..=i, words.split_whitespace().map(OsString::from));
+    }
+    Ok(args)
+}
+
+/// `app` with the configured aliases listed at the end of its help.
+fn with_aliases(
+    app: App<'static, 'static>,
+    aliases: &BTreeMap<String, String>,
+) -> App<'static, 'static> {
+    if aliases.is_empty() {
+        return app;
+    }
+    let width = aliases.keys().map(|name| name.len()).max().unwrap_or(0);
+    let mut help = "ALIASES:".to_owned();
+    for (name, words) in aliases {
+        help.push_str(&format!(
+            "\n    {:width$}    {}",
+            name,
+            words,
+            width = width
+        ));
+    }
+    // clap keeps the help for the life of the program.
+    app.after_help(&*Box::leak(help.into_boxed_str()))
+}
+
+/// The word in the command position of `args`, unless it's one of todo's
+/// own commands: the name of a plugin, or a task to add.
+fn other_command<'a>(app: &App, args: &'a [OsString]) -> Option<(usize, &'a str)> {
+    let i = command_position(app, args);
+    let name = args.get(i)?.to_str()?;
+    if command_names(app).any(|command| command == name) {
+        return None;
+    }
+    Some((i, name))
+}
+
+/// Runs plugin `name`, at `i` in `args`, if there's one on `PATH`, and
+/// returns its exit code.
+fn run_plugin(
+    app: &App<'static, 'static>,
+    config: &Config,
+    args: &[OsString],
+    (i, name): (usize, &str),
+) -> Result<Option<i32>, CliError> {
+    let Some(plugin) = plugins::find(name) else {
+        return Ok(None);
+    };
+    // The options before the plugin's name are todo's own.
+    let Ok(globals) = app.clone().get_matches_from_safe(&args[..i]) else {
+        return Ok(None);
+    };
+    let source = choose_list(&globals, config);
+    let status = process::Command::new(&plugin)
+        .args(&args[i + 1..])
+        .env("TODO_FILE", source_path(&source)?)
+        .env("TODO_LIST", source.name())
+        .status()
+        .map_err(TodoError::from)?;
+    Ok(Some(status.code().unwrap_or(FAILURE)))
+}
+
+fn main() {
+    let app = app();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let command = args
+        .get(command_position(&app, &args))
+        .and_then(|arg| arg.to_str());
+    if let Some(command) = command.filter(|command| GLANCES.contains(command)) {
+        match glance(&app, args.clone()) {
+            Ok(()) => return,
+            // For cron, 1 means only that nothing is due.
+            Err(e) if command == "due-check" && !matches!(e, CliError::Empty) => {
+                eprintln!("todo: {}", e);
+                process::exit(USAGE);
+            }
+            Err(e) => fail(e),
+        }
+    }
+    let config = load_config(&app).unwrap_or_else(|e| fail(e));
+    let args = expand_aliases(&app, &config.aliases, args).unwrap_or_else(|e| fail(e));
+    let app = with_aliases(app, &config.aliases);
+    let other = other_command(&app, &args);
+    if let Some(other) = other {
+        if let Some(code) = run_plugin(&app, &config, &args, other).unwrap_or_else(|e| fail(e)) {
+            process::exit(code);
+        }
+    }
+    if let Some(command) = other.and_then(|(_, name)| similar_command(&app, name)) {
+        let name = other.unwrap().1;
+        fail(CliError::Usage(format!(
+            "unknown command '{}'; did you mean '{}'? `todo add` or `todo --` adds it as a task",
+            name, command
+        )));
+    }
+    let other = other.is_some();
+    // clap would refuse a task close to a command's name, such as `call mom`,
+    // even after `--`, unless it leaves unknown words to external commands:
+    // `similar_command` does the suggesting instead, and extra words are
+    // refused below.
+    let commands: Vec<String> = command_names(&app).map(str::to_owned).collect();
+    let args = app
+        .setting(AppSettings::AllowExternalSubcommands)
+        .get_matches_from_safe(args)
+        .unwrap_or_else(|e| match e.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+            _ => {
+                eprintln!("{}", e.message);
+                let plugins = plugins::list();
+                if other && !plugins.is_empty() {
+                    eprintln!("\nplugins on PATH: {}", plugins.join(", "));
+                }
+                process::exit(USAGE);
+            }
+        });
+
+    if let (name, Some(_)) = args.subcommand() {
+        if !commands.iter().any(|command| command == name) {
+            fail(CliError::Usage(format!(
+                "unexpected argument '{}'; quote a task of several words",
+                name
+            )));
+        }
+    }
+
+    let render = render(&args, &config).unwrap_or_else(|e| fail(e));
+    if let Err(e) = run(&args, &config, &render) {
+        fail_numbered(e, &render);
+    }
+}
+
+/// The commands `glance` runs.
+const GLANCES: [&str; 3] = ["prompt", "status", "due-check"];
+
+/// Runs `todo prompt`, `todo status` or `todo due-check`, which start
+/// before anything else for a shell prompt, status bar or cron job not to
+/// wait on them: with `--no-config` they don't read the config, and they
+/// only read the list, without locking it. Problems go to stderr, never
+/// stdout.
+fn glance(app: &App<'static, 'static>, args: Vec<OsString>) -> Result<(), CliError> {
+    let args = app
+        .clone()
+        .get_matches_from_safe(args)
+        .unwrap_or_else(|e| match e.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+            _ => {
+                eprintln!("{}", e.message);
+                process::exit(USAGE);
+            }
+        });
+    let (command, Some(matches)) = args.subcommand() else {
+        unreachable!("glance runs for a subcommand");
+    };
+    let template: CountTemplate = matches
+        .value_of("format")
+        .unwrap_or("{pending}")
+        .parse()
+        .map_err(CliError::Usage)?;
+    let max_width = match matches.value_of("max width") {
+        Some(width) => Some(
+            width
+                .parse()
+                .map_err(|_| CliError::Usage(format!("invalid width '{}'", width)))?,
+        ),
+        None => None,
+    };
+    let config = if matches.is_present("no config") {
+        Config::default()
+    } else {
+        load_config(app)?
+    };
+    let path = source_path(&choose_list(&args, &config))?;
+    if paths::remote_url(&path).is_some() {
+        return Err(CliError::Usage(format!(
+            "{} can't read a remote list",
+            command
+        )));
+    }
+    let todo_list = match fs::File::open(&path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => TodoList::read(&path, io::empty())?,
+        file => {
+            let file = file.map_err(TodoError::from)?;
+            match Format::of(&path) {
+                Format::Org => TodoList::load(&path)?,
+                Format::Markdown => TodoList::read(&path, io::BufReader::new(file))?,
+            }
+        }
+    };
+    let tasks = match matches.value_of("tooltip tasks") {
+        Some(n) => n
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid number of tasks '{}'", n)))?,
+        None => 5,
+    };
+    let render = &Render {
+        zero_indexed: config.zero_indexed,
+        ..Render::default()
+    };
+    if command == "due-check" {
+        return due_check(render, &todo_list, matches);
+    }
+    let summary = todo::status::Summary::of(&todo_list, date::today(), tasks);
+    let stats = &summary.stats;
+    if command == "prompt" {
+        if stats.pending > 0 {
+            out!("{}", template.render(stats));
+        }
+    } else if matches.is_present("waybar") {
+        outln!("{}", summary.waybar(render));
+    } else if matches.is_present("i3blocks") {
+        out!("{}", summary.i3blocks(render, max_width));
+    } else {
+        let tmux = matches.is_present("tmux");
+        outln!("{}", todo::status::segment(render, stats, tmux, max_width));
+    }
+    Ok(())
+}
+
+/// Writes the tasks `filter` accepts, and those pending or checked under
+/// `--pending` or `--done`, to the list file `output`, or to stdout for
+/// `-`. With `--move` they're removed from the list once they're written,
+/// and the output is put back as it was if the list can't be saved.
+fn export(
+    todo_list: &mut TodoList,
+    filter: &Filter,
+    output: &str,
+    matches: &ArgMatches,
+) -> Result<(), CliError> {
+    let indices: Vec<usize> = todo_list
+        .matching(filter, date::today())
+        .into_iter()
+        .filter(|&i| match todo_list.is_done(i) {
+            Some(done) if matches.is_present("pending") => !done,
+            Some(done) if matches.is_present("done") => done,
+            _ => true,
+        })
+        .collect();
+    if indices.is_empty() {
+        eprintln!("todo: no task to export");
+        return Err(CliError::Empty);
+    }
+    let content = todo_list.export(&indices)?;
+    let path = Path::new(output);
+    let previous = if output == "-" {
+        Stdout.write_all(&content).map_err(TodoError::from)?;
+        None
+    } else if path.exists() && !matches.is_present("force") {
+        return Err(CliError::Usage(format!(
+            "{} exists already; add --force to replace it",
+            output
+        )));
+    } else if is_dry_run() {
+        None
+    } else {
+        let previous = fs::read(path).ok();
+        write_file(path, &content)?;
+        previous
+    };
+    if matches.is_present("move") {
+        let moved = indices
+            .iter()
+            .rev()
+            .try_for_each(|&i| todo_list.remove(i))
+            .and_then(|()| todo_list.commit());
+        if let Err(e) = moved {
+            if output != "-" && !is_dry_run() {
+                let _ = match previous {
+                    Some(previous) => write_file(path, &previous),
+                    None => remove_file(path),
+                };
+            }
+            return Err(e.into());
+        }
+    }
+    if output != "-" {
+        let verb = if is_dry_run() {
+            "would export"
+        } else {
+            "exported"
+        };
+        outln!("{} {} task(s) to {}", verb, indices.len(), output);
+    }
+    Ok(())
+}
+
+/// Lists the tasks of `todo done-today`: those checked on the day, then
+/// those of the archive, in their files' order since completion dates have
+/// no time. Fails with `CliError::Empty` when there's none.
+fn done_today(
+    render: &Render,
+    todo_list: &TodoList,
+    path: &Path,
+    matches: &ArgMatches,
+    plain: bool,
+) -> Result<(), CliError> {
+    let today = date::today();
+    let day = match matches.value_of("on") {
+        Some(on) => {
+            date::parse_since(on, today).ok_or_else(|| invalid_date(on, date::SINCE_FORMS))?
+        }
+        None if matches.is_present("yesterday") => today.pred_opt().unwrap_or(today),
+        None => today,
+    };
+    let archive_path = paths::archive_path(path);
+    let archive = match matches.is_present("include archive") && archive_path.exists() {
+        true => Some(TodoList::load(&archive_path)?),
+        false => None,
+    };
+    let done = todo_list.completed_on(day);
+    let archived = archive.as_ref().map_or(Vec::new(), |a| a.completed_on(day));
+    if matches.is_present("json") {
+        let row = |list: &TodoList, i, archived| {
+            serde_json::json!({
+                "index": render.shown_index(i),
+                "note": list.display_note(i).unwrap_or_default(),
+                "archived": archived,
+            })
+        };
+        let mut rows: Vec<serde_json::Value> =
+            done.iter().map(|&i| row(todo_list, i, false)).collect();
+        if let Some(archive) = &archive {
+            rows.extend(archived.iter().map(|&i| row(archive, i, true)));
+        }
+        outln!("{}", serde_json::Value::Array(rows));
+    } else {
+        let mut out = Stdout;
+        for &i in &done {
+            todo_list.print_task(render, &mut out, i)?;
+        }
+        if let Some(archive) = archive.as_ref().filter(|_| !archived.is_empty()) {
+            if !plain {
+                if !done.is_empty() {
+                    outln!();
+                }
+                outln!(
+                    "{}",
+                    render.style(Style::new().bold()).paint("From the archive")
+                );
+            }
+            for &i in &archived {
+                archive.print_task(render, &mut out, i)?;
+            }
+        }
+    }
+    match done.len() + archived.len() {
+        0 => Err(CliError::Empty),
+        _ => Ok(()),
+    }
+}
+
+/// Fails with `CliError::Empty` unless a pending task is due within the
+/// window of `todo due-check`, listing those that are with `--print`.
+fn due_check(render: &Render, todo_list: &TodoList, matches: &ArgMatches) -> Result<(), CliError> {
+    let today = date::today();
+    let until = match matches.value_of("within") {
+        Some(within) => date::parse_when(within, today)
+            .ok_or_else(|| CliError::Usage(format!("invalid duration '{}'", within)))?,
+        None => today,
+    };
+    let due = todo_list.due_by(today, until);
+    if matches.is_present("print") {
+        for &i in &due {
+            let note = todo_list.display_note(i).unwrap_or_default();
+            outln!("{}\ttodo\t{}", render.shown_index(i), note);
+        }
+    }
+    if due.is_empty() {
+        return Err(CliError::Empty);
+    }
+    Ok(())
+}
+
+/// `--no-config`, for the commands `glance` runs.
+fn no_config_arg() -> Arg<'static, 'static> {
+    Arg::with_name("no config")
+        .long("no-config")
+        .help("Don't read the config file, for a faster start")
+}
+
+/// The help of the index of commands that open the picker without one.
+const PICKED_INDEX: &str = "The number `todo ls` shows, which is the task's position in the whole \
+                            list, or a part of its note. Without it, the task is picked at the \
+                            terminal";
+
+/// `--last`, for commands that otherwise take an index.
+fn last_arg() -> Arg<'static, 'static> {
+    Arg::with_name("last")
+        .long("last")
+        .help("Use the last pending task in the list, usually the one just added")
+}
+
+/// `todo bump` or `todo drop`, which change the priority of tasks.
+fn priority_command(name: &'static str, about: &'static str) -> App<'static, 'static> {
+    SubCommand::with_name(name).about(about).arg(
+        Arg::with_name("index")
+            .required(true)
+            .multiple(true)
+            .help("The numbers `todo ls` shows, or parts of the notes"),
+    )
+}
+
+/// Raises or lowers the priority of the tasks of `todo bump` or `todo
+/// drop` a step, printing what each became. A task that can't go further
+/// is left as it is, with a notice.
+fn shift_priorities(
+    render: &Render,
+    todo_list: &mut TodoList,
+    matches: &ArgMatches,
+    raise: bool,
+) -> Result<(), CliError> {
+    let mut indices = Vec::new();
+    for selector in matches.values_of("index").into_iter().flatten() {
+        let i = resolve_selector(render, todo_list, selector, Scope::Any, matches)?;
+        if !indices.contains(&i) {
+            indices.push(i);
+        }
+    }
+    let name = |p: Option<char>| p.map_or("no priority".to_owned(), |p| format!("({})", p));
+    for i in indices {
+        let old = todo_list.details(i)?.priority;
+        let new = if raise {
+            raised_priority(old)
+        } else {
+            lowered_priority(old)
+        };
+        let note = todo_list.display_note(i).unwrap_or_default();
+        let note = if old.is_some() { &note[4..] } else { &note[..] };
+        if new == old {
+            let limit = if raise {
+                format!("already {}", name(old))
+            } else {
+                "no priority to drop".to_owned()
+            };
+            outln!("{}. {}: {}", render.shown_index(i), note, limit);
+            continue;
+        }
+        todo_list.set_priority(i, new)?;
+        outln!(
+            "{}. {}: {} {} {}",
+            render.shown_index(i),
+            note,
+            name(old),
+            render.glyph("→", "->"),
+            name(new)
+        );
+    }
+    Ok(())
+}
+
+/// `todo top` or `todo bottom`, which move tasks to one end of the list.
+fn ends_command(name: &'static str, about: &'static str) -> App<'static, 'static> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(Arg::with_name("index").multiple(true).help(
+            "The numbers `todo ls` shows; the tasks keep their order. Without them, \
+                 the tasks are picked at the terminal",
+        ))
+        .arg(
+            Arg::with_name("match")
+                .long("match")
+                .takes_value(true)
+                .conflicts_with("index")
+                .help("Move the task whose note has this text instead"),
+        )
+}
+
+/// The positions of the tasks `todo top` or `todo bottom` moves, in order.
+fn ends_selection(
+    render: &Render,
+    todo_list: &TodoList,
+    matches: &ArgMatches,
+) -> Result<Vec<usize>, CliError> {
+    if let Some(text) = matches.value_of("match") {
+        return Ok(vec![resolve_selector(
+            render,
+            todo_list,
+            text,
+            Scope::Any,
+            matches,
+        )?]);
+    }
+    let Some(values) = matches.values_of("index") else {
+        return pick(render, todo_list, true);
+    };
+    let mut indices = Vec::new();
+    for index in values {
+        let number = index
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid index '{}'", index)))?;
+        indices.push(render.given_index(number));
+    }
+    indices.sort_unstable();
+    indices.dedup();
+    Ok(indices)
+}
+
+fn select(
+    render: &Render,
+    todo_list: &TodoList,
+    matches: &ArgMatches,
+    scope: Scope,
+) -> Result<usize, CliError> {
+    if matches.is_present("last") {
+        return todo_list.last_pending().ok_or_else(|| {
+            eprintln!("todo: there are no pending tasks");
+            CliError::Empty
+        });
+    }
+    let Some(selector) = matches.value_of("index") else {
+        return Ok(pick(render, todo_list, false)?[0]);
+    };
+    Ok(resolve_selector(
+        render, todo_list, selector, scope, matches,
+    )?)
+}
+
+/// Resolves `selector` like `TodoList::resolve_selector`, reading a number
+/// in the base `render` shows indices in.
+fn resolve_selector(
+    render: &Render,
+    todo_list: &TodoList,
+    selector: &str,
+    scope: Scope,
+    matches: &ArgMatches,
+) -> Result<usize, TodoError> {
+    match selector.parse() {
+        Ok(number) => Ok(render.given_index(number)),
+        Err(_) => todo_list.resolve_selector(selector, scope, case(matches)),
+    }
+}
+
+/// Lets the user pick pending tasks at the terminal for a command given no
+/// index, only one unless `multi`. Giving up on the picker changes nothing.
+fn pick(render: &Render, todo_list: &TodoList, multi: bool) -> Result<Vec<usize>, CliError> {
+    #[cfg(all(feature = "picker", unix))]
+    if io::stdin().is_terminal() && io::stderr().is_terminal() {
+        let filter = Filter::new(Scope::Pending);
+        if todo_list.matching(&filter, date::today()).is_empty() {
+            eprintln!("todo: there are no pending tasks");
+            return Err(CliError::Empty);
+        }
+        let picked =
+            todo::picker::pick(render, todo_list, &filter, multi).map_err(TodoError::from)?;
+        return picked.ok_or(CliError::Empty);
+    }
+    let _ = (render, todo_list, multi);
+    Err(CliError::Usage(
+        "the index of the task is missing".to_owned(),
+    ))
+}
+
+/// The options that select tasks by their `#tag`, `+project` and
+/// `@context` words or leave out some by their text, which `words` reads.
+fn word_args() -> [Arg<'static, 'static>; 6] {
+    let word = |name, help| {
+        Arg::with_name(name)
+            .long(name)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help(help)
+    };
+    [
+        word(
+            "tag",
+            "Only tasks with this #tag; all of them when given more than once",
+        ),
+        word(
+            "project",
+            "Only tasks with this +project; all of them when given more than once",
+        ),
+        word(
+            "context",
+            "Only tasks with this @context; all of them when given more than once",
+        ),
+        word("not-tag", "Leave out the tasks with this #tag"),
+        word("not-match", "Leave out the tasks containing this text"),
+        Arg::with_name("any tag")
+            .long("any-tag")
+            .requires("tag")
+            .help("Take tasks with any of the --tag tags rather than all of them"),
+    ]
+}
+
+/// The condition of the `word_args` of a command: all of the `--tag`
+/// tags, or any of them with `--any-tag`, and all of the projects and
+/// contexts, and none of the `--not-tag` tags and `--not-match` texts.
+/// Each option not given is taken from `view`.
+fn words<'a>(matches: &'a ArgMatches, view: &'a View) -> Option<Words> {
+    let values = |name, saved: &'a [String]| -> Vec<&'a str> {
+        match matches.values_of(name) {
+            Some(values) => values.collect(),
+            None => saved.iter().map(String::as_str).collect(),
+        }
+    };
+    let given = |name, saved, sigil| {
+        values(name, saved).into_iter().map(move |word: &str| {
+            let word = word.strip_prefix(sigil).unwrap_or(word);
+            Words::has(&format!("{}{}", sigil, word))
+        })
+    };
+    let any_tag = matches.is_present("any tag") || view.any_tag;
+    let tags = given("tag", &view.tags, '#').reduce(if any_tag { Words::or } else { Words::and });
+    tags.into_iter()
+        .chain(given("project", &view.projects, '+'))
+        .chain(given("context", &view.contexts, '@'))
+        .chain(given("not-tag", &view.not_tags, '#').map(|tag| !tag))
+        .chain(
+            values("not-match", &view.not_match)
+                .into_iter()
+                .map(|text| !Words::text(text)),
+        )
+        .reduce(Words::and)
+}
+
+/// The task and the text of `append`, `prepend` and `edit`. With
+/// `--last`, the only argument is the text.
+fn select_with_text<'a>(
+    render: &Render,
+    todo_list: &TodoList,
+    matches: &'a ArgMatches,
+) -> Result<(usize, &'a str), CliError> {
+    let text = matches.value_of("text");
+    if !matches.is_present("last") {
+        return Ok((
+            select(render, todo_list, matches, Scope::Any)?,
+            text.unwrap_or_default(),
+        ));
+    }
+    match (matches.value_of("index"), text) {
+        (Some(text), None) => Ok((select(render, todo_list, matches, Scope::Any)?, text)),
+        (None, _) => Err(CliError::Usage("the text is missing".to_owned())),
+        (Some(_), Some(_)) => Err(CliError::Usage(
+            "--last takes the place of the index".to_owned(),
+        )),
+    }
+}
+
+/// How patterns are matched, from `--ignore-case` and `--case-sensitive`.
+fn case(matches: &ArgMatches) -> Case {
+    if matches.is_present("ignore case") {
+        Case::Ignore
+    } else if matches.is_present("case sensitive") {
+        Case::Sensitive
+    } else {
+        Case::Smart
+    }
+}
+
+fn start(render: &Render, todo_list: &mut TodoList, index: usize) -> Result<(), CliError> {
+    if let Some((i, seconds)) = todo_list.start(index, date::now())? {
+        eprintln!(
+            "stopped {}. {} after {}",
+            render.shown_index(i),
+            todo_list.display_note(i).unwrap_or_default(),
+            date::format_duration(seconds)
+        );
+    }
+    Ok(())
+}
+
+/// Reports the tasks due by the end of the `within` window, defaulting to
+/// today, through a desktop notification or, failing that, on stdout.
+fn remind(render: &Render, todo_list: &TodoList, within: Option<&str>) -> Result<(), CliError> {
+    let today = date::today();
+    let until = match within {
+        Some(within) => date::parse_when(within, today)
+            .ok_or_else(|| CliError::Usage(format!("invalid duration '{}'", within)))?,
+        None => today,
+    };
+    let due = todo_list.due_by(today, until);
+    if due.is_empty() {
+        return Err(CliError::Empty);
+    }
+
+    let summary = match due.len() {
+        1 => "1 task due".to_owned(),
+        n => format!("{} tasks due", n),
+    };
+    let body: Vec<String> = due
+        .iter()
+        .map(|&i| {
+            let note = todo_list.display_note(i).unwrap_or_default();
+            format!("{}. {}", render.shown_index(i), note)
+        })
+        .collect();
+    // Built without notifications, printing the tasks is how it reminds, so
+    // only a notification that fails is worth a warning.
+    #[cfg(feature = "notify")]
+    match notify(&summary, &body.join("\n")) {
+        Ok(()) => return Ok(()),
+        Err(e) => eprintln!("todo: could not send a notification: {}", e),
+    }
+    outln!("{}", summary);
+    for line in &body {
+        outln!("{}", line);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "notify")]
+fn notify(summary: &str, body: &str) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .appname("todo")
+        .summary(summary)
+        .body(body)
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn import(render: &Render, todo_list: &mut TodoList, matches: &ArgMatches) -> Result<(), CliError> {
+    let file = matches.value_of("file").unwrap_or_default();
+    if matches.is_present("merge") {
+        let other = TodoList::load(Path::new(file))?;
+        let merged = todo_list.merge_import(&other)?;
+        let (added, checked) = match is_dry_run() {
+            true => ("would add", "would check"),
+            false => ("added", "checked"),
+        };
+        for (verb, indices) in [(added, &merged.added), (checked, &merged.upgraded)] {
+            for &i in indices {
+                outln!(
+                    "{} {}. {}",
+                    verb,
+                    render.shown_index(i),
+                    todo_list.display_note(i).unwrap_or_default()
+                );
+            }
+        }
+        outln!(
+            "{} added, {} skipped, {} upgraded",
+            merged.added.len(),
+            merged.skipped,
+            merged.upgraded.len()
+        );
+        return Ok(());
+    }
+    let markdown = matches.is_present("markdown")
+        || (!matches.is_present("taskwarrior")
+            && Path::new(file)
+                .extension()
+                .is_some_and(|ext| ext == "md" || ext == "markdown"));
+    if !markdown && !matches.is_present("taskwarrior") {
+        return Err(CliError::Usage(
+            "specify the format to import, e.g. --taskwarrior or --markdown".to_owned(),
+        ));
+    }
+    let content = fs::read_to_string(file).map_err(TodoError::from)?;
+    let (tasks, skipped_statuses) = if markdown {
+        (todo::import::markdown(&content), Vec::new())
+    } else {
+        let import = todo::import::taskwarrior(&content)?;
+        (import.tasks, import.skipped)
+    };
+    let dry_run = is_dry_run();
+
+    let total = tasks.len();
+    let added = todo_list.import(tasks, dry_run)?;
+    for (done, note) in &added {
+        let verb = if dry_run { "would add" } else { "added" };
+        outln!("{} - [{}] {}", verb, if *done { "x" } else { " " }, note);
+    }
+    let mut skipped = vec![format!("{} already present", total - added.len())];
+    skipped.extend(
+        skipped_statuses
+            .iter()
+            .map(|(status, n)| format!("{} {}", n, status)),
+    );
+    outln!("{} imported, skipped {}", added.len(), skipped.join(", "));
+    Ok(())
+}
+
+/// Prints the details of a task as labeled lines, leaving out what the
+/// task doesn't have.
+fn show(render: &Render, details: &Details) {
+    let mut fields = vec![
+        ("task", render.shown_index(details.index).to_string()),
+        (
+            "state",
+            if details.done { "done" } else { "pending" }.to_owned(),
+        ),
+        ("note", details.text.clone()),
+    ];
+    if let Some(priority) = details.priority {
+        fields.push(("priority", priority.to_string()));
+    }
+    if let Some(created) = details.created {
+        fields.push(("created", created.to_string()));
+    }
+    if let Some(completed) = details.completed {
+        fields.push(("done", completed.to_string()));
+    }
+    if !details.tags.is_empty() {
+        fields.push(("tags", details.tags.join(" ")));
+    }
+    if let Some(due) = details.due {
+        fields.push(("due", due.to_string()));
+    }
+    if let Some(estimate) = details.estimate {
+        fields.push(("estimate", estimate.to_string()));
+    }
+    if let Some(until) = details.snoozed_until {
+        fields.push(("snoozed", format!("until {}", until)));
+    }
+    if details.pinned {
+        fields.push(("pinned", "yes".to_owned()));
+    }
+    if details.spent > 0 {
+        fields.push(("tracked", date::format_duration(details.spent)));
+    }
+    if details.waiting {
+        let who = details.waiting_for.as_deref().unwrap_or("someone");
+        fields.push(("waiting", format!("for {}", who)));
+    }
+    if let Some(who) = &details.assignee {
+        fields.push(("assignee", who.clone()));
+    }
+    if let Some(started) = details.started {
+        let started = DateTime::<Local>::from(started);
+        fields.push(("started", started.format("%Y-%m-%d %H:%M").to_string()));
+    }
+    for (n, path) in details.attachments.iter().enumerate() {
+        let missing = if paths::expand_home(path).exists() {
+            ""
+        } else {
+            " (missing)"
+        };
+        let label = if n == 0 { "files" } else { "" };
+        fields.push((label, format!("{}. {}{}", n + 1, path, missing)));
+    }
+    for (n, (done, text)) in details.checklist.iter().enumerate() {
+        let state = if *done { "[x]" } else { "[ ]" };
+        let label = if n == 0 { "items" } else { "" };
+        fields.push((label, format!("{}. {} {}", n + 1, state, text)));
+    }
+    fields.push(("line", details.line.clone()));
+    for (label, value) in fields {
+        outln!("{:<9}{}", label, value);
+    }
+}
+
+/// Opens the links of a task for `todo open`, or its attached files if
+/// it has no links or `--attachment` picks one: the only one, all of them
+/// with `--all`, or the one picked at the terminal. A task with neither
+/// fails with a message.
+fn open_links(render: &Render, todo_list: &TodoList, matches: &ArgMatches) -> Result<(), CliError> {
+    let i = select(render, todo_list, matches, Scope::Any)?;
+    let note = todo_list.note(i).unwrap_or_default();
+    let urls: Vec<&str> = todo::links::urls(note)
+        .into_iter()
+        .map(|url| &note[url])
+        .collect();
+    let attachments = todo_list.details(i)?.attachments;
+    let attachments: Vec<&str> = attachments.iter().map(String::as_str).collect();
+    let (targets, what) = if let Some(n) = matches.value_of("attachment") {
+        let n: usize = n
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid attachment number '{}'", n)))?;
+        let Some(&path) = n.checked_sub(1).and_then(|j| attachments.get(j)) else {
+            return Err(TodoError::NoSuchAttachment {
+                index: i,
+                attachment: n,
+                len: attachments.len(),
+            }
+            .into());
+        };
+        (vec![path], "attachments")
+    } else if !urls.is_empty() {
+        (urls, "links")
+    } else {
+        (attachments, "attachments")
+    };
+    let chosen = match &targets[..] {
+        [] => {
+            eprintln!(
+                "todo: task {} has no links or attachments",
+                render.shown_index(i)
+            );
+            return Err(CliError::Empty);
+        }
+        [_] => targets.clone(),
+        _ if matches.is_present("all") => targets.clone(),
+        _ => vec![pick_target(&targets, what)?],
+    };
+    for target in chosen {
+        let file = what == "attachments";
+        let path = paths::expand_home(target);
+        if file && !path.exists() {
+            eprintln!("todo: {} doesn't exist any more", target);
+            continue;
+        }
+        if is_dry_run() {
+            outln!("would open {}", target);
+            continue;
+        }
+        let opened = if file {
+            todo::links::open_file(path.as_os_str())
+        } else {
+            todo::links::open_url(target)
+        };
+        opened.map_err(|e| TodoError::from(io::Error::other(e)))?;
+        outln!("opened {}", target);
+    }
+    Ok(())
+}
+
+/// Asks which of `targets`, the task's links or attachments as `what`
+/// says, to open, when there's someone at the terminal to answer.
+fn pick_target<'a>(targets: &[&'a str], what: &str) -> Result<&'a str, CliError> {
+    if !io::stdin().is_terminal() {
+        return Err(CliError::Usage(format!(
+            "the task has {} {}; open them with --all, or pick one at a terminal",
+            targets.len(),
+            what
+        )));
+    }
+    for (n, target) in targets.iter().enumerate() {
+        eprintln!("{}. {}", n + 1, target);
+    }
+    eprint!("open which? [1-{}] ", targets.len());
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(TodoError::from)?;
+    match answer.trim().parse::<usize>() {
+        Ok(n) if (1..=targets.len()).contains(&n) => Ok(targets[n - 1]),
+        _ => {
+            eprintln!("todo: nothing opened");
+            Err(CliError::Empty)
+        }
+    }
+}
+
+/// Asks whether to check the `open` items left in the checklist of a task
+/// being checked, when there's someone at the terminal to answer.
+fn confirm_items(open: usize) -> Result<bool, CliError> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    eprint!("check its {} open checklist item(s) too? [y/N] ", open);
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(TodoError::from)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Changes the checklist of a task as `todo item` says, then prints the
+/// task with its checklist.
+fn item(render: &Render, todo_list: &mut TodoList, matches: &ArgMatches) -> Result<(), CliError> {
+    let i = select(render, todo_list, matches, Scope::Any)?;
+    let arg = matches.value_of("item").unwrap_or_default();
+    let number = || {
+        arg.parse::<usize>()
+            .map_err(|_| CliError::Usage(format!("invalid item number '{}'", arg)))
+    };
+    match matches.value_of("action") {
+        Some("add") => {
+            todo_list.add_item(i, arg)?;
+        }
+        Some("check") => todo_list.set_item_done(i, number()?, true)?,
+        Some("undo") => todo_list.set_item_done(i, number()?, false)?,
+        Some("remove") => {
+            let text = todo_list.remove_item(i, number()?)?;
+            outln!("removed {}", text);
+        }
+        _ => (),
+    }
+    todo_list.print_task(render, &mut Stdout, i)?;
+    let checklist = todo_list.checklist(i)?;
+    let width = checklist.len().to_string().len();
+    for (n, (done, text)) in checklist.iter().enumerate() {
+        let state = if *done { "[x]" } else { "[ ]" };
+        outln!("     {:>width$}. {} {}", n + 1, state, text, width = width);
+    }
+    Ok(())
+}
+
+fn report(todo_list: &TodoList, matches: &ArgMatches) -> Result<(), CliError> {
+    let today = date::today();
+    let day = |name| {
+        let value = matches.value_of(name).unwrap_or_default();
+        date::parse_since(value, today).ok_or_else(|| invalid_date(value, date::SINCE_FORMS))
+    };
+    let (since, until) = (day("since")?, day("until")?);
+    if since > until {
+        return Err(CliError::Usage(format!(
+            "the report would end ({}) before it starts ({})",
+            until, since
+        )));
+    }
+    let report = Report::new(todo_list, since, until);
+    if matches.is_present("json") {
+        outln!("{}", report.to_json());
+        return Ok(());
+    }
+
+    let unknown = |n: usize, what: &str| match n {
+        0 => String::new(),
+        n => format!(" (and {} {} on an unknown date)", n, what),
+    };
+    outln!("{} to {}", since, until);
+    outln!(
+        "completed   {}{}",
+        report.completed,
+        unknown(report.completed_unknown, "done")
+    );
+    outln!(
+        "added       {}{}",
+        report.added,
+        unknown(report.added_unknown, "added")
+    );
+    let ages = &report.pending;
+    outln!("pending     {}", ages.total());
+    for (label, n) in [
+        ("under a week old", ages.under_a_week),
+        ("1 to 4 weeks old", ages.under_four_weeks),
+        ("older", ages.older),
+        ("unknown date", ages.unknown),
+    ] {
+        if n > 0 {
+            outln!("  {:<18}{}", label, n);
+        }
+    }
+    outln!("completion  {:.0}%", report.completion() * 100.0);
+    Ok(())
+}
+
+/// Runs `run` on the local copy of the list at `url`, fetched while the
+/// copy is locked, and puts the copy back if it changed.
+#[cfg(feature = "webdav")]
+fn with_remote(
+    url: &str,
+    config: &Config,
+    verbose: &dyn Fn(u64, &dyn fmt::Display),
+    run: impl FnOnce(&Path) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    let remote = todo::webdav::Remote::new(url, &paths::lists_dir()?.join("remote"))?;
+    if let Some(dir) = remote.cache.parent() {
+        fs::create_dir_all(dir).map_err(TodoError::from)?;
+    }
+    let _lock = FileLock::acquire(&remote.cache)?;
+    let fetched = remote.fetch(&config.webdav)?;
+    verbose(
+        1,
+        &format_args!("fetched the list into {}", remote.cache.display()),
+    );
+    let result = run(&remote.cache);
+    // Whatever was saved goes back, even if a later step failed.
+    if !is_dry_run() && fs::read(&remote.cache).unwrap_or_default() != fetched.content {
+        remote.put(&config.webdav, &fetched)?;
+        verbose(1, &"put the list back on the server");
+    }
+    result
+}
+
+#[cfg(not(feature = "webdav"))]
+fn with_remote(
+    url: &str,
+    _config: &Config,
+    _verbose: &dyn Fn(u64, &dyn fmt::Display),
+    _run: impl FnOnce(&Path) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    Err(CliError::Usage(format!(
+        "todo was built without the webdav feature, which {} needs",
+        url
+    )))
+}
+
+#[cfg(feature = "serve")]
+fn serve(path: &std::path::Path, matches: &ArgMatches, render: &Render) -> Result<(), CliError> {
+    let port: u16 = matches
+        .value_of("port")
+        .unwrap_or_default()
+        .parse()
+        .map_err(|_| CliError::Usage("invalid port".to_owned()))?;
+    let addr = format!("{}:{}", matches.value_of("host").unwrap_or_default(), port);
+    eprintln!("serving {} on http://{}", path.display(), addr);
+    Ok(todo::serve::serve(
+        path,
+        &addr,
+        matches.value_of("token"),
+        render,
+    )?)
+}
+
+#[cfg(not(feature = "serve"))]
+fn serve(_path: &std::path::Path, _matches: &ArgMatches, _render: &Render) -> Result<(), CliError> {
+    Err(CliError::Usage(
+        "todo was built without the serve feature".to_owned(),
+    ))
+}
+
+/// Lists the backups of the list at `path`, newest first, or restores the
+/// one selected by `--latest` or `--from` after showing how the list would
+/// change and asking for confirmation. Returns whether it was restored.
+fn recover(path: &Path, matches: &ArgMatches) -> Result<bool, CliError> {
+    let mut backups = backup::list(path);
+    backups.reverse();
+    if backups.is_empty() {
+        eprintln!("todo: there are no backups of this list yet");
+        return Err(CliError::Empty);
+    }
+    let number = match matches.value_of("from") {
+        Some(n) => Some(
+            n.parse::<usize>()
+                .ok()
+                .filter(|n| (1..=backups.len()).contains(n))
+                .ok_or_else(|| CliError::Usage(format!("no backup numbered '{}'", n)))?,
+        ),
+        None if matches.is_present("latest") => Some(1),
+        None => None,
+    };
+    let Some(number) = number else {
+        let today = date::today();
+        for (n, b) in backups.iter().enumerate() {
+            let counts = match TodoList::load_skipping_invalid(&b.path) {
+                Ok(list) => {
+                    let stats = list.stats(today);
+                    format!("{} pending, {} done", stats.pending, stats.done)
+                }
+                Err(e) => e.to_string(),
+            };
+            let taken = DateTime::<Local>::from(b.taken).format("%Y-%m-%d %H:%M:%S");
+            outln!("{:>2}. {}  {}", n + 1, taken, counts);
+        }
+        return Ok(false);
+    };
+
+    let backup = &backups[number - 1];
+    let current = fs::read_to_string(path).unwrap_or_default();
+    let restored = fs::read_to_string(&backup.path).map_err(TodoError::from)?;
+    if !print_changes(&current, &restored) {
+        outln!("the backup is the same as the list");
+        return Ok(false);
+    }
+    if is_dry_run() {
+        outln!("would restore the backup");
+        return Ok(false);
+    }
+    if !matches.is_present("yes") {
+        eprint!("restore this backup? [y/N] ");
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .map_err(TodoError::from)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            eprintln!("todo: nothing restored");
+            return Err(CliError::Empty);
+        }
+    }
+    backup::restore(path, backup)?;
+    outln!("restored the backup");
+    Ok(true)
+}
+
+/// Prints the lines `current` has and `new` doesn't with a `-`, then those
+/// `new` has and `current` doesn't with a `+`. Returns whether there were
+/// any.
+fn print_changes(current: &str, new: &str) -> bool {
+    let mut counts: HashMap<&str, isize> = HashMap::new();
+    for line in new.lines() {
+        *counts.entry(line).or_default() += 1;
+    }
+    for line in current.lines() {
+        *counts.entry(line).or_default() -= 1;
+    }
+    let mut changed = false;
+    for (sign, lines, more) in [("-", current, -1), ("+", new, 1)] {
+        for line in lines.lines() {
+            let count = counts.get_mut(line).unwrap();
+            if *count * more > 0 {
+                *count -= more;
+                outln!("{} {}", sign, line);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Merges the copy of the list given to `merge` into the list at `path`,
+/// against the copy both came from, asking which side to keep of each
+/// conflict unless `--resolve` says. Returns whether the list changed.
+fn merge(path: &Path, matches: &ArgMatches) -> Result<bool, CliError> {
+    let read = |file: &str| -> Result<String, CliError> {
+        fs::read_to_string(file)
+            .map_err(|e| CliError::Usage(format!("couldn't read '{}': {}", file, e)))
+    };
+    let base = read(matches.value_of("base").unwrap_or_default())?;
+    let theirs = read(matches.value_of("theirs").unwrap_or_default())?;
+    let current = match fs::read_to_string(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        content => content.map_err(TodoError::from)?,
+    };
+
+    let mut lines = Vec::new();
+    for merged in merge::merge(&base, &current, &theirs) {
+        let conflict = match merged {
+            Merged::Line(line) => {
+                lines.push(line);
+                continue;
+            }
+            Merged::Conflict(conflict) => conflict,
+        };
+        eprintln!("todo: conflict:");
+        if let Some(base) = &conflict.base {
+            eprintln!("  base:   {}", base);
+        }
+        eprintln!("  ours:   {}", conflict.ours);
+        eprintln!("  theirs: {}", conflict.theirs);
+        let choice = match matches.value_of("resolve") {
+            Some(choice) => choice.to_owned(),
+            None => {
+                eprint!("keep ours, theirs or both? [o/t/b] ");
+                let mut answer = String::new();
+                io::stdin()
+                    .read_line(&mut answer)
+                    .map_err(TodoError::from)?;
+                answer.trim().to_lowercase()
+            }
+        };
+        match choice.as_str() {
+            "o" | "ours" => lines.push(conflict.ours),
+            "t" | "theirs" => lines.push(conflict.theirs),
+            "b" | "both" => lines.extend([conflict.ours, conflict.theirs]),
+            _ => {
+                eprintln!("todo: nothing merged; --resolve chooses for every conflict");
+                return Err(CliError::Empty);
+            }
+        }
+    }
+
+    let merged: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    if !print_changes(&current, &merged) {
+        outln!("the merge is the same as the list");
+        return Ok(false);
+    }
+    if is_dry_run() {
+        outln!("would save the merge");
+        return Ok(false);
+    }
+    merge::write(path, &lines)?;
+    outln!("saved the merge");
+    Ok(true)
+}
+
+/// The user's editor: `$VISUAL`, else `$EDITOR`, else vi.
+fn editor() -> String {
+    std::env::var("VISUAL")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_owned())
+}
+
+/// Runs `editor` on `path`, failing unless it exits successfully, in which
+/// case `what` is left as it is.
+fn run_editor(editor: &str, path: &Path, what: &str) -> Result<(), CliError> {
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| CliError::Usage(format!("couldn't run the editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(CliError::Usage(format!(
+            "the editor exited with {}, leaving {} as it is",
+            status, what
+        )));
+    }
+    Ok(())
+}
+
+/// Lets the user edit `todo_list` as a `todo review` buffer in their
+/// editor, opening it again with the error on top until it reads, then
+/// applies the changes, unless it's left empty.
+fn review_in_editor(render: &Render, todo_list: &mut TodoList) -> Result<(), CliError> {
+    if is_dry_run() {
+        return Err(CliError::Usage(
+            "review can't be combined with --dry-run".to_owned(),
+        ));
+    }
+    let editor = editor();
+    let path = std::env::temp_dir().join(format!("todo-review-{}.txt", process::id()));
+    let mut buffer = review::buffer(todo_list, render);
+    let lines = loop {
+        fs::write(&path, &buffer).map_err(TodoError::from)?;
+        let edited = run_editor(&editor, &path, "the list")
+            .and_then(|()| Ok(fs::read_to_string(&path).map_err(TodoError::from)?));
+        let edited = match edited {
+            Ok(edited) => edited,
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+        match review::parse(todo_list, render, &edited) {
+            Ok(lines) => break lines,
+            Err(message) => {
+                let rest: Vec<&str> = edited
+                    .lines()
+                    .filter(|l| !l.starts_with("# error: "))
+                    .collect();
+                buffer = format!("# error: {}\n{}\n", message, rest.join("\n"));
+            }
+        }
+    };
+    let _ = fs::remove_file(&path);
+    if lines.is_empty() {
+        outln!("the buffer is empty, so the list was left as it is");
+        return Ok(());
+    }
+    Ok(review::apply(todo_list, &lines)?)
+}
+
+/// Lets the user write the tasks to split the task at `index` into in
+/// their editor, one a line, starting from its note. Lines starting with
+/// `# ` are left out.
+fn split_in_editor(
+    render: &Render,
+    todo_list: &TodoList,
+    index: usize,
+) -> Result<Vec<String>, CliError> {
+    if todo_list.is_done(index) == Some(true) {
+        return Err(TodoError::SplitDone { index }.into());
+    }
+    let path = std::env::temp_dir().join(format!("todo-split-{}.txt", process::id()));
+    let buffer = format!(
+        "{}\n\
+         # Write a line for each task to split task {} into. Lines starting\n\
+         # with '# ' are left out, and an empty buffer leaves the task as it is.\n",
+        todo_list.display_note(index).unwrap_or_default(),
+        render.shown_index(index)
+    );
+    fs::write(&path, buffer).map_err(TodoError::from)?;
+    let edited = run_editor(&editor(), &path, "the task")
+        .and_then(|()| Ok(fs::read_to_string(&path).map_err(TodoError::from)?));
+    let _ = fs::remove_file(&path);
+    Ok(edited?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("# ") && *line != "#")
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Opens the list at `path` in the user's editor, after backing it up, and
+/// reports the lines that aren't tasks afterwards, offering to edit again.
+/// The edit is recorded in the undo journal. Returns whether the file
+/// changed.
+fn edit_file(path: &Path) -> Result<bool, CliError> {
+    if is_dry_run() {
+        return Err(CliError::Usage(
+            "edit-file can't be combined with --dry-run".to_owned(),
+        ));
+    }
+    ensure_writable(path)?;
+    let editor = editor();
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let before = fs::read(&target).unwrap_or_default();
+    let previous = Fingerprint::of(&target);
+    let backup = backup::take(&target).map_err(|source| TodoError::Save {
+        path: path.to_owned(),
+        source,
+    })?;
+    loop {
+        run_editor(&editor, &target, "the file")?;
+        let todo_list = TodoList::load_skipping_invalid(&target)?;
+        let invalid: Vec<(usize, &str)> = todo_list.invalid_lines().collect();
+        if invalid.is_empty() {
+            break;
+        }
+        for (line, content) in &invalid {
+            eprintln!("todo: line {} isn't a task: {:?}", line, content);
+        }
+        eprint!("edit again, or keep the lines as they are? [E/k] ");
+        let mut answer = String::new();
+        let read = io::stdin()
+            .read_line(&mut answer)
+            .map_err(TodoError::from)?;
+        if read == 0 || !matches!(answer.trim(), "" | "e" | "E") {
+            eprintln!("todo: kept them; other commands need --skip-invalid until they're fixed");
+            break;
+        }
+    }
+    let changed = fs::read(&target).unwrap_or_default() != before;
+    if changed {
+        journal::record_save(&target, backup, previous, "edit-file");
+    }
+    Ok(changed)
+}
+
+/// Checks the file of the list at `path`, printing its problems, and
+/// repairs those it can if `fix`. Returns whether it repaired any and
+/// whether any are left.
+fn fsck(path: &Path, fix: bool) -> Result<(bool, bool), CliError> {
+    if Format::of(path) == Format::Org {
+        return Err(CliError::Usage("fsck doesn't check org files".to_owned()));
+    }
+    let content = match fs::read(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        content => content.map_err(TodoError::from)?,
+    };
+    let check = todo::fsck::check(&content);
+    let fixed = match (fix, is_dry_run()) {
+        (false, _) => "--fix repairs it",
+        (true, false) => "repaired",
+        (true, true) => "would be repaired",
+    };
+    for problem in &check.problems {
+        match problem.fixable {
+            true => outln!("line {}: {} ({})", problem.line, problem.message, fixed),
+            false => outln!("line {}: {}", problem.line, problem.message),
+        }
+    }
+    let fixable = check.problems.iter().filter(|p| p.fixable).count();
+    let left = check.problems.len() - if fix { fixable } else { 0 };
+    match (check.problems.len(), fix) {
+        (0, _) => outln!("no problems"),
+        (n, false) => outln!(
+            "{} problem{}, {} of which --fix repairs",
+            n,
+            if n == 1 { "" } else { "s" },
+            fixable
+        ),
+        (_, true) => outln!("{} repaired, {} left", fixable, left),
+    }
+    if fix && fixable > 0 {
+        backup::rewrite(path, &check.repaired, "fsck")?;
+    }
+    Ok((fix && fixable > 0 && !is_dry_run(), left > 0))
+}
+
+/// Tidies the list at `path`, printing what changed, and what would with
+/// `--dry-run`. Returns whether the list changed.
+fn tidy(path: &Path) -> Result<bool, CliError> {
+    if Format::of(path) == Format::Org {
+        return Err(CliError::Usage("tidy doesn't rewrite org files".to_owned()));
+    }
+    let content = match fs::read_to_string(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        content => content.map_err(TodoError::from)?,
+    };
+    let (tidied, changes) = todo::tidy::tidy(&content);
+    if tidied == content {
+        outln!("the list is tidy already");
+        return Ok(false);
+    }
+    if is_dry_run() {
+        print_changes(&content, &tidied);
+    }
+    out!("{}", changes);
+    backup::rewrite(path, tidied.as_bytes(), "tidy")?;
+    Ok(!is_dry_run())
+}
+
+fn dedupe(render: &Render, todo_list: &mut TodoList, dry_run: bool) -> Result<(), TodoError> {
+    let removed: Vec<(usize, String)> = if dry_run {
+        let duplicates = todo_list.duplicates();
+        duplicates
+            .into_iter()
+            .map(|i| (i, todo_list.display_note(i).unwrap_or_default()))
+            .collect()
+    } else {
+        todo_list.dedup()?
+    };
+
+    let (verb, remaining) = if dry_run {
+        ("would remove", todo_list.len() - removed.len())
+    } else {
+        ("removed", todo_list.len())
+    };
+    for (i, note) in &removed {
+        outln!("{} {}. {}", verb, render.shown_index(*i), note);
+    }
+    outln!(
+        "{} duplicate(s), {} task(s) remain",
+        removed.len(),
+        remaining
+    );
+    Ok(())
+}
+
+#[cfg(feature = "regex")]
+fn search_regex(
+    render: &Render,
+    todo_list: &TodoList,
+    out: &mut Pager,
+    pattern: &str,
+    scope: Scope,
+    words: Option<&Words>,
+    case: Case,
+) -> Result<usize, CliError> {
+    let pattern = regex::RegexBuilder::new(pattern)
+        .case_insensitive(case.ignores(pattern))
+        .build()
+        .map_err(|e| CliError::Usage(e.to_string()))?;
+    let find = |note: &str| {
+        pattern
+            .find_iter(note)
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    };
+    Ok(todo_list.print_search(render, out, find, scope, words, case)?)
+}
+
+#[cfg(not(feature = "regex"))]
+fn search_regex(
+    _render: &Render,
+    _todo_list: &TodoList,
+    _out: &mut Pager,
+    _pattern: &str,
+    _scope: Scope,
+    _words: Option<&Words>,
+    _case: Case,
+) -> Result<usize, CliError> {
+    Err(CliError::Usage(
+        "todo was built without the regex feature".to_owned(),
+    ))
+}
+
+/// Replaces `pattern` with `replacement` in the notes of the tasks in
+/// `scope`, after printing each change and asking, unless `yes`. Notes the
+/// change would break are left as they are, with a warning.
+#[cfg(feature = "regex")]
+fn sed(
+    render: &Render,
+    todo_list: &mut TodoList,
+    pattern: &str,
+    replacement: &str,
+    scope: Scope,
+    yes: bool,
+) -> Result<(), CliError> {
+    let pattern = regex::Regex::new(pattern).map_err(|e| CliError::Usage(e.to_string()))?;
+    let rewrites = todo_list.rewrites(scope, |note| {
+        pattern.replace_all(note, replacement).into_owned()
+    });
+    let mut count = 0;
+    for rewrite in &rewrites {
+        let index = render.shown_index(rewrite.index);
+        match rewrite.rejected {
+            Some(reason) => eprintln!(
+                "todo: leaving {}. {} as it is: {}",
+                index, rewrite.before, reason
+            ),
+            None => {
+                outln!("- {}. {}", index, rewrite.before);
+                outln!("+ {}. {}", index, rewrite.after);
+                count += 1;
+            }
+        }
+    }
+    if count == 0 {
+        if rewrites.is_empty() {
+            eprintln!("todo: no note matches");
+        }
+        return Err(CliError::Empty);
+    }
+    if !yes && !is_dry_run() {
+        eprint!("rewrite {} note(s)? [y/N] ", count);
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .map_err(TodoError::from)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            eprintln!("todo: nothing rewritten");
+            return Err(CliError::Empty);
+        }
+    }
+    let count = todo_list.rewrite(&rewrites)?;
+    if !is_dry_run() {
+        outln!("rewrote {} note(s)", count);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "regex"))]
+fn sed(
+    _render: &Render,
+    _todo_list: &mut TodoList,
+    _pattern: &str,
+    _replacement: &str,
+    _scope: Scope,
+    _yes: bool,
+) -> Result<(), CliError> {
+    Err(CliError::Usage(
+        "todo was built without the regex feature".to_owned(),
+    ))
+}
+
+/// The key named `name` among the `possible_values` of `ls --sort`.
+fn sort_key(name: &str) -> SortKey {
+    match name {
+        "priority" => SortKey::Priority,
+        "due" => SortKey::Due,
+        "age" => SortKey::Age,
+        "est" => SortKey::Estimate,
+        _ => SortKey::Alpha,
+    }
+}
+
+/// The error for a date that `date::parse_when` or `date::parse_since`
+/// doesn't read, with the `forms` it does.
+fn invalid_date(value: &str, forms: &str) -> CliError {
+    CliError::Usage(format!("invalid date '{}'; use {}", value, forms))
+}
+
+/// The filter of the `ls` options, with those not given taken from `view`.
+fn ls_filter(matches: &ArgMatches, view: &View) -> Result<Filter, CliError> {
+    let scope = if matches.is_present("list all") {
+        Scope::Any
+    } else if matches.is_present("done") {
+        Scope::Done
+    } else if view.all {
+        Scope::Any
+    } else if view.done {
+        Scope::Done
+    } else {
+        Scope::Pending
+    };
+    let due_by = match matches.value_of("due") {
+        Some(when) => Some(
+            date::parse_when(when, date::today())
+                .ok_or_else(|| invalid_date(when, date::WHEN_FORMS))?,
+        ),
+        None => None,
+    };
+    let created = |name| match matches.value_of(name) {
+        Some(when) => date::parse_since(when, date::today())
+            .map(Some)
+            .ok_or_else(|| invalid_date(when, date::SINCE_FORMS)),
+        None => Ok(None),
+    };
+    let count = |name: &str| match matches.value_of(name) {
+        Some(n) => n
+            .parse::<usize>()
+            .map(Some)
+            .map_err(|_| CliError::Usage(format!("invalid {} '{}'", name, n))),
+        None => Ok(None),
+    };
+    Ok(Filter {
+        reverse: matches.is_present("reverse") || view.reverse,
+        offset: count("offset")?.unwrap_or(0),
+        limit: count("limit")?.or(view.limit).filter(|&n| n > 0),
+        overdue: matches.is_present("overdue") || view.overdue,
+        ready: matches.is_present("ready") || view.ready,
+        hide_waiting: matches.is_present("hide waiting") || view.hide_waiting,
+        assignee: matches
+            .value_of("assignee")
+            .map(str::to_owned)
+            .or_else(|| view.assignee.clone()),
+        unassigned: matches.is_present("unassigned") || view.unassigned,
+        words: words(matches, view),
+        due_by,
+        created_since: created("since")?,
+        created_before: created("before")?,
+        include_undated: matches.is_present("include undated"),
+        case: case(matches),
+        sort: matches.value_of("sort").map(sort_key).or(view.sort),
+        ..Filter::new(scope)
+    })
+}
+
+/// Prints the tasks accepted by `filter` with their indices in the whole
+/// list, and a hint below them if hidden tasks leave gaps in the numbering.
+fn print_listing(
+    render: &Render,
+    out: &mut impl Write,
+    todo_list: &TodoList,
+    filter: &Filter,
+    plain: bool,
+) -> Result<(), CliError> {
+    todo_list.print_matching(render, out, filter)?;
+    print_more(render, out, todo_list, filter, plain)?;
+    if !plain && has_gaps(&todo_list.matching(filter, date::today())) {
+        writeln!(
+            out,
+            "{}",
+            render
+                .style(render.theme.muted)
+                .paint("(gaps are hidden tasks; commands take the numbers as shown)")
+        )
+        .map_err(TodoError::from)?;
+    }
+    Ok(())
+}
+
+/// Says how many tasks `--limit` left out after the listing.
+fn print_more(
+    render: &Render,
+    out: &mut impl Write,
+    todo_list: &TodoList,
+    filter: &Filter,
+    plain: bool,
+) -> Result<(), CliError> {
+    let Some(limit) = filter.limit else {
+        return Ok(());
+    };
+    let matching = todo_list.matching(filter, date::today()).len();
+    let more = matching.saturating_sub(filter.offset + limit);
+    if more > 0 && !plain {
+        let line = format!(
+            "{} and {} more (use --limit 0 for all)",
+            render.glyph("…", "..."),
+            more
+        );
+        writeln!(out, "{}", render.style(render.theme.muted).paint(line))
+            .map_err(TodoError::from)?;
+    }
+    Ok(())
+}
+
+/// Prints the completion bar across the terminal, or in 40 columns when
+/// its width is unknown.
+fn print_progress(
+    render: &Render,
+    out: &mut impl Write,
+    todo_list: &TodoList,
+) -> Result<(), CliError> {
+    let width = terminal_size::terminal_size().map_or(40, |(w, _)| w.0 as usize);
+    writeln!(
+        out,
+        "{}",
+        progress_bar(render, &todo_list.stats(date::today()), width)
+    )
+    .map_err(TodoError::from)?;
+    Ok(())
+}
+
+/// Looks up a global argument, which clap only records on the subcommand
+/// when it's given after it.
+fn global_value<'a>(args: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    args.subcommand()
+        .1
+        .and_then(|matches| matches.value_of(name))
+        .or_else(|| args.value_of(name))
+}
+
+/// Whether a global flag is given, before or after the subcommand.
+fn global_flag(args: &ArgMatches, name: &str) -> bool {
+    args.is_present(name)
+        || args
+            .subcommand()
+            .1
+            .is_some_and(|matches| matches.is_present(name))
+}
+
+/// Returns the file of the named list. The "default" list is `~/todo.txt`
+/// (see `paths::default_list`), any other lives in `todo/<name>.txt` in the
+/// user's data directory.
+fn list_path(name: &str) -> Result<PathBuf, CliError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(CliError::Usage(format!(
+            "invalid list name '{}': use letters, digits, '-' and '_'",
+            name
+        )));
+    }
+
+    if name == "default" {
+        return Ok(paths::default_list()?);
+    }
+    let dir = paths::lists_dir()?;
+    fs::create_dir_all(&dir).map_err(TodoError::from)?;
+    Ok(dir.join(format!("{}.txt", name)))
+}
+
+/// Prints every list with its task counts and modification time, marking
+/// the `current` default one.
+fn lists(render: &Render, current: &str, json: bool) -> Result<(), CliError> {
+    let mut names = vec!["default".to_owned()];
+    if let Ok(entries) = paths::lists_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "txt") {
+                // Archives, such as `work.done.txt`, aren't lists of their
+                // own, and nor are files whose names `--list` can't take,
+                // such as `my list.txt`.
+                if let Some(stem) = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .filter(|s| !s.ends_with(".done") && list_path(s).is_ok())
+                {
+                    names.push(stem.to_owned());
+                }
+            }
+        }
+    }
+    names[1..].sort();
+    names.dedup();
+
+    let mut rows = Vec::new();
+    for name in names {
+        let path = list_path(&name)?;
+        let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(modified) => DateTime::<Local>::from(modified),
+            Err(_) => continue,
+        };
+        let stats = TodoList::load(&path).ok().map(|l| l.stats(date::today()));
+        rows.push((name, stats, modified));
+    }
+
+    if json {
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|(name, stats, modified)| {
+                serde_json::json!({
+                    "name": name,
+                    "default": name == current,
+                    "valid": stats.is_some(),
+                    "pending": stats.as_ref().map(|s| s.pending),
+                    "done": stats.as_ref().map(|s| s.done),
+                    "modified": modified.to_rfc3339(),
+                })
+            })
+            .collect();
+        outln!("{}", serde_json::Value::Array(rows));
+        return Ok(());
+    }
+
+    for (name, stats, modified) in &rows {
+        let counts = match stats {
+            Some(s) => format!("{} pending, {} done", s.pending, s.done),
+            None => "invalid".to_owned(),
+        };
+        let line = format!(
+            "{:<16} {:<24} {}",
+            name,
+            counts,
+            modified.format("%Y-%m-%d %H:%M")
+        );
+        if name == current {
+            outln!(
+                "{} {}",
+                render.style(render.theme.done.bold()).paint("*"),
+                render.style(Style::new().bold()).paint(line)
+            );
+        } else {
+            outln!("  {}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Whether to print colors: as `--color` or `--no-color` say, else not with
+/// `NO_COLOR` set, else when stdout is a terminal.
+fn use_color(args: &ArgMatches) -> bool {
+    if global_flag(args, "no color") {
+        return false;
+    }
+    match global_value(args, "color") {
+        Some("always") => {
+            // Printed even if the console can't show them, e.g. for a pager.
+            enable_ansi();
+            true
+        }
+        Some("never") => false,
+        _ => {
+            std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && io::stdout().is_terminal()
+                && enable_ansi()
+        }
+    }
+}
+
+/// Whether the terminal shows escape sequences; the Windows console does
+/// only once asked to.
+fn enable_ansi() -> bool {
+    #[cfg(windows)]
+    {
+        ansi_term::enable_ansi_support().is_ok()
+    }
+    #[cfg(not(windows))]
+    {
+        true
+    }
+}
+
+/// How output looks, as the global options and `config` say.
+fn render(args: &ArgMatches, config: &Config) -> Result<Render, CliError> {
+    let color = use_color(args);
+    let width = match global_value(args, "width") {
+        Some(width) => width
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid width '{}'", width)))?,
+        None => terminal_size::terminal_size().map_or(0, |(w, _)| w.0 as usize),
+    };
+    let preset = global_value(args, "theme")
+        .or(global_flag(args, "colorblind").then_some("colorblind"))
+        .and_then(|s| s.parse().ok())
+        .or(config.theme)
+        .unwrap_or_default();
+    let symbols = global_value(args, "symbols")
+        .and_then(|s| s.parse().ok())
+        .or(config.symbols)
+        .unwrap_or_default();
+    Ok(Render {
+        color,
+        hyperlinks: color && config.hyperlinks.unwrap_or_else(todo::links::supported),
+        // The Linux console shows struck-through text as plain text.
+        strikethrough: !matches!(std::env::var("TERM").as_deref(), Ok("linux") | Ok("dumb")),
+        absolute_dates: global_flag(args, "absolute dates"),
+        width,
+        theme: config.theme(preset),
+        symbols: match symbols {
+            Symbols::Minimal if preset.shows_symbols() => Symbols::Unicode,
+            symbols => symbols,
+        },
+        ascii: global_flag(args, "ascii") || config.ascii,
+        zero_indexed: global_flag(args, "zero indexed") || config.zero_indexed,
+        ..Render::default()
+    })
+}
+
+fn run(args: &ArgMatches, config: &Config, render: &Render) -> Result<(), CliError> {
+    if let ("man", Some(matches)) = args.subcommand() {
+        let page = todo::man::render(&app());
+        match matches.value_of("output") {
+            Some(output) => fs::write(output, page).map_err(TodoError::from)?,
+            None => out!("{}", page),
+        }
+        return Ok(());
+    }
+    set_dry_run(global_flag(args, "dry run"));
+    set_read_only(global_flag(args, "read only"));
+    set_format(global_value(args, "backend").map(|format| match format {
+        "org" => Format::Org,
+        _ => Format::Markdown,
+    }));
+    let verbosity = args.occurrences_of("verbose")
+        + args
+            .subcommand()
+            .1
+            .map_or(0, |matches| matches.occurrences_of("verbose"));
+    let verbose = |level: u64, message: &dyn fmt::Display| {
+        if verbosity >= level {
+            eprintln!("todo: {}", message);
+        }
+    };
+    if let Some(path) = Config::path() {
+        let found = if path.exists() { "" } else { " (not found)" };
+        verbose(1, &format_args!("config {}{}", path.display(), found));
+    }
+    let source = choose_list(args, config);
+    if let ("lists", Some(matches)) = args.subcommand() {
+        return lists(render, source.name(), matches.is_present("json"));
+    }
+    if let ("log", Some(matches)) = args.subcommand() {
+        return log(config, matches);
+    }
+    if let ("init", Some(_)) = args.subcommand() {
+        return init();
+    }
+    let path = source_path(&source)?;
+    verbose(
+        1,
+        &format_args!("using {}, chosen by {}", path.display(), source),
+    );
+    let remote = paths::remote_url(&path);
+    if let ("path", Some(matches)) = args.subcommand() {
+        match remote {
+            Some(url) => outln!("{}", url),
+            None => outln!(
+                "{}",
+                std::path::absolute(&path)
+                    .map_err(TodoError::from)?
+                    .display()
+            ),
+        }
+        if matches.is_present("source") {
+            outln!("chosen by {}", source);
+        }
+        return Ok(());
+    }
+    if let ("serve", Some(matches)) = args.subcommand() {
+        if remote.is_some() {
+            return Err(CliError::Usage(
+                "serve can't serve a remote list".to_owned(),
+            ));
+        }
+        return serve(&path, matches, render);
+    }
+    let mut out = Pager::new(!plain_output(args) && !global_flag(args, "no pager"));
+    let result = match remote {
+        Some(url) => with_remote(url, config, &verbose, |path| {
+            run_locked(args, config, render, path, &verbose, &mut out, true)
+        }),
+        // A command that only reads doesn't make others wait, unless
+        // loading the list has habits to reset or tasks to archive; lists
+        // are replaced whole when they're saved, so it never reads half a
+        // save.
+        None if reads_only(args) && !needs_upkeep(&path, config) => {
+            verbose(1, &"reading the list without locking it");
+            run_locked(args, config, render, &path, &verbose, &mut out, false)
+        }
+        None => {
+            let lock = FileLock::acquire_unless_read_only(&path)?;
+            run_locked(
+                args,
+                config,
+                render,
+                &path,
+                &verbose,
+                &mut out,
+                lock.is_some(),
+            )
+        }
+    };
+    // The list is unlocked by now, so that other commands don't wait for
+    // the pager to be closed.
+    if let Ok(()) | Err(CliError::Empty) = result {
+        out.finish()?;
+    }
+    result
+}
+
+/// The commands that never change the list, whatever their options.
+const READS_ONLY: &[&str] = &[
+    "ls",
+    "view",
+    "views",
+    "search",
+    "show",
+    "yank",
+    "open",
+    "report",
+    "stats",
+    "remind",
+    "agenda",
+    "cal",
+    "today",
+    "done-today",
+];
+
+fn reads_only(args: &ArgMatches) -> bool {
+    args.subcommand_name()
+        .is_some_and(|command| READS_ONLY.contains(&command))
+}
+
+/// Whether loading the list at `path` would reset habits or archive tasks
+/// before the command runs, as `run_locked` does.
+fn needs_upkeep(path: &Path, config: &Config) -> bool {
+    let today = date::today();
+    let cutoff = config
+        .auto_archive_after()
+        .and_then(|span| span.before(today));
+    // A list that can't be read is left for loading it to report.
+    stream::needs_upkeep(path, today, cutoff).unwrap_or(true)
+}
+
+/// Whether output is for scripts, without colors, paging and the like.
+fn plain_output(args: &ArgMatches) -> bool {
+    global_flag(args, "print0")
+        || global_flag(args, "plain")
+        || global_value(args, "format").is_some()
+}
+
+/// Runs the command on the list at `path`, once it's `locked` unless the
+/// command only reads it, with the listings going to `out` as `render`
+/// and the output options of the command line have them.
+fn run_locked(
+    args: &ArgMatches,
+    config: &Config,
+    render: &Render,
+    path: &Path,
+    verbose: &dyn Fn(u64, &dyn fmt::Display),
+    out: &mut Pager,
+    locked: bool,
+) -> Result<(), CliError> {
+    let plain = plain_output(args);
+    let mut listing = Render {
+        plain,
+        template: match global_value(args, "format") {
+            Some(template) => Some(template.parse().map_err(CliError::Usage)?),
+            None => None,
+        },
+        print0: global_flag(args, "print0"),
+        ages: global_flag(args, "age").then(|| config.stale_days.unwrap_or(30)),
+        oneline: global_flag(args, "oneline"),
+        ..render.clone()
+    };
+
+    let skip_invalid = global_flag(args, "skip invalid") || config.skip_invalid;
+    let auto_push = || -> Result<(), CliError> {
+        match (&config.sync.push_cmd, config.sync.auto) {
+            (Some(command), Some(AutoSync::Push)) if !is_dry_run() => {
+                verbose(1, &"running the push command");
+                Ok(todo::sync::run(command, path)?)
+            }
+            _ => Ok(()),
+        }
+    };
+    // Runs the hook once for the command `verb` and the `changes` it saved,
+    // then pushes the list.
+    let changed = |verb: &str, changes: &[Activity]| -> Result<(), CliError> {
+        match &config.hooks.on_change {
+            Some(command) if !is_dry_run() && !global_flag(args, "no hooks") => {
+                verbose(1, &"running the change hook");
+                let timeout = config.hooks.timeout();
+                if let Err(e) = todo::hooks::run(render, command, path, verb, changes, timeout) {
+                    eprintln!("todo: warning: {}", e);
+                }
+            }
+            _ => {}
+        }
+        auto_push()
+    };
+
+    if let ("recover", Some(matches)) = args.subcommand() {
+        if recover(path, matches)? {
+            changed("recover", &[])?;
+        }
+        return Ok(());
+    }
+    if let ("archive", Some(matches)) = args.subcommand() {
+        if let ("purge", Some(matches)) = matches.subcommand() {
+            purge_archive(path, matches)?;
+        }
+        return Ok(());
+    }
+    if let ("fsck", Some(matches)) = args.subcommand() {
+        let (fixed, left) = fsck(path, matches.is_present("fix"))?;
+        if fixed {
+            changed("fsck", &[])?;
+        }
+        return if left { Err(CliError::Empty) } else { Ok(()) };
+    }
+    if let ("tidy", Some(_)) = args.subcommand() {
+        if tidy(path)? {
+            changed("tidy", &[])?;
+        }
+        return Ok(());
+    }
+    if let ("edit-file", Some(_)) = args.subcommand() {
+        if edit_file(path)? {
+            changed("edit-file", &[])?;
+        }
+        return Ok(());
+    }
+    if let ("undo-last", Some(_)) = args.subcommand() {
+        let (what, left) = journal::undo_last(path)?;
+        let verb = if is_dry_run() { "would undo" } else { "undo" };
+        outln!("{}: {} ({} more can be undone)", verb, change(&what), left);
+        return changed("undo", &[]);
+    }
+    if let ("redo", Some(_)) = args.subcommand() {
+        let verb = if is_dry_run() { "would redo" } else { "redo" };
+        outln!("{}: {}", verb, change(&journal::redo(path)?));
+        return changed("redo", &[]);
+    }
+    if let ("merge", Some(matches)) = args.subcommand() {
+        if merge(path, matches)? {
+            changed("merge", &[])?;
+        }
+        return Ok(());
+    }
+    if let ("sync", Some(_)) = args.subcommand() {
+        return sync(path, config, skip_invalid);
+    }
+    if let ("conflicts", Some(matches)) = args.subcommand() {
+        if conflicts(path, config, matches, skip_invalid)? {
+            changed("conflicts", &[])?;
+        }
+        return Ok(());
+    } else {
+        let copies = todo::conflicts::find(path);
+        if !copies.is_empty() {
+            eprintln!(
+                "todo: warning: a sync client left {} of this list after a conflict; \
+                 `todo conflicts` lists them",
+                match copies.len() {
+                    1 => "a copy".to_owned(),
+                    n => format!("{} copies", n),
+                }
+            );
+        }
+    }
+
+    if let (Some(task), ("", None)) = (args.value_of("task"), args.subcommand()) {
+        warn_if_command(task);
+    }
+
+    // Adding only touches the end of the file, so it doesn't load the list,
+    // unless loading it would reset habits or archive tasks first.
+    let added = match args.subcommand() {
+        ("add", Some(matches)) => matches.value_of("task").map(|task| new_note(task, matches)),
+        (_, None) => args.value_of("task").map(Cow::Borrowed),
+        _ => None,
+    }
+    .map(|task| assigned(task, config))
+    .transpose()?
+    .filter(|_| !skip_invalid && !is_dry_run());
+    if let Some(task) = added.filter(|_| !needs_upkeep(path, config)) {
+        let index = stream::append(path, &task)?;
+        let added = Activity::new("add", index, task.trim());
+        verbose(1, &"appended the task without reading the list");
+        verbose(2, &added);
+        let added = [added];
+        write_activity(config, &added);
+        changed("add", &added)?;
+        stream::print_matching(&listing, path, out, &Filter::new(Scope::Pending))?;
+        return Ok(());
+    }
+
+    let mut todo_list = if skip_invalid {
+        let todo_list = TodoList::load_skipping_invalid(path)?;
+        for (line, content) in todo_list.invalid_lines() {
+            eprintln!(
+                "todo: skipping line {}, which isn't a task: {:?}",
+                line, content
+            );
+        }
+        todo_list
+    } else {
+        TodoList::load(path)?
+    };
+    let skipped = todo_list.invalid_lines().count();
+    verbose(
+        1,
+        &format_args!(
+            "read {} task(s) from {} line(s)",
+            todo_list.len(),
+            todo_list.len() + skipped
+        ),
+    );
+    todo_list.defer_saves();
+    // `todo done-today` reads the list without ever writing it, and an
+    // unlocked list isn't written either.
+    let read_only =
+        todo_list.is_read_only() || !locked || args.subcommand_name() == Some("done-today");
+    let result = match read_only {
+        true => Ok(()),
+        false => reset_habits(&mut todo_list, path, verbose),
+    }
+    .and_then(|()| {
+        if todo_list.has_daily_tasks() {
+            listing.streaks = Some(streaks(&todo_list, path)?);
+        }
+        Ok(())
+    })
+    // `todo autoclean` archives on its own terms.
+    .and_then(|()| match config.auto_archive_after() {
+        Some(span) if !read_only && args.subcommand_name() != Some("autoclean") => {
+            autoclean(&mut todo_list, path, span, verbose).map(drop)
+        }
+        _ => Ok(()),
+    })
+    .and_then(|()| execute(args, config, &listing, path, &mut todo_list, out, plain));
+    // Whatever the invocation changed is written once, even if a later
+    // step failed.
+    let unsaved = todo_list.has_unsaved_changes();
+    todo_list.commit()?;
+    let activity = todo_list.take_activity();
+    for entry in &activity {
+        verbose(2, entry);
+    }
+    if is_dry_run() {
+        eprintln!("{}", dry_run_summary(&activity));
+    } else {
+        let saved = if unsaved {
+            "saved the list"
+        } else {
+            "nothing to save"
+        };
+        verbose(1, &saved);
+        write_activity(config, &activity);
+        if unsaved {
+            changed(args.subcommand_name().unwrap_or("add"), &activity)?;
+        }
+    }
+    result
+}
+
+/// Lists the conflict copies of the list at `path`, with how many of their
+/// tasks it doesn't have, or merges those of the one given to `--merge`.
+/// Returns whether the list changed.
+fn conflicts(
+    path: &Path,
+    config: &Config,
+    matches: &ArgMatches,
+    skip_invalid: bool,
+) -> Result<bool, CliError> {
+    let tasks_of = |copy: &Path| -> Result<Vec<(bool, String)>, CliError> {
+        let content = fs::read_to_string(copy).map_err(TodoError::from)?;
+        Ok(todo::import::markdown(&content))
+    };
+    let mut todo_list = if skip_invalid {
+        TodoList::load_skipping_invalid(path)?
+    } else {
+        TodoList::load(path)?
+    };
+    let copies = todo::conflicts::find(path);
+    let name = |copy: &Path| {
+        copy.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    };
+    let Some(merge) = matches.value_of("merge") else {
+        if copies.is_empty() {
+            eprintln!("todo: there are no conflict copies of this list");
+            return Err(CliError::Empty);
+        }
+        for copy in &copies {
+            let tasks = tasks_of(copy)?;
+            let total = tasks.len();
+            let missing = todo_list.import(tasks, true)?.len();
+            outln!(
+                "{}: {} tasks against {} in the list, {} of them not in it",
+                name(copy),
+                total,
+                todo_list.len(),
+                missing
+            );
+        }
+        return Ok(false);
+    };
+
+    let copy = copies
+        .iter()
+        .find(|copy| name(copy) == merge || copy.as_path() == Path::new(merge))
+        .ok_or_else(|| {
+            CliError::Usage(format!(
+                "'{}' isn't a conflict copy of this list; `todo conflicts` lists them",
+                merge
+            ))
+        })?;
+    let dry_run = is_dry_run();
+    let added = todo_list.import(tasks_of(copy)?, dry_run)?;
+    for (done, note) in &added {
+        let verb = if dry_run { "would add" } else { "added" };
+        outln!("{} - [{}] {}", verb, if *done { "x" } else { " " }, note);
+    }
+    if dry_run {
+        return Ok(false);
+    }
+    write_activity(config, &todo_list.take_activity());
+    let merged = copy.with_file_name(format!(".{}.merged", name(copy)));
+    rename_file(copy, &merged)?;
+    outln!(
+        "merged {} task(s) from {}, which is now {}",
+        added.len(),
+        name(copy),
+        name(&merged)
+    );
+    Ok(!added.is_empty())
+}
+
+/// Runs the configured pull command, then the push command.
+fn sync(path: &Path, config: &Config, skip_invalid: bool) -> Result<(), CliError> {
+    let sync = &config.sync;
+    if sync.pull_cmd.is_none() && sync.push_cmd.is_none() {
+        return Err(CliError::Usage(
+            "nothing to sync with; set pull_cmd or push_cmd in the config's [sync]".to_owned(),
+        ));
+    }
+    if is_dry_run() {
+        for command in sync.pull_cmd.iter().chain(&sync.push_cmd) {
+            outln!("would run {}", command);
+        }
+        return Ok(());
+    }
+    if let Some(command) = &sync.pull_cmd {
+        let changed = todo::sync::pull(command, path, skip_invalid)?;
+        outln!(
+            "pulled, {}",
+            if changed {
+                "the list changed"
+            } else {
+                "no changes"
+            }
+        );
+    }
+    if let Some(command) = &sync.push_cmd {
+        todo::sync::run(command, path)?;
+        outln!("pushed");
+    }
+    Ok(())
+}
+
+/// How the list was chosen, for `--verbose`.
+/// Chooses the list of the command line; see `paths::choose_list`.
+fn choose_list(args: &ArgMatches, config: &Config) -> ListSource {
+    paths::choose_list(
+        global_value(args, "list"),
+        global_flag(args, "global"),
+        config.default_list.as_deref(),
+    )
+}
+
+/// The file of the list chosen by `source`.
+fn source_path(source: &ListSource) -> Result<PathBuf, CliError> {
+    match source {
+        ListSource::Env(path) | ListSource::Local(path) => Ok(path.clone()),
+        source => list_path(source.name()),
+    }
+}
+
+/// Creates an empty list for the project of the current directory, in a
+/// `.todo.txt` file there, for the commands run in it or under it.
+fn init() -> Result<(), CliError> {
+    let dir = std::env::current_dir().map_err(TodoError::from)?;
+    if let Some(existing) = paths::LOCAL_LISTS
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+    {
+        return Err(CliError::Usage(format!(
+            "{} exists already",
+            existing.display()
+        )));
+    }
+    let path = dir.join(paths::LOCAL_LISTS[0]);
+    ensure_writable(&path)?;
+    if is_dry_run() {
+        outln!("would create {}", path.display());
+        return Ok(());
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(TodoError::from)?;
+    outln!("created {}", path.display());
+    Ok(())
+}
+
+/// Removes the tasks of the archive of the list at `path` that are older
+/// than `--older-than`, then all but the `--keep-last`, reporting how many
+/// went and how many are left.
+fn purge_archive(path: &Path, matches: &ArgMatches) -> Result<(), CliError> {
+    let archive_path = paths::archive_path(path);
+    if !archive_path.exists() {
+        outln!("there's no archive at {}", archive_path.display());
+        return Ok(());
+    }
+    let keep_last = match matches.value_of("keep last") {
+        Some(n) => Some(
+            n.parse::<usize>()
+                .map_err(|_| CliError::Usage(format!("invalid count '{}'", n)))?,
+        ),
+        None => None,
+    };
+    let mut archive = TodoList::load(&archive_path)?;
+    archive.defer_saves();
+    let mut removed = 0;
+    if let Some(older_than) = matches.value_of("older than") {
+        let cutoff = parse_span(older_than)?
+            .before(date::today())
+            .ok_or_else(|| CliError::Usage(format!("span '{}' is too long", older_than)))?;
+        removed += archive.cleanup_before(cutoff, false)?;
+    }
+    if let Some(n) = keep_last {
+        removed += archive.keep_last(n)?;
+    }
+    archive.commit()?;
+    outln!(
+        "{} {} archived task{}, {} kept",
+        if is_dry_run() {
+            "would remove"
+        } else {
+            "removed"
+        },
+        removed,
+        if removed == 1 { "" } else { "s" },
+        archive.len()
+    );
+    Ok(())
+}
+
+/// The streaks of the daily tasks of the list at `path`, from the list and
+/// its archive.
+fn streaks(todo_list: &TodoList, path: &Path) -> Result<Streaks, CliError> {
+    let archive_path = paths::archive_path(path);
+    let archive = if archive_path.exists() {
+        Some(TodoList::load(&archive_path)?)
+    } else {
+        None
+    };
+    Ok(todo_list.streaks(archive.as_ref(), date::today()))
+}
+
+fn parse_span(s: &str) -> Result<date::Span, CliError> {
+    date::Span::parse(s).ok_or_else(|| {
+        CliError::Usage(format!(
+            "invalid span '{}': use a number of days, weeks, months or years, such as 14d",
+            s
+        ))
+    })
+}
+
+/// Makes the habits of the list at `path` checked on an earlier day
+/// pending again, their completions kept in its archive.
+fn reset_habits(
+    todo_list: &mut TodoList,
+    path: &Path,
+    verbose: &dyn Fn(u64, &dyn fmt::Display),
+) -> Result<(), CliError> {
+    let count = todo_list.reset_habits(date::today(), &paths::archive_path(path))?;
+    if count > 0 {
+        verbose(
+            1,
+            &format_args!("reset {} habit(s) checked before today", count),
+        );
+    }
+    Ok(())
+}
+
+/// Moves the done tasks of the list at `path` completed longer than `span`
+/// ago to its archive, returning how many and where to.
+fn autoclean(
+    todo_list: &mut TodoList,
+    path: &Path,
+    span: date::Span,
+    verbose: &dyn Fn(u64, &dyn fmt::Display),
+) -> Result<(usize, PathBuf), CliError> {
+    let cutoff = span
+        .before(date::today())
+        .ok_or_else(|| CliError::Usage("the archiving span is too long".to_owned()))?;
+    let archive = paths::archive_path(path);
+    let count = todo_list.archive(cutoff, &archive)?;
+    if count > 0 {
+        verbose(
+            1,
+            &format_args!(
+                "archived {} task(s) checked before {} into {}",
+                count,
+                cutoff,
+                archive.display()
+            ),
+        );
+    }
+    Ok((count, archive))
+}
+
+/// Says that a dry run saved nothing, and how many tasks its operations
+/// would have changed, as in "would check 1 task, remove 12 tasks".
+fn dry_run_summary(entries: &[Activity]) -> String {
+    if entries.is_empty() {
+        return "todo: dry run, nothing was saved".to_owned();
+    }
+    format!(
+        "todo: dry run, nothing was saved; would {}",
+        todo::hooks::summary(entries)
+    )
+}
+
+fn activity_path(config: &Config) -> Result<PathBuf, TodoError> {
+    match &config.activity_log {
+        Some(path) => Ok(path.clone()),
+        None => activity::default_path(),
+    }
+}
+
+/// Appends saved operations to the activity log. They're saved already, so
+/// a log that can't be written is only warned about.
+fn write_activity(config: &Config, entries: &[Activity]) {
+    if entries.is_empty() {
+        return;
+    }
+    let written =
+        activity_path(config).and_then(|path| Ok(activity::append(&path, entries, date::now())?));
+    if let Err(e) = written {
+        eprintln!("todo: couldn't write the activity log: {}", e);
+    }
+}
+
+/// Prints the latest entries of the activity log, oldest first.
+fn log(config: &Config, matches: &ArgMatches) -> Result<(), CliError> {
+    let limit = matches.value_of("limit").unwrap_or_default();
+    let limit = limit
+        .parse()
+        .map_err(|_| CliError::Usage(format!("invalid limit '{}'", limit)))?;
+    let entries = activity::recent(&activity_path(config)?, limit)?;
+    if entries.is_empty() {
+        eprintln!("todo: nothing has been logged yet");
+        return Err(CliError::Empty);
+    }
+    for entry in entries {
+        outln!(
+            "{}  {:<7} {}. {}",
+            DateTime::<Local>::from(entry.at).format("%Y-%m-%d %H:%M:%S"),
+            entry.verb,
+            entry.index,
+            entry.note
+        );
+    }
+    Ok(())
+}
+
+/// A change from the undo journal, or "the last change" for one that was
+/// recorded without saying what it did.
+fn change(what: &str) -> &str {
+    if what.is_empty() {
+        "the last change"
+    } else {
+        what
+    }
+}
+
+/// Short names of subcommands, with the subcommands they stand for.
+const ALIASES: [(&str, &str); 3] = [("done", "check"), ("rm", "remove"), ("l", "ls")];
+
+/// Warns that a task added without `add` starts like an invocation of one
+/// of the aliased subcommands, as in `todo -- done` or `todo "rm 3"`.
+fn warn_if_command(task: &str) {
+    let first = task.split_whitespace().next().unwrap_or_default();
+    if let Some((alias, command)) = ALIASES
+        .iter()
+        .find(|(alias, command)| first == *alias || first == *command)
+    {
+        let name = if first == *alias { alias } else { command };
+        eprintln!(
+            "todo: added {:?} as a task, though `{}` is a command; \
+             `todo add` adds such tasks without this warning",
+            task, name
+        );
+    }
+}
+
+/// The note of the task given to `add`, squeezed if asked to.
+fn new_note<'a>(task: &'a str, matches: &ArgMatches) -> Cow<'a, str> {
+    if matches.is_present("squeeze") {
+        Cow::Owned(squeeze_whitespace(task))
+    } else {
+        Cow::Borrowed(task)
+    }
+}
+
+/// `note` assigned to the `default_assignee` of the config, unless it has
+/// an `assignee:` token of its own. A note that can't be added is an error
+/// before it's given one.
+fn assigned<'a>(note: Cow<'a, str>, config: &Config) -> Result<Cow<'a, str>, TodoError> {
+    clean_note(&note)?;
+    Ok(match &config.default_assignee {
+        Some(who) if !note.split(' ').any(|w| w.starts_with("assignee:")) => Cow::Owned(format!(
+            "{} assignee:{}",
+            note.trim_end(),
+            who.trim().replace(' ', "_")
+        )),
+        _ => note,
+    })
+}
+
+/// The view of the config named `name`.
+fn saved_view<'a>(config: &'a Config, name: &str) -> Result<&'a View, CliError> {
+    config.views.get(name).ok_or_else(|| {
+        let names: Vec<&str> = config.views.keys().map(|k| &k[..]).collect();
+        CliError::Usage(format!(
+            "no view named '{}' (available: {})",
+            name,
+            names.join(", ")
+        ))
+    })
+}
+
+/// Runs the command line against `todo_list`, loaded from `path`, with
+/// listings going to `out`.
+fn execute(
+    args: &ArgMatches,
+    config: &Config,
+    render: &Render,
+    path: &Path,
+    todo_list: &mut TodoList,
+    out: &mut Pager,
+    plain: bool,
+) -> Result<(), CliError> {
+    if let Some(task) = args.value_of("task") {
+        todo_list.add(&assigned(Cow::Borrowed(task), config)?)?;
+    }
+
+    match args.subcommand() {
+        ("add", Some(matches)) => todo_list.add(&assigned(
+            new_note(matches.value_of("task").unwrap_or_default(), matches),
+            config,
+        )?)?,
+        ("ls", Some(matches)) | ("view", Some(matches)) => {
+            let view = match matches.value_of("view") {
+                Some(name) => saved_view(config, name)?,
+                None => &View::default(),
+            };
+            let filter = ls_filter(matches, view)?;
+            if matches.is_present("count") {
+                outln!("{}", todo_list.matching(&filter, date::today()).len());
+                return Ok(());
+            }
+            if matches.is_present("group by") {
+                if plain {
+                    return Err(CliError::Usage(
+                        "--group-by can't be combined with --plain".to_owned(),
+                    ));
+                }
+                let key = match matches.value_of("group by") {
+                    Some("project") => GroupKey::Project,
+                    _ => GroupKey::Tag,
+                };
+                todo_list.print_grouped(
+                    render,
+                    out,
+                    &filter,
+                    key,
+                    matches.is_present("by count"),
+                )?;
+            } else if matches.is_present("renumbered") {
+                renumber::print(render, todo_list, out, &filter)?;
+                print_more(render, out, todo_list, &filter, plain)?;
+            } else {
+                print_listing(render, out, todo_list, &filter, plain)?;
+            }
+            let progress = matches.is_present("list all") || matches.is_present("progress");
+            if progress && !plain {
+                print_progress(render, out, todo_list)?;
+            }
+            return Ok(());
+        }
+        ("search", Some(matches)) => {
+            let pattern = matches.value_of("pattern").unwrap_or_default();
+            let scope = if matches.is_present("list all") {
+                Scope::Any
+            } else {
+                Scope::Pending
+            };
+            let case = case(matches);
+            let words = words(matches, &View::default());
+            let found = if matches.is_present("regex") {
+                search_regex(render, todo_list, out, pattern, scope, words.as_ref(), case)?
+            } else {
+                let find = |note: &str| find_with_case(note, pattern, case);
+                todo_list.print_search(render, out, find, scope, words.as_ref(), case)?
+            };
+            return match found {
+                0 => Err(CliError::Empty),
+                _ => Ok(()),
+            };
+        }
+        ("tag", Some(matches)) => {
+            if let ("rename", Some(matches)) = matches.subcommand() {
+                let name = |arg| {
+                    let name = matches.value_of(arg).unwrap_or_default();
+                    let name = name.strip_prefix('#').unwrap_or(name);
+                    if name.is_empty() || name.contains(char::is_whitespace) {
+                        return Err(CliError::Usage(format!("invalid tag '{}'", name)));
+                    }
+                    Ok(name)
+                };
+                let (old, new) = (name("old")?, name("new")?);
+                let count = todo_list.rename_tag(old, new)?;
+                if count == 0 {
+                    eprintln!("todo: no task has #{}", old);
+                    return Err(CliError::Empty);
+                }
+                let verb = if is_dry_run() {
+                    "would rename"
+                } else {
+                    "renamed"
+                };
+                outln!("{} #{} to #{} in {} task(s)", verb, old, new, count);
+            }
+            return Ok(());
+        }
+        ("sed", Some(matches)) => {
+            let scope = if matches.is_present("list all") {
+                Scope::Any
+            } else {
+                Scope::Pending
+            };
+            return sed(
+                render,
+                todo_list,
+                matches.value_of("pattern").unwrap_or_default(),
+                matches.value_of("replacement").unwrap_or_default(),
+                scope,
+                matches.is_present("yes"),
+            );
+        }
+        ("random", Some(matches)) => {
+            let seed = match matches.value_of("seed") {
+                Some(seed) => seed
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("invalid seed '{}'", seed)))?,
+                None => {
+                    let now = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default();
+                    now.as_nanos() as u64 ^ u64::from(process::id())
+                }
+            };
+            let filter = Filter {
+                words: words(matches, &View::default()),
+                case: case(matches),
+                ..Filter::new(Scope::Pending)
+            };
+            let Some(i) = todo_list.random(&filter, date::today(), seed) else {
+                outln!("no pending task to choose from");
+                return Err(CliError::Empty);
+            };
+            if matches.is_present("check") {
+                todo_list.check(i)?;
+            }
+            return Ok(todo_list.print_task(render, &mut Stdout, i)?);
+        }
+        ("next", Some(matches)) => {
+            return match todo_list.next(date::today()) {
+                Some(i) => {
+                    if matches.is_present("start") && todo_list.started() != Some(i) {
+                        start(render, todo_list, i)?;
+                    }
+                    Ok(todo_list.print_task(render, &mut Stdout, i)?)
+                }
+                None => {
+                    outln!("nothing to do{}", render.glyph(" 🎉", ""));
+                    Err(CliError::Empty)
+                }
+            };
+        }
+        ("show", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let details = todo_list.details(i)?;
+            if matches.is_present("json") {
+                outln!("{}", details.to_json(render));
+            } else {
+                show(render, &details);
+            }
+            return Ok(());
+        }
+        ("yank", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let mut text = todo_list.display_note(i).unwrap_or_default();
+            if matches.is_present("with index") {
+                text = format!("{}. {}", render.shown_index(i), text);
+            }
+            match todo::clipboard::copy(&text) {
+                Ok(()) => outln!("copied {:?}", text),
+                Err(e) => {
+                    eprintln!("todo: {}, so here it is instead", e);
+                    outln!("{}", text);
+                }
+            }
+            return Ok(());
+        }
+        ("open", Some(matches)) => return open_links(render, todo_list, matches),
+        ("attach", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let path = matches.value_of("path").unwrap_or_default();
+            if !paths::expand_home(path).exists() {
+                eprintln!("todo: {} doesn't exist, attaching it anyway", path);
+            }
+            todo_list.attach(i, path)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, i)?);
+        }
+        ("detach", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let attachment =
+                match matches.value_of("attachment") {
+                    Some(n) => Some(n.parse().map_err(|_| {
+                        CliError::Usage(format!("invalid attachment number '{}'", n))
+                    })?),
+                    None => None,
+                };
+            for path in todo_list.detach(i, attachment)? {
+                outln!("detached {}", path);
+            }
+            return Ok(());
+        }
+        ("item", Some(matches)) => return item(render, todo_list, matches),
+        ("report", Some(matches)) => return report(todo_list, matches),
+        ("append", Some(matches)) => {
+            let (i, text) = select_with_text(render, todo_list, matches)?;
+            todo_list.append_note(i, text)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, i)?);
+        }
+        ("dup", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let copy = if matches.is_present("end") {
+                todo_list.duplicate_to_end(i)?
+            } else {
+                todo_list.duplicate(i)?
+            };
+            return Ok(todo_list.print_task(render, &mut Stdout, copy)?);
+        }
+        ("split", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let parts: Vec<String> = match matches.values_of("into") {
+                Some(parts) => parts.map(str::to_owned).collect(),
+                None => split_in_editor(render, todo_list, i)?,
+            };
+            if parts.is_empty() {
+                outln!("the buffer is empty, so the task was left as it is");
+                return Ok(());
+            }
+            let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+            let inherit = !matches.is_present("bare");
+            for i in todo_list.split(i, &parts, inherit, matches.is_present("keep"))? {
+                todo_list.print_task(render, &mut Stdout, i)?;
+            }
+            return Ok(());
+        }
+        ("reopen", Some(matches)) if matches.is_present("from archive") => {
+            let archive_path = paths::archive_path(path);
+            if !archive_path.exists() {
+                eprintln!("todo: there's no archive at {}", archive_path.display());
+                return Err(CliError::Empty);
+            }
+            let archive = TodoList::load(&archive_path)?;
+            let Some(index) = matches.value_of("index") else {
+                archive.print_all(render, out)?;
+                return Ok(());
+            };
+            let index = index
+                .parse()
+                .map_err(|_| CliError::Usage(format!("invalid index '{}'", index)))?;
+            let copy = todo_list.reopen_from(&archive, index)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, copy)?);
+        }
+        ("reopen", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Done)?;
+            let copy = todo_list.reopen(i)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, copy)?);
+        }
+        ("sort", Some(matches)) => {
+            let key = sort_key(matches.value_of("by").unwrap_or_default());
+            let moved = todo_list.sort_by(key)?;
+            if moved == 0 {
+                outln!("the list is sorted by {} already", key.name());
+            } else {
+                let verb = if is_dry_run() { "would move" } else { "moved" };
+                outln!(
+                    "{} {} task(s) to sort the list by {}",
+                    verb,
+                    moved,
+                    key.name()
+                );
+            }
+            return Ok(());
+        }
+        ("join", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let other = matches.value_of("other").unwrap_or_default();
+            let other = resolve_selector(render, todo_list, other, Scope::Any, matches)?;
+            let separator = matches.value_of("separator").unwrap_or_default();
+            let i = todo_list.join(i, other, separator)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, i)?);
+        }
+        ("prepend", Some(matches)) => {
+            let (i, text) = select_with_text(render, todo_list, matches)?;
+            todo_list.prepend_note(i, text)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, i)?);
+        }
+        ("edit", Some(matches)) => {
+            let (i, text) = select_with_text(render, todo_list, matches)?;
+            todo_list.edit_note(i, text)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, i)?);
+        }
+        ("bump", Some(matches)) => return shift_priorities(render, todo_list, matches, true),
+        ("drop", Some(matches)) => return shift_priorities(render, todo_list, matches, false),
+        ("pop", Some(matches)) => {
+            let Some(i) = todo_list.last_pending() else {
+                return Err(CliError::Empty);
+            };
+            let note = todo_list.display_note(i).unwrap_or_default();
+            if matches.is_present("remove") {
+                todo_list.remove(i)?;
+            } else {
+                todo_list.check(i)?;
+            }
+            outln!("{}", note);
+            return Ok(());
+        }
+        ("start", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Pending)?;
+            start(render, todo_list, i)?;
+            return Ok(todo_list.print_task(render, &mut Stdout, i)?);
+        }
+        ("stop", Some(_)) => {
+            let (i, seconds) = todo_list.stop(date::now())?;
+            outln!(
+                "stopped {}. {} after {}",
+                render.shown_index(i),
+                todo_list.display_note(i).unwrap_or_default(),
+                date::format_duration(seconds)
+            );
+            return Ok(());
+        }
+        ("remind", Some(matches)) => return remind(render, todo_list, matches.value_of("within")),
+        ("autoclean", Some(matches)) => {
+            let span = match matches.value_of("after") {
+                Some(after) => Some(parse_span(after)?),
+                None => config.auto_archive_after(),
+            };
+            let span = span.ok_or_else(|| {
+                CliError::Usage(
+                    "autoclean needs --after, or auto_archive_after in the config".to_owned(),
+                )
+            })?;
+            let (count, archive) = autoclean(todo_list, path, span, &|_, _| {})?;
+            match count {
+                0 => outln!("nothing to archive"),
+                n => outln!(
+                    "archived {} task{} into {}",
+                    n,
+                    if n == 1 { "" } else { "s" },
+                    archive.display()
+                ),
+            }
+            return Ok(());
+        }
+        ("move", Some(matches)) => {
+            let i = select(render, todo_list, matches, Scope::Any)?;
+            let to = matches.value_of("to").unwrap_or_default();
+            let dest_path = list_path(to)?;
+            if dest_path == path {
+                return Err(CliError::Usage(format!(
+                    "task {} is already in '{}'",
+                    render.shown_index(i),
+                    to
+                )));
+            }
+            let note = todo_list.display_note(i).unwrap_or_default();
+            let mut dest = TodoList::load(&dest_path)?;
+            let new_index = todo_list.move_to(i, &mut dest)?;
+            outln!(
+                "moved to '{}' as {}. {}",
+                to,
+                render.shown_index(new_index),
+                note
+            );
+            return Ok(());
+        }
+        ("template", Some(matches)) => {
+            let name = match matches.value_of("name") {
+                Some(name) => name,
+                None => {
+                    for (name, lines) in &config.templates {
+                        outln!("{} ({} tasks)", name, lines.len());
+                    }
+                    return Ok(());
+                }
+            };
+            let lines = config.templates.get(name).ok_or_else(|| {
+                let names: Vec<&str> = config.templates.keys().map(|k| &k[..]).collect();
+                CliError::Usage(format!(
+                    "no template named '{}' (available: {})",
+                    name,
+                    names.join(", ")
+                ))
+            })?;
+            let today = date::today();
+            let added = todo_list.add_all(
+                lines
+                    .iter()
+                    .map(|l| todo::config::expand_template(l, today)),
+            )?;
+            outln!("added {} task(s) from '{}'", added, name);
+        }
+        ("views", _) => {
+            let width = config.views.keys().map(|k| k.len()).max().unwrap_or(0);
+            for (name, view) in &config.views {
+                outln!("{:width$}  {}", name, view.args().join(" "), width = width);
+            }
+            return Ok(());
+        }
+        ("import", Some(matches)) => {
+            return import(render, todo_list, matches);
+        }
+        ("export", Some(matches)) => {
+            let filter = Filter {
+                words: words(matches, &View::default()),
+                case: case(matches),
+                ..Filter::new(Scope::Any)
+            };
+            if let Some(output) = matches.value_of("output") {
+                // Fail before writing the file if the tasks can't be moved.
+                if matches.is_present("move") && todo_list.is_read_only() {
+                    let path = path.to_owned();
+                    return Err(TodoError::ReadOnly { path }.into());
+                }
+                return export(todo_list, &filter, output, matches);
+            }
+            out!(
+                "{}",
+                todo_list.to_ical(&filter, matches.is_present("due only"), date::now())
+            );
+            return Ok(());
+        }
+        ("stats", Some(matches)) if matches.is_present("time") => {
+            for (i, seconds) in todo_list.tracked(date::now())? {
+                outln!(
+                    "{:>8}  {}. {}",
+                    date::format_duration(seconds),
+                    render.shown_index(i),
+                    todo_list.display_note(i).unwrap_or_default()
+                );
+            }
+            return Ok(());
+        }
+        ("stats", Some(matches)) if matches.is_present("graph") => {
+            let days = matches.value_of("days").unwrap_or("30");
+            let days: u32 = days
+                .parse()
+                .ok()
+                .filter(|&d| d > 0)
+                .ok_or_else(|| CliError::Usage(format!("invalid number of days '{}'", days)))?;
+            let mut counts = todo_list.completions();
+            let archive_path = paths::archive_path(path);
+            if archive_path.exists() {
+                for (day, n) in TodoList::load(&archive_path)?.completions() {
+                    *counts.entry(day).or_insert(0) += n;
+                }
+            }
+            out!(
+                "{}",
+                todo::graph::render(render, &counts, date::today(), days)
+            );
+            return Ok(());
+        }
+        ("stats", Some(_)) => {
+            let stats = todo_list.stats(date::today());
+            outln!("pending: {}", stats.pending);
+            outln!("done: {}", stats.done);
+            outln!("overdue: {}", stats.overdue);
+            for (who, n) in todo_list.pending_by_assignee() {
+                match who {
+                    Some(who) => outln!("pending for {}: {}", who, n),
+                    None => outln!("pending unassigned: {}", n),
+                }
+            }
+            let workloads = todo_list.workloads(date::today());
+            if workloads != Workloads::default() {
+                outln!("estimated pending: {}", workloads.pending);
+                outln!("estimated done this week: {}", workloads.done_this_week);
+            }
+            for (habit, days) in streaks(todo_list, path)?.iter() {
+                outln!(
+                    "streak: {} day{} of {}",
+                    days,
+                    if days == 1 { "" } else { "s" },
+                    habit
+                );
+            }
+            return Ok(());
+        }
+        ("done-today", Some(matches)) => {
+            return done_today(render, todo_list, path, matches, plain)
+        }
+        ("agenda", Some(matches)) => {
+            let days = matches.value_of("days").unwrap_or("7");
+            let today = date::today();
+            let until = days
+                .parse()
+                .ok()
+                .and_then(|d| today.checked_add_days(chrono::Days::new(d)))
+                .ok_or_else(|| CliError::Usage(format!("invalid number of days '{}'", days)))?;
+            let buckets = todo_list.print_agenda(render, out, today, until)?;
+            let urgent = buckets
+                .iter()
+                .any(|(bucket, _)| *bucket != Bucket::Upcoming);
+            return if urgent { Ok(()) } else { Err(CliError::Empty) };
+        }
+        ("cal", Some(matches)) => {
+            let today = date::today();
+            let month = match matches.value_of("month") {
+                None => today.with_day(1).unwrap(),
+                Some(month) => date::parse_date(&format!("{}-01", month)).ok_or_else(|| {
+                    CliError::Usage(format!("invalid month '{}'; use YYYY-MM", month))
+                })?,
+            };
+            let end = month + Months::new(1);
+            let week_start = config.week_start.unwrap_or_else(WeekStart::from_locale);
+            let due = todo_list.due_between(month, end);
+            out!(
+                "{}",
+                todo::cal::render(render, &due, month, today, week_start)
+            );
+            return Ok(());
+        }
+        ("today", Some(matches)) => {
+            let words = words(matches, &View::default());
+            let shown = todo_list.print_today(render, out, words.as_ref(), case(matches))?;
+            return match shown {
+                0 => Err(CliError::Empty),
+                _ => Ok(()),
+            };
+        }
+        ("review", Some(_)) => review_in_editor(render, todo_list)?,
+        ("cleanup", Some(matches)) => match matches.value_of("older than") {
+            None => todo_list.cleanup()?,
+            Some(older_than) => {
+                let cutoff = parse_span(older_than)?
+                    .before(date::today())
+                    .ok_or_else(|| CliError::Usage(format!("span '{}' is too long", older_than)))?;
+                let undated = matches.is_present("include undated");
+                let count = todo_list.cleanup_before(cutoff, undated)?;
+                outln!(
+                    "{} {} task{} checked before {}",
+                    if is_dry_run() {
+                        "would remove"
+                    } else {
+                        "removed"
+                    },
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    cutoff
+                );
+                return Ok(());
+            }
+        },
+        ("clear", Some(_)) => todo_list.clear()?,
+        ("dedupe", Some(_)) => {
+            return Ok(dedupe(render, todo_list, is_dry_run())?);
+        }
+        (action, Some(matches)) => match action {
+            "remove" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                for d in todo_list.dependents(i)? {
+                    eprintln!(
+                        "todo: warning: {}. {} waited for it, and no longer does",
+                        render.shown_index(d),
+                        todo_list.display_note(d).unwrap_or_default()
+                    );
+                }
+                todo_list.remove(i)?
+            }
+            "wait" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                todo_list.wait(i, matches.value_of("for"))?
+            }
+            "assign" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                todo_list.assign(i, matches.value_of("who"))?
+            }
+            "block" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                let on = matches.value_of("on").unwrap_or_default();
+                let on = resolve_selector(render, todo_list, on, Scope::Any, matches)?;
+                todo_list.block(i, on)?
+            }
+            "check" => {
+                let i = if matches.is_present("renumbered") {
+                    let number = matches.value_of("index").unwrap_or_default();
+                    let number = number
+                        .parse()
+                        .map_err(|_| CliError::Usage(format!("invalid number '{}'", number)))?;
+                    renumber::resolve(todo_list, render.given_index(number))?
+                } else {
+                    select(render, todo_list, matches, Scope::Pending)?
+                };
+                let blocked = todo_list.blocked();
+                let len = todo_list.len();
+                todo_list.check(i)?;
+                if todo_list.len() > len {
+                    outln!(
+                        "added {}. {}",
+                        render.shown_index(todo_list.len()),
+                        todo_list.display_note(todo_list.len()).unwrap_or_default()
+                    );
+                }
+                let unblocked = todo_list.blocked();
+                for b in blocked.into_iter().filter(|b| !unblocked.contains(b)) {
+                    outln!(
+                        "unblocked {}. {}",
+                        render.shown_index(b),
+                        todo_list.display_note(b).unwrap_or_default()
+                    );
+                }
+                let open = todo_list
+                    .checklist(i)?
+                    .iter()
+                    .filter(|(done, _)| !done)
+                    .count();
+                if open > 0 && (matches.is_present("items") || confirm_items(open)?) {
+                    todo_list.check_items(i)?;
+                }
+            }
+            "undo" => {
+                let i = select(render, todo_list, matches, Scope::Done)?;
+                todo_list.undo(i)?
+            }
+            "pin" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                todo_list.pin(i)?
+            }
+            "top" => {
+                for (k, i) in ends_selection(render, todo_list, matches)?
+                    .into_iter()
+                    .enumerate()
+                {
+                    todo_list.move_task(i, k + 1)?;
+                }
+            }
+            "bottom" => {
+                let len = todo_list.len();
+                let indices = ends_selection(render, todo_list, matches)?;
+                for (k, i) in indices.into_iter().rev().enumerate() {
+                    todo_list.move_task(i, len - k)?;
+                }
+            }
+            "unpin" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                todo_list.unpin(i)?
+            }
+            "due" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                let due = matches.value_of("date").unwrap_or_default();
+                let due = date::parse_when(due, date::today())
+                    .ok_or_else(|| invalid_date(due, date::WHEN_FORMS))?;
+                todo_list.set_due(i, due)?
+            }
+            "snooze" => {
+                let i = select(render, todo_list, matches, Scope::Any)?;
+                let until = matches.value_of("until").unwrap_or_default();
+                let until = date::parse_when(until, date::today())
+                    .ok_or_else(|| invalid_date(until, date::WHEN_FORMS))?;
+                todo_list.snooze(i, until)?
+            }
+            _ => (),
+        },
         _ => (),
     };
 
-    todo_list.print_unchecked();
+    print_listing(render, out, todo_list, &Filter::new(Scope::Pending), plain)?;
+    Ok(())
 }
@@ -0,0 +1,311 @@
+//! The man page, written in roff from the CLI's own clap definition so that
+//! it lists the same subcommands and options as `--help`.
+
+use clap::{App, ArgSettings};
+
+/// Escapes text for roff: backslashes, dashes (which would otherwise be
+/// hyphens) and a leading `.` or `'`, which would start a request.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// The option and argument entries of `app`, in the order they were
+/// defined, as the tag and the text of a `.TP` paragraph.
+fn entries(app: &App) -> Vec<(String, String)> {
+    let p = &app.p;
+    let text = |help: Option<&str>, values: Option<&Vec<&str>>| {
+        let mut text = escape(help.unwrap_or_default());
+        if let Some(values) = values {
+            let values: Vec<String> = values
+                .iter()
+                .map(|v| format!("\\fB{}\\fR", escape(v)))
+                .collect();
+            text.push_str(&format!(" (one of {})", values.join(", ")));
+        }
+        text
+    };
+    let switch = |short: Option<char>, long: Option<&str>| {
+        let mut names = Vec::new();
+        if let Some(short) = short {
+            names.push(format!("\\fB\\-{}\\fR", short));
+        }
+        if let Some(long) = long {
+            names.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+        }
+        names.join(", ")
+    };
+
+    let mut entries: Vec<(usize, String, String)> = Vec::new();
+    for flag in p.flags.iter().filter(|f| !f.b.is_set(ArgSettings::Hidden)) {
+        entries.push((
+            flag.s.unified_ord,
+            switch(flag.s.short, flag.s.long),
+            text(flag.b.help, None),
+        ));
+    }
+    for opt in p.opts.iter().filter(|o| !o.b.is_set(ArgSettings::Hidden)) {
+        let value = opt
+            .v
+            .val_names
+            .as_ref()
+            .and_then(|names| names.values().next().copied())
+            .unwrap_or(opt.b.name);
+        entries.push((
+            opt.s.unified_ord,
+            format!(
+                "{} \\fI{}\\fR",
+                switch(opt.s.short, opt.s.long),
+                escape(value)
+            ),
+            text(opt.b.help, opt.v.possible_vals.as_ref()),
+        ));
+    }
+    entries.sort_by_key(|(order, _, _)| *order);
+    let positionals = p
+        .positionals
+        .values()
+        // The synopsis already names those without a description.
+        .filter(|a| !a.b.is_set(ArgSettings::Hidden) && a.b.help.is_some())
+        .map(|a| {
+            (
+                format!("\\fI{}\\fR", escape(a.b.name)),
+                text(a.b.help, a.v.possible_vals.as_ref()),
+            )
+        });
+    positionals
+        .chain(entries.into_iter().map(|(_, tag, text)| (tag, text)))
+        .collect()
+}
+
+fn push_entries(page: &mut String, entries: &[(String, String)]) {
+    for (tag, text) in entries {
+        page.push_str(&format!(".TP\n{}\n{}\n", tag, text));
+    }
+}
+
+/// The synopsis of a subcommand: its name and positional arguments.
+fn synopsis(app: &App) -> String {
+    let mut synopsis = format!("\\fB{}\\fR", escape(&app.p.meta.name));
+    for arg in app.p.positionals.values() {
+        if arg.b.is_set(ArgSettings::Required) {
+            synopsis.push_str(&format!(" \\fI{}\\fR", escape(arg.b.name)));
+        } else {
+            synopsis.push_str(&format!(" [\\fI{}\\fR]", escape(arg.b.name)));
+        }
+    }
+    synopsis
+}
+
+/// Adds the section of the command `sub`, then those of its own commands
+/// such as `archive purge`. `prefix` is the start of the synopsis, naming
+/// the commands it's under.
+fn push_command(page: &mut String, sub: &App, prefix: &str) {
+    page.push_str(&format!(".SS {}{}\n", prefix, synopsis(sub)));
+    page.push_str(&format!(
+        "{}\n",
+        escape(sub.p.meta.about.unwrap_or_default())
+    ));
+    let aliases: Vec<String> = sub
+        .p
+        .meta
+        .aliases
+        .iter()
+        .flatten()
+        .filter(|(_, visible)| *visible)
+        .map(|(alias, _)| format!("\\fB{}\\fR", escape(alias)))
+        .collect();
+    if !aliases.is_empty() {
+        page.push_str(&format!(".br\nAlso {}.\n", aliases.join(", ")));
+    }
+    let entries = entries(sub);
+    if !entries.is_empty() {
+        page.push_str(".RS\n");
+        push_entries(page, &entries);
+        page.push_str(".RE\n");
+    }
+    let prefix = format!("{}\\fB{}\\fR ", prefix, escape(&sub.p.meta.name));
+    for nested in sub
+        .p
+        .subcommands
+        .iter()
+        .filter(|s| !s.p.is_set(clap::AppSettings::Hidden))
+    {
+        push_command(page, nested, &prefix);
+    }
+}
+
+const FILE_FORMAT: &str = r#".SH FILE FORMAT
+A list is a text file with one task per line, \fB\- [ ] \fR\fInote\fR for a
+pending task and \fB\- [x] \fR\fInote\fR for a done one. Notes are kept as
+written; todo understands these parts of them:
+.TP
+\fB(A)\fR
+A todo.txt priority, from \fBA\fR to \fBZ\fR, at the start of the note.
+.TP
+\fIYYYY\-MM\-DD\fR
+A todo.txt creation date at the start of the note, after any priority.
+.TP
+\fB#\fR\fItag\fR, \fB+\fR\fIproject\fR
+Tags and projects, for \fBls \-\-tag\fR and \fBls \-\-group\-by\fR.
+.TP
+\fBdue:\fR\fIYYYY\-MM\-DD\fR
+The day the task is due.
+.TP
+\fBest:\fR\fIN\fR\fBh\fR, \fBest:\fR\fIN\fR\fBm\fR, \fBest:\fR\fIN\fR\fBp\fR
+An estimate in hours, minutes or story points, totaled by \fBstats\fR.
+.PP
+A line of two spaces and a task line right under a task, or under another
+of its items, is an item of the task's checklist.
+.PP
+The tokens \fBpin:\fR, \fBt:\fR (snoozed until), \fBstart:\fR, \fBspent:\fR,
+\fBdone:\fR, \fBid:\fR, \fBdep:\fR (the ids of the tasks it waits for)
+and \fBwait:\fR (who it waits on) are written by todo itself and hidden in
+listings.
+"#;
+
+const FILES: &str = r#".SH FILES
+.TP
+\fI~/todo.txt\fR
+The default list, unless \fBTODO_FILE\fR is set.
+.TP
+\fI~/.local/share/todo/\fR\fIname\fR\fI.txt\fR
+The named lists of \fB\-\-list\fR, in the data directory.
+.TP
+\fI~/.local/share/todo/activity.log\fR
+The activity log.
+.TP
+\fI~/.config/todo/config.toml\fR
+The configuration.
+.TP
+\fI.\fR\fIname\fR\fI.backups/\fR
+Backups of a list and the undo journal, next to the list.
+.SH ENVIRONMENT
+.TP
+\fBTODO_FILE\fR
+The file of the default list.
+.TP
+\fBNO_COLOR\fR
+Print without colors, when set to a non\-empty value.
+.TP
+\fBCOLORTERM\fR, \fBTERM\fR
+What colors the terminal can show.
+.TP
+\fBVISUAL\fR, \fBEDITOR\fR
+The editor of \fBedit\-file\fR.
+.TP
+\fBPAGER\fR
+The pager for listings taller than the terminal.
+.SH EXIT STATUS
+.TP
+\fB0\fR
+Success.
+.TP
+\fB1\fR
+The given index or note didn't select a task.
+.TP
+\fB2\fR
+Usage error.
+.TP
+\fB3\fR
+The list couldn't be read, parsed or saved.
+"#;
+
+/// Renders the man page of `app`, with a section per visible subcommand.
+pub fn render(app: &App) -> String {
+    let meta = &app.p.meta;
+    let name = escape(&meta.name);
+    let mut page = format!(
+        ".TH {} 1 \"\" \"{} {}\"\n",
+        name.to_uppercase(),
+        name,
+        escape(meta.version.unwrap_or_default())
+    );
+    page.push_str(&format!(
+        ".SH NAME\n{} \\- {}\n",
+        name,
+        escape(meta.about.unwrap_or_default())
+    ));
+    page.push_str(&format!(
+        ".SH SYNOPSIS\n{} [\\fIoptions\\fR]\n.br\n\\fB{}\\fR \\fIcommand\\fR [\\fIoptions\\fR] [\\fIargs\\fR]\n",
+        synopsis(app),
+        name
+    ));
+    page.push_str(".SH OPTIONS\nThese options can be given before or after any command.\n");
+    push_entries(&mut page, &entries(app));
+
+    page.push_str(".SH COMMANDS\n");
+    for sub in app
+        .p
+        .subcommands
+        .iter()
+        .filter(|s| !s.p.is_set(clap::AppSettings::Hidden))
+    {
+        push_command(&mut page, sub, "");
+    }
+    page.push_str(FILE_FORMAT);
+    page.push_str(FILES);
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{AppSettings, Arg, SubCommand};
+
+    #[test]
+    fn test_render() {
+        let app = App::new("todo")
+            .version("1.0")
+            .about("CLI Todo-List Tool")
+            .arg(Arg::with_name("task").index(1).help("A new task"))
+            .arg(
+                Arg::with_name("symbols")
+                    .long("symbols")
+                    .takes_value(true)
+                    .possible_values(&["unicode", "ascii"])
+                    .help("The status symbols"),
+            )
+            .arg(
+                Arg::with_name("dry run")
+                    .long("dry-run")
+                    .help("Save nothing"),
+            )
+            .subcommand(
+                SubCommand::with_name("check")
+                    .visible_alias("done")
+                    .about("Check a task")
+                    .arg(Arg::with_name("index").required(true).help(".5 is fine")),
+            )
+            .subcommand(
+                SubCommand::with_name("archive")
+                    .about("Manage the archive")
+                    .subcommand(SubCommand::with_name("purge").about("Trim the archive")),
+            )
+            .subcommand(SubCommand::with_name("man").setting(AppSettings::Hidden));
+        let page = render(&app);
+        assert!(page.starts_with(
+            ".TH TODO 1 \"\" \"todo 1.0\"\n.SH NAME\ntodo \\- CLI Todo\\-List Tool\n"
+        ));
+        assert!(page.contains(".SH SYNOPSIS\n\\fBtodo\\fR [\\fItask\\fR] [\\fIoptions\\fR]\n"));
+        assert!(page.contains(
+            ".TP\n\\fItask\\fR\nA new task\n\
+             .TP\n\\fB\\-\\-symbols\\fR \\fIsymbols\\fR\nThe status symbols (one of \\fBunicode\\fR, \\fBascii\\fR)\n\
+             .TP\n\\fB\\-\\-dry\\-run\\fR\nSave nothing\n"
+        ));
+        assert!(page.contains(
+            ".SS \\fBcheck\\fR \\fIindex\\fR\nCheck a task\n.br\nAlso \\fBdone\\fR.\n\
+             .RS\n.TP\n\\fIindex\\fR\n\\&.5 is fine\n.RE\n"
+        ));
+        assert!(page.contains(
+            ".SS \\fBarchive\\fR\nManage the archive\n\
+             .SS \\fBarchive\\fR \\fBpurge\\fR\nTrim the archive\n"
+        ));
+        assert!(!page.contains(".SS \\fBman"));
+        assert!(page.contains(".SH FILE FORMAT\n"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Line-level three-way merges of two diverged copies of a list against
+//! the copy both started from. Tasks are matched by their words (see
+//! `TaskData::text`), so a task checked, pinned or given a priority on one
+//! side is still the same task on the other.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{
+    backup, ensure_writable, is_dry_run, journal, replace_file, Fingerprint, Task, TodoError,
+};
+
+/// A task changed differently on both sides, or added on both with
+/// different lines, which only the user can settle.
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    /// The line in the common copy, unless both sides added the task.
+    pub base: Option<String>,
+    pub ours: String,
+    pub theirs: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Merged {
+    Line(String),
+    Conflict(Conflict),
+}
+
+/// A task's identity across the copies: its words, and which of the tasks
+/// with the same words it is. Lines that aren't tasks are matched as they
+/// are.
+type Key = (String, usize);
+
+fn keyed(content: &str) -> Vec<(Key, &str)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    content
+        .lines()
+        .map(|line| {
+            let text = match line.parse::<Task>() {
+                Ok(task) => task.data().text(),
+                // No task's words have a line break.
+                Err(_) => format!("\n{}", line),
+            };
+            let n = seen.entry(text.clone()).or_default();
+            *n += 1;
+            ((text, *n), line)
+        })
+        .collect()
+}
+
+/// Merges `ours` and `theirs`, both changed from `base`. A change on one
+/// side only is taken, including additions and removals, and a task removed
+/// on one side but changed on the other is kept as changed. The result is
+/// in the order of `ours`, followed by the tasks only `theirs` has.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Vec<Merged> {
+    let base: HashMap<Key, &str> = keyed(base).into_iter().collect();
+    let ours = keyed(ours);
+    let theirs = keyed(theirs);
+    let ours_lines: HashMap<&Key, &str> = ours.iter().map(|(k, l)| (k, *l)).collect();
+    let theirs_lines: HashMap<&Key, &str> = theirs.iter().map(|(k, l)| (k, *l)).collect();
+
+    let mut keys: Vec<&Key> = ours.iter().map(|(k, _)| k).collect();
+    let in_ours: HashSet<&Key> = keys.iter().copied().collect();
+    keys.extend(
+        theirs
+            .iter()
+            .map(|(k, _)| k)
+            .filter(|k| !in_ours.contains(k)),
+    );
+
+    let mut merged = Vec::new();
+    for key in keys {
+        let b = base.get(key).copied();
+        let o = ours_lines.get(key).copied();
+        let t = theirs_lines.get(key).copied();
+        let line = if o == t || b == t {
+            o
+        } else if b == o {
+            t
+        } else if b.is_some() && (o.is_none() || t.is_none()) {
+            o.or(t)
+        } else if let (Some(o), Some(t)) = (o, t) {
+            merged.push(Merged::Conflict(Conflict {
+                base: b.map(str::to_owned),
+                ours: o.to_owned(),
+                theirs: t.to_owned(),
+            }));
+            continue;
+        } else {
+            unreachable!("a side without the task agrees with the base or removed it")
+        };
+        if let Some(line) = line {
+            merged.push(Merged::Line(line.to_owned()));
+        }
+    }
+    merged
+}
+
+/// Replaces the list at `path` with `lines`, like a save: backed up first
+/// and recorded in the undo journal.
+pub fn write(path: &Path, lines: &[String]) -> Result<(), TodoError> {
+    ensure_writable(path)?;
+    if is_dry_run() {
+        return Ok(());
+    }
+    let save_error = |source| TodoError::Save {
+        path: path.to_owned(),
+        source,
+    };
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let previous = Fingerprint::of(&target);
+    let before = backup::take(&target).map_err(save_error)?;
+    replace_file(&target, |file| {
+        for line in lines {
+            writeln!(file, "{}", line)?;
+        }
+        io::Result::Ok(())
+    })
+    .map_err(save_error)?;
+    journal::record_save(&target, before, previous, "merge");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MILK: &str = "- [ ] buy milk";
+    const MILK_DONE: &str = "- [x] buy milk done:2024-07-01";
+    const MILK_URGENT: &str = "- [ ] (A) buy milk";
+    const MOM: &str = "- [ ] call mom";
+    const PLANTS: &str = "- [ ] water plants";
+    const ROSES: &str = "- [ ] water roses";
+
+    fn lines(lines: &[&str]) -> String {
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+
+    fn merged(base: &[&str], ours: &[&str], theirs: &[&str]) -> Vec<Merged> {
+        merge(&lines(base), &lines(ours), &lines(theirs))
+    }
+
+    fn clean(base: &[&str], ours: &[&str], theirs: &[&str]) -> Vec<String> {
+        merged(base, ours, theirs)
+            .into_iter()
+            .map(|m| match m {
+                Merged::Line(line) => line,
+                Merged::Conflict(c) => panic!("unexpected conflict {:?}", c),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_one_side_changes() {
+        let base = [MILK, MOM];
+        // Untouched on both sides.
+        assert_eq!(clean(&base, &base, &base), base);
+        // Added on one side.
+        assert_eq!(
+            clean(&base, &[MILK, MOM, PLANTS], &base),
+            [MILK, MOM, PLANTS]
+        );
+        assert_eq!(
+            clean(&base, &base, &[MILK, MOM, PLANTS]),
+            [MILK, MOM, PLANTS]
+        );
+        // Checked on one side.
+        assert_eq!(clean(&base, &[MILK_DONE, MOM], &base), [MILK_DONE, MOM]);
+        assert_eq!(clean(&base, &base, &[MILK_DONE, MOM]), [MILK_DONE, MOM]);
+        // Removed on one side.
+        assert_eq!(clean(&base, &[MOM], &base), [MOM]);
+        assert_eq!(clean(&base, &base, &[MOM]), [MOM]);
+        // Moved on one side keeps our order.
+        assert_eq!(clean(&base, &[MOM, MILK], &base), [MOM, MILK]);
+    }
+
+    #[test]
+    fn test_both_sides_change() {
+        let base = [MILK, MOM];
+        // Added on both sides, each kept, ours first.
+        assert_eq!(
+            clean(&base, &[MILK, MOM, PLANTS], &[MILK, MOM, ROSES]),
+            [MILK, MOM, PLANTS, ROSES]
+        );
+        // The same addition on both sides is kept once.
+        assert_eq!(
+            clean(&base, &[MILK, MOM, PLANTS], &[MILK, MOM, PLANTS]),
+            [MILK, MOM, PLANTS]
+        );
+        // Checked on both sides.
+        assert_eq!(
+            clean(&base, &[MILK_DONE, MOM], &[MILK_DONE, MOM]),
+            [MILK_DONE, MOM]
+        );
+        // Checked on one side, another task removed on the other.
+        assert_eq!(clean(&base, &[MILK_DONE, MOM], &[MILK]), [MILK_DONE]);
+        // Removed on both sides.
+        assert_eq!(clean(&base, &[MOM], &[MOM]), [MOM]);
+        // Removed on one side, checked on the other: kept as checked.
+        assert_eq!(clean(&base, &[MOM], &[MILK_DONE, MOM]), [MOM, MILK_DONE]);
+        assert_eq!(clean(&base, &[MILK_DONE, MOM], &[MOM]), [MILK_DONE, MOM]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let base = [MILK, MOM];
+        // Checked on one side, given a priority on the other.
+        assert_eq!(
+            merged(&base, &[MILK_DONE, MOM], &[MILK_URGENT, MOM]),
+            vec![
+                Merged::Conflict(Conflict {
+                    base: Some(MILK.to_owned()),
+                    ours: MILK_DONE.to_owned(),
+                    theirs: MILK_URGENT.to_owned(),
+                }),
+                Merged::Line(MOM.to_owned()),
+            ]
+        );
+        // Added on both sides with different lines.
+        assert_eq!(
+            merged(
+                &base,
+                &[MILK, MOM, PLANTS],
+                &[MILK, MOM, "- [ ] (B) water plants"]
+            ),
+            vec![
+                Merged::Line(MILK.to_owned()),
+                Merged::Line(MOM.to_owned()),
+                Merged::Conflict(Conflict {
+                    base: None,
+                    ours: PLANTS.to_owned(),
+                    theirs: "- [ ] (B) water plants".to_owned(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates_and_invalid_lines() {
+        // The second of two equal tasks is checked on one side only.
+        assert_eq!(
+            clean(&[MILK, MILK], &[MILK, MILK_DONE], &[MILK, MILK]),
+            [MILK, MILK_DONE]
+        );
+        assert_eq!(
+            clean(&[MILK, "# notes"], &[MILK], &[MILK, "# notes", MOM]),
+            [MILK, MOM]
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        fs::write(&path, lines(&[MILK])).unwrap();
+        write(&path, &[MILK_DONE.to_owned(), MOM.to_owned()]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), lines(&[MILK_DONE, MOM]));
+        assert_eq!(backup::list(&path).len(), 1);
+        assert_eq!(journal::len(&path).0, 1);
+    }
+}
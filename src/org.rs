@@ -0,0 +1,340 @@
+//! Lists kept in Emacs org files, where the `TODO` and `DONE` headlines
+//! are the tasks, at any level. Everything else is kept as it was: the
+//! lines under a task's headline, up to the next task, move with it, and
+//! the lines before the first task stay at the top. A `[#A]` cookie is the
+//! task's priority, and the `CLOSED:` and `DEADLINE:` dates of the planning
+//! line under its headline are its `done:` and `due:` tokens.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+
+use crate::{date, Task, TaskData};
+
+/// The planning keywords that stand for tokens, with the token and the
+/// brackets of their timestamps: inactive for `CLOSED`, active for
+/// `DEADLINE`.
+const PLANNED_TOKENS: [(&str, &str, (char, char)); 2] = [
+    ("CLOSED", "done", ('[', ']')),
+    ("DEADLINE", "due", ('<', '>')),
+];
+
+/// Where a task's headline sits in the outline, and what's under it.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Outline {
+    /// The number of stars of the headline.
+    pub(crate) level: usize,
+    /// The lines after the headline, such as `SCHEDULED:` lines, text and
+    /// other headlines, written back unchanged.
+    pub(crate) body: Vec<String>,
+}
+
+/// Splits a `** TODO title` headline into its level, whether it's `DONE`
+/// and its title.
+pub(crate) fn headline(line: &str) -> Option<(usize, bool, &str)> {
+    let level = line.bytes().take_while(|&b| b == b'*').count();
+    if level == 0 {
+        return None;
+    }
+    let rest = line[level..].strip_prefix(' ')?;
+    let (done, title) = if let Some(title) = rest.strip_prefix("TODO") {
+        (false, title)
+    } else {
+        (true, rest.strip_prefix("DONE")?)
+    };
+    match title.strip_prefix(' ') {
+        Some(title) => Some((level, done, title)),
+        None if title.is_empty() => Some((level, done, title)),
+        None => None,
+    }
+}
+
+/// Splits a planning line, such as `CLOSED: [2024-06-30 Sun] DEADLINE:
+/// <2024-07-03 Wed>`, into its keywords and timestamps, brackets included.
+fn planning(line: &str) -> Option<Vec<(&str, &str)>> {
+    let mut rest = line.trim_start();
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        let (keyword, after) = rest.split_once(':')?;
+        if !["CLOSED", "DEADLINE", "SCHEDULED"].contains(&keyword) {
+            return None;
+        }
+        let after = after.trim_start();
+        let close = match after.as_bytes().first()? {
+            b'[' => ']',
+            b'<' => '>',
+            _ => return None,
+        };
+        let end = after.find(close)? + 1;
+        entries.push((keyword, &after[..end]));
+        rest = after[end..].trim_start();
+    }
+    (!entries.is_empty()).then_some(entries)
+}
+
+/// The date of an org timestamp such as `[2024-06-30 Sun 10:12]`.
+fn timestamp_date(timestamp: &str) -> Option<NaiveDate> {
+    timestamp.get(1..11).and_then(date::parse_date)
+}
+
+/// The todo.txt priority of a title starting with a `[#A] ` cookie, and
+/// the rest of the title.
+fn priority_cookie(title: &str) -> Option<(char, &str)> {
+    let rest = title.strip_prefix("[#")?;
+    let priority = rest.chars().next().filter(char::is_ascii_uppercase)?;
+    Some((priority, rest[1..].strip_prefix("] ")?))
+}
+
+/// The planning line to write for the `dates` of a task's tokens, given
+/// the one it was read with: unchanged while it says the same, and without
+/// a line once there's nothing to plan.
+fn planning_line<'a>(
+    line: Option<&'a str>,
+    dates: &[(&str, Option<NaiveDate>)],
+) -> Option<Cow<'a, str>> {
+    let mut entries: Vec<(&str, Cow<str>)> = Vec::new();
+    let mut changed = false;
+    let timestamp = |keyword: &str, date: NaiveDate| {
+        let (_, _, (open, close)) = PLANNED_TOKENS.iter().find(|t| t.0 == keyword).unwrap();
+        Cow::Owned(format!("{}{}{}", open, date.format("%Y-%m-%d %a"), close))
+    };
+    for (keyword, written) in line.and_then(planning).unwrap_or_default() {
+        match dates.iter().find(|(k, _)| *k == keyword) {
+            Some((_, Some(date))) if timestamp_date(written) != Some(*date) => {
+                entries.push((keyword, timestamp(keyword, *date)));
+                changed = true;
+            }
+            Some((_, None)) => changed = true,
+            _ => entries.push((keyword, Cow::Borrowed(written))),
+        }
+    }
+    let mut added = 0;
+    for &(keyword, date) in dates {
+        if let Some(date) = date.filter(|_| entries.iter().all(|(k, _)| *k != keyword)) {
+            entries.insert(added, (keyword, timestamp(keyword, date)));
+            added += 1;
+            changed = true;
+        }
+    }
+    if !changed {
+        return line.map(Cow::Borrowed);
+    }
+    if entries.is_empty() {
+        return None;
+    }
+    let indent = line.map_or("", |line| &line[..line.len() - line.trim_start().len()]);
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|(keyword, timestamp)| format!("{}: {}", keyword, timestamp))
+        .collect();
+    Some(Cow::Owned(format!("{}{}", indent, entries.join(" "))))
+}
+
+/// Parses the lines of an org file into the lines before its first task
+/// and its tasks.
+pub(crate) fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> (Vec<String>, Vec<Task>) {
+    let mut preamble = Vec::new();
+    let mut tasks: Vec<Task> = Vec::new();
+    for line in lines {
+        let Some((level, done, title)) = headline(line) else {
+            let Some(data) = tasks.last_mut().map(Task::data_mut) else {
+                preamble.push(line.to_owned());
+                continue;
+            };
+            let Some(outline) = data.outline.as_deref_mut() else {
+                continue;
+            };
+            let planned = outline.body.is_empty().then(|| planning(line)).flatten();
+            outline.body.push(line.to_owned());
+            for (keyword, timestamp) in planned.unwrap_or_default() {
+                let token = PLANNED_TOKENS.iter().find(|t| t.0 == keyword);
+                if let (Some((_, key, _)), Some(date)) = (token, timestamp_date(timestamp)) {
+                    data.set_token(key, Some(&date.to_string()));
+                }
+            }
+            continue;
+        };
+        let note = match priority_cookie(title) {
+            Some((priority, rest)) => format!("({}) {}", priority, rest),
+            None => title.to_owned(),
+        };
+        let task_data = TaskData {
+            note,
+            outline: Some(Box::new(Outline {
+                level,
+                body: Vec::new(),
+            })),
+            checklist: Vec::new(),
+        };
+        tasks.push(if done {
+            Task::DoneTask(task_data)
+        } else {
+            Task::TodoTask(task_data)
+        });
+    }
+    (preamble, tasks)
+}
+
+/// Writes `task` as a headline with the lines under it, its priority as a
+/// cookie and its `done:` and `due:` dates on the planning line. A task
+/// added to the list is a top-level headline.
+pub(crate) fn write_task(file: &mut impl Write, task: &Task) -> io::Result<()> {
+    let mut data = task.data().clone();
+    let (level, body) = match data.outline.take() {
+        Some(outline) => (outline.level, outline.body),
+        None => (1, Vec::new()),
+    };
+    let dates: Vec<(&str, Option<NaiveDate>)> = PLANNED_TOKENS
+        .iter()
+        .map(|&(keyword, key, _)| {
+            let date = data.token(key).and_then(date::parse_date);
+            if date.is_some() {
+                data.set_token(key, None);
+            }
+            (keyword, date)
+        })
+        .collect();
+    let priority = data.priority();
+    data.set_priority(None);
+
+    let state = match task {
+        Task::DoneTask(_) => "DONE",
+        Task::TodoTask(_) => "TODO",
+    };
+    write!(file, "{} {}", "*".repeat(level), state)?;
+    if let Some(priority) = priority {
+        write!(file, " [#{}]", priority)?;
+    }
+    if !data.note.is_empty() {
+        write!(file, " {}", data.note)?;
+    }
+    writeln!(file)?;
+    let (planned, rest) = match body.split_first() {
+        Some((first, rest)) if planning(first).is_some() => (Some(&first[..]), rest),
+        _ => (None, &body[..]),
+    };
+    if let Some(line) = planning_line(planned, &dates) {
+        writeln!(file, "{}", line)?;
+    }
+    for line in rest {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headline() {
+        assert_eq!(headline("* TODO buy milk"), Some((1, false, "buy milk")));
+        assert_eq!(
+            headline("*** DONE call mom :family:"),
+            Some((3, true, "call mom :family:"))
+        );
+        assert_eq!(headline("** TODO"), Some((2, false, "")));
+        for line in [
+            "* Notes",
+            "TODO buy milk",
+            "*TODO buy milk",
+            "* TODOS",
+            "- [ ] x",
+        ] {
+            assert_eq!(headline(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let content = include_str!("../tests/fixtures/projects.org");
+        let (preamble, tasks) = parse(content.lines());
+        assert_eq!(preamble, ["#+TITLE: Projects", "", "* Home"]);
+        let notes: Vec<(bool, &str)> = tasks
+            .iter()
+            .map(|t| (matches!(t, Task::DoneTask(_)), &t.data().note[..]))
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                (false, "paint the fence"),
+                (true, "buy brushes done:2024-06-30"),
+                (false, "(A) call the plumber :urgent:"),
+                (false, "file taxes due:2025-04-15"),
+            ]
+        );
+        let outline = tasks[0].data().outline.as_ref().unwrap();
+        assert_eq!(outline.level, 2);
+        assert_eq!(
+            outline.body,
+            ["SCHEDULED: <2024-07-06 Sat>", "Two coats this time."]
+        );
+
+        let mut written = Vec::new();
+        for line in &preamble {
+            writeln!(written, "{}", line).unwrap();
+        }
+        for task in &tasks {
+            write_task(&mut written, task).unwrap();
+        }
+        assert_eq!(String::from_utf8(written).unwrap(), content);
+    }
+
+    #[test]
+    fn test_write_new_task() {
+        let mut written = Vec::new();
+        write_task(&mut written, &Task::new("buy milk")).unwrap();
+        assert_eq!(written, b"* TODO buy milk\n");
+
+        let mut written = Vec::new();
+        let task = Task::new("(B) call mom due:2024-07-03");
+        write_task(&mut written, &task).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "* TODO [#B] call mom\nDEADLINE: <2024-07-03 Wed>\n"
+        );
+    }
+
+    #[test]
+    fn test_planning() {
+        let write = |lines: &[&str], change: &dyn Fn(&mut TaskData)| {
+            let (_, mut tasks) = parse(lines.iter().copied());
+            change(tasks[0].data_mut());
+            let mut written = Vec::new();
+            write_task(&mut written, &tasks[0]).unwrap();
+            String::from_utf8(written).unwrap()
+        };
+        let lines = [
+            "** TODO pay rent",
+            "   SCHEDULED: <2024-07-01 Mon> DEADLINE: <2024-07-05 Fri 12:00>",
+            "Before noon.",
+        ];
+        assert_eq!(write(&lines, &|_| ()), lines.join("\n") + "\n");
+        assert_eq!(
+            write(&lines, &|data| data.set_token("done", Some("2024-07-02"))),
+            "** TODO pay rent\n   CLOSED: [2024-07-02 Tue] SCHEDULED: <2024-07-01 Mon> \
+             DEADLINE: <2024-07-05 Fri 12:00>\nBefore noon.\n"
+        );
+        assert_eq!(
+            write(&lines, &|data| data.set_token("due", Some("2024-07-08"))),
+            "** TODO pay rent\n   SCHEDULED: <2024-07-01 Mon> DEADLINE: <2024-07-08 Mon>\n\
+             Before noon.\n"
+        );
+        assert_eq!(
+            write(&lines[..2], &|data| data.set_token("due", None)),
+            "** TODO pay rent\n   SCHEDULED: <2024-07-01 Mon>\n"
+        );
+        assert_eq!(
+            write(
+                &["* DONE [#C] water plants", "CLOSED: [2024-06-30 Sun 09:15]"],
+                &|data| { data.set_token("done", None) }
+            ),
+            "* DONE [#C] water plants\n"
+        );
+        assert_eq!(
+            write(&["* TODO [#A]", "Not a planning line."], &|_| ()),
+            "* TODO [#A]\nNot a planning line.\n"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Paging of listings taller than the terminal, the way git does it.
+
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::TodoError;
+
+/// Collects output and, on `finish`, pipes it through `$PAGER` (`less -R`
+/// by default) when stdout is a terminal too short to show it all, or
+/// writes it to stdout otherwise.
+pub struct Pager {
+    buffer: Vec<u8>,
+    enabled: bool,
+}
+
+impl Pager {
+    pub fn new(enabled: bool) -> Self {
+        Pager {
+            buffer: Vec::new(),
+            enabled,
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<(), TodoError> {
+        let lines = self.buffer.iter().filter(|&&b| b == b'\n').count();
+        let height = terminal_size::terminal_size().map(|(_, h)| h.0 as usize);
+        let fits = height.is_none_or(|height| lines < height);
+        if !self.enabled || fits || !self.page() {
+            Stdout.write_all(&self.buffer)?;
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Shows the output in the pager, returning false if it couldn't be
+    /// started.
+    fn page(&self) -> bool {
+        let pager = env::var("PAGER").unwrap_or_else(|_| "less -R".to_owned());
+        let mut words = pager.split_whitespace();
+        let program = match words.next() {
+            Some(program) => program,
+            // An empty PAGER disables paging, as it does for git.
+            None => return false,
+        };
+        let mut child = match Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => return false,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            // Quitting the pager early closes the pipe; that's not an error.
+            let _ = stdin.write_all(&self.buffer);
+        }
+        let _ = child.wait();
+        true
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stdout, taking a closed pipe, as `| head` leaves it, for the end of the
+/// output rather than an error: whatever is written after that is dropped.
+pub struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        ignore_broken_pipe(io::stdout().write(buf)).map(|n| n.unwrap_or(buf.len()))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        ignore_broken_pipe(io::stdout().flush()).map(drop)
+    }
+}
+
+/// `Some` of the result, or `None` for a broken pipe.
+fn ignore_broken_pipe<T>(result: io::Result<T>) -> io::Result<Option<T>> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(None),
+        result => result.map(Some),
+    }
+}
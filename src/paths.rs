@@ -0,0 +1,342 @@
+//! Where lists are kept, for environments that may not have a home
+//! directory, such as containers and system services.
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::TodoError;
+
+/// The file of the default list: `$TODO_FILE` if it's set, otherwise
+/// `todo.txt` in the home directory, `todo/todo.txt` in the data directory
+/// or, failing both, `todo.txt` in the current directory.
+///
+/// On Windows the data directory (`%APPDATA%`) comes before the home
+/// directory, unless there's already a `todo.txt` in the latter.
+pub fn default_list() -> Result<PathBuf, TodoError> {
+    let home = dirs::home_dir();
+    let data_first = cfg!(windows)
+        && !home
+            .as_ref()
+            .is_some_and(|home| home.join("todo.txt").exists());
+    resolve_default_list(
+        env::var_os("TODO_FILE"),
+        home,
+        dirs::data_dir(),
+        env::current_dir().ok(),
+        data_first,
+    )
+}
+
+/// Where the list a command works on comes from; see `choose_list`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListSource {
+    /// `--list <name>`.
+    Flag(String),
+    /// The file in `$TODO_FILE`.
+    Env(PathBuf),
+    /// The list of the project the current directory is in.
+    Local(PathBuf),
+    /// `default_list` in the config.
+    Config(String),
+    /// The default list; see `default_list`.
+    Default,
+}
+
+impl ListSource {
+    /// The name of the list: that of a named list, otherwise `default`.
+    pub fn name(&self) -> &str {
+        match self {
+            ListSource::Flag(name) | ListSource::Config(name) => name,
+            _ => "default",
+        }
+    }
+}
+
+/// What chose the list, as in "chosen by --list work".
+impl fmt::Display for ListSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListSource::Flag(name) => write!(f, "--list {}", name),
+            ListSource::Env(_) => write!(f, "TODO_FILE"),
+            ListSource::Local(_) => write!(f, "the project the current directory is in"),
+            ListSource::Config(name) => write!(f, "default_list = {:?} in the config", name),
+            ListSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// The files that give a directory, and those under it, a list of its
+/// own, in the order they're looked for.
+pub const LOCAL_LISTS: &[&str] = &[".todo.txt", "todo.md"];
+
+/// The list of the project `dir` is in: the first of `LOCAL_LISTS` in
+/// `dir` or else in the nearest of its parents that has one, the way git
+/// finds `.git`.
+pub fn find_local(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| LOCAL_LISTS.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Chooses the list of a command, the first of: the named list of `--list`
+/// (`flag`), the file in `$TODO_FILE`, the list of the project the current
+/// directory is in unless `global`, the `default_list` of the config and
+/// the default list.
+pub fn choose_list(flag: Option<&str>, global: bool, default_list: Option<&str>) -> ListSource {
+    resolve_list(
+        flag,
+        global,
+        env::var_os("TODO_FILE"),
+        env::current_dir().ok().as_deref(),
+        default_list,
+    )
+}
+
+fn resolve_list(
+    flag: Option<&str>,
+    global: bool,
+    todo_file: Option<OsString>,
+    cwd: Option<&Path>,
+    default_list: Option<&str>,
+) -> ListSource {
+    if let Some(name) = flag {
+        return ListSource::Flag(name.to_owned());
+    }
+    if let Some(file) = todo_file.filter(|f| !f.is_empty()) {
+        return ListSource::Env(file.into());
+    }
+    if let Some(local) = cwd.filter(|_| !global).and_then(find_local) {
+        return ListSource::Local(local);
+    }
+    match default_list {
+        Some(name) => ListSource::Config(name.to_owned()),
+        None => ListSource::Default,
+    }
+}
+
+/// `path` with a leading `~` standing for the home directory expanded.
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some("") => "",
+        Some(rest) if rest.starts_with(['/', std::path::MAIN_SEPARATOR]) => &rest[1..],
+        _ => return PathBuf::from(path),
+    };
+    match dirs::home_dir() {
+        Some(home) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+fn resolve_default_list(
+    todo_file: Option<OsString>,
+    home: Option<PathBuf>,
+    data: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+    data_first: bool,
+) -> Result<PathBuf, TodoError> {
+    if let Some(file) = todo_file.filter(|f| !f.is_empty()) {
+        return Ok(file.into());
+    }
+    let home = home.map(|home| home.join("todo.txt"));
+    let data = data.map(|data| data.join("todo").join("todo.txt"));
+    let (first, second) = if data_first {
+        (data, home)
+    } else {
+        (home, data)
+    };
+    first
+        .or(second)
+        .or_else(|| cwd.map(|cwd| cwd.join("todo.txt")))
+        .ok_or(TodoError::NoHome {
+            hint: "TODO_FILE or HOME",
+        })
+}
+
+/// The URL of a list kept on a WebDAV server, if `location` is one: a
+/// `davs://` URL, or `dav://` without TLS.
+pub fn remote_url(location: &Path) -> Option<&str> {
+    location
+        .to_str()
+        .filter(|l| l.starts_with("davs://") || l.starts_with("dav://"))
+}
+
+/// The archive of the list at `list`, where `autoclean` moves old done
+/// tasks: the file next to it with `.done` before the extension, such as
+/// `todo.done.txt`.
+pub fn archive_path(list: &Path) -> PathBuf {
+    let mut name = list.file_stem().unwrap_or_default().to_owned();
+    name.push(".done");
+    if let Some(extension) = list.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    list.with_file_name(name)
+}
+
+/// The directory of the named lists, `todo` in the data directory.
+pub fn lists_dir() -> Result<PathBuf, TodoError> {
+    resolve_lists_dir(dirs::data_dir())
+}
+
+fn resolve_lists_dir(data: Option<PathBuf>) -> Result<PathBuf, TodoError> {
+    data.map(|data| data.join("todo")).ok_or(TodoError::NoHome {
+        hint: "XDG_DATA_HOME or HOME",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(
+        todo_file: Option<&str>,
+        home: Option<&str>,
+        data: Option<&str>,
+        cwd: Option<&str>,
+    ) -> Result<PathBuf, TodoError> {
+        resolve_default_list(
+            todo_file.map(OsString::from),
+            home.map(PathBuf::from),
+            data.map(PathBuf::from),
+            cwd.map(PathBuf::from),
+            false,
+        )
+    }
+
+    #[test]
+    fn test_default_list() {
+        let all = |todo_file| resolve(todo_file, Some("/home/me"), Some("/data"), Some("/cwd"));
+        assert_eq!(
+            all(Some("/srv/todo.txt")).unwrap(),
+            PathBuf::from("/srv/todo.txt")
+        );
+        assert_eq!(all(Some("")).unwrap(), PathBuf::from("/home/me/todo.txt"));
+        assert_eq!(all(None).unwrap(), PathBuf::from("/home/me/todo.txt"));
+        assert_eq!(
+            resolve(None, None, Some("/data"), Some("/cwd")).unwrap(),
+            PathBuf::from("/data/todo/todo.txt")
+        );
+        assert_eq!(
+            resolve(None, None, None, Some("/cwd")).unwrap(),
+            PathBuf::from("/cwd/todo.txt")
+        );
+        let err = resolve(None, None, None, None).unwrap_err();
+        assert!(err.to_string().contains("set TODO_FILE or HOME"));
+    }
+
+    #[test]
+    fn test_default_list_data_first() {
+        let resolve = |home: Option<&str>, data: Option<&str>| {
+            resolve_default_list(
+                None,
+                home.map(PathBuf::from),
+                data.map(PathBuf::from),
+                None,
+                true,
+            )
+            .unwrap()
+        };
+        let appdata = r"C:\Users\me\AppData\Roaming";
+        assert_eq!(
+            resolve(Some(r"C:\Users\me"), Some(appdata)),
+            PathBuf::from(appdata).join("todo").join("todo.txt")
+        );
+        assert_eq!(
+            resolve(Some(r"C:\Users\me"), None),
+            PathBuf::from(r"C:\Users\me").join("todo.txt")
+        );
+        // A drive letter and backslashes in TODO_FILE are taken as they are.
+        assert_eq!(
+            resolve_default_list(
+                Some(r"D:\lists\todo.txt".into()),
+                None,
+                Some(appdata.into()),
+                None,
+                true,
+            )
+            .unwrap(),
+            PathBuf::from(r"D:\lists\todo.txt")
+        );
+    }
+
+    #[test]
+    fn test_resolve_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        let nested = project.join("src/bin");
+        std::fs::create_dir_all(&nested).unwrap();
+        let resolve = |flag, global, todo_file: Option<&str>, config| {
+            resolve_list(
+                flag,
+                global,
+                todo_file.map(OsString::from),
+                Some(&nested),
+                config,
+            )
+        };
+        assert_eq!(resolve(None, false, None, None), ListSource::Default);
+        assert_eq!(
+            resolve(None, false, None, Some("work")),
+            ListSource::Config("work".to_owned())
+        );
+
+        std::fs::write(project.join("todo.md"), "").unwrap();
+        let local = ListSource::Local(project.join("todo.md"));
+        assert_eq!(resolve(None, false, None, Some("work")), local);
+        std::fs::write(project.join(".todo.txt"), "").unwrap();
+        let local = ListSource::Local(project.join(".todo.txt"));
+        assert_eq!(resolve(None, false, Some(""), None), local);
+        std::fs::create_dir(nested.join(".todo.txt")).unwrap();
+        assert_eq!(resolve(None, false, None, None), local);
+        assert_eq!(resolve(None, true, None, None), ListSource::Default);
+        assert_eq!(
+            resolve(None, false, Some("/srv/todo.txt"), None),
+            ListSource::Env(PathBuf::from("/srv/todo.txt"))
+        );
+        assert_eq!(
+            resolve(Some("home"), false, Some("/srv/todo.txt"), Some("work")),
+            ListSource::Flag("home".to_owned())
+        );
+        assert_eq!(local.name(), "default");
+        assert_eq!(ListSource::Config("work".to_owned()).name(), "work");
+    }
+
+    #[test]
+    fn test_remote_url() {
+        let url = "davs://cloud.example.com/dav/todo.txt";
+        assert_eq!(remote_url(Path::new(url)), Some(url));
+        assert_eq!(
+            remote_url(Path::new("dav://localhost:8080/todo.txt")),
+            Some("dav://localhost:8080/todo.txt")
+        );
+        assert_eq!(remote_url(Path::new("/home/me/todo.txt")), None);
+        assert_eq!(remote_url(Path::new("https://example.com/todo.txt")), None);
+    }
+
+    #[test]
+    fn test_archive_path() {
+        assert_eq!(
+            archive_path(Path::new("/home/me/todo.txt")),
+            PathBuf::from("/home/me/todo.done.txt")
+        );
+        assert_eq!(
+            archive_path(Path::new("work.org")),
+            PathBuf::from("work.done.org")
+        );
+        assert_eq!(archive_path(Path::new("todo")), PathBuf::from("todo.done"));
+    }
+
+    #[test]
+    fn test_lists_dir() {
+        assert_eq!(
+            resolve_lists_dir(Some("/data".into())).unwrap(),
+            PathBuf::from("/data/todo")
+        );
+        assert!(matches!(
+            resolve_lists_dir(None),
+            Err(TodoError::NoHome { .. })
+        ));
+    }
+}
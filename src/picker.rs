@@ -0,0 +1,349 @@
+//! The picker that commands given no index open at a terminal: the tasks as
+//! `todo ls` lists them, narrowed down as you type. Enter picks the task
+//! under the cursor, or the ones marked with tab for commands that take
+//! several, and escape picks none.
+
+use std::io::{self, Read, Write};
+
+use chrono::NaiveDate;
+use rustix::termios::{self, OptionalActions};
+
+use crate::{
+    date, index_width, listing_line, listing_order, Blockers, Filter, Render, Task, TodoList,
+};
+
+/// How many tasks are shown at a time, at most.
+const ROWS: usize = 10;
+
+/// A key read from the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Backspace,
+    /// Ctrl-U, which clears what was typed.
+    Clear,
+    Up,
+    Down,
+    Tab,
+    Enter,
+    Escape,
+}
+
+/// The keys of what one read from the terminal returned. Escape sequences
+/// other than the up and down arrows are skipped.
+fn keys(bytes: &[u8]) -> Vec<Key> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' => match chars.next_if(|&c| c == '[' || c == 'O') {
+                None => Key::Escape,
+                Some(_) => {
+                    let mut last = None;
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() || c == '~' {
+                            last = Some(c);
+                            break;
+                        }
+                    }
+                    match last {
+                        Some('A') => Key::Up,
+                        Some('B') => Key::Down,
+                        _ => continue,
+                    }
+                }
+            },
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x15' => Key::Clear,
+            '\x10' => Key::Up,
+            '\x0e' => Key::Down,
+            '\t' => Key::Tab,
+            '\r' | '\n' => Key::Enter,
+            '\x03' | '\x07' => Key::Escape,
+            c if c.is_control() => continue,
+            c => Key::Char(c),
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// The byte ranges of the characters of `text` that `query` matches in
+/// order, ignoring case unless the query has capitals, or none if it
+/// doesn't match.
+fn fuzzy(query: &str, text: &str) -> Option<Vec<(usize, usize)>> {
+    let ignore = !query.chars().any(char::is_uppercase);
+    let same = |a: char, b: char| {
+        if ignore {
+            a.to_lowercase().eq(b.to_lowercase())
+        } else {
+            a == b
+        }
+    };
+    let mut wanted = query.chars().filter(|c| !c.is_whitespace()).peekable();
+    let mut ranges = Vec::new();
+    for (i, c) in text.char_indices() {
+        match wanted.peek() {
+            Some(&w) if same(c, w) => {
+                wanted.next();
+                ranges.push((i, i + c.len_utf8()));
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+    wanted.peek().is_none().then_some(ranges)
+}
+
+struct Picker<'a> {
+    /// The tasks to pick from, with their indices, in listing order.
+    tasks: Vec<(usize, &'a Task)>,
+    multi: bool,
+    query: String,
+    /// The position of the cursor among the tasks that match.
+    cursor: usize,
+    marked: Vec<usize>,
+}
+
+impl Picker<'_> {
+    /// The tasks that match the query, as positions in `tasks`, with the
+    /// ranges of their notes that matched.
+    fn matching(&self) -> Vec<(usize, Vec<(usize, usize)>)> {
+        self.tasks
+            .iter()
+            .enumerate()
+            .filter_map(|(n, (_, t))| Some((n, fuzzy(&self.query, &t.data().to_string())?)))
+            .collect()
+    }
+
+    /// Handles `key`, returning the picked indices once it's Enter, or
+    /// none once it's Escape.
+    fn press(&mut self, key: Key) -> Option<Option<Vec<usize>>> {
+        let matching = self.matching();
+        let current = matching.get(self.cursor).map(|&(n, _)| self.tasks[n].0);
+        match key {
+            Key::Char(c) => {
+                self.query.push(c);
+                self.cursor = 0;
+            }
+            Key::Backspace => {
+                self.query.pop();
+                self.cursor = 0;
+            }
+            Key::Clear => {
+                self.query.clear();
+                self.cursor = 0;
+            }
+            Key::Up => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down => self.cursor = (self.cursor + 1).min(matching.len().saturating_sub(1)),
+            Key::Tab if self.multi => {
+                if let Some(index) = current {
+                    match self.marked.iter().position(|&m| m == index) {
+                        Some(m) => {
+                            self.marked.remove(m);
+                        }
+                        None => self.marked.push(index),
+                    }
+                    self.cursor = (self.cursor + 1).min(matching.len() - 1);
+                }
+            }
+            Key::Tab => {}
+            Key::Enter if !self.marked.is_empty() => {
+                let mut marked = self.marked.clone();
+                marked.sort_unstable();
+                return Some(Some(marked));
+            }
+            Key::Enter => return current.map(|index| Some(vec![index])),
+            Key::Escape => return Some(None),
+        }
+        None
+    }
+
+    /// The picker as it's drawn: the prompt with the query, and a row for
+    /// each listing line of the matching tasks around the cursor.
+    fn draw(
+        &self,
+        render: &Render,
+        today: NaiveDate,
+        blockers: &Blockers,
+    ) -> io::Result<(String, Vec<String>)> {
+        let prompt = format!("> {}", self.query);
+        let matching = self.matching();
+        let width = index_width(render, self.tasks.iter().map(|(i, _)| *i));
+        let skip = (self.cursor + 1).saturating_sub(ROWS);
+        let mut lines = Vec::new();
+        for (row, (n, ranges)) in matching.iter().enumerate().skip(skip).take(ROWS) {
+            let (index, task) = self.tasks[*n];
+            let line = listing_line(
+                render,
+                index,
+                width,
+                task,
+                blockers.block(task),
+                today,
+                ranges,
+            );
+            let pointer = if row == self.cursor { '>' } else { ' ' };
+            let mark = if self.marked.contains(&index) {
+                '+'
+            } else {
+                ' '
+            };
+            for (k, part) in line.lines().enumerate() {
+                match k {
+                    0 => lines.push(format!("{}{}{}", pointer, mark, part)),
+                    _ => lines.push(format!("  {}", part)),
+                }
+            }
+        }
+        Ok((prompt, lines))
+    }
+}
+
+/// Lets the user pick from the tasks of `list` accepted by `filter` at the
+/// terminal, drawing on stderr and reading stdin, which must both be
+/// terminals. Returns their indices, at most one unless `multi`, or none if
+/// the user gave up.
+pub fn pick(
+    render: &Render,
+    list: &TodoList,
+    filter: &Filter,
+    multi: bool,
+) -> io::Result<Option<Vec<usize>>> {
+    let today = date::today();
+    let tasks = listing_order(&list.list, filter, today)
+        .into_iter()
+        .map(|(i, t)| (i + 1, t))
+        .collect();
+    let mut picker = Picker {
+        tasks,
+        multi,
+        query: String::new(),
+        cursor: 0,
+        marked: Vec::new(),
+    };
+    let blockers = Blockers::of(&list.list);
+
+    let stdin = io::stdin();
+    let saved = termios::tcgetattr(&stdin)?;
+    let mut raw = saved.clone();
+    raw.make_raw();
+    termios::tcsetattr(&stdin, OptionalActions::Now, &raw)?;
+    let picked = run(render, &mut picker, today, &blockers);
+    termios::tcsetattr(&stdin, OptionalActions::Now, &saved)?;
+    let mut stderr = io::stderr();
+    write!(stderr, "\r\x1b[J")?;
+    stderr.flush()?;
+    picked
+}
+
+fn run(
+    render: &Render,
+    picker: &mut Picker,
+    today: NaiveDate,
+    blockers: &Blockers,
+) -> io::Result<Option<Vec<usize>>> {
+    let mut stderr = io::stderr();
+    let mut stdin = io::stdin().lock();
+    let mut buffer = [0; 64];
+    loop {
+        // Raw mode doesn't turn line feeds into new lines, so each line
+        // starts with a carriage return, and the cursor goes back to the
+        // end of the query after the lines are drawn.
+        let (prompt, lines) = picker.draw(render, today, blockers)?;
+        let mut frame = format!("\r\x1b[J{}", prompt);
+        for line in &lines {
+            frame.push_str("\r\n");
+            frame.push_str(line);
+        }
+        if !lines.is_empty() {
+            frame.push_str(&format!("\x1b[{}A", lines.len()));
+        }
+        frame.push_str(&format!("\r\x1b[{}C", crate::wrap::display_width(&prompt)));
+        stderr.write_all(frame.as_bytes())?;
+        stderr.flush()?;
+
+        let read = stdin.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        for key in keys(&buffer[..read]) {
+            if let Some(picked) = picker.press(key) {
+                return Ok(picked);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(
+            keys(b"ab\x7f\x1b[A\x1b[B\x1bOA\x1b[3~\t\r"),
+            [
+                Key::Char('a'),
+                Key::Char('b'),
+                Key::Backspace,
+                Key::Up,
+                Key::Down,
+                Key::Up,
+                Key::Tab,
+                Key::Enter,
+            ]
+        );
+        assert_eq!(keys(b"\x1b"), [Key::Escape]);
+        assert_eq!(
+            keys("é\x15\x03".as_bytes()),
+            [Key::Char('é'), Key::Clear, Key::Escape]
+        );
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        assert_eq!(fuzzy("", "buy milk"), Some(vec![]));
+        assert_eq!(fuzzy("bmk", "buy milk"), Some(vec![(0, 1), (4, 5), (7, 8)]));
+        assert_eq!(fuzzy("BM", "buy milk"), None);
+        assert_eq!(fuzzy("Bm", "Buy milk"), Some(vec![(0, 1), (4, 5)]));
+        assert_eq!(fuzzy("mb", "buy milk"), None);
+        assert_eq!(fuzzy("ée", "café crème"), Some(vec![(3, 5), (11, 12)]));
+    }
+
+    #[test]
+    fn test_press() {
+        let tasks: Vec<Task> = ["- [ ] buy milk", "- [ ] call mom", "- [ ] buy bread"]
+            .iter()
+            .map(|l| l.parse().unwrap())
+            .collect();
+        let picker = |multi| Picker {
+            tasks: tasks.iter().enumerate().map(|(i, t)| (i + 1, t)).collect(),
+            multi,
+            query: String::new(),
+            cursor: 0,
+            marked: Vec::new(),
+        };
+
+        let mut one = picker(false);
+        for key in keys(b"by") {
+            assert_eq!(one.press(key), None);
+        }
+        assert_eq!(one.matching().len(), 2);
+        assert_eq!(one.press(Key::Down), None);
+        assert_eq!(one.press(Key::Down), None);
+        assert_eq!(one.press(Key::Tab), None);
+        assert_eq!(one.press(Key::Enter), Some(Some(vec![3])));
+        assert_eq!(one.press(Key::Char('z')), None);
+        assert_eq!(one.press(Key::Enter), None);
+        assert_eq!(one.press(Key::Escape), Some(None));
+
+        let mut several = picker(true);
+        for key in [Key::Down, Key::Tab, Key::Up, Key::Up, Key::Tab] {
+            assert_eq!(several.press(key), None);
+        }
+        assert_eq!(several.marked, [2, 1]);
+        assert_eq!(several.press(Key::Enter), Some(Some(vec![1, 2])));
+    }
+}
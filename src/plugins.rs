@@ -0,0 +1,112 @@
+//! Subcommands of other programs: like git and cargo, `todo week` runs a
+//! `todo-week` executable found on `PATH`, unless todo has a `week`
+//! command of its own.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PREFIX: &str = "todo-";
+
+/// Whether `path` is a file that can be run.
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
+
+/// The file name of the executable of plugin `name`.
+fn file_name(name: &str) -> String {
+    format!("{}{}{}", PREFIX, name, env::consts::EXE_SUFFIX)
+}
+
+/// The executable of plugin `name` in the first of the directories of
+/// `path_var` (in the form of `PATH`) that has one.
+fn find_in(path_var: &OsStr, name: &str) -> Option<PathBuf> {
+    env::split_paths(path_var)
+        .map(|dir| dir.join(file_name(name)))
+        .find(|path| is_executable(path))
+}
+
+/// The names of the plugins in the directories of `path_var`.
+fn list_in(path_var: &OsStr) -> Vec<String> {
+    let mut names = BTreeSet::new();
+    for dir in env::split_paths(path_var) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let name = file_name
+                .to_str()
+                .and_then(|f| f.strip_prefix(PREFIX))
+                .and_then(|f| f.strip_suffix(env::consts::EXE_SUFFIX));
+            if let Some(name) = name.filter(|n| !n.is_empty()) {
+                if is_executable(&entry.path()) {
+                    names.insert(name.to_owned());
+                }
+            }
+        }
+    }
+    names.into_iter().collect()
+}
+
+/// The executable of plugin `name` on `PATH`, if there's one.
+pub fn find(name: &str) -> Option<PathBuf> {
+    // A name with a separator would be a path, not a plugin.
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    find_in(&env::var_os("PATH")?, name)
+}
+
+/// The names of the plugins on `PATH`, in order.
+pub fn list() -> Vec<String> {
+    env::var_os("PATH").map_or_else(Vec::new, |path| list_in(&path))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write(path: &Path, mode: u32) {
+        fs::write(path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_find_and_list() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        write(&first.path().join("todo-review"), 0o755);
+        write(&first.path().join("todo-notes.txt"), 0o644);
+        write(&second.path().join("todo-review"), 0o755);
+        write(&second.path().join("todo-week"), 0o755);
+        write(&second.path().join("other"), 0o755);
+        let path_var = env::join_paths([first.path(), second.path()]).unwrap();
+
+        assert_eq!(
+            find_in(&path_var, "review"),
+            Some(first.path().join("todo-review"))
+        );
+        assert_eq!(
+            find_in(&path_var, "week"),
+            Some(second.path().join("todo-week"))
+        );
+        assert_eq!(find_in(&path_var, "notes.txt"), None);
+        assert_eq!(find_in(&path_var, "missing"), None);
+        assert_eq!(list_in(&path_var), ["review", "week"]);
+    }
+}
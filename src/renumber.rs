@@ -0,0 +1,88 @@
+//! Dense `1..n` numbering of a filtered listing, remembered in a
+//! `.<name>.numbers` file next to the todo file so that the following
+//! commands can take tasks by it.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{date, filter_print_lines, is_read_only, Filter, Render, TodoError, TodoList};
+
+/// Prints the tasks accepted by `filter` numbered from 1 in listing order,
+/// and remembers which task each number stands for, unless lists are
+/// read-only.
+pub fn print(
+    render: &Render,
+    list: &TodoList,
+    out: &mut impl Write,
+    filter: &Filter,
+) -> Result<(), TodoError> {
+    let shown = filter_print_lines(render, out, &list.list, date::today(), true, filter)?;
+    if is_read_only() {
+        return Ok(());
+    }
+    let mut file = BufWriter::new(File::create(numbers_path(list.path))?);
+    for i in shown {
+        writeln!(file, "{:?}", list.list[i - 1])?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// The index of the task numbered `number` by the last `print`. Tasks are
+/// remembered by their line, so a task that changed since, such as one
+/// checked by number already, no longer has a number.
+pub fn resolve(list: &TodoList, number: usize) -> Result<usize, TodoError> {
+    let stale = || TodoError::StaleNumber { number };
+    let numbers = fs::read_to_string(numbers_path(list.path)).map_err(|_| stale())?;
+    let line = numbers
+        .lines()
+        .nth(number.wrapping_sub(1))
+        .ok_or_else(stale)?;
+    list.list
+        .iter()
+        .position(|t| format!("{:?}", t) == line)
+        .map(|i| i + 1)
+        .ok_or_else(stale)
+}
+
+fn numbers_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.numbers", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scope;
+
+    #[test]
+    fn test_renumbered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        fs::write(&path, "- [x] one\n- [ ] two\n- [x] three\n- [ ] four\n").unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        assert!(matches!(
+            resolve(&list, 1),
+            Err(TodoError::StaleNumber { number: 1 })
+        ));
+
+        print(
+            &Render::default(),
+            &list,
+            &mut Vec::new(),
+            &Filter::new(Scope::Pending),
+        )
+        .unwrap();
+        assert_eq!(resolve(&list, 1).unwrap(), 2);
+        assert_eq!(resolve(&list, 2).unwrap(), 4);
+        assert!(resolve(&list, 3).is_err());
+
+        list.check(2).unwrap();
+        assert!(resolve(&list, 1).is_err());
+        assert_eq!(resolve(&list, 2).unwrap(), 4);
+    }
+}
@@ -0,0 +1,152 @@
+//! The summary of a period shown by `todo report`.
+
+use chrono::NaiveDate;
+
+use crate::{Task, TodoList};
+
+/// Pending tasks by how long before the end of the report they were
+/// created.
+#[derive(Debug, Default, PartialEq)]
+pub struct Ages {
+    pub under_a_week: usize,
+    pub under_four_weeks: usize,
+    pub older: usize,
+    /// Tasks without a creation date.
+    pub unknown: usize,
+}
+
+impl Ages {
+    pub fn total(&self) -> usize {
+        self.under_a_week + self.under_four_weeks + self.older + self.unknown
+    }
+}
+
+/// What happened to a list from `since` to `until`, both included.
+/// Completion dates are the `done:` dates stamped by `todo check` and
+/// creation dates are todo.txt ones; tasks without the date in question
+/// are counted apart rather than guessed into or out of the period.
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    /// Tasks checked in the period.
+    pub completed: usize,
+    /// Checked tasks without a completion date.
+    pub completed_unknown: usize,
+    /// Tasks created in the period.
+    pub added: usize,
+    /// Tasks without a creation date.
+    pub added_unknown: usize,
+    pub pending: Ages,
+}
+
+impl Report {
+    pub fn new(list: &TodoList, since: NaiveDate, until: NaiveDate) -> Self {
+        let mut report = Report {
+            since,
+            until,
+            completed: 0,
+            completed_unknown: 0,
+            added: 0,
+            added_unknown: 0,
+            pending: Ages::default(),
+        };
+        let within = |date: NaiveDate| since <= date && date <= until;
+        for task in &list.list {
+            let created = task.data().created();
+            match created {
+                Some(date) if within(date) => report.added += 1,
+                Some(_) => (),
+                None => report.added_unknown += 1,
+            }
+            match task {
+                Task::DoneTask(data) => match data.completed() {
+                    Some(date) if within(date) => report.completed += 1,
+                    Some(_) => (),
+                    None => report.completed_unknown += 1,
+                },
+                Task::TodoTask(_) => {
+                    let ages = &mut report.pending;
+                    match created.map(|date| (until - date).num_days()) {
+                        None => ages.unknown += 1,
+                        Some(..=6) => ages.under_a_week += 1,
+                        Some(7..=27) => ages.under_four_weeks += 1,
+                        Some(_) => ages.older += 1,
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// The share of the tasks completed in the period among those and the
+    /// pending ones, from 0.0 to 1.0.
+    pub fn completion(&self) -> f64 {
+        match self.completed + self.pending.total() {
+            0 => 0.0,
+            total => self.completed as f64 / total as f64,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "since": self.since.to_string(),
+            "until": self.until.to_string(),
+            "completed": self.completed,
+            "completed_unknown_date": self.completed_unknown,
+            "added": self.added,
+            "added_unknown_date": self.added_unknown,
+            "pending": {
+                "under_a_week": self.pending.under_a_week,
+                "under_four_weeks": self.pending.under_four_weeks,
+                "older": self.pending.older,
+                "unknown_date": self.pending.unknown,
+            },
+            "completion": self.completion(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+
+    #[test]
+    fn test_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(
+            &path,
+            "- [x] (A) 2024-06-28 ship it done:2024-07-02\n\
+             - [x] old news done:2024-05-01\n\
+             - [x] from an old file\n\
+             - [ ] 2024-07-01 fresh\n\
+             - [ ] 2024-06-20 lingering\n\
+             - [ ] 2024-01-01 ancient\n\
+             - [ ] undated\n",
+        )
+        .unwrap();
+        let list = TodoList::load(&path).unwrap();
+        let date = |s| date::parse_date(s).unwrap();
+        let report = Report::new(&list, date("2024-07-01"), date("2024-07-03"));
+        assert_eq!(
+            report,
+            Report {
+                since: date("2024-07-01"),
+                until: date("2024-07-03"),
+                completed: 1,
+                completed_unknown: 1,
+                added: 1,
+                added_unknown: 3,
+                pending: Ages {
+                    under_a_week: 1,
+                    under_four_weeks: 1,
+                    older: 1,
+                    unknown: 1,
+                },
+            }
+        );
+        assert_eq!(report.completion(), 0.2);
+    }
+}
@@ -0,0 +1,190 @@
+//! `todo review`: the whole list as a buffer to edit, in the manner of
+//! `git rebase -i`. Each task is a line of its number and its line in the
+//! file; the edited buffer is applied as removals, edits, checks, new tasks
+//! and a new order.
+
+use crate::{parse_line, Render, Task, TodoError, TodoList};
+
+const HELP: &str = "\
+# Edit the tasks above, then save and quit to apply the changes:
+#  - delete a line to remove its task
+#  - edit the text after the checkbox to change the note
+#  - toggle [ ] and [x] to check or reopen the task
+#  - move lines to reorder the tasks
+#  - add a line without a number, such as `- [ ] call mom`, for a new task
+# Lines starting with '#' are ignored. Checklists stay as they are.
+# Leave the buffer empty, or quit the editor with an error, to cancel.
+";
+
+/// A line of an edited buffer: an existing task by its index, or a new one.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    index: Option<usize>,
+    done: bool,
+    note: String,
+}
+
+/// The buffer of `list`, its tasks numbered as `render` shows them, with
+/// the instructions at the end.
+pub fn buffer(list: &TodoList, render: &Render) -> String {
+    let mut buffer = String::new();
+    for (i, task) in list.list.iter().enumerate() {
+        buffer.push_str(&format!("{} {:?}\n", render.shown_index(i + 1), task));
+    }
+    buffer.push('\n');
+    buffer.push_str(HELP);
+    buffer
+}
+
+/// Reads an edited buffer for `list`, refusing lines that aren't tasks and
+/// numbers that aren't tasks of the list or that are given twice, with a
+/// message that names the line. Numbers are read as `render` shows them.
+pub fn parse(list: &TodoList, render: &Render, buffer: &str) -> Result<Vec<Line>, String> {
+    let mut lines: Vec<Line> = Vec::new();
+    for (n, text) in buffer.lines().enumerate().map(|(n, l)| (n + 1, l.trim())) {
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (index, task) = match digits {
+            0 => (None, text),
+            _ => (text[..digits].parse().ok(), text[digits..].trim_start()),
+        };
+        let (done, note) = parse_line(task).ok_or_else(|| {
+            format!(
+                "line {}: {:?} isn't a task; write `- [ ] note`, after its number for an \
+                 existing task",
+                n, text
+            )
+        })?;
+        let index = match index {
+            None if digits == 0 => None,
+            Some(number) if (1..=list.len()).contains(&render.given_index(number)) => {
+                let index = render.given_index(number);
+                if lines.iter().any(|l| l.index == Some(index)) {
+                    return Err(format!("line {}: task {} is listed twice", n, number));
+                }
+                Some(index)
+            }
+            _ => return Err(format!("line {}: there's no task {}", n, &text[..digits])),
+        };
+        lines.push(Line {
+            index,
+            done,
+            note: note.to_owned(),
+        });
+    }
+    Ok(lines)
+}
+
+/// Makes `list` what the edited buffer `lines` says: the tasks whose lines
+/// are gone are removed, the others are edited, checked or reopened and
+/// put in the order of their lines, and lines without a number are added.
+pub fn apply(list: &mut TodoList, lines: &[Line]) -> Result<(), TodoError> {
+    let notes: Vec<String> = list.list.iter().map(|t| t.data().note.clone()).collect();
+    let kept: Vec<usize> = lines.iter().filter_map(|l| l.index).collect();
+    for index in (1..=list.len()).rev().filter(|i| !kept.contains(i)) {
+        list.remove(index)?;
+    }
+    // The original index of the task at each position, or none for a new
+    // one.
+    let mut order: Vec<Option<usize>> = (1..=notes.len())
+        .filter(|i| kept.contains(i))
+        .map(Some)
+        .collect();
+    let mut edited = false;
+    for (p, line) in lines.iter().enumerate() {
+        match line.index {
+            Some(index) => {
+                let at = order.iter().position(|&o| o == Some(index)).unwrap();
+                if at != p {
+                    list.move_task(at + 1, p + 1)?;
+                    let o = order.remove(at);
+                    order.insert(p, o);
+                }
+                // Removing a task may have changed the note since, so only
+                // the user's edits are taken.
+                if line.note != notes[index - 1] {
+                    list.update(p, |t| t.data_mut().note = line.note.clone());
+                    list.log("edit", p + 1);
+                    edited = true;
+                }
+            }
+            None => {
+                list.insert(p, Task::new(&line.note));
+                list.log("add", p + 1);
+                order.insert(p, None);
+                edited = true;
+            }
+        }
+        match (list.is_done(p + 1) == Some(true), line.done) {
+            (false, true) => list.check(p + 1)?,
+            (true, false) => list.undo(p + 1)?,
+            _ => {}
+        }
+    }
+    if edited {
+        list.save()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_review() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        fs::write(
+            &path,
+            "- [ ] buy milk\n- [ ] call mom\n- [x] water plants\n- [ ] pay rent\n",
+        )
+        .unwrap();
+        let mut list = TodoList::load(&path).unwrap();
+        let buffer = buffer(&list, &Render::default());
+        assert!(buffer.starts_with(
+            "1 - [ ] buy milk\n2 - [ ] call mom\n3 - [x] water plants\n4 - [ ] pay rent\n\n#"
+        ));
+        assert_eq!(parse(&list, &Render::default(), &buffer).unwrap().len(), 4);
+
+        let edited = "4 - [ ] pay rent\n# a comment\n\n- [ ] file taxes\n\
+                      1 - [x] buy oat milk\n   3 - [ ] water plants\n";
+        let lines = parse(&list, &Render::default(), edited).unwrap();
+        assert_eq!(
+            lines[1],
+            Line {
+                index: None,
+                done: false,
+                note: "file taxes".to_owned()
+            }
+        );
+        apply(&mut list, &lines).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[..2], ["- [ ] pay rent", "- [ ] file taxes"]);
+        assert!(lines[2].starts_with("- [x] buy oat milk done:"));
+        assert_eq!(lines[3..], ["- [ ] water plants"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        fs::write(&path, "- [ ] buy milk\n- [ ] call mom\n").unwrap();
+        let list = TodoList::load(&path).unwrap();
+        let error = |buffer: &str| parse(&list, &Render::default(), buffer).unwrap_err();
+        assert_eq!(
+            error("1 - [ ] buy milk\nbuy bread\n"),
+            "line 2: \"buy bread\" isn't a task; write `- [ ] note`, after its number for \
+             an existing task"
+        );
+        assert_eq!(error("3 - [ ] buy milk\n"), "line 1: there's no task 3");
+        assert_eq!(
+            error("1 - [ ] buy milk\n1 - [x] buy milk\n"),
+            "line 2: task 1 is listed twice"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! A minimal HTTP JSON API over a todo file, for local integrations.
+//!
+//! * `GET /tasks` lists the tasks.
+//! * `POST /tasks` adds the task given as `{"note": "..."}`.
+//! * `POST /tasks/<index>/check` toggles a task between pending and done.
+//!
+//! The file is reloaded for every request and held under the same
+//! `FileLock` as the command line tool.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::lock::FileLock;
+use crate::{Render, TodoError, TodoList};
+
+/// The header carrying the shared secret when the server requires one.
+pub const TOKEN_HEADER: &str = "X-Todo-Token";
+
+#[derive(Deserialize)]
+struct NewTask {
+    note: String,
+}
+
+/// Serves `path` on `addr` until the process is stopped, answering only
+/// requests carrying `token` in the `TOKEN_HEADER` header if one is set.
+/// Task indices follow `render`, as they do on the command line.
+pub fn serve(
+    path: &Path,
+    addr: &str,
+    token: Option<&str>,
+    render: &Render,
+) -> Result<(), TodoError> {
+    let server = Server::http(addr).map_err(|e| TodoError::Io(std::io::Error::other(e)))?;
+    for mut request in server.incoming_requests() {
+        let authorized = token.is_none_or(|token| {
+            request
+                .headers()
+                .iter()
+                .any(|h| h.field.equiv(TOKEN_HEADER) && h.value.as_str() == token)
+        });
+        let mut body = String::new();
+        let (status, json) = if !authorized {
+            (401, error("missing or wrong token"))
+        } else if request.as_reader().read_to_string(&mut body).is_err() {
+            (400, error("unreadable body"))
+        } else {
+            handle(path, render, request.method(), request.url(), &body)
+        };
+
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = Response::from_string(json.to_string())
+            .with_status_code(status)
+            .with_header(content_type);
+        // The client going away isn't the server's problem.
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+fn error(message: &str) -> serde_json::Value {
+    serde_json::json!({ "error": message })
+}
+
+/// Answers one request against the current content of `path`.
+fn handle(
+    path: &Path,
+    render: &Render,
+    method: &Method,
+    url: &str,
+    body: &str,
+) -> (u16, serde_json::Value) {
+    let result = (|| {
+        let _lock = FileLock::acquire(path)?;
+        let mut list = TodoList::load(path)?;
+        let segments: Vec<&str> = url
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_matches('/')
+            .split('/')
+            .collect();
+        match (method, &segments[..]) {
+            (Method::Get, ["tasks"]) => Ok((200, list.to_json(render))),
+            (Method::Post, ["tasks"]) => match serde_json::from_str::<NewTask>(body) {
+                Ok(task) => {
+                    list.add(&task.note)?;
+                    Ok((201, list.to_json(render)))
+                }
+                Err(e) => Ok((400, error(&e.to_string()))),
+            },
+            (Method::Post, ["tasks", index, "check"]) => {
+                let index = match index.parse().map(|n| render.given_index(n)) {
+                    Ok(index) if index > 0 => index,
+                    _ => return Ok((404, error("no such task"))),
+                };
+                let toggled = match list.is_done(index) {
+                    Some(true) => list.undo(index),
+                    Some(false) => list.check(index),
+                    None => return Ok((404, error("no such task"))),
+                };
+                toggled.map(|_| (200, list.to_json(render)))
+            }
+            _ => Ok((404, error("not found"))),
+        }
+    })();
+    result.unwrap_or_else(|e: TodoError| (500, error(&e.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "- [ ] buy milk\n").unwrap();
+        let render = Render::default();
+
+        let (status, json) = handle(&path, &render, &Method::Get, "/tasks", "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            json,
+            serde_json::json!([{"index": 1, "done": false, "note": "buy milk"}])
+        );
+
+        let (status, json) = handle(
+            &path,
+            &render,
+            &Method::Post,
+            "/tasks",
+            r#"{"note": "call mom"}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(json[1]["note"], "call mom");
+
+        std::fs::write(
+            &path,
+            "- [ ] buy milk\n- [ ] call mom\n- [ ] edited elsewhere\n",
+        )
+        .unwrap();
+        let (status, json) = handle(&path, &render, &Method::Post, "/tasks/3/check", "");
+        assert_eq!(status, 200);
+        assert_eq!(json[2]["done"], true);
+        let (_, json) = handle(&path, &render, &Method::Post, "/tasks/3/check", "");
+        assert_eq!(json[2]["done"], false);
+
+        assert_eq!(
+            handle(&path, &render, &Method::Post, "/tasks/0/check", "").0,
+            404
+        );
+        assert_eq!(
+            handle(&path, &render, &Method::Post, "/tasks/9/check", "").0,
+            404
+        );
+        assert_eq!(
+            handle(&path, &render, &Method::Post, "/tasks", "milk").0,
+            400
+        );
+        assert_eq!(handle(&path, &render, &Method::Delete, "/tasks", "").0, 404);
+    }
+}
@@ -0,0 +1,190 @@
+//! The output of `todo status` for status bars: the pending and overdue
+//! counts, as plain text or with tmux's `#[...]` style markup, waybar's
+//! JSON or i3blocks' lines.
+
+use chrono::NaiveDate;
+
+use crate::{Render, Stats, Task, TodoList};
+
+/// The tmux style of the overdue count, and the one going back to the
+/// status bar's own after it.
+const OVERDUE: &str = "#[fg=red,bold]";
+const DEFAULT: &str = "#[default]";
+
+/// The segment for `stats`: `3 pending, 1 overdue`, leaving out the
+/// overdue count when there are none. With `tmux`, the overdue count is
+/// wrapped in `#[fg=red,bold]` and `#[default]`. Past `max_width` columns
+/// the text is cut, ending in an ellipsis; the markup isn't counted.
+pub fn segment(render: &Render, stats: &Stats, tmux: bool, max_width: Option<usize>) -> String {
+    let mut parts = vec![(format!("{} pending", stats.pending), false)];
+    if stats.overdue > 0 {
+        parts.push((", ".to_owned(), false));
+        parts.push((format!("{} overdue", stats.overdue), true));
+    }
+    let width: usize = parts.iter().map(|(text, _)| text.chars().count()).sum();
+    if let Some(max) = max_width.filter(|&max| max < width) {
+        let ellipsis = render.glyph("…", ".");
+        let mut left = max.saturating_sub(1);
+        for (text, _) in &mut parts {
+            let kept: String = text.chars().take(left).collect();
+            left -= kept.chars().count();
+            *text = kept;
+        }
+        parts.retain(|(text, _)| !text.is_empty());
+        if max > 0 {
+            parts.push((ellipsis.to_owned(), false));
+        }
+    }
+
+    let mut segment = String::new();
+    for (text, overdue) in parts {
+        if tmux && overdue {
+            segment.push_str(&format!("{}{}{}", OVERDUE, text, DEFAULT));
+        } else {
+            segment.push_str(&text);
+        }
+    }
+    segment
+}
+
+/// What the bars show of a list: its counts and the first pending notes.
+pub struct Summary {
+    pub stats: Stats,
+    /// The notes of the first pending tasks, in file order.
+    pub notes: Vec<String>,
+    /// How many pending tasks `notes` leaves out.
+    pub more: usize,
+}
+
+impl Summary {
+    /// The summary of `list`, with the notes of up to `tasks` pending tasks.
+    pub fn of(list: &TodoList, today: NaiveDate, tasks: usize) -> Summary {
+        let stats = list.stats(today);
+        let notes: Vec<String> = list
+            .list
+            .iter()
+            .filter(|task| matches!(task, Task::TodoTask(_)))
+            .take(tasks)
+            .map(|task| task.data().to_string())
+            .collect();
+        let more = stats.pending - notes.len();
+        Summary { stats, notes, more }
+    }
+
+    /// The CSS class of the bar's module: `overdue`, `pending`, or `empty`
+    /// when nothing is pending.
+    fn class(&self) -> &'static str {
+        if self.stats.overdue > 0 {
+            "overdue"
+        } else if self.stats.pending > 0 {
+            "pending"
+        } else {
+            "empty"
+        }
+    }
+
+    /// The object of a waybar custom module with `"return-type": "json"`,
+    /// on one line: the pending count as its text, the notes one per line
+    /// as its tooltip, and its class.
+    pub fn waybar(&self, render: &Render) -> String {
+        let mut tooltip = self.notes.join("\n");
+        if self.more > 0 {
+            tooltip.push_str(&format!(
+                "\n{} and {} more",
+                render.glyph("…", "..."),
+                self.more
+            ));
+        }
+        serde_json::json!({
+            "text": self.stats.pending.to_string(),
+            "tooltip": tooltip,
+            "class": self.class(),
+        })
+        .to_string()
+    }
+
+    /// The three lines of an i3blocks block: the full text, cut to
+    /// `max_width` like `segment`, the pending count as the short text, and
+    /// red as the color when something is overdue, else no color.
+    pub fn i3blocks(&self, render: &Render, max_width: Option<usize>) -> String {
+        let color = if self.stats.overdue > 0 {
+            "#FF0000"
+        } else {
+            ""
+        };
+        format!(
+            "{}\n{}\n{}\n",
+            segment(render, &self.stats, false, max_width),
+            self.stats.pending,
+            color
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_segment() {
+        let stats = Stats {
+            pending: 3,
+            done: 5,
+            overdue: 1,
+        };
+        assert_eq!(
+            segment(&Render::default(), &stats, false, None),
+            "3 pending, 1 overdue"
+        );
+        assert_eq!(
+            segment(&Render::default(), &stats, true, None),
+            "3 pending, #[fg=red,bold]1 overdue#[default]"
+        );
+        assert_eq!(
+            segment(&Render::default(), &stats, true, Some(16)),
+            "3 pending, #[fg=red,bold]1 ov#[default]…"
+        );
+        assert_eq!(
+            segment(&Render::default(), &stats, true, Some(8)),
+            "3 pendi…"
+        );
+        assert_eq!(
+            segment(&Render::default(), &stats, true, Some(20)),
+            segment(&Render::default(), &stats, true, None)
+        );
+        assert_eq!(segment(&Render::default(), &stats, true, Some(0)), "");
+
+        let stats = Stats {
+            pending: 0,
+            done: 2,
+            overdue: 0,
+        };
+        assert_eq!(segment(&Render::default(), &stats, true, None), "0 pending");
+    }
+
+    #[test]
+    fn test_summary() {
+        let content = "- [ ] buy milk \"2%\" due:2024-06-30\n- [x] call mom\n\
+                       - [ ] water plants\n- [ ] pay rent\n";
+        let list = TodoList::read(Path::new(""), content.as_bytes()).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let summary = Summary::of(&list, today, 2);
+        assert_eq!(
+            summary.waybar(&Render::default()),
+            r#"{"class":"overdue","text":"3","tooltip":"buy milk \"2%\" due:2024-06-30\nwater plants\n… and 1 more"}"#
+        );
+        assert_eq!(
+            summary.i3blocks(&Render::default(), None),
+            "3 pending, 1 overdue\n3\n#FF0000\n"
+        );
+
+        let empty = TodoList::read(Path::new(""), &b""[..]).unwrap();
+        let summary = Summary::of(&empty, today, 2);
+        assert_eq!(
+            summary.waybar(&Render::default()),
+            r#"{"class":"empty","text":"0","tooltip":""}"#
+        );
+        assert_eq!(summary.i3blocks(&Render::default(), Some(4)), "0 p…\n0\n\n");
+    }
+}
@@ -0,0 +1,269 @@
+//! Line-at-a-time access to todo files, for commands that don't need the
+//! whole list in memory.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use chrono::NaiveDate;
+
+use crate::{
+    backup, clean_note, date, ensure_writable, is_dry_run, journal, listing_line, org, parse_line,
+    read_tasks, Activity, Blockers, Filter, Fingerprint, Format, Render, Stats, Task, TodoError,
+};
+
+/// Calls `f` with the 1-based index and task of each line of the todo file
+/// at `path`, keeping only one task in memory at a time, and returns the
+/// number of tasks. A missing file has none.
+fn for_each(path: &Path, mut f: impl FnMut(usize, &Task)) -> Result<usize, TodoError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    if Format::of(path) == Format::Org {
+        let mut content = String::new();
+        BufReader::new(file).read_to_string(&mut content)?;
+        let (_, tasks) = org::parse(content.lines());
+        for (i, task) in tasks.iter().enumerate() {
+            f(i + 1, task);
+        }
+        return Ok(tasks.len());
+    }
+    read_tasks(BufReader::new(file), |i, task| f(i, &task))
+}
+
+/// Calls `f` with whether each task of the todo file at `path` is done and
+/// the line it starts on, telling the tasks by their checkbox or headline
+/// alone, without parsing the rest, through one line buffer. Returns the
+/// number of tasks; lines that aren't tasks, like a missing file, have
+/// none.
+fn scan(path: &Path, mut f: impl FnMut(bool, &str)) -> Result<usize, TodoError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let format = Format::of(path);
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut count = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(count);
+        }
+        let Ok(text) = std::str::from_utf8(&line) else {
+            continue;
+        };
+        let text = text.trim_end_matches(['\n', '\r']);
+        let done = match format {
+            Format::Org => org::headline(text).map(|(_, done, _)| done),
+            Format::Markdown => parse_line(text).map(|(done, _)| done),
+        };
+        if let Some(done) = done {
+            count += 1;
+            f(done, text);
+        }
+    }
+}
+
+/// Whether loading the todo file at `path` would change it before any
+/// command runs: it has a habit checked before `today` to reset, or, with
+/// a `cutoff` for archiving, a task done before it. Only the lines of done
+/// tasks that could be either are parsed, unless it's an org file.
+pub fn needs_upkeep(
+    path: &Path,
+    today: NaiveDate,
+    cutoff: Option<NaiveDate>,
+) -> Result<bool, TodoError> {
+    let needs =
+        |task: &Task| task.is_stale_habit(today) || cutoff.is_some_and(|c| task.is_done_before(c));
+    let mut due = false;
+    // An org task's done date is on the line under its headline, so the
+    // whole file is parsed.
+    if Format::of(path) == Format::Org {
+        for_each(path, |_, task| due = due || needs(task))?;
+        return Ok(due);
+    }
+    scan(path, |done, line| {
+        if due || !done || (cutoff.is_none() && !line.contains("habit:")) {
+            return;
+        }
+        due = line.parse().ok().is_some_and(|task: Task| needs(&task));
+    })?;
+    Ok(due)
+}
+
+/// The task counts of the todo file at `path`.
+pub fn stats(path: &Path, today: NaiveDate) -> Result<Stats, TodoError> {
+    let mut stats = Stats {
+        pending: 0,
+        done: 0,
+        overdue: 0,
+    };
+    for_each(path, |_, task| match task {
+        Task::TodoTask(data) => {
+            stats.pending += 1;
+            if data.is_overdue(today) {
+                stats.overdue += 1;
+            }
+        }
+        Task::DoneTask(_) => stats.done += 1,
+    })?;
+    Ok(stats)
+}
+
+/// Adds a task to the end of the todo file at `path` without rewriting the
+/// rest of it, returning the task's index. Its lines aren't parsed, so
+/// they aren't checked either; see `needs_upkeep` for what loading the
+/// list would have done. The file is backed up first, as every save is.
+pub fn append(path: &Path, note: &str) -> Result<usize, TodoError> {
+    let note = clean_note(note)?;
+    ensure_writable(path)?;
+    let count = scan(path, |_, _| ())?;
+    if is_dry_run() {
+        return Ok(count + 1);
+    }
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let previous = Fingerprint::of(&target);
+    backup::take(&target).map_err(|source| TodoError::Save {
+        path: path.to_owned(),
+        source,
+    })?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+    let mut last = [0];
+    let needs_newline = file.seek(SeekFrom::End(-1)).is_ok()
+        && file.read_exact(&mut last).is_ok()
+        && last[0] != b'\n';
+    let separator = if needs_newline { "\n" } else { "" };
+    let len = file.metadata()?.len();
+    write!(file, "{}", separator)?;
+    match Format::of(path) {
+        Format::Org => org::write_task(&mut file, &Task::new(note))?,
+        Format::Markdown => writeln!(file, "{:?}", Task::new(note))?,
+    }
+    journal::record_append(
+        &target,
+        len,
+        previous,
+        &Activity::new("add", count + 1, note).to_string(),
+    );
+    Ok(count + 1)
+}
+
+/// Prints the tasks of the todo file at `path` accepted by `filter` like
+/// `TodoList::print_matching`, in three passes over the file rather than
+/// loading it.
+pub fn print_matching(
+    render: &Render,
+    path: &Path,
+    out: &mut impl Write,
+    filter: &Filter,
+) -> Result<(), TodoError> {
+    let today = date::today();
+    let mut blockers = Blockers::default();
+    for_each(path, |_, task| blockers.add(task))?;
+    let mut last = 0;
+    for_each(path, |i, task| {
+        if filter.accepts(task, &blockers, today) {
+            last = i;
+        }
+    })?;
+    let width = last.to_string().len();
+
+    let mut result = Ok(());
+    for pinned in [true, false] {
+        for_each(path, |i, task| {
+            if result.is_ok()
+                && task.data().is_pinned() == pinned
+                && filter.accepts(task, &blockers, today)
+            {
+                let line = listing_line(render, i, width, task, blockers.block(task), today, &[]);
+                result = write!(out, "{}", line);
+            }
+        })?;
+    }
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Scope, TodoList};
+
+    #[test]
+    fn test_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        assert_eq!(append(&path, "buy milk").unwrap(), 1);
+        std::fs::write(&path, "- [ ] buy milk\n- [x] call mom").unwrap();
+        assert_eq!(append(&path, "pay rent").unwrap(), 3);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [x] call mom\n- [ ] pay rent\n"
+        );
+        let backups = backup::list(&path);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&backups[0].path).unwrap(),
+            "- [ ] buy milk\n- [x] call mom"
+        );
+        // Checklist items and other lines aren't tasks of their own.
+        std::fs::write(&path, "- [ ] fine\n  - [x] item\noops\n").unwrap();
+        assert_eq!(append(&path, "x").unwrap(), 2);
+        let path = dir.path().join("todo.org");
+        std::fs::write(&path, "#+TITLE: x\n* TODO one\n** notes\n** DONE two\n").unwrap();
+        assert_eq!(append(&path, "three").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_needs_upkeep() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let day = |s| date::parse_date(s).unwrap();
+        let today = day("2024-07-02");
+        assert!(!needs_upkeep(&path, today, None).unwrap());
+        std::fs::write(
+            &path,
+            "- [ ] buy milk\n- [x] call mom done:2024-06-01\n\
+             - [x] stretch habit:daily done:2024-07-02\n",
+        )
+        .unwrap();
+        assert!(!needs_upkeep(&path, today, None).unwrap());
+        assert!(!needs_upkeep(&path, today, Some(day("2024-06-01"))).unwrap());
+        assert!(needs_upkeep(&path, today, Some(day("2024-06-02"))).unwrap());
+        assert!(needs_upkeep(&path, day("2024-07-03"), None).unwrap());
+    }
+
+    #[test]
+    fn test_matches_loaded_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        let content: String = (1..=12)
+            .map(|i| match i % 3 {
+                0 => format!("- [x] task {}\n", i),
+                1 => format!("- [ ] task {} pin:1 due:2000-01-01\n", i),
+                _ => format!("- [ ] task {}\n", i),
+            })
+            .collect();
+        std::fs::write(&path, content).unwrap();
+        let today = date::today();
+
+        let list = TodoList::load(&path).unwrap();
+        assert_eq!(stats(&path, today).unwrap(), list.stats(today));
+        for scope in [Scope::Pending, Scope::Done, Scope::Any] {
+            let filter = Filter::new(scope);
+            let mut streamed = Vec::new();
+            print_matching(&Render::default(), &path, &mut streamed, &filter).unwrap();
+            let mut loaded = Vec::new();
+            list.print_matching(&Render::default(), &mut loaded, &filter)
+                .unwrap();
+            assert_eq!(streamed, loaded);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Syncing a list through shell commands from the configuration, such as
+//! `rsync ~/todo.txt host:`. The commands find the list's file in
+//! `TODO_FILE`.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{backup, ensure_writable, journal, replace_file, Fingerprint, TodoError, TodoList};
+
+pub(crate) fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    shell.arg(command);
+    shell
+}
+
+/// Runs `command` for the list at `path`. Its output goes to stderr, so as
+/// not to mix with listings; what it writes to stderr is also part of the
+/// error if it fails.
+pub fn run(command: &str, path: &Path) -> Result<(), TodoError> {
+    let error = |message: String| TodoError::Sync {
+        command: command.to_owned(),
+        message,
+    };
+    let output = shell(command)
+        .env("TODO_FILE", path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(io::stderr()))
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| error(e.to_string()))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        let stderr = stderr.trim_end();
+        return Err(error(if stderr.is_empty() {
+            output.status.to_string()
+        } else {
+            format!("{}: {}", output.status, stderr)
+        }));
+    }
+    // Warnings of a command that succeeded are still worth seeing.
+    io::stderr().write_all(stderr.as_bytes())?;
+    Ok(())
+}
+
+/// Puts the content the file at `target` had back.
+fn put_back(target: &Path, before: &Option<Vec<u8>>) -> Result<(), TodoError> {
+    let result = match before {
+        Some(content) => replace_file(target, |file| file.write_all(content)),
+        None => match fs::remove_file(target) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    };
+    result.map_err(|source| TodoError::Save {
+        path: target.to_owned(),
+        source,
+    })
+}
+
+/// Runs the pull `command` for the list at `path`, after backing it up.
+/// If the command fails, or leaves something that isn't a list (with lines
+/// that aren't tasks, unless `skip_invalid`), the file is put back as it
+/// was. Returns whether the list changed, which can be undone like a save.
+pub fn pull(command: &str, path: &Path, skip_invalid: bool) -> Result<bool, TodoError> {
+    ensure_writable(path)?;
+    let target: PathBuf = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let before = match fs::read(&target) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let previous = Fingerprint::of(&target);
+    let backup = backup::take(&target).map_err(|source| TodoError::Save {
+        path: target.clone(),
+        source,
+    })?;
+    if let Err(e) = run(command, &target) {
+        put_back(&target, &before)?;
+        return Err(e);
+    }
+    let loaded = if skip_invalid {
+        TodoList::load_skipping_invalid(&target).map(|_| ())
+    } else {
+        TodoList::load(&target).map(|_| ())
+    };
+    if let Err(e) = loaded {
+        put_back(&target, &before)?;
+        return Err(TodoError::Sync {
+            command: command.to_owned(),
+            message: format!(
+                "it left a file that isn't a list, so it was put back: {}",
+                e
+            ),
+        });
+    }
+    let changed = fs::read(&target).ok() != before;
+    if changed {
+        journal::record_save(&target, backup, previous, "sync");
+    }
+    Ok(changed)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pull() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.txt");
+        fs::write(&path, "- [ ] buy milk\n").unwrap();
+
+        assert!(!pull("true", &path, false).unwrap());
+        assert!(pull(
+            "printf -- '- [ ] call mom\\n' >> \"$TODO_FILE\"",
+            &path,
+            false
+        )
+        .unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [ ] call mom\n"
+        );
+
+        let err = pull(
+            "echo broken > \"$TODO_FILE\"; echo oops >&2; exit 3",
+            &path,
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("exit status: 3: oops"), "{}", err);
+        let err = pull("echo broken > \"$TODO_FILE\"", &path, false).unwrap_err();
+        assert!(err.to_string().contains("isn't a list"), "{}", err);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- [ ] buy milk\n- [ ] call mom\n"
+        );
+        assert!(pull("echo broken >> \"$TODO_FILE\"", &path, true).unwrap());
+    }
+}
@@ -0,0 +1,388 @@
+//! Line templates for listings, such as `{index}\t{status}\t{note}`, for
+//! `todo ls --format`. Each listed task is one line of the template with
+//! its placeholders filled in; `{{` and `}}` are literal braces, and `\t`,
+//! `\n` and `\\` a tab, a newline and a backslash. The templates of
+//! `todo prompt`, such as `[{pending}]`, are filled in with the counts of
+//! the list instead.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use crate::{format_age, Stats, Symbols, Task};
+
+/// What a placeholder stands for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Index,
+    /// `todo` or `done`.
+    Status,
+    /// The done or pending symbol of the listing, uncolored.
+    Symbol,
+    Note,
+    /// The priority letter, if any.
+    Priority,
+    Due,
+    /// The `#tag`, `+project` and `@context` words, separated by spaces.
+    Tags,
+    Created,
+    /// The age of the task as listings show it, such as `12d` or `6w`.
+    Age,
+}
+
+const FIELDS: [(&str, Field); 9] = [
+    ("index", Field::Index),
+    ("status", Field::Status),
+    ("symbol", Field::Symbol),
+    ("note", Field::Note),
+    ("priority", Field::Priority),
+    ("due", Field::Due),
+    ("tags", Field::Tags),
+    ("created", Field::Created),
+    ("age", Field::Age),
+];
+
+/// What a placeholder of a `todo prompt` template stands for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Count {
+    Pending,
+    Done,
+    Overdue,
+}
+
+const COUNTS: [(&str, Count); 3] = [
+    ("pending", Count::Pending),
+    ("done", Count::Done),
+    ("overdue", Count::Overdue),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part<F> {
+    Text(String),
+    Field(F),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part<Field>>,
+}
+
+/// The template of `todo prompt`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountTemplate {
+    parts: Vec<Part<Count>>,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    /// Parses a template, refusing unknown placeholders and unmatched
+    /// braces with a message saying what's wrong.
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(Template {
+            parts: parse(s, &FIELDS)?,
+        })
+    }
+}
+
+impl FromStr for CountTemplate {
+    type Err = String;
+
+    /// Parses a template like `Template` does, with the placeholders of
+    /// the counts.
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(CountTemplate {
+            parts: parse(s, &COUNTS)?,
+        })
+    }
+}
+
+/// The parts of template `s`, with the placeholders of `fields`.
+fn parse<F: Copy>(s: &str, fields: &[(&str, F)]) -> Result<Vec<Part<F>>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some('\\') => text.push('\\'),
+                Some(c) => {
+                    return Err(format!(
+                        "unknown escape '\\{}' (write '\\\\' for a backslash)",
+                        c
+                    ))
+                }
+                None => return Err("'\\' at the end (write '\\\\' for a backslash)".to_owned()),
+            },
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if !closed {
+                    return Err("'{' without a closing '}' (write '{{' for a brace)".to_owned());
+                }
+                let Some(&(_, field)) = fields.iter().find(|(n, _)| *n == name) else {
+                    let names: Vec<&str> = fields.iter().map(|(n, _)| *n).collect();
+                    return Err(format!(
+                        "unknown placeholder '{{{}}}' (available: {})",
+                        name,
+                        names.join(", ")
+                    ));
+                };
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Field(field));
+            }
+            '}' => return Err("'}' without an opening '{' (write '}}' for a brace)".to_owned()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+impl CountTemplate {
+    /// The template filled in with `stats`.
+    pub fn render(&self, stats: &Stats) -> String {
+        let mut line = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => line.push_str(text),
+                Part::Field(Count::Pending) => write!(line, "{}", stats.pending).unwrap(),
+                Part::Field(Count::Done) => write!(line, "{}", stats.done).unwrap(),
+                Part::Field(Count::Overdue) => write!(line, "{}", stats.overdue).unwrap(),
+            }
+        }
+        line
+    }
+}
+
+impl Template {
+    /// The template of the scripting layout, `--plain`.
+    pub fn plain() -> Template {
+        Template {
+            parts: vec![
+                Part::Field(Field::Index),
+                Part::Text("\t".to_owned()),
+                Part::Field(Field::Status),
+                Part::Text("\t".to_owned()),
+                Part::Field(Field::Note),
+            ],
+        }
+    }
+
+    /// The line of the task at `index`, without a line ending, with its
+    /// status as one of `symbols`.
+    pub(crate) fn render(
+        &self,
+        index: usize,
+        task: &Task,
+        today: NaiveDate,
+        symbols: Symbols,
+    ) -> String {
+        let data = task.data();
+        let date = |date: Option<NaiveDate>| date.map_or(String::new(), |d| d.to_string());
+        let mut line = String::new();
+        for part in &self.parts {
+            let field = match part {
+                Part::Text(text) => {
+                    line.push_str(text);
+                    continue;
+                }
+                Part::Field(field) => field,
+            };
+            match field {
+                Field::Index => write!(line, "{}", index).unwrap(),
+                Field::Status => line.push_str(match task {
+                    Task::DoneTask(_) => "done",
+                    Task::TodoTask(_) => "todo",
+                }),
+                Field::Symbol => {
+                    let [done, pending, ..] = symbols.glyphs();
+                    line.push_str(match task {
+                        Task::DoneTask(_) => done,
+                        Task::TodoTask(_) => pending,
+                    })
+                }
+                // A tab would split the field, the way a newline would split
+                // the line if notes could have one. Backslashes are doubled
+                // first, for a `\t` in the note to read back as written.
+                Field::Note => {
+                    line.push_str(&data.to_string().replace('\\', "\\\\").replace('\t', "\\t"))
+                }
+                Field::Priority => line.extend(data.priority()),
+                Field::Due => line.push_str(&date(data.due())),
+                Field::Tags => {
+                    let tags: Vec<&str> = data
+                        .note
+                        .split_whitespace()
+                        .filter(|w| w.len() > 1 && w.starts_with(['#', '+', '@']))
+                        .collect();
+                    line.push_str(&tags.join(" "));
+                }
+                Field::Created => line.push_str(&date(data.created())),
+                Field::Age => {
+                    if let Some(created) = data.created() {
+                        line.push_str(&format_age((today - created).num_days()));
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, index: usize, line: &str) -> String {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let template: Template = template.parse().unwrap();
+        template.render(index, &line.parse().unwrap(), today, Symbols::Unicode)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "{index}: {note}".parse::<Template>().unwrap().parts,
+            [
+                Part::Field(Field::Index),
+                Part::Text(": ".to_owned()),
+                Part::Field(Field::Note),
+            ]
+        );
+        assert_eq!(
+            "{{{due}}}".parse::<Template>().unwrap().parts,
+            [
+                Part::Text("{".to_owned()),
+                Part::Field(Field::Due),
+                Part::Text("}".to_owned()),
+            ]
+        );
+        assert_eq!("".parse::<Template>().unwrap().parts, []);
+        assert_eq!(
+            r"{index}\t{note}\n\\".parse::<Template>().unwrap().parts,
+            [
+                Part::Field(Field::Index),
+                Part::Text("\t".to_owned()),
+                Part::Field(Field::Note),
+                Part::Text("\n\\".to_owned()),
+            ]
+        );
+        assert_eq!(
+            r"{index}\s".parse::<Template>().unwrap_err(),
+            r"unknown escape '\s' (write '\\' for a backslash)"
+        );
+        assert_eq!(
+            r"{index}\".parse::<Template>().unwrap_err(),
+            r"'\' at the end (write '\\' for a backslash)"
+        );
+        assert_eq!(
+            "{index} {colour}".parse::<Template>().unwrap_err(),
+            "unknown placeholder '{colour}' (available: index, status, symbol, \
+             note, priority, due, tags, created, age)"
+        );
+        assert_eq!(
+            "{}".parse::<Template>().unwrap_err(),
+            "unknown placeholder '{}' (available: index, status, symbol, \
+             note, priority, due, tags, created, age)"
+        );
+        assert_eq!(
+            "{index".parse::<Template>().unwrap_err(),
+            "'{' without a closing '}' (write '{{' for a brace)"
+        );
+        assert_eq!(
+            "index}".parse::<Template>().unwrap_err(),
+            "'}' without an opening '{' (write '}}' for a brace)"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let line = "- [ ] (A) 2024-06-21 call mom +family @phone due:2024-07-03";
+        assert_eq!(
+            render(
+                "{index}|{status}|{priority}|{due}|{tags}|{created}|{age}",
+                3,
+                line
+            ),
+            "3|todo|A|2024-07-03|+family @phone|2024-06-21|10d"
+        );
+        assert_eq!(
+            render(r"{index}\t{status}\t{note}", 3, "- [ ] buy milk"),
+            "3\ttodo\tbuy milk"
+        );
+        assert_eq!(
+            render("{note}", 3, line),
+            "(A) 2024-06-21 call mom +family @phone due:2024-07-03"
+        );
+        assert_eq!(render("{age}", 1, "- [ ] 2024-03-01 buy milk"), "17w");
+        assert_eq!(
+            render("{status} {{{priority}}}{due}{age}", 1, "- [x] buy milk"),
+            "done {}"
+        );
+        assert_eq!(
+            Template::plain().render(
+                1,
+                &"- [ ] buy milk".parse().unwrap(),
+                NaiveDate::MIN,
+                Symbols::Unicode
+            ),
+            "1\ttodo\tbuy milk"
+        );
+        assert_eq!(
+            Template::plain().render(
+                2,
+                &"- [x] buy\tmilk".parse().unwrap(),
+                NaiveDate::MIN,
+                Symbols::Unicode
+            ),
+            "2\tdone\tbuy\\tmilk"
+        );
+        assert_eq!(
+            Template::plain().render(
+                3,
+                &r"- [ ] buy\tmilk C:\\".parse().unwrap(),
+                NaiveDate::MIN,
+                Symbols::Unicode
+            ),
+            "3\ttodo\tbuy\\\\tmilk C:\\\\\\\\"
+        );
+    }
+
+    #[test]
+    fn test_count_template() {
+        let stats = Stats {
+            pending: 3,
+            done: 5,
+            overdue: 1,
+        };
+        let template: CountTemplate = "[{pending}/{overdue}] {{{done}}}".parse().unwrap();
+        assert_eq!(template.render(&stats), "[3/1] {5}");
+        assert_eq!(
+            "{due}".parse::<CountTemplate>().unwrap_err(),
+            "unknown placeholder '{due}' (available: pending, done, overdue)"
+        );
+    }
+}
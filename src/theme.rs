@@ -0,0 +1,459 @@
+//! The colors of listings: a preset, "dark", "light" or "colorblind", with
+//! any of its styles overridden in the `[colors]` table of the config.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use ansi_term::Colour::{self, *};
+use ansi_term::Style;
+use serde::Deserialize;
+
+/// The built-in themes.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// The terminal's own colors, and dimmed text for the less important
+    /// parts, for dark backgrounds.
+    #[default]
+    Dark,
+    /// Darker shades from the 256-color palette instead of dimmed text,
+    /// which light backgrounds wash out.
+    Light,
+    /// Blue and orange instead of green and red, which red-green color
+    /// blindness can't tell apart, with overdue dates underlined as well.
+    /// Listings always show status symbols with it; see `shows_symbols`.
+    Colorblind,
+}
+
+impl Preset {
+    /// Whether listings show the status symbols even with `Symbols::Minimal`,
+    /// for done and pending tasks not to differ by color alone.
+    pub fn shows_symbols(self) -> bool {
+        self == Preset::Colorblind
+    }
+}
+
+impl FromStr for Preset {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dark" => Ok(Preset::Dark),
+            "light" => Ok(Preset::Light),
+            "colorblind" => Ok(Preset::Colorblind),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The style of each part of a listing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    /// The done status symbol, and the filled part of progress bars.
+    pub done: Style,
+    /// The pending status symbol.
+    pub pending: Style,
+    pub pinned: Style,
+    pub started: Style,
+    pub index: Style,
+    /// The notes of pending tasks.
+    pub note: Style,
+    /// The notes of done tasks, also struck through where the terminal
+    /// can show it.
+    pub done_note: Style,
+    pub priority_a: Style,
+    pub priority_b: Style,
+    pub priority_c: Style,
+    /// Priorities below `(C)`.
+    pub priority_low: Style,
+    /// The date of overdue tasks.
+    pub overdue: Style,
+    /// The date of tasks due today or tomorrow.
+    pub due_soon: Style,
+    /// The date of tasks due later.
+    pub due_later: Style,
+    /// The ages of `ls --age`.
+    pub age: Style,
+    /// The ages over the `stale_days` of the config.
+    pub stale: Style,
+    /// Hints, headings and annotations such as "snoozed until".
+    pub muted: Style,
+    /// Search matches.
+    pub highlight: Style,
+    /// Who tasks are assigned to.
+    pub assignee: Style,
+}
+
+impl Theme {
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Dark => Theme {
+                done: Green.normal(),
+                pending: Red.normal(),
+                pinned: Yellow.normal(),
+                started: Cyan.normal(),
+                index: Style::new().dimmed(),
+                note: Style::new(),
+                done_note: Style::new().dimmed(),
+                priority_a: Red.bold(),
+                priority_b: Yellow.normal(),
+                priority_c: Green.normal(),
+                priority_low: Style::new().dimmed(),
+                overdue: Red.bold(),
+                due_soon: Yellow.normal(),
+                due_later: Style::new().dimmed(),
+                age: Style::new().dimmed(),
+                stale: Yellow.normal(),
+                muted: Style::new().dimmed(),
+                highlight: Style::new().reverse(),
+                assignee: Purple.normal(),
+            },
+            Preset::Light => Theme {
+                done: Fixed(28).normal(),
+                pending: Fixed(160).normal(),
+                pinned: Fixed(136).normal(),
+                started: Fixed(31).normal(),
+                index: Fixed(240).normal(),
+                note: Style::new(),
+                done_note: Fixed(245).normal(),
+                priority_a: Fixed(160).bold(),
+                priority_b: Fixed(136).normal(),
+                priority_c: Fixed(28).normal(),
+                priority_low: Fixed(240).normal(),
+                overdue: Fixed(160).bold(),
+                due_soon: Fixed(136).normal(),
+                due_later: Fixed(240).normal(),
+                age: Fixed(240).normal(),
+                stale: Fixed(136).normal(),
+                muted: Fixed(240).normal(),
+                highlight: Style::new().reverse(),
+                assignee: Fixed(90).normal(),
+            },
+            Preset::Colorblind => Theme {
+                done: Fixed(33).normal(),
+                pending: Fixed(208).normal(),
+                pinned: Fixed(220).normal(),
+                started: Fixed(141).normal(),
+                index: Style::new().dimmed(),
+                note: Style::new(),
+                done_note: Style::new().dimmed(),
+                priority_a: Fixed(208).bold(),
+                priority_b: Fixed(220).normal(),
+                priority_c: Fixed(33).normal(),
+                priority_low: Style::new().dimmed(),
+                overdue: Fixed(208).bold().underline(),
+                due_soon: Fixed(220).normal(),
+                due_later: Style::new().dimmed(),
+                age: Style::new().dimmed(),
+                stale: Fixed(220).normal(),
+                muted: Style::new().dimmed(),
+                highlight: Style::new().reverse(),
+                assignee: Fixed(75).normal(),
+            },
+        }
+    }
+
+    /// The theme of `preset` with the styles named in `colors` replaced;
+    /// see `parse_style`. Fails with a message on an unknown name or style.
+    pub fn new(preset: Preset, colors: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut theme = Theme::preset(preset);
+        for (name, spec) in colors {
+            let slot = theme
+                .get_mut(name)
+                .ok_or_else(|| format!("unknown color {}", name))?;
+            *slot = parse_style(spec)
+                .ok_or_else(|| format!("invalid color '{}' for {}", spec, name))?;
+        }
+        Ok(theme)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Style> {
+        Some(match name {
+            "done" => &mut self.done,
+            "pending" => &mut self.pending,
+            "pinned" => &mut self.pinned,
+            "started" => &mut self.started,
+            "index" => &mut self.index,
+            "note" => &mut self.note,
+            "done_note" => &mut self.done_note,
+            "priority_a" => &mut self.priority_a,
+            "priority_b" => &mut self.priority_b,
+            "priority_c" => &mut self.priority_c,
+            "priority_low" => &mut self.priority_low,
+            "overdue" => &mut self.overdue,
+            "due_soon" => &mut self.due_soon,
+            "due_later" => &mut self.due_later,
+            "age" => &mut self.age,
+            "stale" => &mut self.stale,
+            "muted" => &mut self.muted,
+            "highlight" => &mut self.highlight,
+            "assignee" => &mut self.assignee,
+            _ => return None,
+        })
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::preset(Preset::Dark)
+    }
+}
+
+/// The colors a terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Depth {
+    /// Any RGB color, as announced by `COLORTERM=truecolor` (or `24bit`).
+    TrueColor,
+    /// The xterm palette of 256 colors, for a `TERM` like `xterm-256color`.
+    Xterm256,
+    /// The 16 basic colors.
+    Basic,
+}
+
+impl Depth {
+    pub fn detect() -> Self {
+        Depth::from_env(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            Depth::TrueColor
+        } else if term.is_some_and(|t| t.contains("256color")) {
+            Depth::Xterm256
+        } else {
+            Depth::Basic
+        }
+    }
+}
+
+/// The RGB values of the 16 basic colors, as xterm shows them.
+const BASIC: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels of each channel in the 6×6×6 color cube of the xterm palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// The nearest color to `rgb` in the xterm palette: the closest of the
+/// color cube (16-231) and the gray ramp (232-255).
+pub fn xterm256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (i32::from(CUBE[i]) - i32::from(c)).abs())
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (CUBE[r], CUBE[g], CUBE[b]);
+    let average = (u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * step;
+    if distance(rgb, (gray_level, gray_level, gray_level)) < distance(rgb, cube) {
+        232 + step
+    } else {
+        (16 + 36 * r + 6 * g + b) as u8
+    }
+}
+
+/// The nearest of the 16 basic colors to `rgb`.
+pub fn basic16(rgb: (u8, u8, u8)) -> u8 {
+    (0..BASIC.len())
+        .min_by_key(|&i| distance(rgb, BASIC[i]))
+        .unwrap_or(0) as u8
+}
+
+/// Parses `#rrggbb` or `#rgb`.
+fn hex(s: &str) -> Option<(u8, u8, u8)> {
+    let digits = s.strip_prefix('#')?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize, len: usize| {
+        let v = u8::from_str_radix(&digits[i * len..(i + 1) * len], 16).ok()?;
+        Some(if len == 1 { v * 17 } else { v })
+    };
+    let len = match digits.len() {
+        6 => 2,
+        3 => 1,
+        _ => return None,
+    };
+    Some((channel(0, len)?, channel(1, len)?, channel(2, len)?))
+}
+
+/// A color as the terminal can show it, and whether it's one of the bright
+/// basic colors, which are shown as bold.
+fn colour(name: &str, depth: Depth) -> Option<(Colour, bool)> {
+    if let Some(rgb) = hex(name) {
+        return Some(match depth {
+            Depth::TrueColor => (RGB(rgb.0, rgb.1, rgb.2), false),
+            Depth::Xterm256 => (Fixed(xterm256(rgb)), false),
+            Depth::Basic => {
+                let i = basic16(rgb);
+                (Fixed(i % 8), i >= 8)
+            }
+        });
+    }
+    Some(match name {
+        "black" => Black,
+        "red" => Red,
+        "green" => Green,
+        "yellow" => Yellow,
+        "blue" => Blue,
+        "purple" | "magenta" => Purple,
+        "cyan" => Cyan,
+        "white" => White,
+        _ => Fixed(name.parse().ok()?),
+    })
+    .map(|c| (c, false))
+}
+
+/// Parses a style such as `"bold red"`, `"208"` (from the 256-color
+/// palette), `"#ff8800"`, `"dimmed"` or `"white on 24"`; `""` and `"none"`
+/// are the plain style. Hex colors are degraded to what the terminal can
+/// show, see `Depth::detect`.
+pub fn parse_style(spec: &str) -> Option<Style> {
+    parse_style_for(spec, Depth::detect())
+}
+
+fn parse_style_for(spec: &str, depth: Depth) -> Option<Style> {
+    let mut style = Style::new();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        style = match word {
+            "none" => style,
+            "bold" => style.bold(),
+            "dimmed" | "dim" => style.dimmed(),
+            "italic" => style.italic(),
+            "underline" => style.underline(),
+            "reverse" => style.reverse(),
+            "on" => style.on(colour(words.next()?, depth)?.0),
+            _ => match colour(word, depth)? {
+                (colour, true) => style.fg(colour).bold(),
+                (colour, false) => style.fg(colour),
+            },
+        };
+    }
+    Some(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(parse_style("bold red"), Some(Red.bold()));
+        assert_eq!(parse_style("208"), Some(Fixed(208).normal()));
+        assert_eq!(parse_style("white on 24"), Some(White.on(Fixed(24))));
+        assert_eq!(parse_style(" dim "), Some(Style::new().dimmed()));
+        assert_eq!(parse_style("none"), Some(Style::new()));
+        assert_eq!(parse_style("256"), None);
+        assert_eq!(parse_style("pink"), None);
+        assert_eq!(parse_style("red on"), None);
+    }
+
+    #[test]
+    fn test_hex_colors() {
+        assert_eq!(hex("#ff8800"), Some((255, 136, 0)));
+        assert_eq!(hex("#f80"), Some((255, 136, 0)));
+        assert_eq!(hex("#ff880"), None);
+        assert_eq!(hex("#gg8800"), None);
+
+        assert_eq!(xterm256((255, 136, 0)), 208);
+        assert_eq!(xterm256((0, 0, 0)), 16);
+        assert_eq!(xterm256((255, 255, 255)), 231);
+        assert_eq!(xterm256((128, 128, 128)), 244);
+        assert_eq!(xterm256((95, 135, 175)), 67);
+
+        assert_eq!(basic16((255, 136, 0)), 3);
+        assert_eq!(basic16((250, 10, 10)), 9);
+        assert_eq!(basic16((0, 0, 200)), 4);
+        assert_eq!(basic16((130, 130, 130)), 8);
+
+        assert_eq!(
+            parse_style_for("#ff8800", Depth::TrueColor),
+            Some(RGB(255, 136, 0).normal())
+        );
+        assert_eq!(
+            parse_style_for("#ff8800", Depth::Xterm256),
+            Some(Fixed(208).normal())
+        );
+        assert_eq!(
+            parse_style_for("#ff8800", Depth::Basic),
+            Some(Fixed(3).normal())
+        );
+        assert_eq!(
+            parse_style_for("black on #ff0000", Depth::Basic),
+            Some(Black.on(Fixed(1)))
+        );
+        assert_eq!(
+            parse_style_for("#ff0000", Depth::Basic),
+            Some(Fixed(1).bold())
+        );
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(
+            Depth::from_env(Some("truecolor"), Some("xterm")),
+            Depth::TrueColor
+        );
+        assert_eq!(
+            Depth::from_env(None, Some("xterm-256color")),
+            Depth::Xterm256
+        );
+        assert_eq!(Depth::from_env(Some(""), Some("linux")), Depth::Basic);
+        assert_eq!(Depth::from_env(None, None), Depth::Basic);
+    }
+
+    #[test]
+    fn test_new() {
+        let colors = BTreeMap::from([("index".to_owned(), "244".to_owned())]);
+        let theme = Theme::new(Preset::Dark, &colors).unwrap();
+        assert_eq!(theme.index, Fixed(244).normal());
+        assert_eq!(theme.done, Green.normal());
+
+        let colors = BTreeMap::from([("indx".to_owned(), "244".to_owned())]);
+        assert_eq!(
+            Theme::new(Preset::Light, &colors),
+            Err("unknown color indx".to_owned())
+        );
+        let colors = BTreeMap::from([("index".to_owned(), "grey".to_owned())]);
+        assert_eq!(
+            Theme::new(Preset::Light, &colors),
+            Err("invalid color 'grey' for index".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_colorblind() {
+        let theme = Theme::preset("colorblind".parse().unwrap());
+        assert_eq!(theme.done, Fixed(33).normal());
+        assert_eq!(theme.pending, Fixed(208).normal());
+        assert!(theme.overdue.is_underline);
+        assert!(Preset::Colorblind.shows_symbols());
+        assert!(!Preset::Dark.shows_symbols());
+    }
+}
@@ -0,0 +1,150 @@
+//! `todo tidy`: a file edited by hand rewritten in the form todo writes,
+//! with the lines that aren't tasks kept as they are.
+
+use std::fmt;
+
+/// What tidying a file changed, by kind.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    /// Checkboxes written another way than `- [ ] `, such as `* [ ]`.
+    pub checkboxes: usize,
+    /// Uppercase `X` markers.
+    pub markers: usize,
+    /// Lines ending in whitespace, carriage returns included.
+    pub trailing_whitespace: usize,
+    /// Blank lines at the start or the end, or after another one.
+    pub blank_lines: usize,
+    /// Whether the last line had no newline.
+    pub final_newline: bool,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        *self == Changes::default()
+    }
+}
+
+/// One line per kind of change with its count, leaving out those that
+/// didn't happen.
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (kind, count) in [
+            ("checkboxes", self.checkboxes),
+            ("markers", self.markers),
+            ("trailing whitespace", self.trailing_whitespace),
+            ("blank lines", self.blank_lines),
+            ("final newline", usize::from(self.final_newline)),
+        ] {
+            if count > 0 {
+                writeln!(f, "{}: {}", kind, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A task line, however loosely it's written, as todo writes it:
+/// `- [x] note`, after two spaces for a checklist item. Returns none for
+/// any other line.
+pub(crate) fn task_line(line: &str, changes: &mut Changes) -> Option<String> {
+    let (indent, rest) = match line.strip_prefix("  ") {
+        Some(rest) if !rest.starts_with(char::is_whitespace) => ("  ", rest),
+        _ if line.starts_with(char::is_whitespace) => return None,
+        _ => ("", line),
+    };
+    let rest = rest.strip_prefix(['-', '*', '+'])?;
+    let rest = rest.trim_start().strip_prefix('[')?;
+    let marker = rest.chars().next()?;
+    let rest = rest[marker.len_utf8()..].strip_prefix(']')?;
+    let note = rest.trim_start();
+    if !matches!(marker, ' ' | 'x' | 'X') || note.is_empty() || note.len() == rest.len() {
+        return None;
+    }
+    if marker == 'X' {
+        changes.markers += 1;
+    }
+    if format!("{}- [{}] {}", indent, marker, note) != line {
+        changes.checkboxes += 1;
+    }
+    let marker = if marker == ' ' { ' ' } else { 'x' };
+    Some(format!("{}- [{}] {}", indent, marker, note))
+}
+
+/// `content` in the form todo writes: task lines as `- [ ] note`, no
+/// trailing whitespace, no blank lines at the ends or after another, and a
+/// newline at the end. Other lines stay as they are. Returns the content,
+/// unchanged if it was tidy already, and what changed.
+pub fn tidy(content: &str) -> (String, Changes) {
+    let mut changes = Changes::default();
+    let mut lines: Vec<String> = Vec::new();
+    let mut blank = false;
+    for line in content.split_terminator('\n') {
+        let trimmed = line.trim_end();
+        if trimmed.len() != line.len() {
+            changes.trailing_whitespace += 1;
+        }
+        if trimmed.is_empty() {
+            if lines.is_empty() || blank {
+                changes.blank_lines += 1;
+            } else {
+                blank = true;
+            }
+            continue;
+        }
+        if blank {
+            lines.push(String::new());
+            blank = false;
+        }
+        lines.push(task_line(trimmed, &mut changes).unwrap_or_else(|| trimmed.to_owned()));
+    }
+    if blank {
+        changes.blank_lines += 1;
+    }
+    changes.final_newline = !content.is_empty() && !content.ends_with('\n');
+    let mut tidied = lines.join("\n");
+    if !tidied.is_empty() {
+        tidied.push('\n');
+    }
+    (tidied, changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tidy() {
+        let messy = "\n* [ ] buy milk  \r\n- [X] call mom\n\n\n-  [ ]   water plants\n\
+                     # groceries\n  + [x] oat milk\n    - [ ] deep\n\n";
+        let (tidied, changes) = tidy(messy);
+        assert_eq!(
+            tidied,
+            "- [ ] buy milk\n- [x] call mom\n\n- [ ] water plants\n# groceries\n\
+             \x20 - [x] oat milk\n    - [ ] deep\n"
+        );
+        assert_eq!(
+            changes,
+            Changes {
+                checkboxes: 3,
+                markers: 1,
+                trailing_whitespace: 1,
+                blank_lines: 3,
+                final_newline: false,
+            }
+        );
+        assert_eq!(
+            changes.to_string(),
+            "checkboxes: 3\nmarkers: 1\ntrailing whitespace: 1\nblank lines: 3\n"
+        );
+
+        let (again, changes) = tidy(&tidied);
+        assert_eq!(again, tidied);
+        assert!(changes.is_empty());
+
+        let (tidied, changes) = tidy("- [ ] buy milk");
+        assert_eq!(tidied, "- [ ] buy milk\n");
+        assert!(changes.final_newline);
+        assert_eq!(tidy(""), (String::new(), Changes::default()));
+        assert_eq!(tidy("- [?] odd\n-[ ]\n").0, "- [?] odd\n-[ ]\n");
+    }
+}
@@ -0,0 +1,287 @@
+//! Lists kept on a WebDAV server, such as Nextcloud, at a `davs://` URL
+//! (or `dav://` without TLS). The list is fetched into a local copy that
+//! the commands work on as usual, and put back if it changed, on the
+//! condition that it still has the ETag it was fetched with, so that
+//! changes made meanwhile are never overwritten. `curl` makes the requests;
+//! the credentials are handed to it on stdin, never on its command line.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::WebDav;
+use crate::TodoError;
+
+/// A list on a WebDAV server.
+pub struct Remote {
+    /// The URL as given.
+    pub url: String,
+    /// The URL of the requests, `https://` or `http://`.
+    http: String,
+    /// The local copy of the list.
+    pub cache: PathBuf,
+}
+
+/// The list as it was fetched.
+pub struct Fetched {
+    /// The ETag the server sent, or `None` if there's no list there yet.
+    pub etag: Option<String>,
+    pub content: Vec<u8>,
+}
+
+impl Remote {
+    /// The list at `url`, with its local copy under `cache_dir`.
+    pub fn new(url: &str, cache_dir: &Path) -> Result<Self, TodoError> {
+        let invalid = || TodoError::Remote {
+            url: url.to_owned(),
+            message: "not a URL of a file, such as davs://host/path/todo.txt".to_owned(),
+        };
+        let (scheme, rest) = if let Some(rest) = url.strip_prefix("davs://") {
+            ("https", rest)
+        } else if let Some(rest) = url.strip_prefix("dav://") {
+            ("http", rest)
+        } else {
+            return Err(invalid());
+        };
+        let (host, file) = rest.split_once('/').ok_or_else(invalid)?;
+        if host.is_empty() || file.is_empty() || file.ends_with('/') {
+            return Err(invalid());
+        }
+        let safe = |s: &str| s.replace(['/', ':', '\\'], "_");
+        Ok(Remote {
+            url: url.to_owned(),
+            http: format!("{}://{}", scheme, rest),
+            cache: cache_dir.join(safe(host)).join(safe(file)),
+        })
+    }
+
+    fn error(&self, message: impl Into<String>) -> TodoError {
+        TodoError::Remote {
+            url: self.url.clone(),
+            message: message.into(),
+        }
+    }
+
+    /// Runs curl with `args` for this list, returning the status and ETag
+    /// of the response.
+    fn curl(&self, credentials: &WebDav, args: &[&str]) -> Result<Response, TodoError> {
+        let mut child = Command::new("curl")
+            .args([
+                "--silent",
+                "--show-error",
+                "--config",
+                "-",
+                "--dump-header",
+                "-",
+            ])
+            .args(args)
+            .arg(&self.http)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => {
+                    self.error("remote lists need curl, which isn't installed or isn't on PATH")
+                }
+                _ => self.error(format!("couldn't run curl, which remote lists need: {}", e)),
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(curl_config(credentials).as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(self.error(format!("couldn't reach the server: {}", stderr.trim_end())));
+        }
+        Response::parse(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| self.error("the server's answer isn't HTTP"))
+    }
+
+    /// Fetches the list into its local copy, which is replaced only once
+    /// the whole list has been received. A list that isn't on the server
+    /// yet is empty.
+    pub fn fetch(&self, credentials: &WebDav) -> Result<Fetched, TodoError> {
+        let dir = self.cache.parent().unwrap_or(Path::new("."));
+        let download = dir.join(format!(
+            ".{}.download",
+            self.cache.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        let download_arg = download.to_string_lossy();
+        let response = self.curl(credentials, &["--output", &download_arg]);
+        let content = fs::read(&download).unwrap_or_default();
+        let _ = fs::remove_file(&download);
+        let response = response?;
+        let (etag, content) = match response.status {
+            200 => (response.etag, content),
+            404 => (None, Vec::new()),
+            _ => return Err(self.error(format!("the server answered {}", response.line))),
+        };
+        if fs::read(&self.cache).ok().as_ref() != Some(&content) {
+            crate::replace_file(&self.cache, |file| file.write_all(&content)).map_err(
+                |source| TodoError::Save {
+                    path: self.cache.clone(),
+                    source,
+                },
+            )?;
+        }
+        Ok(Fetched { etag, content })
+    }
+
+    /// Puts the local copy back on the server, unless the list there has
+    /// changed since it was `fetched`.
+    pub fn put(&self, credentials: &WebDav, fetched: &Fetched) -> Result<(), TodoError> {
+        let condition = match &fetched.etag {
+            Some(etag) => format!("If-Match: {}", etag),
+            None => "If-None-Match: *".to_owned(),
+        };
+        let cache = self.cache.to_string_lossy();
+        let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+        let response = self.curl(
+            credentials,
+            &[
+                "--upload-file",
+                &cache,
+                "--header",
+                &condition,
+                "--output",
+                null,
+            ],
+        )?;
+        match response.status {
+            200..=299 => Ok(()),
+            412 => Err(self.error(
+                "the list was changed on the server since it was read, so it wasn't \
+                 overwritten; run the command again to apply it to the new list",
+            )),
+            _ => Err(self.error(format!("the server answered {}", response.line))),
+        }
+    }
+}
+
+/// The configuration curl reads on stdin: the credentials, or where to find
+/// them. Values are quoted with curl's escapes, so that no character of a
+/// password can end its line and start another option.
+fn curl_config(credentials: &WebDav) -> String {
+    let quote = |s: &str| {
+        let mut quoted = String::from('"');
+        for c in s.chars() {
+            match c {
+                '\\' => quoted.push_str("\\\\"),
+                '"' => quoted.push_str("\\\""),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                '\x0b' => quoted.push_str("\\v"),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    };
+    match (&credentials.user, &credentials.password, &credentials.netrc) {
+        (Some(user), Some(password), _) => {
+            format!("user = {}\n", quote(&format!("{}:{}", user, password)))
+        }
+        (_, _, Some(netrc)) => format!("netrc-file = {}\n", quote(&netrc.to_string_lossy())),
+        _ => "netrc-optional\n".to_owned(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u16,
+    /// The status line without the HTTP version, such as `404 Not Found`.
+    line: String,
+    etag: Option<String>,
+}
+
+impl Response {
+    /// Parses the headers curl dumped, of which the last block is the
+    /// final response, after any `100 Continue`.
+    fn parse(headers: &str) -> Option<Self> {
+        let lines: Vec<&str> = headers.lines().collect();
+        let start = lines.iter().rposition(|l| l.starts_with("HTTP/"))?;
+        let mut lines = lines[start..].iter();
+        let line = lines.next()?.split_once(' ')?.1.trim().to_owned();
+        let status = line.split(' ').next()?.parse().ok()?;
+        let etag = lines
+            .filter_map(|l| l.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("etag"))
+            .map(|(_, value)| value.trim().to_owned());
+        Some(Response { status, line, etag })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let remote = Remote::new(
+            "davs://cloud.example.com/remote.php/dav/files/me/todo.txt",
+            Path::new("/cache"),
+        )
+        .unwrap();
+        assert_eq!(
+            remote.http,
+            "https://cloud.example.com/remote.php/dav/files/me/todo.txt"
+        );
+        assert_eq!(
+            remote.cache,
+            Path::new("/cache/cloud.example.com/remote.php_dav_files_me_todo.txt")
+        );
+        let remote = Remote::new("dav://localhost:8080/todo.txt", Path::new("/cache")).unwrap();
+        assert_eq!(remote.http, "http://localhost:8080/todo.txt");
+        assert_eq!(remote.cache, Path::new("/cache/localhost_8080/todo.txt"));
+        for url in [
+            "davs://host",
+            "davs:///todo.txt",
+            "davs://host/dir/",
+            "http://host/a",
+        ] {
+            assert!(Remote::new(url, Path::new("/cache")).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_response() {
+        let headers = "HTTP/1.1 100 Continue\r\n\r\n\
+                       HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\netag: \"5-abc\"\r\n\r\n";
+        assert_eq!(
+            Response::parse(headers),
+            Some(Response {
+                status: 200,
+                line: "200 OK".to_owned(),
+                etag: Some("\"5-abc\"".to_owned()),
+            })
+        );
+        assert_eq!(
+            Response::parse("HTTP/2 412 \r\n\r\n").map(|r| r.status),
+            Some(412)
+        );
+        assert_eq!(Response::parse("garbage"), None);
+    }
+
+    #[test]
+    fn test_curl_config() {
+        let mut credentials = WebDav::default();
+        assert_eq!(curl_config(&credentials), "netrc-optional\n");
+        credentials.netrc = Some(PathBuf::from("/home/me/.netrc-dav"));
+        assert_eq!(
+            curl_config(&credentials),
+            "netrc-file = \"/home/me/.netrc-dav\"\n"
+        );
+        credentials.user = Some("me".to_owned());
+        credentials.password = Some("p\"w\\d".to_owned());
+        assert_eq!(curl_config(&credentials), "user = \"me:p\\\"w\\\\d\"\n");
+        credentials.password = Some("pw\"\nurl = \"http://evil\r\t".to_owned());
+        assert_eq!(
+            curl_config(&credentials),
+            "user = \"me:pw\\\"\\nurl = \\\"http://evil\\r\\t\"\n"
+        );
+        assert_eq!(curl_config(&credentials).lines().count(), 1);
+    }
+}
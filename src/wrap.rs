@@ -0,0 +1,181 @@
+//! Word wrapping by display width, for output that may contain wide
+//! characters, ANSI color codes and links.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Splits `s` into whole `ESC[...m` color codes, OSC sequences such as
+/// links, and grapheme clusters: characters together with the zero-width
+/// characters that follow them (combining marks, variation selectors), the
+/// emoji joined to them with a zero-width joiner or modified by a skin
+/// tone, and the pairs of regional indicators that make flags, so that
+/// neither is split or miscounted.
+fn units(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let len = if rest.starts_with("\x1b]") {
+            // An OSC sequence, such as a link's, ends with `ESC \`.
+            rest.find("\x1b\\").map_or(rest.len(), |end| end + 2)
+        } else if first == '\x1b' {
+            rest.find('m').map_or(rest.len(), |end| end + 1)
+        } else {
+            let mut prev = first;
+            let mut flag = is_regional_indicator(first);
+            rest[first.len_utf8()..]
+                .char_indices()
+                .find(|&(_, c)| {
+                    let joined = c != '\x1b'
+                        && (c.width() == Some(0)
+                            || prev == '\u{200d}'
+                            || ('\u{1f3fb}'..='\u{1f3ff}').contains(&c)
+                            || (flag && is_regional_indicator(c)));
+                    flag = !flag && is_regional_indicator(c);
+                    prev = c;
+                    !joined
+                })
+                .map_or(rest.len(), |(i, _)| first.len_utf8() + i)
+        };
+        let (unit, tail) = rest.split_at(len);
+        rest = tail;
+        Some(unit)
+    })
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+fn unit_width(unit: &str) -> usize {
+    match unit.chars().next() {
+        Some('\x1b') | None => 0,
+        Some(_) => unit.width(),
+    }
+}
+
+/// The number of terminal columns `s` takes up, ignoring color codes.
+pub fn display_width(s: &str) -> usize {
+    units(s).map(unit_width).sum()
+}
+
+/// `text` cut to `width` columns, ending in `ellipsis`, if it's wider.
+/// Characters and clusters are never split, and color codes are all kept,
+/// so that colors still end where they did.
+pub fn truncate(text: &str, width: usize, ellipsis: &str) -> String {
+    if display_width(text) <= width {
+        return text.to_owned();
+    }
+    let available = width.saturating_sub(display_width(ellipsis));
+    let mut out = String::new();
+    let mut column = 0;
+    let mut cut = false;
+    for unit in units(text) {
+        let w = unit_width(unit);
+        if w == 0 && unit.starts_with('\x1b') {
+            out.push_str(unit);
+        } else if !cut && column + w <= available {
+            out.push_str(unit);
+            column += w;
+        } else if !cut {
+            if display_width(ellipsis) <= width {
+                out.push_str(ellipsis);
+            }
+            cut = true;
+        }
+    }
+    out
+}
+
+/// Wraps `text` at spaces so that, printed after `indent` columns, no line
+/// exceeds `width` columns. Continuation lines are indented by `indent`
+/// spaces, and words too long for a line are broken between characters.
+pub fn wrap(text: &str, indent: usize, width: usize) -> String {
+    let available = width.saturating_sub(indent).max(1);
+    let mut out = String::new();
+    let mut column = 0;
+    for (i, word) in text.split(' ').enumerate() {
+        if i > 0 {
+            if column > 0 && column + 1 + display_width(word) > available {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                column = 0;
+            } else {
+                out.push(' ');
+                column += 1;
+            }
+        }
+        for unit in units(word) {
+            let w = unit_width(unit);
+            if column > 0 && column + w > available {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                column = 0;
+            }
+            out.push_str(unit);
+            column += w;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("milk"), 4);
+        assert_eq!(display_width("牛奶"), 4);
+        assert_eq!(display_width("\x1b[1;31mmilk\x1b[0m"), 4);
+        assert_eq!(display_width("🎉 買い物"), 9);
+        // U+2764 is narrow on its own but wide with VS16 appended.
+        assert_eq!(display_width("\u{2764}"), 1);
+        assert_eq!(display_width("\u{2764}\u{fe0f}"), 2);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("buy milk", 6, 80), "buy milk");
+        assert_eq!(wrap("buy milk and eggs", 6, 16), "buy milk\n      and eggs");
+        assert_eq!(
+            wrap("\x1b[33mbuy milk and\x1b[0m", 2, 10),
+            "\x1b[33mbuy milk\n  and\x1b[0m"
+        );
+        assert_eq!(wrap("买牛奶和鸡蛋", 2, 8), "买牛奶\n  和鸡蛋");
+        assert_eq!(wrap("abcdefgh", 0, 3), "abc\ndef\ngh");
+        assert_eq!(wrap("ab\u{2764}\u{fe0f}c", 0, 3), "ab\n\u{2764}\u{fe0f}c");
+        assert_eq!(wrap("a\u{301}bc", 0, 2), "a\u{301}b\nc");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("buy milk", 8, "…"), "buy milk");
+        assert_eq!(truncate("buy milk", 7, "…"), "buy mi…");
+        assert_eq!(truncate("买牛奶和鸡蛋", 6, "…"), "买牛…");
+        assert_eq!(truncate("买牛奶和鸡蛋", 7, "…"), "买牛奶…");
+        assert_eq!(truncate("cafe\u{301} au lait", 5, "…"), "cafe\u{301}…");
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(display_width(family), 2);
+        assert_eq!(truncate(&format!("a{}b", family), 3, "…"), "a…");
+        assert_eq!(
+            truncate(&format!("a{}bc", family), 4, "…"),
+            format!("a{}…", family)
+        );
+        assert_eq!(display_width("\u{1f44d}\u{1f3fd}\u{1f1ef}\u{1f1f5}"), 4);
+        assert_eq!(
+            truncate("\u{1f1ef}\u{1f1f5}\u{1f1ef}\u{1f1f5}", 3, "..."),
+            "..."
+        );
+        assert_eq!(
+            truncate("\x1b[33mbuy milk\x1b[0m and eggs", 5, "…"),
+            "\x1b[33mbuy …\x1b[0m"
+        );
+        assert_eq!(truncate("buy milk", 0, "…"), "");
+        let link = "\x1b]8;;https://example.com\x1b\\https://example.com\x1b]8;;\x1b\\";
+        assert_eq!(display_width(link), 19);
+        assert_eq!(
+            truncate(link, 10, "…"),
+            "\x1b]8;;https://example.com\x1b\\https://e…\x1b]8;;\x1b\\"
+        );
+    }
+}
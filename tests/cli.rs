@@ -0,0 +1,4373 @@
+use std::fs;
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn todo(home: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("todo").unwrap();
+    cmd.env("HOME", home.path())
+        .env("TODO_TODAY", "2024-07-01")
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME");
+    cmd
+}
+
+fn home_with(content: &str) -> TempDir {
+    let home = TempDir::new().unwrap();
+    fs::write(home.path().join("todo.txt"), content).unwrap();
+    home
+}
+
+#[test]
+fn success_exits_zero() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home).args(["check", "1"]).assert().code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [x] buy milk done:2024-07-01\n");
+}
+
+#[test]
+fn bare_invocation_lists_tasks() {
+    let home = home_with("- [ ] buy milk\n- [x] call mom\n");
+    let ls = todo(&home).args(["ls", "--no-color"]).output().unwrap();
+    todo(&home)
+        .arg("--no-color")
+        .assert()
+        .code(0)
+        .stdout(String::from_utf8(ls.stdout).unwrap());
+
+    todo(&home).args(["--", "-5 degrees"]).assert().code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [ ] buy milk\n- [x] call mom\n- [ ] -5 degrees\n"
+    );
+}
+
+#[test]
+fn missing_index_exits_one() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home)
+        .args(["check", "999"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(predicate::str::contains("no task at index 999"));
+}
+
+#[test]
+fn unmatched_selector_exits_one() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home).args(["check", "tea"]).assert().code(1);
+}
+
+#[test]
+fn usage_errors_exit_two() {
+    let home = home_with("");
+    todo(&home).arg("check").assert().code(2);
+    todo(&home).args(["frobnicate", "1", "2"]).assert().code(2);
+}
+
+#[test]
+fn a_missing_index_is_a_usage_error_without_a_terminal() {
+    let home = home_with("- [ ] buy milk\n");
+    for command in ["check", "remove", "top", "bottom"] {
+        todo(&home)
+            .arg(command)
+            .assert()
+            .code(2)
+            .stderr("todo: the index of the task is missing\n");
+    }
+    todo(&home).args(["check", "--renumbered"]).assert().code(2);
+}
+
+#[test]
+fn parse_failures_exit_three() {
+    let home = home_with("- [ ] fine\nnot a task\n");
+    todo(&home)
+        .arg("ls")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("line 2"));
+}
+
+#[test]
+fn check_by_fuzzy_selector() {
+    let home = home_with("- [ ] buy milk\n- [ ] call mom\n");
+    todo(&home).args(["check", "Milk"]).assert().code(1);
+    todo(&home)
+        .args(["check", "Milk", "--ignore-case"])
+        .assert()
+        .code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [x] buy milk done:2024-07-01\n- [ ] call mom\n");
+}
+
+#[test]
+fn search_uses_smart_case() {
+    let home = home_with("- [ ] Buy Milk\n- [ ] milk the cow\n- [ ] Über-Termin #Work\n");
+    let search = |args: &[&str]| {
+        let output = todo(&home)
+            .arg("search")
+            .args(args)
+            .arg("--plain")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        search(&["milk"]),
+        "1\ttodo\tBuy Milk\n2\ttodo\tmilk the cow\n"
+    );
+    assert_eq!(search(&["Milk"]), "1\ttodo\tBuy Milk\n");
+    assert_eq!(
+        search(&["milk", "--case-sensitive"]),
+        "2\ttodo\tmilk the cow\n"
+    );
+    assert_eq!(search(&["MILK", "--ignore-case"]).lines().count(), 2);
+    assert_eq!(search(&["über"]), "3\ttodo\tÜber-Termin #Work\n");
+
+    let ls = |args: &[&str]| {
+        let output = todo(&home)
+            .arg("ls")
+            .args(args)
+            .arg("--plain")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(ls(&["--tag", "work"]).lines().count(), 1);
+    assert_eq!(ls(&["--tag", "WORK"]).lines().count(), 0);
+}
+
+#[test]
+fn ambiguous_selector_lists_candidates() {
+    let home = home_with("- [ ] buy milk\n- [ ] buy bread\n");
+    todo(&home).args(["check", "buy"]).assert().code(1).stderr(
+        predicate::str::contains("1. buy milk").and(predicate::str::contains("2. buy bread")),
+    );
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [ ] buy bread\n");
+}
+
+#[test]
+fn dedupe_dry_run_leaves_file_alone() {
+    let content = "- [x] buy milk\n- [ ] buy milk\n- [ ] call mom\n- [ ] buy milk\n";
+    let home = home_with(content);
+    todo(&home)
+        .args(["dedupe", "--dry-run"])
+        .assert()
+        .code(0)
+        .stdout("would remove 4. buy milk\n1 duplicate(s), 3 task(s) remain\n");
+    let after = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(after, content);
+
+    todo(&home).arg("dedupe").assert().code(0);
+    let after = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(after, "- [x] buy milk\n- [ ] buy milk\n- [ ] call mom\n");
+    todo(&home)
+        .arg("dedupe")
+        .assert()
+        .code(0)
+        .stdout("0 duplicate(s), 3 task(s) remain\n");
+}
+
+#[test]
+fn pinned_tasks_list_first() {
+    let home = home_with("- [ ] buy milk\n- [ ] inbox zero\n");
+    todo(&home).args(["pin", "2"]).assert().code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [ ] inbox zero pin:1\n");
+
+    let output = todo(&home).arg("ls").output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].contains("2.") && lines[0].contains("inbox zero"));
+    assert!(lines[1].contains("1.") && lines[1].contains("buy milk"));
+
+    todo(&home).args(["unpin", "inbox"]).assert().code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [ ] inbox zero\n");
+}
+
+#[test]
+fn show_prints_one_task_in_detail() {
+    let home = home_with("- [ ] buy milk\n- [ ] (A) call mom +party due:2024-07-01 pin:1\n");
+    todo(&home).args(["show", "mom"]).assert().code(0).stdout(
+        "task     2\n\
+         state    pending\n\
+         note     call mom\n\
+         priority A\n\
+         tags     +party\n\
+         due      2024-07-01\n\
+         pinned   yes\n\
+         line     - [ ] (A) call mom +party due:2024-07-01 pin:1\n",
+    );
+
+    let output = todo(&home).args(["show", "1", "--json"]).output().unwrap();
+    let details: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(details["text"], "buy milk");
+    assert_eq!(details["done"], false);
+    assert_eq!(details["line"], "- [ ] buy milk");
+
+    todo(&home).args(["show", "3"]).assert().code(1);
+}
+
+#[test]
+fn yank_prints_the_note_without_a_clipboard() {
+    let home = home_with("- [ ] buy milk pin:1\n");
+    todo(&home)
+        .args(["yank", "1", "--with-index"])
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .assert()
+        .code(0)
+        .stdout("1. buy milk\n")
+        .stderr(predicate::str::contains("so here it is instead"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn yank_copies_with_wl_copy() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = home_with("- [ ] buy milk pin:1\n");
+    let bin = home.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let wl_copy = bin.join("wl-copy");
+    let copied = home.path().join("copied");
+    fs::write(
+        &wl_copy,
+        format!("#!/bin/sh\ncat > '{}'\n", copied.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&wl_copy, fs::Permissions::from_mode(0o755)).unwrap();
+
+    todo(&home)
+        .args(["yank", "milk"])
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+        .env("WAYLAND_DISPLAY", "wayland-0")
+        .env_remove("DISPLAY")
+        .assert()
+        .code(0)
+        .stdout("copied \"buy milk\"\n");
+    assert_eq!(fs::read_to_string(copied).unwrap(), "buy milk");
+}
+
+#[test]
+fn append_adds_to_a_note() {
+    let home = home_with("- [ ] buy milk\n- [x] review PR\n");
+    let output = todo(&home)
+        .args(["append", "2", "#412", "--no-color"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    assert!(stdout.contains("2.") && stdout.contains("review PR #412"));
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [x] review PR #412\n");
+
+    todo(&home).args(["append", "3", "x"]).assert().code(1);
+    todo(&home).args(["append", "1", ""]).assert().code(2);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [x] review PR #412\n");
+}
+
+#[test]
+fn prepend_adds_to_the_start_of_a_note() {
+    let home = home_with("- [ ] buy milk\n- [ ] review PR\n");
+    let output = todo(&home)
+        .args(["prepend", "review", "URGENT:", "--no-color"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("2.") && stdout.contains("URGENT: review PR"));
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [ ] URGENT: review PR\n");
+
+    todo(&home).args(["prepend", "3", "x"]).assert().code(1);
+    todo(&home).args(["prepend", "1", " "]).assert().code(2);
+}
+
+#[test]
+fn dup_copies_a_task() {
+    let home = home_with("- [x] (A) water plants\n- [ ] buy milk\n");
+    let output = todo(&home)
+        .args(["dup", "1", "--no-color"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("2.") && stdout.contains("(A) water plants"));
+    todo(&home).args(["dup", "milk", "--end"]).assert().code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [x] (A) water plants\n- [ ] (A) water plants\n- [ ] buy milk\n- [ ] buy milk\n"
+    );
+
+    todo(&home).args(["dup", "5"]).assert().code(1);
+}
+
+#[test]
+fn ls_sort_reorders_only_the_listing() {
+    let content =
+        "- [ ] buy milk\n- [ ] (B) call mom due:2024-07-03\n- [ ] (A) pay rent due:2024-07-01\n";
+    let home = home_with(content);
+    todo(&home)
+        .args(["ls", "--sort", "priority", "--plain"])
+        .assert()
+        .code(0)
+        .stdout("3\ttodo\t(A) pay rent due:2024-07-01\n2\ttodo\t(B) call mom due:2024-07-03\n1\ttodo\tbuy milk\n");
+    todo(&home)
+        .args(["ls", "--sort", "alpha", "--plain"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::starts_with("1\ttodo\tbuy milk\n2\t"));
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        content
+    );
+    todo(&home).args(["ls", "--sort", "size"]).assert().code(2);
+}
+
+#[test]
+fn ls_group_by_tag() {
+    let home = home_with("- [ ] deploy #backend #ops\n- [ ] buy milk\n- [x] fix login #backend\n");
+    todo(&home)
+        .args(["ls", "--group-by", "tag", "--no-color", "--all"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::starts_with(
+            "#backend — 1 pending, 1 done\n 1. ✖ deploy #backend #ops\n 3. ✓ fix login #backend [done]\n\n\
+             #ops — 1 pending\n 1. ✖ deploy #backend #ops\n\n\
+             untagged — 1 pending\n 2. ✖ buy milk\n",
+        ));
+    todo(&home)
+        .args(["ls", "--group-by", "tag", "--plain"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn ls_group_by_project() {
+    let home = home_with(
+        "- [ ] fix footer +website\n- [ ] plan trip\n- [ ] taxes +admin\n- [ ] hero image +website\n",
+    );
+    let sections = |args: &[&str]| {
+        let output = todo(&home)
+            .args(["ls", "--group-by", "project", "--no-color"])
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter(|l| !l.starts_with(' ') && !l.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        sections(&[]),
+        [
+            "+admin — 1 pending",
+            "+website — 2 pending",
+            "no project — 1 pending"
+        ]
+    );
+    assert_eq!(
+        sections(&["--by-count"]),
+        [
+            "+website — 2 pending",
+            "+admin — 1 pending",
+            "no project — 1 pending"
+        ]
+    );
+    todo(&home).args(["ls", "--by-count"]).assert().code(2);
+}
+
+#[test]
+fn snoozed_tasks_are_hidden_until_their_date() {
+    let home = home_with("- [ ] buy milk\n- [ ] file taxes\n- [ ] call mom t:2000-01-01\n");
+    todo(&home)
+        .args(["snooze", "2", "2999-01-01"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("file taxes").not())
+        .stdout(predicate::str::contains("call mom"));
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert!(content.contains("- [ ] file taxes t:2999-01-01\n"));
+
+    todo(&home)
+        .args(["ls", "--all"])
+        .assert()
+        .stdout(predicate::str::contains("snoozed until 2999-01-01"));
+    todo(&home)
+        .args(["snooze", "1", "tomorrowish"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn due_and_snooze_take_dates_in_words() {
+    let home = home_with("- [ ] pay rent\n- [ ] renew passport due:2024-09-01\n");
+    todo(&home)
+        .args(["due", "1", "tomorrow"])
+        .assert()
+        .success();
+    todo(&home)
+        .args(["due", "2", "next  Friday"])
+        .assert()
+        .success();
+    todo(&home)
+        .args(["snooze", "1", "in 2 weeks"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] pay rent due:2024-07-02 t:2024-07-15\n- [ ] renew passport due:2024-07-05\n"
+    );
+    todo(&home)
+        .args(["due", "1", "this friday"])
+        .assert()
+        .code(2)
+        .stderr(
+            "todo: invalid date 'this friday'; use 2024-07-01, today, tomorrow, friday, \
+             next friday, next week, next month, in 3 days, in 2 weeks, or 3d, 2w, 1m\n",
+        );
+}
+
+#[cfg(unix)]
+#[test]
+fn today_is_the_local_date_around_midnight() {
+    let home = home_with("- [ ] pay rent due:2024-07-01\n");
+    let due = |tz: &str, now: &str| {
+        let output = todo(&home)
+            .args(["ls", "--no-color"])
+            .env_remove("TODO_TODAY")
+            .env("TZ", tz)
+            .env("TODO_NOW", now)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // 23:59 and 00:01 in Berlin, two hours ahead of UTC in July.
+    assert!(due("Europe/Berlin", "2024-07-01T21:59:00Z").contains("due today"));
+    assert!(due("Europe/Berlin", "2024-07-01T22:01:00Z").contains("overdue 1d"));
+    // 23:59 in New York, four hours behind, is the next day in UTC.
+    assert!(due("America/New_York", "2024-07-02T03:59:00Z").contains("due today"));
+    assert!(due("America/New_York", "2024-06-30T23:59:00Z").contains("due tomorrow"));
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "timezone = \"Europe/Berlin\"\n").unwrap();
+    assert!(due("America/New_York", "2024-07-01T22:01:00Z").contains("overdue 1d"));
+    fs::write(config.join("config.toml"), "timezone = \"Mars/Olympus\"\n").unwrap();
+    todo(&home)
+        .arg("ls")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "timezone 'Mars/Olympus' isn't in the system's time zone database",
+        ));
+}
+
+#[test]
+fn sort_reorders_the_file() {
+    let content =
+        "- [ ] call mom\n- [ ] pay rent due:2024-07-03\n- [ ] file taxes due:2024-06-20\n";
+    let home = home_with(content);
+    let file = home.path().join("todo.txt");
+    todo(&home)
+        .args(["sort", "--by", "due", "--dry-run"])
+        .assert()
+        .success()
+        .stdout("would move 2 task(s) to sort the list by due\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+    todo(&home)
+        .args(["sort", "--by", "due"])
+        .assert()
+        .success()
+        .stdout("moved 2 task(s) to sort the list by due\n");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] file taxes due:2024-06-20\n- [ ] pay rent due:2024-07-03\n- [ ] call mom\n"
+    );
+    todo(&home)
+        .args(["sort", "--by", "due"])
+        .assert()
+        .success()
+        .stdout("the list is sorted by due already\n");
+    todo(&home).args(["sort", "--by", "size"]).assert().code(2);
+}
+
+#[test]
+fn cal_shows_the_month_with_its_due_tasks() {
+    let home =
+        home_with("- [ ] pay rent due:2024-07-03\n- [x] call mom due:2024-07-03\n- [ ] file taxes due:2024-08-01\n");
+    let config = home.path().join(".config").join("todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "week_start = \"sunday\"\n").unwrap();
+    todo(&home)
+        .args(["--ascii", "--no-color", "cal", "--month", "2024-07"])
+        .assert()
+        .success()
+        .stdout(
+            "+--------------------------------------------------+\n\
+             |                    July 2024                     |\n\
+             +--------------------------------------------------+\n\
+             | Su     Mo     Tu     We     Th     Fr     Sa     |\n\
+             |         1      2      3(1)   4      5      6     |\n\
+             |  7      8      9     10     11     12     13     |\n\
+             | 14     15     16     17     18     19     20     |\n\
+             | 21     22     23     24     25     26     27     |\n\
+             | 28     29     30     31                          |\n\
+             +--------------------------------------------------+\n\
+             Wed 3 Jul\n   1. pay rent due:2024-07-03\n",
+        );
+    todo(&home)
+        .args(["cal", "--month", "July"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn today_view_exit_status() {
+    let content = "- [ ] pay rent due:2000-01-01\n- [ ] buy milk\n";
+    let home = home_with(content);
+    todo(&home)
+        .arg("today")
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("Due today or overdue"))
+        .stdout(predicate::str::contains("pay rent"))
+        .stdout(predicate::str::contains("buy milk").not());
+    let after = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(after, content);
+
+    todo(&home)
+        .args(["today", "--tag", "home"])
+        .assert()
+        .code(1)
+        .stdout("");
+
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home)
+        .arg("today")
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+
+    let home =
+        home_with("- [ ] pay rent due:2000-01-01 #home\n- [ ] file report due:2000-01-01 #work\n");
+    todo(&home)
+        .args(["--plain", "today", "--tag", "work"])
+        .assert()
+        .code(0)
+        .stdout("2\ttodo\tfile report due:2000-01-01 #work\n");
+}
+
+#[test]
+fn agenda_buckets_tasks_by_due_date() {
+    let home = home_with(
+        "- [ ] pay rent due:2024-06-30\n- [ ] buy milk due:2024-07-03\n\
+         - [ ] file taxes due:2024-06-20\n- [ ] call mom t:2024-07-01\n\
+         - [ ] renew passport due:2024-07-20\n",
+    );
+    todo(&home)
+        .args(["--no-color", "agenda"])
+        .assert()
+        .code(0)
+        .stdout(
+            "Overdue\n 3. ✖ file taxes overdue 11d\n 1. ✖ pay rent overdue 1d\n\n\
+             Today\n 4. ✖ call mom\n\n\
+             Upcoming\n 2. ✖ buy milk due in 2d\n",
+        );
+    todo(&home)
+        .args(["--no-color", "agenda", "--days", "30"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains(
+            " 5. ✖ renew passport due in 19d\n",
+        ));
+
+    let home = home_with("- [ ] buy milk due:2024-07-03\n- [ ] call mom\n");
+    todo(&home)
+        .args(["--no-color", "agenda"])
+        .assert()
+        .code(1)
+        .stdout("Upcoming\n 1. ✖ buy milk due in 2d\n");
+    todo(&home)
+        .args(["agenda", "--days", "-1"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn overdue_listing_and_stats() {
+    let home = home_with(
+        "- [ ] pay rent due:2024-06-28\n- [ ] buy milk due:2024-07-01\n- [x] file taxes due:2024-06-01\n",
+    );
+    todo(&home)
+        .env("TODO_TODAY", "2024-07-01")
+        .args(["ls", "--overdue"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("pay rent").and(predicate::str::contains("overdue 3d")))
+        .stdout(predicate::str::contains("buy milk").not());
+    todo(&home)
+        .env("TODO_TODAY", "2024-07-01")
+        .arg("stats")
+        .assert()
+        .code(0)
+        .stdout("pending: 2\ndone: 1\noverdue: 1\n");
+}
+
+#[test]
+fn stats_graph_charts_checked_tasks_per_day() {
+    let home = home_with(
+        "- [x] buy milk done:2024-06-28\n- [x] call mom done:2024-07-01\n\
+         - [x] pay rent done:2024-07-01\n- [x] water plants\n- [ ] file taxes\n",
+    );
+    fs::write(
+        home.path().join("todo.done.txt"),
+        "- [x] old news done:2024-06-26\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["stats", "--graph", "--days", "7", "--no-color"])
+        .assert()
+        .success()
+        .stdout(
+            "·▄·▄··█\nTWTFSSM\n4 checked from 2024-06-25 to 2024-07-01, at most 2 on 2024-07-01\n",
+        );
+    todo(&home)
+        .args(["--ascii", "stats", "--graph", "--days", "3", "--no-color"])
+        .assert()
+        .success()
+        .stdout("..@\nSSM\n2 checked from 2024-06-29 to 2024-07-01, at most 2 on 2024-07-01\n");
+    todo(&home)
+        .args(["stats", "--graph", "--no-color"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("from 2024-06-02 to 2024-07-01"));
+}
+
+#[test]
+fn daily_tasks_show_their_streaks() {
+    let home = home_with(
+        "- [ ] stretch rec:1d\n- [x] stretch rec:1d done:2024-06-30\n- [ ] water plants\n",
+    );
+    fs::write(
+        home.path().join("todo.done.txt"),
+        "- [x] stretch rec:1d done:2024-06-29\n- [x] stretch rec:1d done:2024-06-27\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["ls", "--no-color"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            " 1. ✖ stretch rec:1d 🔥 2\n 3. ✖ water plants\n",
+        ));
+    todo(&home)
+        .args(["--ascii", "ls", "--no-color"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            " 1. [ ] stretch rec:1d streak 2\n",
+        ));
+    todo(&home)
+        .args(["stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("streak: 2 days of stretch\n"));
+    todo(&home)
+        .args(["ls", "--no-color"])
+        .env("TODO_TODAY", "2024-07-02")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(" 1. ✖ stretch rec:1d\n"));
+}
+
+#[test]
+fn next_prints_one_task() {
+    let home = home_with("- [ ] buy milk\n- [ ] (A) call mom\n");
+    let output = todo(&home).args(["next", "--no-color"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    assert!(stdout.contains("2.") && stdout.contains("(A) call mom"));
+
+    let home = home_with("- [x] buy milk\n");
+    todo(&home)
+        .arg("next")
+        .assert()
+        .code(1)
+        .stdout("nothing to do 🎉\n");
+}
+
+#[test]
+fn time_tracking() {
+    let home = home_with("- [ ] write report\n- [ ] review PR\n");
+    todo(&home)
+        .env("TODO_NOW", "2024-06-02T10:00:00Z")
+        .args(["start", "1"])
+        .assert()
+        .code(0);
+    todo(&home)
+        .env("TODO_NOW", "2024-06-02T10:45:00Z")
+        .args(["start", "2"])
+        .assert()
+        .code(0)
+        .stderr("stopped 1. write report after 45m\n");
+    todo(&home)
+        .env("TODO_NOW", "2024-06-02T12:00:00Z")
+        .args(["check", "2"])
+        .assert()
+        .code(0);
+    todo(&home)
+        .arg("stop")
+        .assert()
+        .code(1)
+        .stderr("todo: no task is started\n");
+    todo(&home)
+        .args(["stats", "--time"])
+        .assert()
+        .stdout("     45m  1. write report\n  1h 15m  2. review PR\n");
+}
+
+#[test]
+fn estimates_are_totaled_and_sorted() {
+    let home = home_with(
+        "- [ ] write report est:2h\n- [ ] fix the login bug est:3p\n\
+         - [ ] reply to Ann est:30m\n- [ ] plan est:soon\n",
+    );
+    todo(&home).args(["check", "write"]).assert().success();
+    todo(&home).arg("stats").assert().success().stdout(
+        "pending: 3\ndone: 1\noverdue: 0\n\
+             estimated pending: 30m and 3p\nestimated done this week: 2h\n",
+    );
+    todo(&home)
+        .args(["ls", "--sort", "est", "--no-color"])
+        .assert()
+        .success()
+        .stdout(
+            " 3. ✖ reply to Ann est:30m\n 2. ✖ fix the login bug est:3p\n 4. ✖ plan est:soon\n\
+             (gaps are hidden tasks; commands take the numbers as shown)\n",
+        );
+    todo(&home)
+        .args(["show", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("estimate 30m\n"));
+}
+
+#[test]
+fn limit_and_offset_page_the_listing() {
+    let content: String = (1..=12).map(|i| format!("- [ ] task {}\n", i)).collect();
+    let home = home_with(&content);
+    todo(&home)
+        .args(["ls", "--limit", "2", "--offset", "3", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 4. ✖ task 4\n 5. ✖ task 5\n… and 7 more (use --limit 0 for all)\n");
+    todo(&home)
+        .args(["ls", "--reverse", "--limit", "2", "--no-color", "--ascii"])
+        .assert()
+        .success()
+        .stdout(" 12. [ ] task 12\n 11. [ ] task 11\n... and 10 more (use --limit 0 for all)\n");
+    todo(&home)
+        .args(["ls", "--limit", "0", "--offset", "11", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 12. ✖ task 12\n");
+    todo(&home).args(["ls", "--limit", "-1"]).assert().code(2);
+}
+
+#[test]
+fn ls_filters_by_creation_date() {
+    let home =
+        home_with("- [ ] 2024-06-28 pay rent\n- [ ] 2024-01-15 renew passport\n- [ ] call mom\n");
+    let ls = |args: &[&str]| {
+        let output = todo(&home).arg("ls").args(args).output().unwrap();
+        assert!(output.status.success(), "{:?}", args);
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter(|l| !l.starts_with('('))
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ls(&["--since", "1w"]), [" 1. ✖ 2024-06-28 pay rent"]);
+    assert_eq!(
+        ls(&["--before", "2024-06-01"]),
+        [" 2. ✖ 2024-01-15 renew passport"]
+    );
+    assert_eq!(
+        ls(&["--before", "4w", "--include-undated"]),
+        [" 2. ✖ 2024-01-15 renew passport", " 3. ✖ call mom"]
+    );
+    todo(&home)
+        .args(["ls", "--since", "someday"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::starts_with(
+            "todo: invalid date 'someday'; use 2024-07-01, today, yesterday, monday,",
+        ));
+}
+
+#[test]
+fn tag_filters_combine() {
+    let home = home_with(
+        "- [ ] fix the api #backend #urgent\n- [ ] restyle #frontend #urgent\n\
+         - [ ] add an index #backend +launch\n- [ ] rewrite it all #backend #someday\n",
+    );
+    let shown = |args: &[&str]| {
+        let output = todo(&home).args(args).output().unwrap();
+        assert!(output.status.success(), "{:?}", args);
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.trim_start().split('.').next()?.parse().ok())
+            .collect::<Vec<usize>>()
+    };
+    assert_eq!(shown(&["ls", "--tag", "backend", "--tag", "urgent"]), [1]);
+    // --any-tag only joins the --tag tags; --not-tag and --project still
+    // apply to all of them.
+    let any = ["ls", "--tag", "backend", "--tag", "#urgent", "--any-tag"];
+    assert_eq!(shown(&any), [1, 2, 3, 4]);
+    assert_eq!(
+        shown(&[&any[..], &["--not-tag", "someday"]].concat()),
+        [1, 2, 3]
+    );
+    assert_eq!(shown(&[&any[..], &["--project", "launch"]].concat()), [3]);
+    assert_eq!(shown(&["search", "i", "--tag", "backend"]), [1, 3, 4]);
+    let output = todo(&home)
+        .args(["export", "--ical", "--not-tag", "backend"])
+        .output()
+        .unwrap();
+    let ical = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(ical.matches("BEGIN:VTODO").count(), 1);
+}
+
+#[test]
+fn negative_filters_leave_out_tasks() {
+    let home = home_with(
+        "- [ ] fix the api #backend\n- [ ] wait for waiting room quote #someday\n\
+         - [x] call the bank, waiting on hold #backend\n- [ ] write docs\n",
+    );
+    let shown = |args: &[&str]| {
+        let output = todo(&home).arg("ls").args(args).output().unwrap();
+        assert!(output.status.success(), "{:?}", args);
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.trim_start().split('.').next()?.parse().ok())
+            .collect::<Vec<usize>>()
+    };
+    assert_eq!(
+        shown(&["--not-tag", "someday", "--not-match", "waiting"]),
+        [1, 4]
+    );
+    // Exclusions apply after --all and --done pick the tasks, and along
+    // with the positive filters.
+    assert_eq!(shown(&["--all", "--not-match", "docs"]), [1, 2, 3]);
+    assert_eq!(
+        shown(&["--done", "--not-match", "waiting"]),
+        Vec::<usize>::new()
+    );
+    assert_eq!(
+        shown(&["--all", "--tag", "backend", "--not-match", "waiting"]),
+        [1]
+    );
+    // Leaving out what isn't there changes nothing.
+    assert_eq!(
+        shown(&["--not-tag", "nope", "--not-match", "nothing"]),
+        [1, 2, 4]
+    );
+}
+
+#[test]
+fn ls_format_formats_each_task() {
+    let home = home_with("- [ ] (A) 2024-06-21 call mom +family due:2024-07-03\n- [x] buy milk\n");
+    todo(&home)
+        .args([
+            "ls",
+            "--all",
+            "--format",
+            "{index}\t{status}\t{priority}\t{due}\t{age}",
+        ])
+        .assert()
+        .success()
+        .stdout("1\ttodo\tA\t2024-07-03\t10d\n2\tdone\t\t\t\n");
+    todo(&home)
+        .args(["ls", "--format", r"{index}\t{status}\\"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\\\n");
+    todo(&home)
+        .args(["--print0", "ls", "--format", "{{{tags}}} {note}"])
+        .assert()
+        .success()
+        .stdout("{+family} (A) 2024-06-21 call mom +family due:2024-07-03\0");
+    todo(&home)
+        .args(["ls", "--format", "{index} {title}"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::starts_with(
+            "todo: unknown placeholder '{title}' (available: index, status,",
+        ));
+}
+
+#[test]
+fn ls_age_shows_how_old_tasks_are() {
+    let home =
+        home_with("- [ ] 2024-06-28 buy milk\n- [ ] (A) 2024-05-01 call mom\n- [ ] water plants\n");
+    todo(&home)
+        .args(["ls", "--age", "--no-color"])
+        .assert()
+        .success()
+        .stdout(
+            " 1. ✖ 2024-06-28 buy milk 3d\n 2. ✖ (A) 2024-05-01 call mom 8w\n 3. ✖ water plants –\n",
+        );
+    let output = todo(&home)
+        .args(["ls", "--age", "--color", "always"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(" \u{1b}[2m3d\u{1b}[0m\n"), "{}", stdout);
+    assert!(stdout.contains(" \u{1b}[33m8w\u{1b}[0m\n"), "{}", stdout);
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "stale_days = 90\n").unwrap();
+    let output = todo(&home)
+        .args(["ls", "--age", "--color", "always"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(" \u{1b}[2m8w\u{1b}[0m\n"), "{}", stdout);
+}
+
+#[test]
+fn auto_archive_after_moves_old_checked_tasks() {
+    let content = "- [x] file taxes done:2024-06-16\n- [ ] buy milk\n\
+                   - [x] water plants done:2024-06-17\n- [x] call mom\n";
+    let home = home_with(content);
+    let archive = home.path().join("todo.done.txt");
+    todo(&home).args(["ls"]).assert().success();
+    assert!(!archive.exists());
+    todo(&home).args(["autoclean"]).assert().code(2);
+
+    todo(&home)
+        .args(["--dry-run", "autoclean", "--after", "14d"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("archived 1 task into "))
+        .stderr("todo: dry run, nothing was saved; would archive 1 task\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        content
+    );
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "auto_archive_after = \"14d\"\n").unwrap();
+    todo(&home)
+        .args(["-v", "ls"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ buy milk\n")
+        .stderr(predicate::str::contains(
+            "archived 1 task(s) checked before 2024-06-17 into ",
+        ));
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk\n- [x] water plants done:2024-06-17\n- [x] call mom\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&archive).unwrap(),
+        "- [x] file taxes done:2024-06-16\n"
+    );
+    todo(&home)
+        .args(["autoclean"])
+        .assert()
+        .success()
+        .stdout("nothing to archive\n");
+}
+
+#[test]
+fn zero_indexed_numbers_from_zero() {
+    for (flag, first, second) in [(None, "1", "2"), (Some("--zero-indexed"), "0", "1")] {
+        let home = home_with("- [ ] buy milk\n- [ ] call mom\n");
+        let todo = || {
+            let mut cmd = todo(&home);
+            cmd.args(flag);
+            cmd
+        };
+        todo()
+            .args(["--plain", "ls"])
+            .assert()
+            .success()
+            .stdout(format!(
+                "{}\ttodo\tbuy milk\n{}\ttodo\tcall mom\n",
+                first, second
+            ));
+        todo().args(["check", second]).assert().success();
+        todo()
+            .args(["--plain", "ls", "--all"])
+            .assert()
+            .success()
+            .stdout(format!(
+                "{}\ttodo\tbuy milk\n{}\tdone\tcall mom\n",
+                first, second
+            ));
+        todo().args(["check", "5"]).assert().code(1).stderr(format!(
+            "todo: no task at index 5 (valid: {}-{})\n",
+            first, second
+        ));
+    }
+    let home = home_with("- [ ] buy milk\n");
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "zero_indexed = true\n").unwrap();
+    todo(&home)
+        .args(["--plain", "ls"])
+        .assert()
+        .success()
+        .stdout("0\ttodo\tbuy milk\n");
+}
+
+#[test]
+fn last_selects_the_last_pending_task() {
+    let home = home_with("- [ ] buy milk\n- [x] call mom\n");
+    todo(&home).arg("water plants").assert().success();
+    todo(&home)
+        .args(["append", "--last", "and roses"])
+        .assert()
+        .success()
+        .stdout(" 3. ✖ water plants and roses\n");
+    todo(&home)
+        .args(["edit", "--last", "water the roses"])
+        .assert()
+        .success()
+        .stdout(" 3. ✖ water the roses\n");
+    todo(&home)
+        .args(["edit", "--last", "2", "call dad"])
+        .assert()
+        .code(2)
+        .stderr("todo: --last takes the place of the index\n");
+    todo(&home).args(["check", "--last"]).assert().success();
+    todo(&home).args(["remove", "--last"]).assert().success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [x] call mom\n- [x] water the roses done:2024-07-01\n"
+    );
+    todo(&home).args(["check", "--last", "2"]).assert().code(2);
+    todo(&home)
+        .args(["prepend", "--last", "2", "today"])
+        .assert()
+        .code(2)
+        .stderr("todo: --last takes the place of the index\n");
+    todo(&home)
+        .args(["check", "--last"])
+        .assert()
+        .code(1)
+        .stderr("todo: there are no pending tasks\n");
+}
+
+#[test]
+fn pop_checks_the_last_pending_task() {
+    let home = home_with("- [ ] buy milk\n- [ ] call mom\n- [x] water plants\n");
+    todo(&home)
+        .arg("pop")
+        .assert()
+        .success()
+        .stdout("call mom\n");
+    todo(&home)
+        .args(["pop", "--remove"])
+        .assert()
+        .success()
+        .stdout("buy milk\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [x] call mom done:2024-07-01\n- [x] water plants\n"
+    );
+    todo(&home)
+        .arg("pop")
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+    let empty = home_with("");
+    todo(&empty)
+        .arg("pop")
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn random_chooses_a_pending_task() {
+    let home = home_with("- [ ] a #home\n- [x] b #home\n- [ ] c t:2099-01-01 #home\n- [ ] d\n");
+    let chosen = |seed: &str| {
+        let output = todo(&home)
+            .args(["--no-color", "random", "--seed", seed])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(chosen("42"), chosen("42"));
+    let mut seen: Vec<String> = (0..20).map(|seed| chosen(&seed.to_string())).collect();
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen, [" 1. ✖ a #home\n", " 4. ✖ d\n"]);
+
+    todo(&home)
+        .args(["--no-color", "random", "--tag", "home", "--check"])
+        .assert()
+        .success()
+        .stdout(" 1. ✓ a #home [done]\n");
+    assert!(fs::read_to_string(home.path().join("todo.txt"))
+        .unwrap()
+        .starts_with("- [x] a #home done:2024-07-01\n"));
+    todo(&home)
+        .args(["random", "--tag", "home"])
+        .assert()
+        .code(1)
+        .stdout("no pending task to choose from\n");
+    todo(&home).args(["random", "--seed", "x"]).assert().code(2);
+}
+
+#[test]
+fn bump_and_drop_change_priorities() {
+    let home = home_with("- [ ] call mom\n- [ ] (B) pay rent\n- [ ] (A) file taxes\n");
+    let file = || fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    todo(&home)
+        .args(["bump", "1", "2", "3", "1"])
+        .assert()
+        .success()
+        .stdout(
+            "1. call mom: no priority → (C)\n2. pay rent: (B) → (A)\n\
+             3. file taxes: already (A)\n",
+        );
+    assert_eq!(
+        file(),
+        "- [ ] (C) call mom\n- [ ] (A) pay rent\n- [ ] (A) file taxes\n"
+    );
+    todo(&home)
+        .args(["--ascii", "drop", "call", "2"])
+        .assert()
+        .success()
+        .stdout("1. call mom: (C) -> no priority\n2. pay rent: (A) -> (B)\n");
+    todo(&home)
+        .args(["drop", "1"])
+        .assert()
+        .success()
+        .stdout("1. call mom: no priority to drop\n");
+    assert_eq!(
+        file(),
+        "- [ ] call mom\n- [ ] (B) pay rent\n- [ ] (A) file taxes\n"
+    );
+    todo(&home).args(["bump", "9"]).assert().code(1);
+}
+
+#[test]
+fn top_and_bottom_reorder_tasks() {
+    let home = home_with("- [ ] a\n- [ ] b\n- [ ] c\n- [ ] d\n- [ ] e\n");
+    let file = || fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    todo(&home)
+        .args(["top", "4", "2"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ b\n 2. ✖ d\n 3. ✖ a\n 4. ✖ c\n 5. ✖ e\n");
+    todo(&home).args(["bottom", "1", "3"]).assert().success();
+    assert_eq!(file(), "- [ ] d\n- [ ] c\n- [ ] e\n- [ ] b\n- [ ] a\n");
+    todo(&home).args(["top", "--match", "e"]).assert().success();
+    assert_eq!(file(), "- [ ] e\n- [ ] d\n- [ ] c\n- [ ] b\n- [ ] a\n");
+    todo(&home).args(["bottom", "9"]).assert().code(1);
+    todo(&home).args(["top", "x"]).assert().code(2);
+}
+
+#[test]
+fn due_dates_are_listed_relative_to_today() {
+    let content = "- [ ] pay rent due:2024-06-28\n- [ ] call mom due:2024-07-01\n\
+                   - [ ] buy milk due:2024-07-02\n- [ ] file taxes due:2024-07-06 #home\n";
+    let home = home_with(content);
+    let ls = |args: &[&str]| {
+        let output = todo(&home)
+            .env("TODO_TODAY", "2024-07-01")
+            .args(["ls", "--no-color"])
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        ls(&[]).lines().collect::<Vec<_>>(),
+        [
+            " 1. ✖ pay rent overdue 3d",
+            " 2. ✖ call mom due today",
+            " 3. ✖ buy milk due tomorrow",
+            " 4. ✖ file taxes due in 5d #home",
+        ]
+    );
+    assert_eq!(
+        ls(&["--absolute-dates"]).lines().collect::<Vec<_>>(),
+        [
+            " 1. ✖ pay rent due:2024-06-28 (+3d)",
+            " 2. ✖ call mom due:2024-07-01",
+            " 3. ✖ buy milk due:2024-07-02",
+            " 4. ✖ file taxes due:2024-07-06 #home",
+        ]
+    );
+    assert!(ls(&["--plain"]).contains("pay rent due:2024-06-28"));
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        content
+    );
+}
+
+#[test]
+fn report_summarizes_a_period() {
+    let home = home_with(
+        "- [x] (A) 2024-06-28 ship it done:2024-07-02\n- [x] from an old file\n\
+         - [ ] 2024-07-01 fresh\n- [ ] undated\n",
+    );
+    let report = |args: &[&str]| {
+        todo(&home)
+            .env("TODO_TODAY", "2024-07-03")
+            .arg("report")
+            .args(args)
+            .assert()
+    };
+    report(&["--since", "monday"]).code(0).stdout(
+        "2024-07-01 to 2024-07-03\n\
+         completed   1 (and 1 done on an unknown date)\n\
+         added       1 (and 2 added on an unknown date)\n\
+         pending     2\n  \
+         under a week old  1\n  \
+         unknown date      1\n\
+         completion  33%\n",
+    );
+    let output = report(&["--since", "2024-07-03", "--json"]).code(0);
+    let json: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+    assert_eq!(json["completed"], 0);
+    assert_eq!(json["pending"]["unknown_date"], 1);
+
+    report(&["--since", "soon"]).code(2);
+    report(&["--since", "today", "--until", "2d"]).code(2);
+}
+
+#[test]
+fn remind_exit_status() {
+    let home = home_with("- [ ] pay rent due:2024-07-01\n- [ ] buy milk due:2024-07-03\n");
+    let remind = |within: &[&str]| {
+        let mut cmd = todo(&home);
+        cmd.env("TODO_TODAY", "2024-06-30")
+            .env("DBUS_SESSION_BUS_ADDRESS", "unix:path=/nonexistent")
+            .arg("remind")
+            .args(within);
+        cmd
+    };
+    remind(&[]).assert().code(1).stdout("");
+    remind(&["--within", "1d"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("1. pay rent").or(predicate::str::is_empty()));
+    remind(&["--within", "soon"]).assert().code(2);
+}
+
+#[cfg(not(feature = "notify"))]
+#[test]
+fn remind_prints_quietly_without_notifications() {
+    let home = home_with("- [ ] pay rent due:2024-07-01\n");
+    todo(&home)
+        .arg("remind")
+        .assert()
+        .code(0)
+        .stdout("1 task due\n1. pay rent due:2024-07-01\n")
+        .stderr("");
+}
+
+#[test]
+fn named_lists() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home)
+        .args(["--list", "work", "add", "review PR"])
+        .assert()
+        .code(0);
+    todo(&home)
+        .args(["check", "1", "--list", "work"])
+        .assert()
+        .code(0);
+    let work = home.path().join(".local/share/todo/work.txt");
+    assert_eq!(
+        fs::read_to_string(work).unwrap(),
+        "- [x] review PR done:2024-07-01\n"
+    );
+    let default = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(default, "- [ ] buy milk\n");
+
+    fs::create_dir_all(home.path().join(".config/todo")).unwrap();
+    fs::write(
+        home.path().join(".config/todo/config.toml"),
+        "default_list = \"work\"\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["ls", "--all"])
+        .assert()
+        .stdout(predicate::str::contains("review PR"));
+
+    todo(&home)
+        .args(["--list", "../etc", "ls"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid list name"));
+}
+
+#[test]
+fn lists_reports_counts_and_invalid_files() {
+    let home = home_with("- [ ] buy milk\n- [x] call mom\n");
+    let dir = home.path().join(".local/share/todo");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("work.txt"), "- [ ] review PR\n").unwrap();
+    fs::write(dir.join("broken.txt"), "not a task\n").unwrap();
+    fs::write(dir.join("notes.md"), "ignored\n").unwrap();
+    fs::write(dir.join("my list.txt"), "- [ ] not a list name\n").unwrap();
+
+    let output = todo(&home).args(["lists", "--json"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let lists: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let summary: Vec<_> = lists
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| {
+            (
+                l["name"].as_str().unwrap(),
+                l["default"].as_bool().unwrap(),
+                l["valid"].as_bool().unwrap(),
+                l["pending"].as_u64(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("default", true, true, Some(1)),
+            ("broken", false, false, None),
+            ("work", false, true, Some(1)),
+        ]
+    );
+
+    todo(&home)
+        .arg("lists")
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("invalid"))
+        .stdout(predicate::str::contains("1 pending, 1 done"));
+}
+
+#[test]
+fn move_between_lists() {
+    let home = home_with("- [ ] buy milk\n- [x] review PR due:2024-07-01\n");
+    todo(&home)
+        .args(["move", "2", "--to", "work"])
+        .assert()
+        .code(0)
+        .stdout("moved to 'work' as 1. review PR due:2024-07-01\n");
+    let work = home.path().join(".local/share/todo/work.txt");
+    assert_eq!(
+        fs::read_to_string(work).unwrap(),
+        "- [x] review PR due:2024-07-01\n"
+    );
+    let default = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(default, "- [ ] buy milk\n");
+
+    todo(&home)
+        .args(["move", "1", "--to", "default"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn templates_from_config() {
+    let home = home_with("- [ ] buy milk\n");
+    fs::create_dir_all(home.path().join(".config/todo")).unwrap();
+    fs::write(
+        home.path().join(".config/todo/config.toml"),
+        "[templates]\nweekly = [\"review inbox\", \"plan week due:{date}\"]\n",
+    )
+    .unwrap();
+
+    todo(&home)
+        .arg("template")
+        .assert()
+        .code(0)
+        .stdout("weekly (2 tasks)\n");
+    todo(&home)
+        .env("TODO_TODAY", "2024-07-01")
+        .args(["template", "weekly"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::starts_with(
+            "added 2 task(s) from 'weekly'\n",
+        ));
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [ ] buy milk\n- [ ] review inbox\n- [ ] plan week due:2024-07-01\n"
+    );
+    todo(&home)
+        .args(["template", "daily"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("available: weekly"));
+}
+
+#[test]
+fn import_taskwarrior() {
+    let home = home_with("- [ ] buy milk\n");
+    let export = home.path().join("export.json");
+    fs::write(
+        &export,
+        r#"[{"status": "pending", "description": "buy milk"},
+            {"status": "completed", "description": "file taxes", "priority": "M"},
+            {"status": "deleted", "description": "old"}]"#,
+    )
+    .unwrap();
+
+    todo(&home)
+        .args(["import", "--taskwarrior", "--dry-run"])
+        .arg(&export)
+        .assert()
+        .code(0)
+        .stdout(
+            "would add - [x] (B) file taxes\n1 imported, skipped 1 already present, 1 deleted\n",
+        );
+    todo(&home)
+        .args(["import", "--taskwarrior"])
+        .arg(&export)
+        .assert()
+        .code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [x] (B) file taxes\n");
+
+    fs::write(&export, "{ not json").unwrap();
+    todo(&home)
+        .args(["import", "--taskwarrior"])
+        .arg(&export)
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("cannot import"));
+}
+
+#[test]
+fn import_markdown() {
+    let home = home_with("- [ ] buy milk\n");
+    let notes = home.path().join("meeting.md");
+    fs::write(
+        &notes,
+        "# Standup\n\n- [ ] buy milk\n- actions:\n  - [x] send minutes\n  - [ ] book room\n",
+    )
+    .unwrap();
+
+    todo(&home)
+        .args(["import", "--dry-run"])
+        .arg(&notes)
+        .assert()
+        .code(0)
+        .stdout(
+            "would add - [x] send minutes\nwould add - [ ] book room\n\
+             2 imported, skipped 1 already present\n",
+        );
+    todo(&home).arg("import").arg(&notes).assert().code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [ ] buy milk\n- [x] send minutes\n- [ ] book room\n"
+    );
+
+    let notes = home.path().join("notes.txt");
+    fs::write(&notes, "- [ ] call mom\n").unwrap();
+    todo(&home)
+        .arg("import")
+        .arg(&notes)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--markdown"));
+    todo(&home)
+        .args(["import", "--markdown"])
+        .arg(&notes)
+        .assert()
+        .code(0)
+        .stdout("added - [ ] call mom\n1 imported, skipped 0 already present\n");
+}
+
+#[test]
+fn import_merge_skips_tasks_the_list_has() {
+    let home = home_with("- [ ] buy milk\n- [ ] (A) pay rent due:2024-07-01\n");
+    let other = home.path().join("laptop.txt");
+    fs::write(
+        &other,
+        "- [ ] buy milk\n- [x] pay rent done:2024-06-30\n- [ ] call mom\n- [ ] buy milk\n",
+    )
+    .unwrap();
+
+    todo(&home)
+        .args(["import", "--merge", "--dry-run"])
+        .arg(&other)
+        .assert()
+        .code(0)
+        .stdout(
+            "would add 3. call mom\nwould check 2. (A) pay rent due:2024-07-01\n\
+             1 added, 2 skipped, 1 upgraded\n",
+        );
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk\n- [ ] (A) pay rent due:2024-07-01\n"
+    );
+    todo(&home)
+        .args(["import", "--merge"])
+        .arg(&other)
+        .assert()
+        .code(0)
+        .stdout(
+            "added 3. call mom\nchecked 2. (A) pay rent due:2024-07-01\n\
+             1 added, 2 skipped, 1 upgraded\n",
+        );
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk\n- [x] (A) pay rent due:2024-07-01 done:2024-06-30\n- [ ] call mom\n"
+    );
+    todo(&home)
+        .args(["import", "--merge"])
+        .arg(&other)
+        .assert()
+        .code(0)
+        .stdout("0 added, 4 skipped, 0 upgraded\n");
+}
+
+#[test]
+fn export_ical() {
+    let home = home_with("- [ ] pay rent due:2024-07-01\n- [x] buy milk, eggs\n");
+    let output = todo(&home).args(["export", "--ical"]).output().unwrap();
+    let ical = String::from_utf8(output.stdout).unwrap();
+    assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert_eq!(ical.matches("BEGIN:VTODO").count(), 2);
+    assert!(ical.contains("SUMMARY:buy milk\\, eggs\r\nSTATUS:COMPLETED\r\n"));
+    assert!(ical.contains("DUE;VALUE=DATE:20240701\r\n"));
+
+    let again = todo(&home).args(["export", "--ical"]).output().unwrap();
+    let uids = |s: &str| -> Vec<String> {
+        s.lines()
+            .filter(|l| l.starts_with("UID:"))
+            .map(|l| l.to_owned())
+            .collect()
+    };
+    assert_eq!(uids(&ical), uids(&String::from_utf8(again.stdout).unwrap()));
+
+    let due_only = todo(&home)
+        .args(["export", "--ical", "--due-only"])
+        .output()
+        .unwrap();
+    let due_only = String::from_utf8(due_only.stdout).unwrap();
+    assert_eq!(due_only.matches("BEGIN:VTODO").count(), 1);
+}
+
+#[test]
+fn export_writes_matching_tasks_to_a_new_list() {
+    let content = "- [ ] fix login #work\n  - [x] reproduce\n- [ ] buy milk\n\
+                   - [x] ship release #work\n- [ ] review docs #work\n";
+    let home = home_with(content);
+    let file = home.path().join("todo.txt");
+    let handoff = home.path().join("handoff.txt");
+    todo(&home)
+        .args(["export", "--tag", "work", "--pending", "--output", "-"])
+        .assert()
+        .success()
+        .stdout("- [ ] fix login #work\n  - [x] reproduce\n- [ ] review docs #work\n");
+    todo(&home)
+        .args(["export", "--tag", "work", "--pending", "--move", "--output"])
+        .arg(&handoff)
+        .assert()
+        .success()
+        .stdout(format!("exported 2 task(s) to {}\n", handoff.display()));
+    assert_eq!(
+        fs::read_to_string(&handoff).unwrap(),
+        "- [ ] fix login #work\n  - [x] reproduce\n- [ ] review docs #work\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] buy milk\n- [x] ship release #work\n"
+    );
+    todo(&home)
+        .args(["--no-color", "ls"])
+        .env("TODO_FILE", &handoff)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(" 2. ✖ review docs #work\n"));
+
+    todo(&home)
+        .args(["export", "--done", "--output"])
+        .arg(&handoff)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--force"));
+    todo(&home)
+        .args(["export", "--done", "--force", "--output"])
+        .arg(&handoff)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&handoff).unwrap(),
+        "- [x] ship release #work\n"
+    );
+    todo(&home)
+        .args(["export", "--tag", "home", "--output", "-"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn progress_bar_without_color() {
+    let home = home_with("- [x] buy milk\n- [ ] call mom\n");
+    todo(&home)
+        .args(["ls", "--all", "--no-color"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("] 1/2 done (50%)"))
+        .stdout(predicate::str::contains("\x1b[").not());
+    todo(&home)
+        .args(["ls", "--progress"])
+        .env("NO_COLOR", "1")
+        .assert()
+        .stdout(predicate::str::contains("buy milk").not())
+        .stdout(predicate::str::contains(
+            "\n[████████████░░░░░░░░░░░] 1/2 done (50%)\n",
+        ))
+        .stdout(predicate::str::contains("\x1b[").not());
+}
+
+#[test]
+fn priorities_stay_readable_without_color() {
+    let home = home_with("- [ ] (A) file taxes\n- [ ] (C) water plants\n- [ ] call mom\n");
+    todo(&home)
+        .args(["ls", "--no-color"])
+        .assert()
+        .code(0)
+        .stdout(" 1. ✖ (A) file taxes\n 2. ✖ (C) water plants\n 3. ✖ call mom\n");
+}
+
+#[test]
+fn long_notes_wrap_under_the_note() {
+    let home = home_with("- [ ] call the plumber about the leaking kitchen sink\n");
+    todo(&home)
+        .args(["ls", "--no-color", "--width", "24"])
+        .assert()
+        .code(0)
+        .stdout(" 1. ✖ call the plumber\n      about the leaking\n      kitchen sink\n");
+    todo(&home)
+        .args(["ls", "--no-color"])
+        .assert()
+        .stdout(" 1. ✖ call the plumber about the leaking kitchen sink\n");
+}
+
+#[test]
+fn piped_output_is_not_paged() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home)
+        .arg("ls")
+        .env("PAGER", "true")
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("buy milk"));
+    todo(&home)
+        .args(["ls", "--no-pager"])
+        .assert()
+        .stdout(predicate::str::contains("buy milk"));
+}
+
+/// Runs `todo` in `home` with `args` and its stdout a pipe nobody reads,
+/// as `todo ... | head -0` leaves it, returning its exit code and stderr.
+fn with_closed_stdout(home: &TempDir, args: &[&str]) -> (Option<i32>, String) {
+    let todo = todo(home);
+    let mut cmd = std::process::Command::new(todo.get_program());
+    for (key, value) in todo.get_envs() {
+        match value {
+            Some(value) => cmd.env(key, value),
+            None => cmd.env_remove(key),
+        };
+    }
+    let (reader, writer) = std::io::pipe().unwrap();
+    drop(reader);
+    let output = cmd.args(args).stdout(writer).output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn a_closed_stdout_ends_the_output_quietly() {
+    let home = home_with("- [ ] buy milk\n- [x] call mom done:2024-07-01\n");
+    for args in [&["ls"][..], &["stats"], &["lists"], &["done-today"]] {
+        assert_eq!(with_closed_stdout(&home, args), (Some(0), String::new()));
+    }
+    // The command still does its work.
+    assert_eq!(
+        with_closed_stdout(&home, &["check", "1"]),
+        (Some(0), String::new())
+    );
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [x] buy milk done:2024-07-01\n- [x] call mom done:2024-07-01\n"
+    );
+}
+
+#[test]
+fn ical_export_to_a_closed_stdout_ends_quietly() {
+    let home = home_with("- [ ] pay rent due:2024-07-05\n");
+    assert_eq!(
+        with_closed_stdout(&home, &["export", "--ical"]),
+        (Some(0), String::new())
+    );
+}
+
+#[test]
+fn show_json_to_a_closed_stdout_ends_quietly() {
+    let home = home_with("- [ ] buy milk\n");
+    assert_eq!(
+        with_closed_stdout(&home, &["show", "1", "--json"]),
+        (Some(0), String::new())
+    );
+}
+
+#[test]
+fn export_to_a_closed_stdout_ends_quietly() {
+    let home = home_with("- [ ] buy milk\n");
+    assert_eq!(
+        with_closed_stdout(&home, &["export", "--output", "-"]),
+        (Some(0), String::new())
+    );
+}
+
+#[test]
+fn indices_are_right_aligned() {
+    let content: String = (1..=105).map(|i| format!("- [ ] task {}\n", i)).collect();
+    let home = home_with(&content);
+    let output = todo(&home).args(["ls", "--no-color"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 105);
+    assert_eq!(lines[0], "   1. ✖ task 1");
+    assert_eq!(lines[9], "  10. ✖ task 10");
+    assert_eq!(lines[104], " 105. ✖ task 105");
+}
+
+#[test]
+fn wide_characters_keep_columns_aligned() {
+    let mut content = String::from(
+        "- [ ] buy milk and some eggs\n- [ ] 買い物に行って牛乳を買う\n- [ ] 🎉 party 🎉 with 🎉🎉 friends\n",
+    );
+    for i in 4..=10 {
+        content.push_str(&format!("- [x] filler {}\n", i));
+    }
+    content.push_str("- [ ] 🎉🎉🎉🎉🎉🎉🎉🎉\n");
+    let home = home_with(&content);
+    todo(&home)
+        .args(["ls", "--no-color", "--width", "20"])
+        .assert()
+        .code(0)
+        .stdout(concat!(
+            "  1. ✖ buy milk and\n",
+            "       some eggs\n",
+            "  2. ✖ 買い物に行っ\n",
+            "       て牛乳を買う\n",
+            "  3. ✖ 🎉 party 🎉\n",
+            "       with 🎉🎉\n",
+            "       friends\n",
+            " 11. ✖ 🎉🎉🎉🎉🎉🎉\n",
+            "       🎉🎉\n",
+            "(gaps are hidden tasks; commands take the numbers as shown)\n",
+        ));
+}
+
+#[test]
+fn colors_only_go_to_a_terminal_unless_forced() {
+    let home = home_with("- [ ] buy milk\n");
+    let ls = |args: &[&str], no_color: Option<&str>| -> String {
+        let mut cmd = todo(&home);
+        cmd.arg("ls").args(args);
+        match no_color {
+            Some(v) => cmd.env("NO_COLOR", v),
+            None => cmd.env_remove("NO_COLOR"),
+        };
+        String::from_utf8(cmd.output().unwrap().stdout).unwrap()
+    };
+    // Stdout is a pipe here, so auto means no colors.
+    assert_eq!(ls(&[], None), " 1. ✖ buy milk\n");
+    assert_eq!(ls(&["--color", "auto"], None), " 1. ✖ buy milk\n");
+    // --color always beats both the pipe and NO_COLOR.
+    assert!(ls(&["--color", "always"], None).contains("\x1b["));
+    assert!(ls(&["--color", "always"], Some("1")).contains("\x1b["));
+    assert!(!ls(&["--color", "never"], None).contains("\x1b["));
+    todo(&home)
+        .args(["ls", "--color", "always", "--no-color"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn done_tasks_are_struck_through() {
+    let home = home_with("- [x] buy milk\n");
+    let ls = |term: &str| -> String {
+        let output = todo(&home)
+            .args(["ls", "--all", "--color", "always"])
+            .env("TERM", term)
+            .env_remove("NO_COLOR")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(ls("xterm-256color").contains("\x1b[2;9mbuy milk\x1b[0m"));
+    assert!(ls("linux").contains("\x1b[2mbuy milk\x1b[0m"));
+    todo(&home)
+        .args(["ls", "--all", "--no-color"])
+        .assert()
+        .stdout(predicate::str::starts_with(" 1. ✓ buy milk [done]\n"));
+}
+
+#[test]
+fn symbol_presets() {
+    let home = home_with("- [x] buy milk\n- [ ] pin:1 call mom\n");
+    todo(&home)
+        .args(["ls", "--all", "--no-color", "--symbols", "ascii"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::starts_with(
+            " 2. [ ] * call mom\n 1. [x] buy milk [done]\n",
+        ));
+    todo(&home)
+        .args(["ls", "--no-color", "--symbols", "minimal"])
+        .assert()
+        .stdout(" 2. ★ call mom\n(gaps are hidden tasks; commands take the numbers as shown)\n");
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "symbols = \"ascii\"\n").unwrap();
+    todo(&home).args(["ls", "--no-color"]).assert().stdout(
+        " 2. [ ] * call mom\n(gaps are hidden tasks; commands take the numbers as shown)\n",
+    );
+    todo(&home)
+        .args(["ls", "--symbols", "emoji"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn plain_output_format() {
+    let home =
+        home_with("- [x] buy milk\n- [ ] (A) call mom due:2000-01-01\n- [ ] pin:1 water plants\n");
+    todo(&home)
+        .args(["ls", "--plain"])
+        .assert()
+        .code(0)
+        .stdout("3\ttodo\twater plants\n2\ttodo\t(A) call mom due:2000-01-01\n");
+    todo(&home)
+        .args(["ls", "--all", "--plain"])
+        .assert()
+        .stdout("3\ttodo\twater plants\n1\tdone\tbuy milk\n2\ttodo\t(A) call mom due:2000-01-01\n");
+    todo(&home)
+        .args(["today", "--plain"])
+        .assert()
+        .stdout("2\ttodo\t(A) call mom due:2000-01-01\n3\ttodo\twater plants\n");
+}
+
+#[test]
+fn print0_separates_tasks_with_nul() {
+    let home = home_with("- [ ] buy milk\n- [x] call mom\n");
+    todo(&home)
+        .args(["ls", "--all", "--plain", "-0"])
+        .assert()
+        .code(0)
+        .stdout("1\ttodo\tbuy milk\x002\tdone\tcall mom\x00");
+    todo(&home)
+        .args(["ls", "--print0"])
+        .assert()
+        .stdout("1\ttodo\tbuy milk\x00");
+}
+
+#[test]
+fn count_matching_tasks() {
+    let home =
+        home_with("- [ ] pay rent #home\n- [x] buy milk #home\n- [ ] call mom due:2000-01-01\n");
+    let count = |args: &[&str]| {
+        let output = todo(&home)
+            .arg("ls")
+            .args(args)
+            .arg("--count")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(count(&[]), "2\n");
+    assert_eq!(count(&["--all"]), "3\n");
+    assert_eq!(count(&["--done"]), "1\n");
+    assert_eq!(count(&["--all", "--tag", "home"]), "2\n");
+    assert_eq!(count(&["--due", "2000-01-31"]), "1\n");
+    assert_eq!(count(&["--tag", "work"]), "0\n");
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [ ] pay rent #home\n- [x] buy milk #home\n- [ ] call mom due:2000-01-01\n"
+    );
+}
+
+#[test]
+fn search_highlights_matches() {
+    let home = home_with("- [ ] buy Milk and milk\n- [ ] call mom\n- [x] milk the cow\n");
+    todo(&home)
+        .args(["search", "milk", "--color", "always"])
+        .env_remove("NO_COLOR")
+        .assert()
+        .code(0)
+        .stdout(
+            " \x1b[2m1.\x1b[0m \x1b[31m✖\x1b[0m buy \x1b[7mMilk\x1b[0m and \x1b[7mmilk\x1b[0m\n",
+        );
+    todo(&home)
+        .args(["search", "milk", "--plain"])
+        .assert()
+        .stdout("1\ttodo\tbuy Milk and milk\n");
+    todo(&home).args(["search", "tea"]).assert().code(1);
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn search_with_regex() {
+    let home = home_with("- [ ] buy Milk and milk\n- [ ] call mom\n- [x] milk the cow\n");
+    todo(&home)
+        .args(["search", "--all", "--no-color", "-e", "m(il|o)"])
+        .assert()
+        .stdout(" 1. ✖ buy Milk and milk\n 2. ✖ call mom\n 3. ✓ milk the cow [done]\n");
+    todo(&home).args(["search", "-e", "("]).assert().code(2);
+}
+
+#[test]
+fn index_out_of_range() {
+    let home = home_with("");
+    todo(&home)
+        .args(["check", "1"])
+        .assert()
+        .code(1)
+        .stderr("todo: no task at index 1 (the list is empty)\n");
+
+    let home = home_with("- [ ] buy milk\n- [ ] call mom\n");
+    for index in ["0", "3"] {
+        for action in ["check", "undo", "remove"] {
+            todo(&home)
+                .args([action, index])
+                .assert()
+                .code(1)
+                .stderr(format!("todo: no task at index {} (valid: 1-2)\n", index));
+        }
+    }
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [ ] call mom\n");
+}
+
+#[test]
+fn skip_invalid_lines() {
+    let home = home_with("- [ ] buy milk\n- [ ] call mom\nTODO: fix later\n- [ ] pay rent\n");
+    todo(&home)
+        .arg("ls")
+        .assert()
+        .code(3)
+        .stderr("todo: line 3 isn't a task: \"TODO: fix later\"\n");
+
+    todo(&home)
+        .args(["--skip-invalid", "check", "3"])
+        .assert()
+        .code(0)
+        .stderr("todo: skipping line 3, which isn't a task: \"TODO: fix later\"\n");
+    todo(&home)
+        .args(["add", "water plants", "--skip-invalid"])
+        .assert()
+        .code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [ ] buy milk\n- [ ] call mom\nTODO: fix later\n- [x] pay rent done:2024-07-01\n- [ ] water plants\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn unwritable_list_fails_to_save() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = home_with("- [ ] buy milk\n");
+    let content = || fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    // A directory in the way of the temporary file fails the save even for
+    // root, whom permissions don't bind.
+    let tmp = home.path().join(".todo.txt.tmp");
+    fs::create_dir(&tmp).unwrap();
+    todo(&home)
+        .args(["check", "1"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::starts_with(
+            "todo: could not save ~/todo.txt: ",
+        ));
+    assert_eq!(content(), "- [ ] buy milk\n");
+    fs::remove_dir(&tmp).unwrap();
+
+    // Create the lock file while the directory is still writable.
+    fs::write(home.path().join(".todo.txt.lock"), "").unwrap();
+    fs::set_permissions(home.path(), fs::Permissions::from_mode(0o555)).unwrap();
+    let writable = fs::write(home.path().join("probe"), "").is_ok();
+    if writable {
+        fs::set_permissions(home.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        eprintln!("skipping the read-only directory, which doesn't bind this user");
+        return;
+    }
+    let assert = todo(&home).args(["check", "1"]).assert();
+    fs::set_permissions(home.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    assert.code(3).stderr(predicate::str::contains(
+        "could not save ~/todo.txt: permission denied",
+    ));
+    assert_eq!(content(), "- [ ] buy milk\n");
+}
+
+#[test]
+fn reading_commands_dont_wait_for_the_lock() {
+    let home = home_with("- [ ] buy milk\n- [x] call mom done:2024-06-30\n");
+    let lock = fs::File::create(home.path().join(".todo.txt.lock")).unwrap();
+    lock.lock().unwrap();
+    let wait = std::time::Duration::from_secs(5);
+    todo(&home)
+        .args(["-v", "ls"])
+        .timeout(wait)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("buy milk"))
+        .stderr(predicate::str::contains(
+            "reading the list without locking it",
+        ));
+    for args in [&["stats"][..], &["cal"], &["show", "1"]] {
+        todo(&home).args(args).timeout(wait).assert().success();
+    }
+    todo(&home)
+        .args(["check", "1"])
+        .timeout(std::time::Duration::from_millis(500))
+        .assert()
+        .interrupted();
+    drop(lock);
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk\n- [x] call mom done:2024-06-30\n"
+    );
+}
+
+#[test]
+fn multiline_notes_are_rejected() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home)
+        .args(["add", "buy\nmilk"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("can't contain line breaks"));
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n");
+}
+
+#[test]
+fn todo_file_overrides_home() {
+    let home = home_with("- [ ] at home\n");
+    let file = home.path().join("elsewhere.txt");
+    fs::write(&file, "- [ ] elsewhere\n").unwrap();
+    todo(&home)
+        .env_remove("HOME")
+        .env("TODO_FILE", &file)
+        .args(["ls", "--no-color"])
+        .assert()
+        .code(0)
+        .stdout(" 1. ✖ elsewhere\n");
+}
+
+#[test]
+fn empty_tasks_are_rejected() {
+    let home = home_with("- [ ] buy milk\n");
+    for note in ["", "   "] {
+        todo(&home)
+            .arg(note)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("refusing to add an empty task"));
+    }
+    todo(&home)
+        .args(["add", "--squeeze", "  call   mom "])
+        .assert()
+        .code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n- [ ] call mom\n");
+}
+
+#[test]
+fn empty_tasks_are_rejected_with_a_default_assignee() {
+    let home = home_with("- [ ] buy milk\n");
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "default_assignee = \"alice\"\n").unwrap();
+    for args in [&["   "][..], &["add", ""]] {
+        todo(&home)
+            .args(args)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("refusing to add an empty task"));
+    }
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(content, "- [ ] buy milk\n");
+}
+
+#[test]
+fn check_takes_numbers_as_listed() {
+    let home = home_with("- [x] one\n- [ ] two\n- [x] three\n- [ ] four\n");
+    todo(&home).args(["ls", "--no-color"]).assert().stdout(
+        " 2. ✖ two\n 4. ✖ four\n(gaps are hidden tasks; commands take the numbers as shown)\n",
+    );
+    // `check 2` is the task shown as 2, not the second one shown.
+    todo(&home).args(["check", "2"]).assert().code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [x] one\n- [x] two done:2024-07-01\n- [x] three\n- [ ] four\n"
+    );
+
+    fs::write(
+        home.path().join("todo.txt"),
+        "- [x] one\n- [ ] two\n- [x] three\n- [ ] four\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["check", "--renumbered", "1"])
+        .assert()
+        .code(1);
+    todo(&home)
+        .args(["ls", "--renumbered", "--no-color"])
+        .assert()
+        .stdout(" 1. ✖ two\n 2. ✖ four\n");
+    todo(&home)
+        .args(["check", "--renumbered", "2"])
+        .assert()
+        .code(0);
+    todo(&home)
+        .args(["check", "--renumbered", "1"])
+        .assert()
+        .code(0);
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [x] one\n- [x] two done:2024-07-01\n- [x] three\n- [x] four done:2024-07-01\n"
+    );
+    todo(&home)
+        .args(["check", "--renumbered", "1"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("list again"));
+}
+
+#[test]
+fn recover_from_backup() {
+    let home = home_with("- [ ] buy milk\n- [ ] call mom\n");
+    todo(&home).arg("recover").assert().code(1);
+    todo(&home).args(["check", "1"]).assert().code(0);
+    todo(&home).arg("clear").assert().code(0);
+    todo(&home)
+        .arg("recover")
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains(" 1. ").and(predicate::str::contains("1 pending, 1 done")))
+        .stdout(
+            predicate::str::contains(" 2. ").and(predicate::str::contains("2 pending, 0 done")),
+        );
+
+    todo(&home)
+        .args(["recover", "--latest"])
+        .write_stdin("n\n")
+        .assert()
+        .code(1)
+        .stdout("+ - [x] buy milk done:2024-07-01\n+ - [ ] call mom\n");
+    let path = home.path().join("todo.txt");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+    todo(&home)
+        .args(["recover", "--from", "2"])
+        .write_stdin("y\n")
+        .assert()
+        .code(0);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "- [ ] buy milk\n- [ ] call mom\n"
+    );
+    todo(&home)
+        .args(["recover", "--latest", "--yes"])
+        .assert()
+        .code(0)
+        .stdout("- - [ ] buy milk\n+ - [x] buy milk done:2024-07-01\nrestored the backup\n");
+    todo(&home)
+        .args(["recover", "--from", "9"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+#[cfg(feature = "regex")]
+fn sed_rewrites_notes_after_confirmation() {
+    let home =
+        home_with("- [ ] acme docs\n- [ ] fix acme-7 bug id:7\n- [x] acme site\n- [ ] call mom\n");
+    let path = home.path().join("todo.txt");
+    let sed = |args: &[&str]| {
+        let mut cmd = todo(&home);
+        cmd.arg("sed").args(args);
+        cmd
+    };
+    sed(&["acme-(\\d)", "zenith-$1"])
+        .write_stdin("n\n")
+        .assert()
+        .code(1)
+        .stdout("- 2. fix acme-7 bug\n+ 2. fix zenith-7 bug\n")
+        .stderr(predicate::str::contains("nothing rewritten"));
+    sed(&["acme", "zenith", "--dry-run"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("would rewrite 2 tasks"));
+    sed(&[":7", ":8", "--yes"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "leaving 2. fix acme-7 bug as it is: it would change tokens that todo keeps hidden",
+        ));
+    sed(&["^call mom$", ""])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("it would be empty"));
+    sed(&["acme", "zenith", "--all"])
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(
+            "- 3. acme site\n+ 3. zenith site\nrewrote 3 note(s)\n",
+        ));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "- [ ] zenith docs\n- [ ] fix zenith-7 bug id:7\n- [x] zenith site\n- [ ] call mom\n"
+    );
+    sed(&["nothing", "here"]).assert().code(1);
+    sed(&["(", "x"]).assert().code(2);
+}
+
+#[test]
+fn tag_rename_rewrites_the_tag_everywhere() {
+    let home = home_with("- [ ] fix bug #WIP\n- [x] docs #wip\n- [ ] site #in-progress #wip\n");
+    todo(&home)
+        .args(["tag", "rename", "#wip", "in-progress"])
+        .assert()
+        .success()
+        .stdout("renamed #wip to #in-progress in 3 task(s)\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] fix bug #in-progress\n- [x] docs #in-progress\n- [ ] site #in-progress\n"
+    );
+    todo(&home)
+        .args(["tag", "rename", "wip", "done"])
+        .assert()
+        .code(1)
+        .stderr("todo: no task has #wip\n");
+    todo(&home)
+        .args(["tag", "rename", "in-progress", "two words"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn command_aliases() {
+    let home = home_with("- [ ] buy milk\n- [ ] call mom\n- [ ] pay rent\n");
+    todo(&home).args(["done", "1"]).assert().code(0);
+    todo(&home).args(["done", "99"]).assert().code(1);
+    todo(&home).args(["rm", "mom"]).assert().code(0);
+    todo(&home)
+        .args(["l", "--all", "--no-color"])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::starts_with(
+            " 1. ✓ buy milk [done]\n 2. ✖ pay rent\n",
+        ));
+
+    todo(&home)
+        .args(["--", "done soon"])
+        .assert()
+        .code(0)
+        .stderr(predicate::str::contains("though `done` is a command"));
+    todo(&home)
+        .args(["add", "done"])
+        .assert()
+        .code(0)
+        .stderr("");
+    let content = fs::read_to_string(home.path().join("todo.txt")).unwrap();
+    assert_eq!(
+        content,
+        "- [x] buy milk done:2024-07-01\n- [ ] pay rent\n- [ ] done soon\n- [ ] done\n"
+    );
+}
+
+#[test]
+fn misspelt_commands_are_suggested_rather_than_added() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home).arg("lss").assert().code(2).stderr(
+        "todo: unknown command 'lss'; did you mean 'ls'? `todo add` or `todo --` adds it \
+             as a task\n",
+    );
+    todo(&home)
+        .args(["chek", "--", "1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("did you mean 'check'?"));
+    todo(&home)
+        .args(["buy", "bread"])
+        .assert()
+        .code(2)
+        .stderr("todo: unexpected argument 'bread'; quote a task of several words\n");
+    todo(&home).args(["--", "call"]).assert().success();
+    todo(&home).arg("call mom").assert().success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk\n- [ ] call\n- [ ] call mom\n"
+    );
+}
+
+#[test]
+fn operations_are_logged() {
+    let home = TempDir::new().unwrap();
+    let now = "2024-06-02T10:11:12Z";
+    todo(&home)
+        .env("TODO_NOW", now)
+        .arg("buy milk")
+        .assert()
+        .success();
+    todo(&home)
+        .env("TODO_NOW", now)
+        .args(["check", "1"])
+        .assert()
+        .success();
+    todo(&home).arg("ls").assert().success();
+    let log = home.path().join(".local/share/todo/activity.log");
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "2024-06-02T10:11:12Z add 1 \"buy milk\"\n\
+         2024-06-02T10:11:12Z check 1 \"buy milk\"\n"
+    );
+
+    todo(&home)
+        .env("TZ", "UTC")
+        .args(["log", "--limit", "1"])
+        .assert()
+        .success()
+        .stdout("2024-06-02 10:11:12  check   1. buy milk\n");
+}
+
+#[test]
+fn unwritable_activity_log_only_warns() {
+    let home = home_with("- [ ] buy milk\n");
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!("activity_log = {:?}\n", home.path()),
+    )
+    .unwrap();
+    todo(&home)
+        .args(["check", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::starts_with(
+            "todo: couldn't write the activity log:",
+        ));
+    assert!(fs::read_to_string(home.path().join("todo.txt"))
+        .unwrap()
+        .starts_with("- [x] buy milk"));
+    todo(&home).arg("log").assert().code(3);
+}
+
+#[test]
+fn views_from_the_config_are_listed() {
+    let home = home_with("- [ ] (B) write report #work\n- [ ] buy milk\n- [ ] (A) fix bug #work\n");
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "[views.work]\ntags = [\"work\"]\nsort = \"priority\"\n\n[views.errands]\nnot_tags = [\"work\"]\n",
+    )
+    .unwrap();
+    let work = " 3. ✖ (A) fix bug #work\n 1. ✖ (B) write report #work\n\
+                (gaps are hidden tasks; commands take the numbers as shown)\n";
+    todo(&home)
+        .args(["ls", "--view", "work"])
+        .assert()
+        .success()
+        .stdout(work);
+    todo(&home)
+        .args(["view", "work"])
+        .assert()
+        .success()
+        .stdout(work);
+    todo(&home)
+        .args(["--plain", "ls", "--view", "work", "--reverse"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\t(B) write report #work\n3\ttodo\t(A) fix bug #work\n");
+    todo(&home)
+        .args(["--plain", "ls", "--view", "errands", "--not-tag", "home"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\t(B) write report #work\n2\ttodo\tbuy milk\n3\ttodo\t(A) fix bug #work\n");
+    todo(&home)
+        .args(["view", "home"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "no view named 'home' (available: errands, work)",
+        ));
+    todo(&home)
+        .arg("views")
+        .assert()
+        .success()
+        .stdout("errands  --not-tag work\nwork     --tag work --sort priority\n");
+}
+
+#[test]
+fn aliases_from_the_config_expand() {
+    let home = home_with("- [ ] (B) write report #work\n- [ ] dentist\n- [ ] (A) fix bug #work\n");
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    let write_config = |aliases: &str| fs::write(config.join("config.toml"), aliases).unwrap();
+    write_config(
+        "[alias]\nw = \"ls --tag work --sort priority\"\nwp = \"w --plain\"\ndid = \"check\"\n",
+    );
+    todo(&home)
+        .args(["--plain", "w"])
+        .assert()
+        .success()
+        .stdout("3\ttodo\t(A) fix bug #work\n1\ttodo\t(B) write report #work\n");
+    todo(&home)
+        .args(["wp", "--reverse"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\t(B) write report #work\n3\ttodo\t(A) fix bug #work\n");
+    todo(&home)
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(
+            "ALIASES:\n    did    check\n    w      ls --tag work --sort priority\n    wp     w --plain\n",
+        ));
+    todo(&home)
+        .args(["--plain", "did", "dentist"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(home.path().join("todo.txt"))
+        .unwrap()
+        .contains("- [x] dentist"));
+
+    write_config("[alias]\na = \"b --all\"\nb = \"c\"\nc = \"a\"\n");
+    todo(&home)
+        .arg("a")
+        .assert()
+        .code(2)
+        .stderr("todo: alias cycle: a -> b -> c -> a\n");
+
+    write_config("[alias]\nrm = \"ls\"\n");
+    todo(&home)
+        .arg("ls")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "alias 'rm' has the name of a command",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn sync_runs_the_configured_commands() {
+    let home = home_with("- [ ] buy milk\n");
+    let file = home.path().join("todo.txt");
+    let remote = home.path().join("remote.txt");
+    fs::write(&remote, "- [ ] call mom\n").unwrap();
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    let write_config = |pull: &str, auto: &str| {
+        let push = format!("cp \"$TODO_FILE\" '{}'", remote.display());
+        fs::write(
+            config.join("config.toml"),
+            format!(
+                "[sync]\npull_cmd = {:?}\npush_cmd = {:?}\n{}",
+                pull, push, auto
+            ),
+        )
+        .unwrap();
+    };
+
+    write_config(&format!("cp '{}' \"$TODO_FILE\"", remote.display()), "");
+    todo(&home)
+        .arg("sync")
+        .assert()
+        .success()
+        .stdout("pulled, the list changed\npushed\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "- [ ] call mom\n");
+
+    write_config("true", "auto = \"push\"\n");
+    todo(&home).args(["check", "1"]).assert().success();
+    todo(&home).arg("water plants").assert().success();
+    assert_eq!(
+        fs::read_to_string(&remote).unwrap(),
+        "- [x] call mom done:2024-07-01\n- [ ] water plants\n"
+    );
+
+    write_config(
+        "echo garbage > \"$TODO_FILE\"; echo unreachable >&2; exit 1",
+        "",
+    );
+    todo(&home)
+        .arg("sync")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("exit status: 1: unreachable"));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [x] call mom done:2024-07-01\n- [ ] water plants\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn the_change_hook_runs_after_saves() {
+    let home = home_with("- [ ] buy milk\n");
+    let log = home.path().join("hook.log");
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    let write_config = |hook: &str| {
+        fs::write(
+            config.join("config.toml"),
+            format!("[hooks]\non_change = {:?}\ntimeout = 1\n", hook),
+        )
+        .unwrap();
+    };
+    write_config(&format!(
+        "echo \"$TODO_VERB $TODO_INDEX $TODO_NOTE\" >> '{}'",
+        log.display()
+    ));
+    todo(&home).arg("call mom").assert().success();
+    todo(&home).args(["check", "1"]).assert().success();
+    todo(&home).arg("undo-last").assert().success();
+    todo(&home)
+        .args(["--no-hooks", "check", "2"])
+        .assert()
+        .success();
+    todo(&home).arg("ls").assert().success();
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "add 2 call mom\ncheck 1 buy milk\nundo  \n"
+    );
+
+    // A bulk command runs it once, however many tasks it changes.
+    fs::remove_file(&log).unwrap();
+    write_config(&format!(
+        "echo \"$TODO_VERB $TODO_COUNT $TODO_SUMMARY\" >> '{}'",
+        log.display()
+    ));
+    todo(&home).arg("clear").assert().success();
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "clear 2 remove 2 tasks\n"
+    );
+
+    write_config("exit 4");
+    todo(&home)
+        .arg("water plants")
+        .assert()
+        .success()
+        .stderr("todo: warning: hook `exit 4` failed: exit status: 4\n");
+    write_config("exec sleep 5");
+    todo(&home)
+        .arg("water roses")
+        .assert()
+        .success()
+        .stderr("todo: warning: hook `exec sleep 5` failed: stopped after 1s\n");
+    assert!(fs::read_to_string(home.path().join("todo.txt"))
+        .unwrap()
+        .ends_with("- [ ] water plants\n- [ ] water roses\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn plugins_run_unknown_commands() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = home_with("- [ ] buy milk\n");
+    let bin = home.path().join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    for (name, script) in [
+        ("todo-week", "echo \"$TODO_LIST $TODO_FILE $*\"; exit 3"),
+        ("todo-ls", "echo plugin"),
+    ] {
+        let path = bin.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let todo = || {
+        let mut cmd = todo(&home);
+        cmd.env("PATH", &path);
+        cmd
+    };
+    todo()
+        .args(["week", "--since", "7d"])
+        .assert()
+        .code(3)
+        .stdout(format!(
+            "default {} --since 7d\n",
+            home.path().join("todo.txt").display()
+        ));
+    let work = home.path().join(".local/share/todo/work.txt");
+    todo()
+        .args(["--list", "work", "week"])
+        .assert()
+        .code(3)
+        .stdout(format!("work {} \n", work.display()));
+    todo()
+        .args(["--plain", "ls"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\tbuy milk\n");
+    todo()
+        .args(["weeks", "--since", "7d"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::ends_with("\nplugins on PATH: ls, week\n"));
+}
+
+#[test]
+fn conflict_copies_are_reported_and_merged() {
+    let home = home_with("- [ ] buy milk\n- [x] call mom\n");
+    let copy = home.path().join("todo (conflicted copy 2024-06-02).txt");
+    fs::write(
+        &copy,
+        "- [ ] buy milk\n- [ ] call mom\n- [ ] water plants\n",
+    )
+    .unwrap();
+
+    todo(&home)
+        .args(["ls", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ buy milk\n")
+        .stderr(predicate::str::contains(
+            "warning: a sync client left a copy of this list",
+        ));
+    todo(&home)
+        .arg("conflicts")
+        .assert()
+        .success()
+        .stdout(
+            "todo (conflicted copy 2024-06-02).txt: 3 tasks against 2 in the list, \
+             1 of them not in it\n",
+        )
+        .stderr("");
+    todo(&home)
+        .args(["conflicts", "--merge", "notes.txt"])
+        .assert()
+        .code(2);
+    todo(&home)
+        .args([
+            "conflicts",
+            "--merge",
+            "todo (conflicted copy 2024-06-02).txt",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "added - [ ] water plants\n\
+             merged 1 task(s) from todo (conflicted copy 2024-06-02).txt, \
+             which is now .todo (conflicted copy 2024-06-02).txt.merged\n",
+        );
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk\n- [x] call mom\n- [ ] water plants\n"
+    );
+    assert!(!copy.exists());
+    todo(&home).arg("conflicts").assert().code(1);
+    todo(&home).arg("ls").assert().success().stderr("");
+}
+
+#[test]
+fn merge_combines_two_copies_against_their_base() {
+    let home = home_with("- [x] buy milk done:2024-07-01\n- [ ] call mom\n- [ ] pay rent\n");
+    fs::write(
+        home.path().join("base.txt"),
+        "- [ ] buy milk\n- [ ] call mom\n- [ ] pay rent\n",
+    )
+    .unwrap();
+    fs::write(
+        home.path().join("theirs.txt"),
+        "- [ ] (A) buy milk\n- [ ] (A) pay rent\n- [ ] water plants\n",
+    )
+    .unwrap();
+    let merge = || {
+        let mut cmd = todo(&home);
+        cmd.current_dir(home.path())
+            .args(["merge", "--base", "base.txt", "theirs.txt"]);
+        cmd
+    };
+
+    merge()
+        .write_stdin("")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "todo: conflict:\n  base:   - [ ] buy milk\n  \
+             ours:   - [x] buy milk done:2024-07-01\n  theirs: - [ ] (A) buy milk\n",
+        ))
+        .stderr(predicate::str::contains("nothing merged"));
+    merge()
+        .args(["--resolve", "ours", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(
+            "- - [ ] call mom\n- - [ ] pay rent\n\
+             + - [ ] (A) pay rent\n+ - [ ] water plants\nwould save the merge\n",
+        );
+    merge().write_stdin("t\n").assert().success().stdout(
+        "- - [x] buy milk done:2024-07-01\n- - [ ] call mom\n- - [ ] pay rent\n\
+             + - [ ] (A) buy milk\n+ - [ ] (A) pay rent\n+ - [ ] water plants\n\
+             saved the merge\n",
+    );
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] (A) buy milk\n- [ ] (A) pay rent\n- [ ] water plants\n"
+    );
+    todo(&home).arg("undo-last").assert().success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [x] buy milk done:2024-07-01\n- [ ] call mom\n- [ ] pay rent\n"
+    );
+}
+
+#[test]
+fn org_files_keep_their_outline() {
+    let home = TempDir::new().unwrap();
+    let file = home.path().join("projects.org");
+    let fixture = include_str!("fixtures/projects.org");
+    fs::write(&file, fixture).unwrap();
+    let org = || {
+        let mut cmd = todo(&home);
+        cmd.env("TODO_FILE", &file);
+        cmd
+    };
+
+    org()
+        .args(["ls", "--all", "--plain"])
+        .assert()
+        .success()
+        .stdout(
+            "1\ttodo\tpaint the fence\n2\tdone\tbuy brushes\n\
+             3\ttodo\t(A) call the plumber :urgent:\n4\ttodo\tfile taxes due:2025-04-15\n",
+        );
+    org().args(["pin", "1"]).assert().success();
+    org().args(["unpin", "1"]).assert().success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), fixture);
+
+    org().args(["check", "3"]).assert().success();
+    org().arg("buy paint").assert().success();
+    let expected = fixture.replace(
+        "** TODO [#A] call the plumber :urgent:",
+        "** DONE [#A] call the plumber :urgent:\nCLOSED: [2024-07-01 Mon]",
+    ) + "* TODO buy paint\n";
+    assert_eq!(fs::read_to_string(&file).unwrap(), expected);
+    org().args(["remove", "1"]).assert().success();
+    assert!(!fs::read_to_string(&file).unwrap().contains("Two coats"));
+
+    let home = home_with("* TODO buy milk\n");
+    todo(&home)
+        .args(["ls", "--plain", "--backend", "org"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\tbuy milk\n");
+}
+
+#[test]
+fn checklists_are_listed_under_their_task() {
+    let home = home_with("- [ ] pack\n- [ ] call mom\n");
+    todo(&home)
+        .args(["item", "1", "add", "socks"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ pack (0/1)\n     1. [ ] socks\n");
+    todo(&home)
+        .args(["item", "1", "add", "shirts"])
+        .assert()
+        .success();
+    todo(&home)
+        .args(["item", "pack", "check", "2"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ pack (1/2)\n     1. [ ] socks\n     2. [x] shirts\n");
+    todo(&home)
+        .args(["item", "1", "check", "3"])
+        .assert()
+        .code(1)
+        .stderr("todo: no item 3 in task 1 (valid: 1-2)\n");
+    todo(&home)
+        .args(["item", "1", "check", "two"])
+        .assert()
+        .code(2);
+    todo(&home)
+        .args(["ls", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ pack (1/2)\n 2. ✖ call mom\n");
+    todo(&home)
+        .args(["show", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "items    1. [ ] socks\n         2. [x] shirts\n",
+        ));
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] pack\n  - [ ] socks\n  - [x] shirts\n- [ ] call mom\n"
+    );
+
+    todo(&home)
+        .args(["check", "1", "--items"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [x] pack done:2024-07-01\n  - [x] socks\n  - [x] shirts\n- [ ] call mom\n"
+    );
+
+    let home = TempDir::new().unwrap();
+    let file = home.path().join("todo.org");
+    fs::write(&file, "* TODO pack\n").unwrap();
+    todo(&home)
+        .env("TODO_FILE", &file)
+        .args(["item", "1", "add", "socks"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("org lists can't keep checklists"));
+}
+
+#[test]
+fn blocked_tasks_wait_for_their_blocker() {
+    let home = home_with("- [ ] buy paint\n- [ ] paint fence\n- [ ] call mom\n");
+    todo(&home)
+        .args(["block", "2", "--on", "1"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ buy paint\n 2. ✖ 🔒 paint fence\n 3. ✖ call mom\n");
+    todo(&home)
+        .args(["block", "1", "--on", "fence"])
+        .assert()
+        .code(2)
+        .stderr("todo: task 2 already waits for task 1, so it can't block it\n");
+    todo(&home)
+        .args(["--symbols", "ascii", "ls", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 1. [ ] buy paint\n 2. [ ] ~ paint fence\n 3. [ ] call mom\n");
+    todo(&home)
+        .args(["ls", "--ready", "--no-color"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            " 1. ✖ buy paint\n 3. ✖ call mom\n",
+        ));
+    todo(&home)
+        .args(["check", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unblocked 2. paint fence\n"));
+
+    let home = home_with("- [ ] buy paint\n- [ ] paint fence\n");
+    todo(&home)
+        .args(["block", "2", "--on", "1"])
+        .assert()
+        .success();
+    todo(&home)
+        .args(["remove", "1"])
+        .assert()
+        .success()
+        .stderr("todo: warning: 2. paint fence waited for it, and no longer does\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] paint fence\n"
+    );
+}
+
+#[test]
+fn waiting_tasks_are_listed_but_not_suggested() {
+    let home = home_with("- [ ] send contract\n- [ ] call mom\n");
+    todo(&home)
+        .args(["wait", "1", "--for", "Alice"])
+        .assert()
+        .success();
+    todo(&home)
+        .args(["--symbols", "ascii", "ls", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 1. [ ] w send contract (waiting for Alice)\n 2. [ ] call mom\n");
+    todo(&home)
+        .args(["ls", "--hide-waiting", "--no-color"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(" 2. ✖ call mom\n"));
+    todo(&home)
+        .args(["next", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 2. ✖ call mom\n");
+    todo(&home).args(["cleanup"]).assert().success();
+    todo(&home).args(["undo", "1"]).assert().success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] send contract\n- [ ] call mom\n"
+    );
+}
+
+#[test]
+fn assigned_tasks_are_listed_and_counted_by_assignee() {
+    let home = home_with("- [ ] buy milk\n- [ ] pay rent\n- [ ] call mom\n");
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "default_assignee = \"bob\"\n").unwrap();
+    todo(&home)
+        .args(["assign", "1", "alice"])
+        .assert()
+        .success();
+    todo(&home).args(["assign", "1", "bob"]).assert().success();
+    todo(&home)
+        .args(["assign", "3", "alice"])
+        .assert()
+        .success();
+    todo(&home).arg("water plants").assert().success();
+    todo(&home)
+        .args(["add", "feed cat assignee:alice"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk assignee:bob\n- [ ] pay rent\n- [ ] call mom assignee:alice\n\
+         - [ ] water plants assignee:bob\n- [ ] feed cat assignee:alice\n"
+    );
+    todo(&home)
+        .args(["--no-color", "ls", "--assignee", "alice"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            " 3. ✖ call mom → alice\n 5. ✖ feed cat → alice\n",
+        ));
+    todo(&home)
+        .args(["--no-color", "ls", "--unassigned"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(" 2. ✖ pay rent\n"));
+    todo(&home).arg("stats").assert().success().stdout(
+        "pending: 5\ndone: 0\noverdue: 0\npending for alice: 2\npending for bob: 2\n\
+             pending unassigned: 1\n",
+    );
+    todo(&home)
+        .args(["assign", "1", "--clear"])
+        .assert()
+        .success();
+    todo(&home).args(["assign", "1"]).assert().code(2);
+    assert!(fs::read_to_string(home.path().join("todo.txt"))
+        .unwrap()
+        .starts_with("- [ ] buy milk\n"));
+}
+
+#[test]
+fn ascii_output_is_pure_ascii() {
+    let home = home_with(
+        "- [ ] pay rent due:2024-07-01 pin:1 #home\n- [ ] buy paint id:1\n\
+         - [ ] paint fence dep:1 #home\n- [ ] send contract wait:Alice\n\
+         - [x] call mom done:2024-07-01\n",
+    );
+    todo(&home).args(["start", "2"]).assert().success();
+    for args in [
+        &["ls", "--all"][..],
+        &["ls", "--group-by", "tag"],
+        &["search", "a"],
+        &["today"],
+        &["stats"],
+        &["next"],
+    ] {
+        let output = todo(&home).arg("--ascii").args(args).output().unwrap();
+        assert!(output.status.success(), "{:?}", args);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.is_ascii(), "{:?}: {}", args, stdout);
+    }
+    let home = home_with("- [x] call mom\n");
+    todo(&home)
+        .args(["--ascii", "next"])
+        .assert()
+        .code(1)
+        .stdout("nothing to do\n");
+}
+
+#[test]
+fn undo_last_reverts_the_last_save() {
+    let home = TempDir::new().unwrap();
+    let file = home.path().join("todo.txt");
+    todo(&home).arg("buy milk").assert().success();
+    todo(&home).args(["check", "1"]).assert().success();
+    todo(&home)
+        .arg("undo-last")
+        .assert()
+        .success()
+        .stdout("undo: check 1 \"buy milk\" (1 more can be undone)\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "- [ ] buy milk\n");
+    todo(&home)
+        .arg("redo")
+        .assert()
+        .success()
+        .stdout("redo: check 1 \"buy milk\"\n");
+    assert!(fs::read_to_string(&file)
+        .unwrap()
+        .starts_with("- [x] buy milk"));
+    todo(&home).arg("undo-last").assert().success();
+
+    todo(&home).arg("call mom").assert().success();
+    fs::write(&file, "- [ ] buy bread\n").unwrap();
+    todo(&home)
+        .arg("undo-last")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "changed by something other than todo",
+        ));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "- [ ] buy bread\n");
+    todo(&home).arg("undo-last").assert().code(1);
+    todo(&home)
+        .arg("redo")
+        .assert()
+        .code(1)
+        .stderr("todo: nothing to redo\n");
+}
+
+#[test]
+fn dry_run_saves_nothing() {
+    let content = "- [x] buy milk\n- [ ] call mom\n";
+    let home = home_with(content);
+    let file = home.path().join("todo.txt");
+    todo(&home)
+        .args(["--dry-run", "cleanup"])
+        .assert()
+        .success()
+        .stderr("todo: dry run, nothing was saved; would remove 1 task\n");
+    todo(&home)
+        .args(["--no-color", "--dry-run", "water plants"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3. ✖ water plants"))
+        .stderr("todo: dry run, nothing was saved; would add 1 task\n");
+    todo(&home)
+        .args(["check", "9", "--dry-run"])
+        .assert()
+        .code(1);
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+    assert!(!home.path().join(".todo.txt.backups").exists());
+    assert!(!home.path().join(".local/share/todo/activity.log").exists());
+}
+
+#[test]
+fn read_only_lists_but_changes_nothing() {
+    let content = "- [ ] buy milk\n- [x] call mom\n";
+    let home = home_with(content);
+    let file = home.path().join("todo.txt");
+    let refused = "todo: ~/todo.txt is read-only (--read-only, or the file can't be \
+                   written); nothing was changed\n";
+    todo(&home)
+        .args(["--read-only", "--no-color", "ls"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ buy milk\n");
+    todo(&home)
+        .args(["--read-only", "search", "milk"])
+        .assert()
+        .success();
+    todo(&home)
+        .args(["--read-only", "stats"])
+        .assert()
+        .success();
+    todo(&home)
+        .args(["--read-only", "export", "--output", "-"])
+        .assert()
+        .success()
+        .stdout(content);
+    for args in [
+        &["check", "1"][..],
+        &["water plants"],
+        &["undo-last"],
+        &["export", "--output", "moved.txt", "--move"],
+    ] {
+        todo(&home)
+            .arg("--read-only")
+            .args(args)
+            .assert()
+            .code(3)
+            .stderr(refused);
+    }
+    todo(&home)
+        .args(["--read-only", "export", "--output", "exported.txt"])
+        .current_dir(home.path())
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("exported.txt is read-only"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+    assert!(!home.path().join("moved.txt").exists());
+    assert!(!home.path().join("exported.txt").exists());
+    for name in [".todo.txt.lock", ".todo.txt.backups", ".local"] {
+        assert!(!home.path().join(name).exists(), "{} was created", name);
+    }
+
+    // A file that can't be written is read-only without the flag.
+    let mut permissions = fs::metadata(&file).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&file, permissions).unwrap();
+    todo(&home)
+        .args(["--no-color", "ls"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ buy milk\n");
+    todo(&home)
+        .args(["check", "1"])
+        .assert()
+        .code(3)
+        .stderr(refused);
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+
+    let empty = tempfile::tempdir().unwrap();
+    todo(&empty).args(["--read-only", "ls"]).assert().success();
+    assert!(!empty.path().join("todo.txt").exists());
+}
+
+#[test]
+fn cleanup_older_than_keeps_recent_and_undated_tasks() {
+    let content = "- [x] file taxes done:2024-05-31\n- [ ] buy milk\n\
+                   - [x] water plants done:2024-06-01\n- [x] call mom\n";
+    let home = home_with(content);
+    let file = home.path().join("todo.txt");
+    todo(&home)
+        .args(["--dry-run", "cleanup", "--older-than", "30d"])
+        .assert()
+        .success()
+        .stdout("would remove 1 task checked before 2024-06-01\n")
+        .stderr("todo: dry run, nothing was saved; would remove 1 task\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+
+    todo(&home)
+        .args(["cleanup", "--older-than", "30d"])
+        .assert()
+        .success()
+        .stdout("removed 1 task checked before 2024-06-01\n");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] buy milk\n- [x] water plants done:2024-06-01\n- [x] call mom\n"
+    );
+    todo(&home)
+        .args(["cleanup", "--older-than", "30d", "--include-undated"])
+        .assert()
+        .success()
+        .stdout("removed 1 task checked before 2024-06-01\n");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] buy milk\n- [x] water plants done:2024-06-01\n"
+    );
+    todo(&home)
+        .args(["cleanup", "--older-than", "soon"])
+        .assert()
+        .code(2);
+    todo(&home)
+        .args(["cleanup", "--include-undated"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn archive_purge_trims_the_archive() {
+    let home = home_with("- [ ] buy milk\n");
+    let archive = home.path().join("todo.done.txt");
+    todo(&home)
+        .args(["archive", "purge", "--keep-last", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("there's no archive at "));
+    let content = "- [x] file taxes done:2023-06-30\n- [x] call mom\n\
+                   - [x] pay rent done:2023-07-01\n- [x] water plants done:2024-06-30\n";
+    fs::write(&archive, content).unwrap();
+
+    todo(&home)
+        .args(["--dry-run", "archive", "purge", "--older-than", "1y"])
+        .assert()
+        .success()
+        .stdout("would remove 1 archived task, 3 kept\n");
+    assert_eq!(fs::read_to_string(&archive).unwrap(), content);
+
+    todo(&home)
+        .args(["archive", "purge", "--older-than", "1y", "--keep-last", "2"])
+        .assert()
+        .success()
+        .stdout("removed 2 archived tasks, 2 kept\n");
+    assert_eq!(
+        fs::read_to_string(&archive).unwrap(),
+        "- [x] pay rent done:2023-07-01\n- [x] water plants done:2024-06-30\n"
+    );
+    assert!(home.path().join(".todo.done.txt.backups").is_dir());
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] buy milk\n"
+    );
+    todo(&home).args(["archive", "purge"]).assert().code(2);
+}
+
+#[test]
+fn tidy_rewrites_the_file_once() {
+    let messy = "* [ ] buy milk  \n\n\n- [X] call mom\n# notes\n- [ ] water plants";
+    let home = home_with(messy);
+    let file = home.path().join("todo.txt");
+    todo(&home)
+        .args(["--dry-run", "tidy"])
+        .assert()
+        .success()
+        .stdout(
+            "- * [ ] buy milk  \n- \n- - [X] call mom\n+ - [ ] buy milk\n+ - [x] call mom\n\
+             checkboxes: 1\nmarkers: 1\ntrailing whitespace: 1\nblank lines: 1\n\
+             final newline: 1\n",
+        );
+    assert_eq!(fs::read_to_string(&file).unwrap(), messy);
+
+    todo(&home).args(["tidy"]).assert().success().stdout(
+        "checkboxes: 1\nmarkers: 1\ntrailing whitespace: 1\nblank lines: 1\n\
+             final newline: 1\n",
+    );
+    let tidied = "- [ ] buy milk\n\n- [x] call mom\n# notes\n- [ ] water plants\n";
+    assert_eq!(fs::read_to_string(&file).unwrap(), tidied);
+    let backups = || {
+        fs::read_dir(home.path().join(".todo.txt.backups"))
+            .unwrap()
+            .count()
+    };
+    let taken = backups();
+    assert!(taken > 0);
+
+    todo(&home)
+        .args(["tidy"])
+        .assert()
+        .success()
+        .stdout("the list is tidy already\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), tidied);
+    assert_eq!(backups(), taken);
+}
+
+#[test]
+fn a_command_saves_the_list_once() {
+    // A habit to reset before the command, and three tasks it changes.
+    let home = home_with("- [x] stretch habit:1 done:2024-06-30\n- [ ] a\n- [ ] b\n- [ ] c\n");
+    todo(&home).args(["bump", "2", "3", "4"]).assert().success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] stretch habit:1\n- [ ] (C) a\n- [ ] (C) b\n- [ ] (C) c\n"
+    );
+    let backups = home.path().join(".todo.txt.backups");
+    let taken = fs::read_dir(&backups)
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name() != "journal")
+        .count();
+    assert_eq!(taken, 1);
+    assert_eq!(todo::journal::len(&home.path().join("todo.txt")), (1, 0));
+}
+
+#[test]
+fn fsck_reports_problems_and_fixes_the_safe_ones() {
+    let broken = "- [ ] buy milk id:1\r\n* [X] call mom\nnotes\n- [ ] water plants id:1\n";
+    let home = home_with(broken);
+    let file = home.path().join("todo.txt");
+    todo(&home).args(["fsck"]).assert().code(1).stdout(
+        "line 1: has a carriage return (--fix repairs it)\n\
+         line 2: \"* [X] call mom\" should be written \"- [x] call mom\" (--fix repairs it)\n\
+         line 3: \"notes\" isn't a task\n\
+         line 4: id:1 is also on line 1; giving it id:2 (--fix repairs it)\n\
+         4 problems, 3 of which --fix repairs\n",
+    );
+    todo(&home)
+        .args(["--dry-run", "fsck", "--fix"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::ends_with("3 repaired, 1 left\n"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), broken);
+
+    todo(&home)
+        .args(["fsck", "--fix"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("has a carriage return (repaired)"));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] buy milk id:1\n- [x] call mom\nnotes\n- [ ] water plants id:2\n"
+    );
+
+    fs::write(&file, "- [ ] buy milk\n").unwrap();
+    todo(&home)
+        .args(["fsck"])
+        .assert()
+        .success()
+        .stdout("no problems\n");
+}
+
+#[test]
+fn prompt_prints_pending_counts_or_nothing() {
+    let home = home_with("- [ ] buy milk due:2024-06-30\n- [ ] call mom\n- [x] water plants\n");
+    let file = home.path().join("todo.txt");
+    let before = fs::read_to_string(&file).unwrap();
+    todo(&home).args(["prompt"]).assert().success().stdout("2");
+    todo(&home)
+        .args([
+            "prompt",
+            "--no-config",
+            "--format",
+            "[{pending}, {overdue} late]",
+        ])
+        .assert()
+        .success()
+        .stdout("[2, 1 late]");
+    assert_eq!(fs::read_to_string(&file).unwrap(), before);
+
+    fs::write(&file, "- [x] buy milk\n").unwrap();
+    todo(&home)
+        .args(["prompt", "--format", "[{pending}]"])
+        .assert()
+        .success()
+        .stdout("");
+
+    fs::write(&file, "not a task\n").unwrap();
+    todo(&home)
+        .args(["prompt"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::starts_with("todo: "));
+}
+
+#[test]
+fn status_prints_a_tmux_segment() {
+    let home = home_with("- [ ] buy milk due:2024-06-30\n- [ ] call mom\n- [x] water plants\n");
+    todo(&home)
+        .args(["status"])
+        .assert()
+        .success()
+        .stdout("2 pending, 1 overdue\n");
+    todo(&home)
+        .args(["status", "--tmux"])
+        .assert()
+        .success()
+        .stdout("2 pending, #[fg=red,bold]1 overdue#[default]\n");
+    todo(&home)
+        .args(["status", "--tmux", "--max-width", "15"])
+        .assert()
+        .success()
+        .stdout("2 pending, #[fg=red,bold]1 o#[default]…\n");
+    todo(&home)
+        .args(["status", "--max-width", "wide"])
+        .assert()
+        .code(2)
+        .stdout("");
+}
+
+#[test]
+fn status_prints_waybar_and_i3blocks_output() {
+    let home = home_with("- [ ] buy milk due:2024-06-30\n- [ ] call \"mom\"\n- [ ] water plants\n");
+    todo(&home)
+        .args(["status", "--waybar", "--tooltip-tasks", "2"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"class\":\"overdue\",\"text\":\"3\",\
+             \"tooltip\":\"buy milk due:2024-06-30\\ncall \\\"mom\\\"\\n… and 1 more\"}\n",
+        );
+    todo(&home)
+        .args(["status", "--i3blocks"])
+        .assert()
+        .success()
+        .stdout("3 pending, 1 overdue\n3\n#FF0000\n");
+    todo(&home)
+        .args(["status", "--waybar", "--tmux"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn due_check_exits_by_whether_anything_is_due() {
+    let home =
+        home_with("- [ ] buy milk due:2024-07-02\n- [ ] call mom\n- [x] pay rent due:2024-06-30\n");
+    todo(&home)
+        .args(["due-check"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+    todo(&home)
+        .args(["due-check", "--within", "1d", "--print"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\tbuy milk due:2024-07-02\n");
+    todo(&home)
+        .args(["due-check", "--within", "soon"])
+        .assert()
+        .code(2)
+        .stdout("");
+
+    fs::write(home.path().join("todo.txt"), "not a task\n").unwrap();
+    todo(&home).args(["due-check"]).assert().code(2);
+}
+
+#[test]
+fn verbose_explains_on_stderr() {
+    let home = home_with("- [ ] buy milk\nnot a task\n");
+    let file = home.path().join("todo.txt");
+    todo(&home)
+        .args(["-v", "--skip-invalid", "ls", "--plain"])
+        .assert()
+        .success()
+        .stdout("1\ttodo\tbuy milk\n")
+        .stderr(
+            predicate::str::contains(format!(
+                "todo: using {}, chosen by default\n",
+                file.display()
+            ))
+            .and(predicate::str::contains(
+                "todo: read 1 task(s) from 2 line(s)\n",
+            ))
+            .and(predicate::str::contains("todo: nothing to save\n")),
+        );
+    todo(&home)
+        .args(["-vv", "--skip-invalid", "check", "1"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(
+            predicate::str::contains("todo: check 1 \"buy milk\"\n")
+                .and(predicate::str::contains("todo: saved the list\n")),
+        );
+    todo(&home)
+        .args(["--skip-invalid", "ls"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("todo: using").not());
+}
+
+#[test]
+fn man_page_covers_the_cli() {
+    let home = home_with("");
+    let output = todo(&home).arg("man").output().unwrap();
+    assert!(output.status.success());
+    let page = String::from_utf8(output.stdout).unwrap();
+    assert!(page.starts_with(".TH TODO 1"));
+    assert!(page.contains(".SS \\fBcheck\\fR [\\fIindex\\fR]\n"));
+    assert!(page.contains("\\fB\\-\\-dry\\-run\\fR\n"));
+    assert!(page.contains(".SH FILE FORMAT\n"));
+    assert!(!page.contains(".SS \\fBman"));
+
+    let file = home.path().join("todo.1");
+    todo(&home)
+        .arg("man")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(fs::read_to_string(&file).unwrap(), page);
+}
+
+#[test]
+fn path_prints_the_list_file() {
+    let home = TempDir::new().unwrap();
+    todo(&home)
+        .arg("path")
+        .assert()
+        .success()
+        .stdout(format!("{}\n", home.path().join("todo.txt").display()));
+    todo(&home)
+        .args(["path", "--list", "work"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\n",
+            home.path().join(".local/share/todo/work.txt").display()
+        ));
+    todo(&home)
+        .arg("path")
+        .current_dir(home.path())
+        .env("TODO_FILE", "lists/todo.txt")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\n",
+            home.path().join("lists/todo.txt").display()
+        ));
+    assert!(!home.path().join("todo.txt").exists());
+}
+
+#[test]
+fn a_project_keeps_its_own_list() {
+    let home = home_with("- [ ] buy milk\n");
+    let project = home.path().join("project");
+    let nested = project.join("src/bin");
+    fs::create_dir_all(&nested).unwrap();
+    let local = project.join(".todo.txt");
+    todo(&home)
+        .arg("init")
+        .current_dir(&project)
+        .assert()
+        .success()
+        .stdout(format!("created {}\n", local.display()));
+    todo(&home)
+        .arg("init")
+        .current_dir(&project)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("exists already"));
+
+    todo(&home)
+        .args(["add", "fix the build"])
+        .current_dir(&nested)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&local).unwrap(), "- [ ] fix the build\n");
+    todo(&home)
+        .args(["path", "--source"])
+        .current_dir(&nested)
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\nchosen by the project the current directory is in\n",
+            local.display()
+        ));
+    todo(&home)
+        .args(["--global", "ls"])
+        .current_dir(&nested)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("buy milk"))
+        .stdout(predicate::str::contains("fix the build").not());
+    todo(&home)
+        .arg("path")
+        .current_dir(&nested)
+        .env("TODO_FILE", home.path().join("todo.txt"))
+        .assert()
+        .success()
+        .stdout(format!("{}\n", home.path().join("todo.txt").display()));
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "default_list = \"work\"\n").unwrap();
+    todo(&home)
+        .args(["path", "--source"])
+        .current_dir(&nested)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!(
+            "{}\n",
+            local.display()
+        )));
+
+    let notes = home.path().join("notes");
+    fs::create_dir_all(&notes).unwrap();
+    fs::write(notes.join("todo.md"), "- [ ] write it up\n").unwrap();
+    todo(&home)
+        .arg("ls")
+        .current_dir(&notes)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("write it up"));
+}
+
+#[cfg(unix)]
+#[test]
+fn edit_file_checks_the_edited_list() {
+    let home = home_with("- [ ] buy milk\n");
+    let file = home.path().join("todo.txt");
+    let script = home.path().join("edit.sh");
+    fs::write(
+        &script,
+        "if [ -e \"$HOME/edited\" ]; then printf -- '- [ ] buy bread\\n' > \"$1\"; \
+         else touch \"$HOME/edited\"; printf -- '- [ ] buy milk\\nbuy bread\\n' > \"$1\"; fi\n",
+    )
+    .unwrap();
+    let editor = format!("sh {}", script.display());
+    todo(&home)
+        .arg("edit-file")
+        .env("EDITOR", &editor)
+        .env_remove("VISUAL")
+        .write_stdin("e\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "todo: line 2 isn't a task: \"buy bread\"",
+        ));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "- [ ] buy bread\n");
+    todo(&home).arg("undo-last").assert().success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "- [ ] buy milk\n");
+
+    fs::remove_file(home.path().join("edited")).unwrap();
+    todo(&home)
+        .arg("edit-file")
+        .env("EDITOR", &editor)
+        .env_remove("VISUAL")
+        .write_stdin("k\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("kept them"));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] buy milk\nbuy bread\n"
+    );
+    todo(&home)
+        .arg("edit-file")
+        .env("EDITOR", "false")
+        .env_remove("VISUAL")
+        .assert()
+        .code(2);
+}
+
+#[cfg(unix)]
+#[test]
+fn review_applies_the_edited_buffer() {
+    let home = home_with("- [ ] buy milk\n- [ ] call mom\n- [x] water plants\n");
+    let file = home.path().join("todo.txt");
+    let script = home.path().join("review.sh");
+    fs::write(
+        &script,
+        "if [ -e \"$HOME/first\" ]; then cp \"$1\" \"$HOME/second\"; \
+         printf -- '3 - [ ] water plants\\n- [ ] buy bread\\n1 - [x] buy milk\\n' > \"$1\"; \
+         else cp \"$1\" \"$HOME/first\"; printf -- '1 - [x] buy milk\\nbuy bread\\n' > \"$1\"; fi\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["review", "--no-color"])
+        .env("EDITOR", format!("sh {}", script.display()))
+        .env_remove("VISUAL")
+        .assert()
+        .success()
+        .stdout(" 1. ✖ water plants\n 2. ✖ buy bread\n");
+    let first = fs::read_to_string(home.path().join("first")).unwrap();
+    assert!(first.starts_with("1 - [ ] buy milk\n2 - [ ] call mom\n3 - [x] water plants\n\n# "));
+    let second = fs::read_to_string(home.path().join("second")).unwrap();
+    assert!(second.starts_with(
+        "# error: line 2: \"buy bread\" isn't a task; write `- [ ] note`, after its number \
+         for an existing task\n1 - [x] buy milk\nbuy bread\n"
+    ));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] water plants\n- [ ] buy bread\n- [x] buy milk done:2024-07-01\n"
+    );
+    todo(&home).arg("undo-last").assert().success();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] buy milk\n- [ ] call mom\n- [x] water plants\n"
+    );
+
+    let empty = home.path().join("empty.sh");
+    fs::write(&empty, "printf '# nothing\\n' > \"$1\"\n").unwrap();
+    todo(&home)
+        .args(["review", "--no-color"])
+        .env("EDITOR", format!("sh {}", empty.display()))
+        .env_remove("VISUAL")
+        .assert()
+        .success()
+        .stdout(
+            "the buffer is empty, so the list was left as it is\n 1. ✖ buy milk\n 2. ✖ call mom\n",
+        );
+    todo(&home)
+        .arg("review")
+        .env("EDITOR", "false")
+        .env_remove("VISUAL")
+        .assert()
+        .code(2);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] buy milk\n- [ ] call mom\n- [x] water plants\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn split_replaces_a_task_with_several() {
+    let home = home_with("- [ ] call mom\n- [ ] (B) plan trip #travel\n- [x] pay rent\n");
+    let file = home.path().join("todo.txt");
+    todo(&home)
+        .args([
+            "split",
+            "2",
+            "--into",
+            "book flights",
+            "--into",
+            "book hotel",
+        ])
+        .args(["--no-color"])
+        .assert()
+        .success()
+        .stdout(" 2. ✖ (B) book flights #travel\n 3. ✖ (B) book hotel #travel\n");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] call mom\n- [ ] (B) book flights #travel\n- [ ] (B) book hotel #travel\n\
+         - [x] pay rent\n"
+    );
+
+    let script = home.path().join("split.sh");
+    fs::write(
+        &script,
+        "cp \"$1\" \"$HOME/buffer\"; printf -- 'ring mom\\n# left out\\n\\nvisit mom\\n' > \"$1\"\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["split", "1", "--keep", "--bare", "--no-color"])
+        .env("EDITOR", format!("sh {}", script.display()))
+        .env_remove("VISUAL")
+        .assert()
+        .success()
+        .stdout(" 1. ✖ call mom\n 2. ✖ ring mom\n 3. ✖ visit mom\n");
+    let buffer = fs::read_to_string(home.path().join("buffer")).unwrap();
+    assert!(buffer.starts_with("call mom\n# Write a line for each task to split task 1 into."));
+    todo(&home)
+        .args(["split", "6", "--into", "pay"])
+        .assert()
+        .code(3)
+        .stderr("todo: task 6 is checked, and only pending tasks can be split\n");
+}
+
+#[test]
+fn join_combines_two_tasks() {
+    let home =
+        home_with("- [ ] (B) plan trip #travel\n- [ ] call mom\n- [x] (A) book hotel #travel\n");
+    todo(&home)
+        .args(["join", "1", "3", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 1. ✖ (A) plan trip #travel; book hotel\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] (A) plan trip #travel; book hotel\n- [ ] call mom\n"
+    );
+    todo(&home)
+        .args(["join", "1", "1"])
+        .assert()
+        .code(2)
+        .stderr("todo: task 1 can't be joined with itself\n");
+    todo(&home).args(["join", "1", "5"]).assert().code(1);
+}
+
+#[test]
+fn reopen_adds_a_pending_copy_of_a_checked_task() {
+    let home = home_with("- [x] (B) water plants done:2024-06-30\n- [ ] call mom\n");
+    todo(&home)
+        .args(["reopen", "1", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 3. ✖ (B) water plants\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [x] (B) water plants done:2024-06-30\n- [ ] call mom\n- [ ] (B) water plants\n"
+    );
+    todo(&home)
+        .args(["reopen", "2"])
+        .assert()
+        .code(3)
+        .stderr("todo: task 2 isn't checked, and only checked tasks can be reopened\n");
+    todo(&home)
+        .args(["reopen", "--from-archive"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("there's no archive at"));
+
+    fs::write(
+        home.path().join("todo.done.txt"),
+        "- [x] pay rent done:2024-05-01\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["reopen", "--from-archive", "1", "--no-color"])
+        .assert()
+        .success()
+        .stdout(" 4. ✖ pay rent\n");
+    todo(&home)
+        .arg("dedupe")
+        .assert()
+        .success()
+        .stdout("0 duplicate(s), 4 task(s) remain\n");
+}
+
+#[test]
+fn checking_a_recurring_task_adds_its_next_occurrence() {
+    let home =
+        home_with("- [ ] (A) pay rent +home due:2024-01-31 rec:+1m\n- [ ] water plants rec:3d\n");
+    let file = home.path().join("todo.txt");
+    todo(&home)
+        .args(["check", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "added 3. (A) pay rent +home due:2024-02-29 rec:+1m\n",
+        ));
+    todo(&home)
+        .args(["check", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "added 4. water plants rec:3d due:2024-07-04\n",
+        ));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [x] (A) pay rent +home due:2024-01-31 rec:+1m done:2024-07-01\n\
+         - [x] water plants rec:3d done:2024-07-01\n\
+         - [ ] (A) pay rent +home due:2024-02-29 rec:+1m\n\
+         - [ ] water plants rec:3d due:2024-07-04\n"
+    );
+}
+
+#[test]
+fn habits_reset_once_a_new_day_starts() {
+    let home = home_with("- [ ] stretch habit:1\n- [ ] buy milk\n");
+    let file = home.path().join("todo.txt");
+    let at = |now: &str, args: &[&str]| {
+        todo(&home)
+            .args(args)
+            .env_remove("TODO_TODAY")
+            .env("TZ", "UTC")
+            .env("TODO_NOW", now)
+            .assert()
+            .success();
+    };
+    at("2024-07-01T23:58:00Z", &["check", "1"]);
+    at("2024-07-01T23:59:00Z", &["ls"]);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [x] stretch habit:1 done:2024-07-01\n- [ ] buy milk\n"
+    );
+    at("2024-07-02T00:01:00Z", &["ls"]);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "- [ ] stretch habit:1\n- [ ] buy milk\n"
+    );
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.done.txt")).unwrap(),
+        "- [x] stretch habit:1 done:2024-07-01\n"
+    );
+    at("2024-07-02T08:00:00Z", &["check", "1"]);
+    let output = todo(&home)
+        .args(["ls", "--all", "--no-color"])
+        .env_remove("TODO_TODAY")
+        .env("TZ", "UTC")
+        .env("TODO_NOW", "2024-07-02T09:00:00Z")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("1. ✓ stretch habit:1 [done] 🔥 2\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn adding_resets_habits_and_archives_like_other_commands() {
+    let home = home_with(
+        "- [x] stretch habit:1 done:2024-06-30\n- [x] file taxes done:2024-06-01\n\
+         - [ ] buy milk\n",
+    );
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "auto_archive_after = \"14d\"\n").unwrap();
+    todo(&home).arg("call mom").assert().success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] stretch habit:1\n- [ ] buy milk\n- [ ] call mom\n"
+    );
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.done.txt")).unwrap(),
+        "- [x] stretch habit:1 done:2024-06-30\n- [x] file taxes done:2024-06-01\n"
+    );
+    // With nothing to reset or archive, it only appends.
+    todo(&home)
+        .args(["-v", "water plants"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "appended the task without reading the list",
+        ));
+}
+
+#[test]
+fn done_today_lists_the_tasks_checked_on_a_day() {
+    let content = "- [x] call mom done:2024-07-01\n- [ ] pay rent\n\
+                   - [x] stretch habit:1 done:2024-06-30\n- [x] buy milk\n\
+                   - [x] water plants done:2024-07-01\n";
+    let home = home_with(content);
+    let file = home.path().join("todo.txt");
+    let archive = home.path().join("todo.done.txt");
+    let archived = "- [x] file taxes done:2024-07-01\n- [x] read done:2024-06-28\n";
+    fs::write(&archive, archived).unwrap();
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "auto_archive_after = \"1d\"\n").unwrap();
+
+    todo(&home)
+        .args(["--no-color", "done-today"])
+        .assert()
+        .success()
+        .stdout(" 1. ✓ call mom [done]\n 5. ✓ water plants [done]\n");
+    todo(&home)
+        .args(["--no-color", "done-today", "--include-archive"])
+        .assert()
+        .success()
+        .stdout(
+            " 1. ✓ call mom [done]\n 5. ✓ water plants [done]\n\n\
+             From the archive\n 1. ✓ file taxes [done]\n",
+        );
+    todo(&home)
+        .args(["--no-color", "done-today", "--yesterday"])
+        .assert()
+        .success()
+        .stdout(" 3. ✓ stretch habit:1 [done] 🔥 1\n");
+    todo(&home)
+        .args([
+            "done-today",
+            "--on",
+            "2024-06-28",
+            "--include-archive",
+            "--json",
+        ])
+        .assert()
+        .success()
+        .stdout("[{\"archived\":true,\"index\":2,\"note\":\"read\"}]\n");
+    todo(&home)
+        .args(["done-today", "--on", "last monday"])
+        .assert()
+        .code(1)
+        .stdout("");
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), archived);
+}
+
+#[test]
+fn themes_color_listings() {
+    let home = home_with("- [ ] buy milk\n");
+    todo(&home)
+        .args(["--theme", "light", "--color", "always", "ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\u{1b}[38;5;240m1.\u{1b}[0m"));
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "theme = \"light\"\n[colors]\nindex = \"bold blue\"\n",
+    )
+    .unwrap();
+    todo(&home)
+        .args(["ls", "--color", "always"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\u{1b}[1;34m1.\u{1b}[0m"));
+
+    fs::write(config.join("config.toml"), "[colors]\nindex = \"grey\"\n").unwrap();
+    todo(&home)
+        .arg("ls")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("invalid color 'grey' for index"));
+}
+
+#[test]
+fn colorblind_theme_keeps_status_symbols() {
+    let home = home_with("- [x] buy milk\n- [ ] call mom\n");
+    let ls = |args: &[&str]| {
+        let output = todo(&home).args(args).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let stdout = ls(&[
+        "--colorblind",
+        "--symbols",
+        "minimal",
+        "ls",
+        "--all",
+        "--color",
+        "always",
+    ]);
+    assert!(stdout.contains("\u{1b}[38;5;33m✓\u{1b}[0m"), "{}", stdout);
+    assert!(stdout.contains("\u{1b}[38;5;208m✖\u{1b}[0m"), "{}", stdout);
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "theme = \"colorblind\"\nsymbols = \"minimal\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        ls(&["ls", "--all", "--no-color"]),
+        ls(&["ls", "--all", "--no-color", "--symbols", "unicode"])
+    );
+    assert!(!ls(&["--theme", "dark", "ls", "--all", "--no-color"]).contains('✓'));
+}
+
+#[test]
+fn ls_oneline_cuts_notes_to_the_width() {
+    let content = "- [ ] buy milk and eggs and bread\n- [ ] 买牛奶和鸡蛋还有面包\n- [ ] call mom\n";
+    let home = home_with(content);
+    todo(&home)
+        .args(["--width", "20", "ls", "--oneline"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            " 1. ✖ buy milk and …\n 2. ✖ 买牛奶和鸡蛋…\n 3. ✖ call mom\n",
+        ));
+    todo(&home)
+        .args(["--width", "20", "show", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("buy milk and eggs and bread"));
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        content
+    );
+}
+
+#[test]
+fn urls_become_links_where_the_terminal_shows_them() {
+    let home = home_with("- [ ] read (https://example.com/a).\n");
+    let ls = |program: &str, args: &[&str]| {
+        let output = todo(&home)
+            .env("TERM_PROGRAM", program)
+            .env_remove("VTE_VERSION")
+            .env_remove("WT_SESSION")
+            .env_remove("KITTY_WINDOW_ID")
+            .args(["ls"])
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let link = "\x1b]8;;https://example.com/a\x1b\\https://example.com/a\x1b]8;;\x1b\\";
+    assert!(ls("WezTerm", &["--color", "always"]).contains(&format!("read ({}).", link)));
+    assert!(!ls("WezTerm", &["--no-color"]).contains("\x1b]8"));
+    assert!(!ls("WezTerm", &["--plain"]).contains("\x1b]8"));
+    assert!(!ls("Apple_Terminal", &["--color", "always"]).contains("\x1b]8"));
+
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "hyperlinks = true\n").unwrap();
+    assert!(ls("Apple_Terminal", &["--color", "always"]).contains(link));
+    fs::write(config.join("config.toml"), "hyperlinks = false\n").unwrap();
+    assert!(!ls("WezTerm", &["--color", "always"]).contains("\x1b]8"));
+}
+
+#[cfg(unix)]
+#[test]
+fn open_launches_the_links_of_a_task() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let content = "- [ ] review https://example.com/pull/42.\n\
+                   - [ ] compare (https://a.example) and https://b.example\n- [ ] call mom\n";
+    let home = home_with(content);
+    let browser = home.path().join("browser");
+    let opened = home.path().join("opened");
+    fs::write(
+        &browser,
+        format!("#!/bin/sh\necho \"$1\" >> '{}'\n", opened.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&browser, fs::Permissions::from_mode(0o755)).unwrap();
+    let todo = || {
+        let mut cmd = todo(&home);
+        cmd.env("BROWSER", &browser);
+        cmd
+    };
+
+    todo()
+        .args(["open", "1"])
+        .assert()
+        .success()
+        .stdout("opened https://example.com/pull/42\n");
+    todo()
+        .args(["open", "2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("the task has 2 links"));
+    todo()
+        .args(["--dry-run", "open", "2", "--all"])
+        .assert()
+        .success()
+        .stdout("would open https://a.example\nwould open https://b.example\n");
+    todo().args(["open", "2", "--all"]).assert().success();
+    assert_eq!(
+        fs::read_to_string(&opened).unwrap(),
+        "https://example.com/pull/42\nhttps://a.example\nhttps://b.example\n"
+    );
+    todo()
+        .args(["open", "3"])
+        .assert()
+        .code(1)
+        .stderr("todo: task 3 has no links or attachments\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        content
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn attached_files_are_shown_opened_and_detached() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = home_with("- [ ] read spec\n");
+    fs::create_dir_all(home.path().join("my docs")).unwrap();
+    fs::write(home.path().join("my docs/spec.pdf"), "").unwrap();
+    let bin = home.path().join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let opened = home.path().join("opened");
+    let opener = bin.join("xdg-open");
+    fs::write(
+        &opener,
+        format!("#!/bin/sh\necho \"$1\" >> '{}'\n", opened.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&opener, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let todo = || {
+        let mut cmd = todo(&home);
+        cmd.env("PATH", &path).env_remove("BROWSER");
+        cmd
+    };
+
+    todo()
+        .args(["attach", "1", "~/my docs/spec.pdf"])
+        .assert()
+        .success();
+    todo()
+        .args(["attach", "1", "~/gone.txt"])
+        .assert()
+        .success()
+        .stderr("todo: ~/gone.txt doesn't exist, attaching it anyway\n");
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] read spec file:~/my%20docs/spec.pdf file:~/gone.txt\n"
+    );
+    todo()
+        .args(["show", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "files    1. ~/my docs/spec.pdf\n         2. ~/gone.txt (missing)\n",
+        ));
+
+    todo()
+        .args(["open", "1", "--all"])
+        .assert()
+        .success()
+        .stdout("opened ~/my docs/spec.pdf\n")
+        .stderr("todo: ~/gone.txt doesn't exist any more\n");
+    assert_eq!(
+        fs::read_to_string(&opened).unwrap(),
+        format!("{}\n", home.path().join("my docs/spec.pdf").display())
+    );
+
+    todo()
+        .args(["detach", "1", "2"])
+        .assert()
+        .success()
+        .stdout("detached ~/gone.txt\n");
+    todo()
+        .args(["detach", "1", "2"])
+        .assert()
+        .code(1)
+        .stderr("todo: no attachment 2 in task 1 (valid: 1-1)\n");
+    todo().args(["detach", "1"]).assert().success();
+    assert_eq!(
+        fs::read_to_string(home.path().join("todo.txt")).unwrap(),
+        "- [ ] read spec\n"
+    );
+}
+
+#[cfg(feature = "webdav")]
+#[derive(Default)]
+struct DavFile {
+    content: Option<String>,
+    version: u32,
+    racy: bool,
+}
+
+/// A WebDAV server keeping one file, which needs the user `me` with the
+/// password `secret`. With `racy` set, the file changes right after each
+/// GET, as if someone else saved it meanwhile.
+#[cfg(feature = "webdav")]
+fn dav_server() -> (String, std::sync::Arc<std::sync::Mutex<DavFile>>) {
+    use std::sync::{Arc, Mutex};
+    use tiny_http::{Header, Method, Response, Server};
+
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!(
+        "dav://127.0.0.1:{}/files/todo.txt",
+        server.server_addr().to_ip().unwrap().port()
+    );
+    let state = Arc::new(Mutex::new(DavFile::default()));
+    let shared = Arc::clone(&state);
+    std::thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let header = |name: &str| {
+                request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.to_string().eq_ignore_ascii_case(name))
+                    .map(|h| h.value.as_str().to_owned())
+            };
+            let (authorization, if_match, if_none_match) = (
+                header("Authorization"),
+                header("If-Match"),
+                header("If-None-Match"),
+            );
+            let mut state = shared.lock().unwrap();
+            let etag = format!("\"v{}\"", state.version);
+            let response = if authorization.as_deref() != Some("Basic bWU6c2VjcmV0") {
+                Response::from_string("").with_status_code(401)
+            } else if *request.method() == Method::Get {
+                match state.content.clone() {
+                    Some(content) => {
+                        if state.racy {
+                            state.content = Some(format!("{}- [ ] meanwhile\n", content));
+                            state.version += 1;
+                        }
+                        Response::from_string(content)
+                            .with_header(Header::from_bytes("ETag", etag.as_bytes()).unwrap())
+                    }
+                    None => Response::from_string("").with_status_code(404),
+                }
+            } else {
+                let matches = match &state.content {
+                    Some(_) => if_match.as_deref() == Some(etag.as_str()),
+                    None => if_none_match.as_deref() == Some("*"),
+                };
+                if matches {
+                    let mut content = String::new();
+                    std::io::Read::read_to_string(request.as_reader(), &mut content).unwrap();
+                    state.content = Some(content);
+                    state.version += 1;
+                    Response::from_string("").with_status_code(204)
+                } else {
+                    Response::from_string("").with_status_code(412)
+                }
+            };
+            drop(state);
+            request.respond(response).unwrap();
+        }
+    });
+    (url, state)
+}
+
+#[cfg(feature = "webdav")]
+#[test]
+fn webdav_lists_are_fetched_and_put_back() {
+    let home = TempDir::new().unwrap();
+    let (url, state) = dav_server();
+    let config = home.path().join(".config/todo");
+    fs::create_dir_all(&config).unwrap();
+    let dav = |home: &TempDir| {
+        let mut cmd = todo(home);
+        cmd.env("TODO_FILE", &url).arg("--no-color");
+        cmd
+    };
+
+    dav(&home)
+        .arg("buy milk")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("the server answered 401"));
+    fs::write(
+        config.join("config.toml"),
+        "[webdav]\nuser = \"me\"\npassword = \"secret\"\n",
+    )
+    .unwrap();
+    dav(&home)
+        .arg("path")
+        .assert()
+        .success()
+        .stdout(format!("{}\n", url));
+    dav(&home).arg("buy milk").assert().success();
+    assert_eq!(
+        state.lock().unwrap().content.as_deref(),
+        Some("- [ ] buy milk\n")
+    );
+
+    {
+        let mut state = state.lock().unwrap();
+        state.content = Some("- [ ] buy milk\n- [ ] call mom\n".to_owned());
+        state.version += 1;
+    }
+    dav(&home).args(["check", "2"]).assert().success();
+    assert_eq!(
+        state.lock().unwrap().content.as_deref(),
+        Some("- [ ] buy milk\n- [x] call mom done:2024-07-01\n")
+    );
+    dav(&home)
+        .arg("ls")
+        .assert()
+        .success()
+        .stdout(" 1. ✖ buy milk\n");
+
+    state.lock().unwrap().racy = true;
+    dav(&home)
+        .args(["check", "1"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("changed on the server"));
+    assert_eq!(
+        state.lock().unwrap().content.as_deref(),
+        Some("- [ ] buy milk\n- [x] call mom done:2024-07-01\n- [ ] meanwhile\n")
+    );
+
+    todo(&home)
+        .env("TODO_FILE", "dav://127.0.0.1:1/todo.txt")
+        .arg("ls")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("couldn't reach the server"));
+    todo(&home)
+        .env("TODO_FILE", &url)
+        .env("PATH", home.path())
+        .arg("ls")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "remote lists need curl, which isn't installed or isn't on PATH",
+        ));
+}